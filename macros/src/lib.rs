@@ -0,0 +1,239 @@
+//! `#[openinput_dispatch_table]`, re-exported by `openinput-rust` with the `macros` feature
+
+use proc_macro::TokenStream;
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Error, Ident, ImplItem, ItemImpl, LitInt, Token};
+
+const INFO_FUNCTION_PAGE: u8 = 0x00;
+const ERROR_FUNCTION_PAGE: u8 = 0xFF;
+
+/// implement `MatchTable` for the type of an inherent impl, see `openinput_rust::dispatch::MatchTable`
+///
+/// every associated function marked `#[function(page = .., id = ..)]` becomes a handler, the rest are left alone
+#[proc_macro_attribute]
+pub fn openinput_dispatch_table(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand(attr.into(), item.into())
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// `#[function(page = 0xA0, id = 0x00)]`
+struct FunctionAttr {
+    page: u8,
+    id: u8,
+}
+
+impl Parse for FunctionAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let page = parse_id(input, "page")?;
+        input.parse::<Token![,]>()?;
+        let id = parse_id(input, "id")?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { page, id })
+    }
+}
+
+/// `key = <u8 literal>`
+fn parse_id(input: ParseStream, key: &str) -> syn::Result<u8> {
+    let ident: Ident = input.parse()?;
+    if ident != key {
+        return Err(Error::new(ident.span(), format!("expected `{}`", key)));
+    }
+    input.parse::<Token![=]>()?;
+    let lit: LitInt = input.parse()?;
+    match lit.suffix() {
+        "" | "u8" => lit
+            .base10_parse::<u8>()
+            .map_err(|_| Error::new(lit.span(), "ids are u8, 0x00 to 0xFF")),
+        _ => Err(Error::new(lit.span(), "ids are u8, 0x00 to 0xFF")),
+    }
+}
+
+fn expand(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+    if !attr.is_empty() {
+        return Err(Error::new_spanned(attr, "takes no arguments"));
+    }
+    let mut item: ItemImpl = syn::parse2(item)?;
+    if let Some((_, path, _)) = &item.trait_ {
+        return Err(Error::new_spanned(path, "goes on an inherent impl"));
+    }
+    if !item.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &item.generics,
+            "the table is a const, it can't be generic",
+        ));
+    }
+
+    // pages in order of appearance, each with its functions
+    let mut pages: Vec<(u8, Vec<(u8, Ident)>)> = Vec::new();
+    for impl_item in &mut item.items {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        let mut function = None;
+        let mut i = 0;
+        while i < method.attrs.len() {
+            if !method.attrs[i].path.is_ident("function") {
+                i += 1;
+                continue;
+            }
+            let attr = method.attrs.remove(i);
+            if function.is_some() {
+                return Err(Error::new_spanned(attr, "a handler has one page and id"));
+            }
+            function = Some((attr.parse_args::<FunctionAttr>()?, attr));
+        }
+        let (FunctionAttr { page, id }, attr) = match function {
+            Some(function) => function,
+            None => continue,
+        };
+        if page == INFO_FUNCTION_PAGE {
+            return Err(Error::new_spanned(
+                attr,
+                "page 0x00 is the info page, it's always included",
+            ));
+        }
+        if page == ERROR_FUNCTION_PAGE {
+            return Err(Error::new_spanned(attr, "0xFF is the error page"));
+        }
+        let index = match pages.iter().position(|(fn_page, _)| *fn_page == page) {
+            Some(index) => index,
+            None => {
+                pages.push((page, Vec::new()));
+                pages.len() - 1
+            }
+        };
+        let functions = &mut pages[index].1;
+        if functions.iter().any(|(fn_id, _)| *fn_id == id) {
+            return Err(Error::new_spanned(
+                attr,
+                format!("duplicate function id {:#04X} in page {:#04X}", id, page),
+            ));
+        }
+        functions.push((id, method.sig.ident.clone()));
+    }
+
+    let static_pages = pages.iter().map(|(page, functions)| {
+        let page = Literal::u8_unsuffixed(*page);
+        let ids = functions.iter().map(|(id, _)| Literal::u8_unsuffixed(*id));
+        let handlers = functions.iter().map(|(_, handler)| handler);
+        quote! {
+            ::openinput_rust::dispatch::StaticPage {
+                id: #page,
+                functions: &[#((
+                    #ids,
+                    ::openinput_rust::dispatch::DispatchHandler::Function(Self::#handlers),
+                ),)*],
+            }
+        }
+    });
+    let arms = pages.iter().map(|(page, functions)| {
+        let page = Literal::u8_unsuffixed(*page);
+        let ids = functions.iter().map(|(id, _)| Literal::u8_unsuffixed(*id));
+        let handlers = functions.iter().map(|(_, handler)| handler);
+        quote! {
+            #page => match id {
+                #(#ids => ::core::option::Option::Some(
+                    ::openinput_rust::dispatch::DispatchHandler::Function(Self::#handlers),
+                ),)*
+                _ => ::core::option::Option::None,
+            }
+        }
+    });
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        impl ::openinput_rust::dispatch::MatchTable for #self_ty {
+            const PAGES: &'static [::openinput_rust::dispatch::StaticPage] = &[
+                ::openinput_rust::dispatch::INFO_PAGE,
+                #(#static_pages,)*
+            ];
+
+            fn lookup(page: u8, id: u8) -> ::core::option::Option<::openinput_rust::dispatch::DispatchHandler> {
+                match page {
+                    0x00 => ::openinput_rust::dispatch::INFO_PAGE.handler(id),
+                    #(#arms,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        }
+
+        const _: () = ::openinput_rust::dispatch::StaticPage::check_table(
+            <#self_ty as ::openinput_rust::dispatch::MatchTable>::PAGES,
+        );
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_err(item: TokenStream2) -> String {
+        expand(TokenStream2::new(), item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expands() {
+        let out = expand(
+            TokenStream2::new(),
+            quote! {
+                impl Table {
+                    #[function(page = 0xA0, id = 0x01)]
+                    fn dpi_set(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+                    #[function(page = 0xA0, id = 0x00)]
+                    fn dpi_get(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+                    fn helper() {}
+                }
+            },
+        )
+        .unwrap()
+        .to_string();
+        // the marker attributes are gone, the handlers are matched on
+        assert!(!out.contains("# [function"));
+        assert!(out.contains("160 => match id { 1 =>"));
+        assert!(out.contains("fn helper"));
+        assert!(!out.contains("Self :: helper"));
+    }
+
+    #[test]
+    fn rejects() {
+        assert!(expand_err(quote! {
+            impl Table {
+                #[function(page = 0xA0, id = 0x00)]
+                fn a(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+                #[function(page = 0xA0, id = 0x00)]
+                fn b(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+            }
+        })
+        .contains("duplicate function id 0x00 in page 0xA0"));
+
+        for attr in [
+            quote!(#[function(page = 0x100, id = 0x00)]),
+            quote!(#[function(page = 0xA0, id = 256)]),
+            quote!(#[function(page = 0xA0, id = 0x00u16)]),
+        ] {
+            assert_eq!(
+                expand_err(quote! {
+                    impl Table {
+                        #attr
+                        fn a(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+                    }
+                }),
+                "ids are u8, 0x00 to 0xFF"
+            );
+        }
+
+        for page in [quote!(0x00), quote!(0xFF)] {
+            expand_err(quote! {
+                impl Table {
+                    #[function(page = #page, id = 0x00)]
+                    fn a(data: &[u8], ctx: DispatchContext) -> DispatchReturn { todo!() }
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! Absolute pointer (pen digitizer) report for graphics tablets and drawing pads
+//!
+//! Coordinates run from 0 to a logical maximum that's part of the descriptor, so the descriptor is built
+//! with [`descriptor`] (or `AbsolutePointerConfig::descriptor` with the dispatch page) for the actual
+//! surface. [`OiAbsolutePointerReport::desc`] uses [`DEFAULT_LOGICAL_MAX`] on both axes.
+//!
+//! ```ignore
+//! static DESC: [u8; ABS_POINTER_DESC_LEN] = absolute_pointer::descriptor(32000, 18000);
+//! let class = OpenInputHIDClass::<_, OiAbsolutePointerReport>::new(HIDClass::new(&alloc, &DESC, 1));
+//! ```
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
+
+use crate::{
+    concat_desc, read_openinput, OIError, OiReport, LONG_LEN, OPENINPUT_DESCRIPTOR, SHORT_LEN,
+};
+
+use super::OpenInputHidReport;
+
+/// x/y logical maximum of [`OiAbsolutePointerReport::desc`]
+pub const DEFAULT_LOGICAL_MAX: u16 = 0x7fff;
+
+const PEN: [u8; 58] = [
+    0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+    0x09, 0x02, /* USAGE (Pen) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x0a, /*  REPORT_ID (0x0A) */
+    0x09, 0x20, /*  USAGE (Stylus) */
+    0xa1, 0x00, /*  COLLECTION (Physical) */
+    0x09, 0x42, /*   USAGE (Tip Switch) */
+    0x09, 0x44, /*   USAGE (Barrel Switch) */
+    0x09, 0x45, /*   USAGE (Eraser) */
+    0x09, 0x32, /*   USAGE (In Range) */
+    0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+    0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+    0x75, 0x01, /*   REPORT_SIZE (1) */
+    0x95, 0x04, /*   REPORT_COUNT (4) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+    0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+    0x75, 0x10, /*   REPORT_SIZE (16) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0x27, 0x00, 0x00, 0x00, 0x00, /*   LOGICAL_MAXIMUM (max x) */
+    0x09, 0x30, /*   USAGE (X) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x27, 0x00, 0x00, 0x00, 0x00, /*   LOGICAL_MAXIMUM (max y) */
+    0x09, 0x31, /*   USAGE (Y) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0xc0, /*  END_COLLECTION */
+    0xc0, /* END_COLLECTION */
+];
+
+/// offsets of the x and y LOGICAL_MAXIMUM values in [`PEN`], the 4 byte form is used since the 2 byte one
+/// is signed
+const MAX_X_OFFSET: usize = 39;
+const MAX_Y_OFFSET: usize = 48;
+
+pub const ABS_POINTER_DESC_LEN: usize = PEN.len() + OPENINPUT_DESCRIPTOR.len();
+
+const fn pen(max_x: u16, max_y: u16) -> [u8; PEN.len()] {
+    let mut out = PEN;
+    let x = max_x.to_le_bytes();
+    let y = max_y.to_le_bytes();
+    out[MAX_X_OFFSET] = x[0];
+    out[MAX_X_OFFSET + 1] = x[1];
+    out[MAX_Y_OFFSET] = y[0];
+    out[MAX_Y_OFFSET + 1] = y[1];
+    out
+}
+
+/// pen input report 0x0A with coordinates in `0..=max_x` / `0..=max_y`, followed by the OpenInput collections
+pub const fn descriptor(max_x: u16, max_y: u16) -> [u8; ABS_POINTER_DESC_LEN] {
+    concat_desc(&pen(max_x, max_y), OPENINPUT_DESCRIPTOR)
+}
+
+static DESC: [u8; ABS_POINTER_DESC_LEN] = descriptor(DEFAULT_LOGICAL_MAX, DEFAULT_LOGICAL_MAX);
+
+#[derive(Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OiAbsolutePointerReport {
+    // openinput
+    out_short_buf: [u8; SHORT_LEN],
+    out_long_buf: [u8; LONG_LEN],
+}
+
+impl SerializedDescriptor for OiAbsolutePointerReport {
+    fn desc() -> &'static [u8] {
+        &DESC
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AbsolutePointerReportId {
+    OpenInputShort = 0x20,
+    OpenInputLong = 0x21,
+    Pointer = 0x0A,
+}
+
+impl TryFrom<u8> for AbsolutePointerReportId {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0A => Ok(AbsolutePointerReportId::Pointer),
+            0x20 => Ok(AbsolutePointerReportId::OpenInputShort),
+            0x21 => Ok(AbsolutePointerReportId::OpenInputLong),
+            _ => Err(()),
+        }
+    }
+}
+
+/// pens have no output report of their own
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiAbsolutePointerOutputReport<'a> {
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+/// a single pen position
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Touchpoint {
+    /// pen touches the surface
+    pub tip: bool,
+    pub barrel: bool,
+    pub eraser: bool,
+    /// pen is close enough to track, hosts ignore the position otherwise
+    pub in_range: bool,
+    pub x: u16,
+    pub y: u16,
+}
+
+impl Serialize for Touchpoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // 4 switches and 4 padding bits
+        let flags = self.tip as u8
+            | (self.barrel as u8) << 1
+            | (self.eraser as u8) << 2
+            | (self.in_range as u8) << 3;
+        let mut s = serializer.serialize_tuple(3)?;
+        s.serialize_element(&flags)?;
+        s.serialize_element(&self.x)?;
+        s.serialize_element(&self.y)?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiAbsolutePointerInputReport<'a> {
+    /// Pen report
+    Pointer(Touchpoint),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+impl<'a> Serialize for OiAbsolutePointerInputReport<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            OiAbsolutePointerInputReport::Pointer(point) => {
+                // report id + report
+                let mut s = serializer.serialize_tuple(2)?;
+                s.serialize_element(&(AbsolutePointerReportId::Pointer as u8))?;
+                s.serialize_element(point)?;
+                s.end()
+            }
+            OiAbsolutePointerInputReport::OpenInput(oi) => oi.serialize(serializer),
+        }
+    }
+}
+
+impl OpenInputHidReport for OiAbsolutePointerReport {
+    type PullReport<'a> = OiAbsolutePointerOutputReport<'a>;
+    type PushReport<'a> = OiAbsolutePointerInputReport<'a>;
+    type ReportId = AbsolutePointerReportId;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
+            AbsolutePointerReportId::Pointer => Err(OIError::FuckyBuffer),
+            AbsolutePointerReportId::OpenInputShort => {
+                Ok(OiAbsolutePointerOutputReport::OpenInput(read_openinput(
+                    buf,
+                    &mut self.out_short_buf,
+                )?))
+            }
+            AbsolutePointerReportId::OpenInputLong => Ok(OiAbsolutePointerOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_long_buf)?,
+            )),
+        }
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        match report {
+            OiAbsolutePointerOutputReport::OpenInput(oi) => Ok(oi),
+        }
+    }
+
+    fn push_report<'b, 'ep, B: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, B>,
+        report: Self::PushReport<'b>,
+    ) -> Result<(), OIError> {
+        let mut buf = [0; 64];
+        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        hid.push_raw_input(&buf[..m])?;
+        Ok(())
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{has_openinput_collections, OPENINPUT_SNAPSHOT};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn conformance() {
+        let desc = OiAbsolutePointerReport::desc();
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // USAGE_PAGE (Digitizer), USAGE (Pen), COLLECTION (Application), REPORT_ID (0x0A)
+        assert!(desc.starts_with(&[0x05, 0x0d, 0x09, 0x02, 0xa1, 0x01, 0x85, 0x0a]));
+        // LOGICAL_MAXIMUM (0x7fff) before USAGE (X) and USAGE (Y)
+        assert!(contains(desc, &[0x27, 0xff, 0x7f, 0x00, 0x00, 0x09, 0x30]));
+        assert!(contains(desc, &[0x27, 0xff, 0x7f, 0x00, 0x00, 0x09, 0x31]));
+        // walk the short items so data bytes aren't mistaken for collection tags
+        let (mut depth, mut i) = (0, 0);
+        while i < desc.len() {
+            match desc[i] & 0xfc {
+                0xa0 => depth += 1,
+                0xc0 => depth -= 1,
+                _ => (),
+            }
+            i += 1 + [0, 1, 2, 4][(desc[i] & 0x03) as usize];
+        }
+        assert_eq!((depth, i), (0, desc.len()));
+    }
+
+    #[test]
+    fn custom_bounds() {
+        let desc = descriptor(32000, 65535);
+        assert!(has_openinput_collections(&desc));
+        assert!(contains(&desc, &[0x27, 0x00, 0x7d, 0x00, 0x00, 0x09, 0x30]));
+        // unsigned, doesn't turn into -1
+        assert!(contains(&desc, &[0x27, 0xff, 0xff, 0x00, 0x00, 0x09, 0x31]));
+        assert_eq!(desc.len(), OiAbsolutePointerReport::desc().len());
+    }
+
+    #[test]
+    fn serialize() {
+        let report = OiAbsolutePointerInputReport::Pointer(Touchpoint {
+            tip: true,
+            in_range: true,
+            x: 32000,
+            y: 1,
+            ..Default::default()
+        });
+        let mut buf = [0; 64];
+        let len = ssmarshal::serialize(&mut buf, &report).unwrap();
+        assert_eq!(&buf[..len], &[0x0a, 0x09, 0x00, 0x7d, 0x01, 0x00]);
+    }
+
+    const DESC_SNAPSHOT: [&[u8]; 3] = [
+        &[
+            0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+            0x09, 0x02, /* USAGE (Pen) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x0a, /*  REPORT_ID (0x0A) */
+            0x09, 0x20, /*  USAGE (Stylus) */
+            0xa1, 0x00, /*  COLLECTION (Physical) */
+            0x09, 0x42, /*   USAGE (Tip Switch) */
+            0x09, 0x44, /*   USAGE (Barrel Switch) */
+            0x09, 0x45, /*   USAGE (Eraser) */
+            0x09, 0x32, /*   USAGE (In Range) */
+            0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+            0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+            0x75, 0x01, /*   REPORT_SIZE (1) */
+            0x95, 0x04, /*   REPORT_COUNT (4) */
+            0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+            0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+            0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+            0x75, 0x10, /*   REPORT_SIZE (16) */
+            0x95, 0x01, /*   REPORT_COUNT (1) */
+            0x27, 0xff, 0x7f, 0x00, 0x00, /*   LOGICAL_MAXIMUM (32767) */
+            0x09, 0x30, /*   USAGE (X) */
+            0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+            0x27, 0xff, 0x7f, 0x00, 0x00, /*   LOGICAL_MAXIMUM (32767) */
+            0x09, 0x31, /*   USAGE (Y) */
+            0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+            0xc0, /*  END_COLLECTION */
+            0xc0, /* END_COLLECTION */
+        ],
+        OPENINPUT_SNAPSHOT[0],
+        OPENINPUT_SNAPSHOT[1],
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        crate::descriptor::assert_snapshot(
+            "absolute-pointer",
+            OiAbsolutePointerReport::desc(),
+            &DESC_SNAPSHOT,
+        );
+    }
+}
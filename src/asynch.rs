@@ -0,0 +1,273 @@
+//! Async push/pull for executors like embassy
+//!
+//! usb-device has no endpoint wakers, so the futures park on a [`UsbWaker`] which firmware wakes after polling
+//! the usb device, usually from the usb interrupt:
+//!
+//! ```ignore
+//! static USB_WAKER: UsbWaker = UsbWaker::new();
+//!
+//! #[interrupt]
+//! fn USB() {
+//!     // usb device and class shared with the task, e.g. through a mutex
+//!     usb_dev.poll(&mut [&mut class]);
+//!     USB_WAKER.wake();
+//! }
+//!
+//! let report = class.pull_host_data_async(&USB_WAKER).await?;
+//! ```
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use critical_section::Mutex;
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+
+use crate::{OIError, OpenInputHIDClass, OpenInputHidReport, REPORT_BUFFER_SIZE};
+
+/// wakes the task waiting in [`OpenInputHIDClass::pull_host_data_async`] or
+/// [`OpenInputHIDClass::push_report_async`]
+pub struct UsbWaker(Mutex<RefCell<WakerState>>);
+
+struct WakerState {
+    /// set by `wake`, so a wake between a failed endpoint access and the next poll isn't lost
+    woken: bool,
+    waker: Option<Waker>,
+}
+
+impl UsbWaker {
+    pub const fn new() -> Self {
+        Self(Mutex::new(RefCell::new(WakerState {
+            woken: false,
+            waker: None,
+        })))
+    }
+
+    /// call after polling the usb device, wakes the waiting task if there is one
+    pub fn wake(&self) {
+        let waker = critical_section::with(|cs| {
+            let mut state = self.0.borrow_ref_mut(cs);
+            state.woken = true;
+            state.waker.take()
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    /// resolves after the next [`wake`](Self::wake), or right away if one happened since the last wait
+    fn wait(&self) -> Wait<'_> {
+        Wait(self)
+    }
+}
+
+impl Default for UsbWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Wait<'w>(&'w UsbWaker);
+
+impl Future for Wait<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        critical_section::with(|cs| {
+            let mut state = self.0 .0.borrow_ref_mut(cs);
+            if core::mem::take(&mut state.woken) {
+                return Poll::Ready(());
+            }
+            match &state.waker {
+                Some(waker) if waker.will_wake(cx.waker()) => (),
+                _ => state.waker = Some(cx.waker().clone()),
+            }
+            Poll::Pending
+        })
+    }
+}
+
+impl<'ep, B: UsbBus, R: OpenInputHidReport> OpenInputHIDClass<'ep, B, R> {
+    /// wait until the host sends a report
+    ///
+    /// reads the endpoints like the default [`OpenInputHidReport::pull_ep_out`] and parses the report with
+    /// [`OpenInputHidReport::read_report`]
+    pub async fn pull_host_data_async(
+        &mut self,
+        waker: &UsbWaker,
+    ) -> Result<R::PullReport<'_>, OIError> {
+        let mut buf = [0; REPORT_BUFFER_SIZE];
+        let (report_id, len) = loop {
+            match crate::pull_raw(&mut self.inner, &mut buf) {
+                Err(OIError::UsbError(UsbError::WouldBlock)) => waker.wait().await,
+                res => break res?,
+            }
+        };
+        self.report.read_report(report_id, &buf[..len])
+    }
+
+    /// wait until the IN endpoint is free and push `report`
+    ///
+    /// goes through [`try_push_report`](Self::try_push_report), so the idle filter and the busy hint apply
+    pub async fn push_report_async<'r>(
+        &mut self,
+        waker: &UsbWaker,
+        report: R::PushReport<'r>,
+    ) -> Result<(), OIError>
+    where
+        R::PushReport<'r>: Clone,
+    {
+        loop {
+            match self.try_push_report(report.clone()) {
+                Err(OIError::NotReady) => waker.wait().await,
+                res => return res,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use usbd_hid::hid_class::HIDClass;
+
+    use super::*;
+    use crate::mock::MockUsbBus;
+
+    /// passes raw interrupt packets through
+    #[derive(Default)]
+    struct RawReport {
+        buf: [u8; 64],
+    }
+
+    impl OpenInputHidReport for RawReport {
+        type ReportId = u8;
+        type PullReport<'a> = &'a [u8];
+        type PushReport<'r> = u8;
+
+        fn pull_ep_out<'a, 'ep, B: UsbBus>(
+            &'a mut self,
+            hid: &mut HIDClass<'ep, B>,
+        ) -> Result<Self::PullReport<'a>, OIError> {
+            let len = hid.pull_raw_output(&mut self.buf)?;
+            Ok(&self.buf[..len])
+        }
+
+        fn read_report<'a>(
+            &'a mut self,
+            _report_id: u8,
+            buf: &[u8],
+        ) -> Result<Self::PullReport<'a>, OIError> {
+            self.buf[..buf.len()].copy_from_slice(buf);
+            Ok(&self.buf[..buf.len()])
+        }
+
+        fn push_report<'r, 'ep, B: UsbBus>(
+            &mut self,
+            hid: &mut HIDClass<'ep, B>,
+            report: Self::PushReport<'r>,
+        ) -> Result<(), OIError> {
+            hid.push_raw_input(&[report])?;
+            Ok(())
+        }
+
+        fn idle_report<'r>(&self, report: &Self::PushReport<'r>, buf: &mut [u8]) -> Option<usize> {
+            buf[0] = *report;
+            Some(1)
+        }
+    }
+
+    /// counts how often the task was woken
+    #[derive(Default)]
+    struct Count(AtomicUsize);
+
+    impl Wake for Count {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn poll<F: Future>(fut: &mut Pin<Box<F>>, count: &Arc<Count>) -> Poll<F::Output> {
+        let waker = Waker::from(count.clone());
+        fut.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn pull_resolves_when_report_arrives() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut class = OpenInputHIDClass::<_, RawReport>::new(HIDClass::new(&alloc, &[], 1));
+
+        let waker = UsbWaker::new();
+        let count = Arc::new(Count::default());
+
+        let mut fut = Box::pin(class.pull_host_data_async(&waker));
+        assert!(poll(&mut fut, &count).is_pending());
+        // nothing happened on the bus, the future doesn't wake itself
+        assert!(poll(&mut fut, &count).is_pending());
+        assert_eq!(count.0.load(Ordering::Relaxed), 0);
+
+        host.send(&[0x20, 0x00, 0x00]);
+        waker.wake();
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        match poll(&mut fut, &count) {
+            Poll::Ready(Ok(data)) => assert_eq!(data, &[0x20, 0x00, 0x00]),
+            _ => panic!("pull did not resolve"),
+        }
+    }
+
+    #[test]
+    fn push_waits_for_endpoint() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut class = OpenInputHIDClass::<_, RawReport>::new(HIDClass::new(&alloc, &[], 1));
+
+        let waker = UsbWaker::new();
+        let count = Arc::new(Count::default());
+
+        host.set_in_busy(true);
+        let mut fut = Box::pin(class.push_report_async(&waker, 0x42));
+        assert!(poll(&mut fut, &count).is_pending());
+
+        host.set_in_busy(false);
+        waker.wake();
+        assert_eq!(count.0.load(Ordering::Relaxed), 1);
+        assert!(matches!(poll(&mut fut, &count), Poll::Ready(Ok(()))));
+        assert_eq!(host.recv(), Some(vec![0x42]));
+    }
+
+    #[test]
+    fn push_goes_through_try_push() {
+        use usb_device::prelude::*;
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class = OpenInputHIDClass::<_, RawReport>::new(HIDClass::new(&alloc, &[], 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        let waker = UsbWaker::new();
+        let count = Arc::new(Count::default());
+
+        host.set_in_busy(true);
+        let mut fut = Box::pin(class.push_report_async(&waker, 0x42));
+        assert!(poll(&mut fut, &count).is_pending());
+        drop(fut);
+        assert!(!class.is_ready_to_push());
+        host.set_in_busy(false);
+
+        // repeats are never sent with an idle rate of 0
+        host.set_idle(0, 0);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        for _ in 0..2 {
+            let mut fut = Box::pin(class.push_report_async(&waker, 0x42));
+            assert!(matches!(poll(&mut fut, &count), Poll::Ready(Ok(()))));
+        }
+        assert!(class.is_ready_to_push());
+        assert_eq!(host.recv(), Some(vec![0x42]));
+        assert_eq!(host.recv(), None);
+    }
+}
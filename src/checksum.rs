@@ -0,0 +1,112 @@
+//! CRC-32 over long OpenInput reports
+//!
+//! With the `crc` feature the last 4 bytes of a long report's data carry a little endian CRC-32 of everything
+//! before them (report id, page, function id and 25 bytes of data), `pull_ep_out` drops long reports that
+//! don't match with [`OIError::CrcMismatch`]. Short reports aren't covered. The checksum stays in the data
+//! handlers see, payloads of checked reports are limited to [`CRC_DATA_LEN`] bytes.
+//!
+//! The CRC is the IEEE 802.3 one (`CRC_32_ISO_HDLC`), the polynomial IEC 60335 class B RAM/flash checks and
+//! most MCU CRC peripherals use.
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::{OIError, OiReport, LONG_LEN, OPENINPUT_LONG_REPORT_ID};
+
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+pub const CRC_LEN: usize = 4;
+
+/// data bytes of a long report left next to the checksum
+pub const CRC_DATA_LEN: usize = LONG_LEN - 3 - CRC_LEN;
+
+fn checksum(header: [u8; 3], data: &[u8]) -> [u8; CRC_LEN] {
+    let mut digest = CRC32.digest();
+    digest.update(&header);
+    digest.update(data);
+    digest.finalize().to_le_bytes()
+}
+
+/// [`OIError::CrcMismatch`] for long reports whose checksum doesn't match, `frame` includes the report id
+pub(crate) fn check_frame(frame: &[u8]) -> Result<(), OIError> {
+    match frame {
+        [OPENINPUT_LONG_REPORT_ID, page, fn_id, data @ ..] if frame.len() == LONG_LEN => {
+            let (data, crc) = data.split_at(CRC_DATA_LEN);
+            if checksum([OPENINPUT_LONG_REPORT_ID, *page, *fn_id], data) != crc {
+                return Err(OIError::CrcMismatch);
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+impl<'a> OiReport<'a> {
+    /// serialized long report with the checksum in the last 4 bytes
+    ///
+    /// [`OiReport::new_long`] borrows its data so it can't append the checksum, read the bytes back with
+    /// [`OiReport::read`] if a report is needed
+    pub fn new_long_crc(page: u8, fn_id: u8, data: &[u8; CRC_DATA_LEN]) -> [u8; LONG_LEN] {
+        let header = [OPENINPUT_LONG_REPORT_ID, page, fn_id];
+        let mut report = [0; LONG_LEN];
+        report[..3].copy_from_slice(&header);
+        report[3..3 + CRC_DATA_LEN].copy_from_slice(data);
+        report[3 + CRC_DATA_LEN..].copy_from_slice(&checksum(header, data));
+        report
+    }
+
+    /// false if this is a long report with a wrong checksum, short reports always pass
+    pub fn verify_crc(&self) -> bool {
+        if self.is_short() || self.data.len() != LONG_LEN - 3 {
+            return true;
+        }
+        let (data, crc) = self.data.split_at(CRC_DATA_LEN);
+        checksum([self.id, self.function_page, self.function_id], data) == crc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut data = [0; CRC_DATA_LEN];
+        data[..3].copy_from_slice(&[1, 2, 3]);
+        let bytes = OiReport::new_long_crc(0x10, 0x02, &data);
+        let report = OiReport::read(&bytes).unwrap();
+        assert!(report.verify_crc());
+        assert!(check_frame(&bytes).is_ok());
+
+        // check value of the catalog entry
+        assert_eq!(CRC32.checksum(b"123456789"), 0xcbf43926);
+
+        let mut corrupt = bytes;
+        corrupt[5] ^= 0x01;
+        assert!(!OiReport::read(&corrupt).unwrap().verify_crc());
+        assert!(matches!(check_frame(&corrupt), Err(OIError::CrcMismatch)));
+
+        // short reports aren't checked
+        assert!(OiReport::new_short(0x00, 0x00, &[0; 5]).verify_crc());
+    }
+
+    #[test]
+    fn pull_rejects_corrupt_report() {
+        use usbd_hid::descriptor::SerializedDescriptor;
+        use usbd_hid::hid_class::HIDClass;
+
+        use crate::mock::MockUsbBus;
+        use crate::{OiKeyboardReport, OpenInputKeyboardHID};
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let bytes = OiReport::new_long_crc(0x10, 0x02, &[0x55; CRC_DATA_LEN]);
+        let mut corrupt = bytes;
+        corrupt[31] ^= 0x80;
+
+        host.send(&corrupt);
+        assert!(matches!(class.pull_host_data(), Err(OIError::CrcMismatch)));
+        host.send(&bytes);
+        assert!(class.pull_host_data().is_ok());
+    }
+}
@@ -0,0 +1,286 @@
+//! Several reports (e.g. keyboard + mouse) behind a single HID interface
+//!
+//! Report ids are shared by the whole interface, so the reports must not use the same ids except for the
+//! OpenInput collections, which [`CompositeReport::write_desc`] only keeps once. Pulled reports are routed to
+//! whichever report claims the id (`first` wins if both do, which is how OpenInput frames reach `first`).
+//!
+//! The descriptor has to outlive the class, so write it into a static buffer once at startup:
+//!
+//! ```ignore
+//! static mut DESC: [u8; 256] = [0; 256];
+//! let len = CompositeReport::<OiKeyboardReport, OiTrackballReport>::write_desc(unsafe { &mut DESC })?;
+//! let hid = HIDClass::new(&usb_alloc, unsafe { &DESC[..len] }, 1);
+//! ```
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::descriptor::{generator_prelude::*, SerializedDescriptor};
+use usbd_hid::hid_class::HIDClass;
+
+use crate::descriptor::{find, has_openinput_collections, OPENINPUT_DESC};
+use crate::dispatch::DispatchMeta;
+use crate::{pull_feature_report, OIError, OiReport, OpenInputHidReport, OPENINPUT_GLOBALS};
+
+#[derive(Default)]
+pub struct CompositeReport<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A: SerializedDescriptor, B: SerializedDescriptor> CompositeReport<A, B> {
+    /// concatenate both descriptors into `buf`, returns the total length
+    ///
+    /// `B`'s OpenInput collections (and the globals hand written descriptors put in front of them) are left out
+    /// if `A` has them too
+    pub fn write_desc(buf: &mut [u8]) -> Result<usize, OIError> {
+        let (a, b) = (A::desc(), B::desc());
+        let (b_head, b_tail) = match find(b, &OPENINPUT_DESC) {
+            Some(i) if has_openinput_collections(a) => {
+                let head = &b[..i];
+                (
+                    head.strip_suffix(&OPENINPUT_GLOBALS).unwrap_or(head),
+                    &b[i + OPENINPUT_DESC.len()..],
+                )
+            }
+            _ => (b, &[][..]),
+        };
+        let len = a.len() + b_head.len() + b_tail.len();
+        if buf.len() < len {
+            return Err(OIError::BufferTooSmall);
+        }
+        let (a_buf, rest) = buf.split_at_mut(a.len());
+        let (head_buf, rest) = rest.split_at_mut(b_head.len());
+        a_buf.copy_from_slice(a);
+        head_buf.copy_from_slice(b_head);
+        rest[..b_tail.len()].copy_from_slice(b_tail);
+        Ok(len)
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CompositeOutputReport<A, B> {
+    First(A),
+    Second(B),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CompositeInputReport<A, B> {
+    First(A),
+    Second(B),
+}
+
+impl<A: Serialize, B: Serialize> Serialize for CompositeInputReport<A, B> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            CompositeInputReport::First(a) => a.serialize(serializer),
+            CompositeInputReport::Second(b) => b.serialize(serializer),
+        }
+    }
+}
+
+impl<A: OpenInputHidReport, B: OpenInputHidReport> OpenInputHidReport for CompositeReport<A, B> {
+    type ReportId = u8;
+    type PullReport<'a> = CompositeOutputReport<A::PullReport<'a>, B::PullReport<'a>>
+    where
+        Self: 'a;
+    type PushReport<'r> = CompositeInputReport<A::PushReport<'r>, B::PushReport<'r>>;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        if A::ReportId::try_from(report_id).is_ok() {
+            self.first
+                .read_report(report_id, buf)
+                .map(CompositeOutputReport::First)
+        } else if B::ReportId::try_from(report_id).is_ok() {
+            self.second
+                .read_report(report_id, buf)
+                .map(CompositeOutputReport::Second)
+        } else {
+            Err(UsbError::ParseError.into())
+        }
+    }
+
+    /// routed by report id like output reports
+    fn pull_feature<'a, 'ep, Bus: UsbBus>(
+        &'a mut self,
+        hid: &mut HIDClass<'ep, Bus>,
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        pull_feature_report(self, hid)
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>>
+    where
+        Self: 'a,
+    {
+        match report {
+            CompositeOutputReport::First(a) => {
+                A::openinput_frame(a).map_err(CompositeOutputReport::First)
+            }
+            CompositeOutputReport::Second(b) => {
+                B::openinput_frame(b).map_err(CompositeOutputReport::Second)
+            }
+        }
+    }
+
+    fn push_report<'r, 'ep, Bus: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, Bus>,
+        report: Self::PushReport<'r>,
+    ) -> Result<(), OIError> {
+        match report {
+            CompositeInputReport::First(r) => self.first.push_report(hid, r),
+            CompositeInputReport::Second(r) => self.second.push_report(hid, r),
+        }
+    }
+
+    fn input_report(&self, report_id: u8, buf: &mut [u8]) -> Option<usize> {
+        self.first
+            .input_report(report_id, buf)
+            .or_else(|| self.second.input_report(report_id, buf))
+    }
+
+    fn feature_report(
+        &self,
+        report_id: u8,
+        meta: Option<&DispatchMeta>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        self.first
+            .feature_report(report_id, meta, buf)
+            .or_else(|| self.second.feature_report(report_id, meta, buf))
+    }
+
+    /// the first report's, falling back to the second
+    fn device_info(&self) -> Option<&DispatchMeta> {
+        self.first
+            .device_info()
+            .or_else(|| self.second.device_info())
+    }
+
+    fn set_boot_mode(&mut self, boot: bool) {
+        self.first.set_boot_mode(boot);
+        self.second.set_boot_mode(boot);
+    }
+
+    fn boot_mode(&self) -> bool {
+        self.first.boot_mode() || self.second.boot_mode()
+    }
+
+    fn reset_buffers(&mut self) {
+        self.first.reset_buffers();
+        self.second.reset_buffers();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::{LedFlags, OiKeyboardOutputReport};
+    use crate::OiKeyboardReport;
+
+    #[cfg(feature = "trackball")]
+    use crate::trackball::{OiTrackballOutputReport, OiTrackballReport};
+
+    #[cfg(feature = "trackball")]
+    type KeyboardTrackball = CompositeReport<OiKeyboardReport, OiTrackballReport>;
+
+    #[test]
+    #[cfg(feature = "trackball")]
+    fn routes_by_report_id() {
+        let mut report = KeyboardTrackball::default();
+
+        assert!(matches!(
+            report.read_report(0x02, &[0b010]),
+            Ok(CompositeOutputReport::First(
+                OiKeyboardOutputReport::Keyboard(leds)
+            )) if leds == LedFlags::CAPS_LOCK
+        ));
+
+        // both claim the OpenInput ids, the keyboard's collections are the ones in the descriptor
+        let frame = [0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        match report.read_report(0x20, &frame) {
+            Ok(CompositeOutputReport::First(OiKeyboardOutputReport::OpenInput(oi))) => {
+                assert!(oi.is_short())
+            }
+            _ => panic!("openinput report not routed to the keyboard"),
+        }
+
+        // trackball resolution multiplier feature report
+        assert!(matches!(
+            report.read_report(0x05, &[0x05, 0x01]),
+            Ok(CompositeOutputReport::Second(
+                OiTrackballOutputReport::ResolutionMultiplier(true)
+            ))
+        ));
+
+        assert!(report.read_report(0x42, &[0]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "trackball")]
+    fn concatenated_descriptor() {
+        let mut buf = [0; 512];
+        let len = KeyboardTrackball::write_desc(&mut buf).unwrap();
+        let desc = &buf[..len];
+        let (keyboard, trackball) = (OiKeyboardReport::desc(), OiTrackballReport::desc());
+        assert_eq!(&desc[..keyboard.len()], keyboard);
+        // the trackball's copy of the OpenInput collections and the globals before them are dropped
+        assert_eq!(
+            len,
+            keyboard.len() + trackball.len() - OPENINPUT_GLOBALS.len() - OPENINPUT_DESC.len()
+        );
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // REPORT_ID (2), (4) and (5)
+        for id in [0x02, 0x04, 0x05] {
+            assert!(find(desc, &[0x85, id]).is_some(), "{:x?}", desc);
+        }
+
+        // no OpenInput collections left in the trackball part
+        assert!(find(&desc[keyboard.len()..], &OPENINPUT_DESC).is_none());
+
+        assert!(matches!(
+            KeyboardTrackball::write_desc(&mut buf[..len - 1]),
+            Err(OIError::BufferTooSmall)
+        ));
+    }
+
+    #[test]
+    fn read_report_default() {
+        #[derive(Default)]
+        struct InputOnly;
+
+        impl OpenInputHidReport for InputOnly {
+            type ReportId = u8;
+            type PullReport<'a> = ();
+            type PushReport<'r> = u8;
+
+            fn push_report<'r, 'ep, B: UsbBus>(
+                &mut self,
+                _hid: &mut HIDClass<'ep, B>,
+                _report: Self::PushReport<'r>,
+            ) -> Result<(), OIError> {
+                Ok(())
+            }
+        }
+
+        assert!(matches!(
+            InputOnly.read_report(0x01, &[0]),
+            Err(OIError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn device_info() {
+        let report = CompositeReport::<OiKeyboardReport, OiKeyboardReport>::default();
+        assert!(report.device_info().is_none());
+    }
+}
@@ -0,0 +1,434 @@
+//! Reference OpenInput report descriptor
+//!
+//! Every report type has to end its descriptor with two vendor defined application collections, one per
+//! OpenInput report:
+//!
+//! ```text
+//! USAGE_PAGE (Vendor 0xFF00), USAGE (0)
+//! COLLECTION (Application)
+//!     REPORT_ID (0x20 short | 0x21 long)
+//!     USAGE (0), REPORT_COUNT (8 | 32), INPUT (Data,Arr,Abs)
+//!     USAGE (0), OUTPUT (Data,Arr,Abs)
+//! END_COLLECTION
+//! ```
+//!
+//! The reference firmware also sets LOGICAL_MINIMUM (0), LOGICAL_MAXIMUM (255) and REPORT_SIZE (8) in each
+//! collection, generated descriptors inherit them from the report before (these are global items) so they're
+//! left out here.
+//!
+//! The usage page and usage default to vendor page 0xFF00 usage 0, devices that need a different vendor page
+//! (e.g. to avoid clashing with another vendor collection in a composite device) can build the collections
+//! with [`openinput_collections`] and put them in a hand written descriptor. `gen_hid_descriptor` reports
+//! always generate the defaults, [`write_with_usage`] (or
+//! [`OpenInputHIDClassBuilder::openinput_usage`](crate::OpenInputHIDClassBuilder::openinput_usage)) copies
+//! their descriptor with the collections moved to another usage page.
+//!
+//! modified from https://github.com/openinput-fw/openinput/blob/a8723282bd50aa01a2062d9289c16087c4712c7e/src/protocol/reports.h
+
+use crate::meta_feature::META_FEATURE_REPORT_ID;
+use crate::{
+    concat_desc, OIError, LONG_LEN, OPENINPUT_LONG_REPORT_ID, OPENINPUT_SHORT_REPORT_ID, SHORT_LEN,
+};
+
+// TODO discuss ordering and derived value diff with openinput ppl
+pub const OPENINPUT_SHORT_COLLECTION: [u8; 20] = [
+    0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+    0x09, 0x00, /* USAGE (Vendor Usage 0) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x20, /*  REPORT_ID (0x20) */
+    0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+    0x95, 0x08, /*  REPORT_COUNT (8) */
+    0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+    0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+    0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+    0xc0, /* END_COLLECTION */
+];
+
+pub const OPENINPUT_LONG_COLLECTION: [u8; 20] = [
+    0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+    0x09, 0x00, /* USAGE (Vendor Usage 0) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x21, /*  REPORT_ID (0x21) */
+    0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+    0x95, 0x20, /*  REPORT_COUNT (32) */
+    0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+    0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+    0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+    0xc0, /* END_COLLECTION */
+];
+
+/// both collections, short first
+pub const OPENINPUT_DESC: [u8; 40] =
+    concat_desc(&OPENINPUT_SHORT_COLLECTION, &OPENINPUT_LONG_COLLECTION);
+
+pub const OPENINPUT_USAGE_PAGE: u16 = 0xFF00;
+pub const OPENINPUT_USAGE: u8 = 0x00;
+
+/// byte offsets of the USAGE_PAGE, USAGE, REPORT_ID and REPORT_COUNT values in a collection
+const USAGE_PAGE_OFFSET: usize = 1;
+const USAGE_OFFSET: usize = 4;
+const REPORT_ID_OFFSET: usize = 8;
+const REPORT_COUNT_OFFSET: usize = 12;
+
+const fn with_usage(mut collection: [u8; 20], usage_page: u16, usage: u8) -> [u8; 20] {
+    let page = usage_page.to_le_bytes();
+    collection[USAGE_PAGE_OFFSET] = page[0];
+    collection[USAGE_PAGE_OFFSET + 1] = page[1];
+    collection[USAGE_OFFSET] = usage;
+    collection
+}
+
+/// [`OPENINPUT_DESC`] with another application collection usage page and usage, the report ids stay
+/// 0x20/0x21
+pub const fn openinput_collections(usage_page: u16, usage: u8) -> [u8; 40] {
+    concat_desc(
+        &with_usage(OPENINPUT_SHORT_COLLECTION, usage_page, usage),
+        &with_usage(OPENINPUT_LONG_COLLECTION, usage_page, usage),
+    )
+}
+
+/// USAGE_PAGE, USAGE and REPORT_ID of the [`meta_feature`](crate::meta_feature) collection as generated
+const META_COLLECTION_HEAD: [u8; 9] = [
+    0x06,
+    0x00,
+    0xff, /* USAGE_PAGE (Vendor Page) */
+    0x09,
+    0x01, /* USAGE (Vendor Usage 1) */
+    0xa1,
+    0x01, /* COLLECTION (Application) */
+    0x85,
+    META_FEATURE_REPORT_ID, /*  REPORT_ID (0x22) */
+];
+
+/// copy `desc` into `buf` with its OpenInput collections moved to `usage_page` and `usage`, returns the length
+///
+/// for generated descriptors, which always use [`OPENINPUT_USAGE_PAGE`]. The meta feature collection (if
+/// `desc` has one) moves to `usage_page` too and keeps usage 1. Fails with [`OIError::Unsupported`] if `desc`
+/// doesn't have the default collections
+pub fn write_with_usage(
+    desc: &[u8],
+    usage_page: u16,
+    usage: u8,
+    buf: &mut [u8],
+) -> Result<usize, OIError> {
+    let short = find(desc, &OPENINPUT_SHORT_COLLECTION).ok_or(OIError::Unsupported)?;
+    let long = find(desc, &OPENINPUT_LONG_COLLECTION).ok_or(OIError::Unsupported)?;
+    let buf = buf.get_mut(..desc.len()).ok_or(OIError::BufferTooSmall)?;
+    buf.copy_from_slice(desc);
+    for (start, collection) in [
+        (short, OPENINPUT_SHORT_COLLECTION),
+        (long, OPENINPUT_LONG_COLLECTION),
+    ] {
+        buf[start..start + collection.len()]
+            .copy_from_slice(&with_usage(collection, usage_page, usage));
+    }
+    if let Some(meta) = find(desc, &META_COLLECTION_HEAD) {
+        let page = usage_page.to_le_bytes();
+        buf[meta + USAGE_PAGE_OFFSET..meta + USAGE_PAGE_OFFSET + 2].copy_from_slice(&page);
+    }
+    Ok(desc.len())
+}
+
+// the reference collections have to agree with the framing the rest of the crate uses
+const _: () = {
+    assert!(OPENINPUT_SHORT_COLLECTION[REPORT_ID_OFFSET] == OPENINPUT_SHORT_REPORT_ID);
+    assert!(OPENINPUT_SHORT_COLLECTION[REPORT_COUNT_OFFSET] as usize == SHORT_LEN);
+    assert!(OPENINPUT_LONG_COLLECTION[REPORT_ID_OFFSET] == OPENINPUT_LONG_REPORT_ID);
+    assert!(OPENINPUT_LONG_COLLECTION[REPORT_COUNT_OFFSET] as usize == LONG_LEN);
+};
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn occurrences(haystack: &[u8], needle: &[u8]) -> usize {
+    haystack
+        .windows(needle.len())
+        .filter(|&w| w == needle)
+        .count()
+}
+
+/// true if `desc` has each OpenInput collection exactly once and the short one comes first, use it in
+/// tests of custom reports
+pub fn has_openinput_collections(desc: &[u8]) -> bool {
+    has_openinput_collections_with(desc, OPENINPUT_USAGE_PAGE, OPENINPUT_USAGE)
+}
+
+/// [`has_openinput_collections`] for collections built with [`openinput_collections`]
+pub fn has_openinput_collections_with(desc: &[u8], usage_page: u16, usage: u8) -> bool {
+    let short = with_usage(OPENINPUT_SHORT_COLLECTION, usage_page, usage);
+    let long = with_usage(OPENINPUT_LONG_COLLECTION, usage_page, usage);
+    if occurrences(desc, &short) != 1 || occurrences(desc, &long) != 1 {
+        return false;
+    }
+    matches!(
+        (find(desc, &short), find(desc, &long)),
+        (Some(short), Some(long)) if short < long
+    )
+}
+
+/// iterator over the top level collections of a descriptor, see [`top_level_collections`]
+pub struct TopLevelCollections<'a> {
+    rest: &'a [u8],
+}
+
+/// split `desc` into its top level collections, each with the items in front of it up to its END_COLLECTION
+///
+/// lets tests compare descriptors a collection at a time. items after the last collection come as a piece of
+/// their own
+pub fn top_level_collections(desc: &[u8]) -> TopLevelCollections<'_> {
+    TopLevelCollections { rest: desc }
+}
+
+impl<'a> Iterator for TopLevelCollections<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rest.is_empty() {
+            return None;
+        }
+        let mut depth = 0usize;
+        let mut i = 0;
+        while i < self.rest.len() {
+            let prefix = self.rest[i];
+            let len = match prefix {
+                // long item, data size in the next byte
+                0xfe => 3 + self.rest.get(i + 1).copied().unwrap_or(0) as usize,
+                _ => 1 + [0, 1, 2, 4][(prefix & 0x03) as usize],
+            };
+            i = (i + len).min(self.rest.len());
+            match prefix & 0xfc {
+                0xa0 => depth += 1,
+                0xc0 => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                _ => (),
+            }
+        }
+        let (piece, rest) = self.rest.split_at(i);
+        self.rest = rest;
+        Some(piece)
+    }
+}
+
+/// panics unless `desc` matches `golden` collection by collection, printing the pieces that changed in hex
+///
+/// `golden` is one array per top level collection, so a change shows up in the collection it touched
+#[cfg(test)]
+pub(crate) fn assert_snapshot(name: &str, desc: &[u8], golden: &[&[u8]]) {
+    use core::fmt::Write;
+
+    let pieces: std::vec::Vec<&[u8]> = top_level_collections(desc).collect();
+    let mut diff = std::string::String::new();
+    for i in 0..pieces.len().max(golden.len()) {
+        let (got, expected) = (pieces.get(i).copied(), golden.get(i).copied());
+        if got == expected {
+            continue;
+        }
+        let (got, expected) = (got.unwrap_or_default(), expected.unwrap_or_default());
+        let at = got
+            .iter()
+            .zip(expected)
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| got.len().min(expected.len()));
+        write!(
+            diff,
+            "\ncollection {} differs at byte {}\n  expected {}\n       got {}",
+            i,
+            at,
+            hex::encode(expected),
+            hex::encode(got)
+        )
+        .unwrap();
+    }
+    assert!(
+        diff.is_empty(),
+        "{} descriptor changed, update its snapshot if that's intended:{}",
+        name,
+        diff
+    );
+}
+
+/// the last two collections of every hand written descriptor for [`assert_snapshot`], the OpenInput globals
+/// come with the short collection
+#[cfg(all(
+    test,
+    any(
+        feature = "absolute-pointer",
+        feature = "gamepad",
+        feature = "multitouch",
+        feature = "touchpad",
+        feature = "trackball"
+    )
+))]
+pub(crate) const OPENINPUT_SNAPSHOT: [&[u8]; 2] = [
+    &[
+        0x15, 0x00, /* LOGICAL_MINIMUM (0) */
+        0x26, 0xff, 0x00, /* LOGICAL_MAXIMUM (255) */
+        0x75, 0x08, /* REPORT_SIZE (8) */
+        0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+        0x09, 0x00, /* USAGE (Vendor Usage 0) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x20, /*  REPORT_ID (0x20) */
+        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+        0x95, 0x08, /*  REPORT_COUNT (8) */
+        0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+        0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+        0xc0, /* END_COLLECTION */
+    ],
+    &[
+        0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+        0x09, 0x00, /* USAGE (Vendor Usage 0) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x21, /*  REPORT_ID (0x21) */
+        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+        0x95, 0x20, /*  REPORT_COUNT (32) */
+        0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+        0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+        0xc0, /* END_COLLECTION */
+    ],
+];
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+    use crate::OiKeyboardReport;
+
+    #[test]
+    fn keyboard_collections() {
+        let desc = OiKeyboardReport::desc();
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // the generated collections come last except for the meta feature collection
+        let meta = top_level_collections(desc).last().unwrap();
+        assert!(find(meta, &[0x85, 0x22]).is_some(), "{:x?}", meta);
+        assert!(
+            desc[..desc.len() - meta.len()].ends_with(&OPENINPUT_DESC),
+            "{:x?}",
+            desc
+        );
+    }
+
+    #[test]
+    fn custom_usage_page() {
+        assert_eq!(
+            openinput_collections(OPENINPUT_USAGE_PAGE, OPENINPUT_USAGE),
+            OPENINPUT_DESC
+        );
+
+        let desc = openinput_collections(0xFF42, 0x01);
+        // USAGE_PAGE (0xFF42), USAGE (1), COLLECTION (Application), REPORT_ID
+        assert_eq!(
+            &desc[..9],
+            &[0x06, 0x42, 0xff, 0x09, 0x01, 0xa1, 0x01, 0x85, 0x20]
+        );
+        assert_eq!(
+            &desc[20..29],
+            &[0x06, 0x42, 0xff, 0x09, 0x01, 0xa1, 0x01, 0x85, 0x21]
+        );
+        assert_eq!(desc[REPORT_COUNT_OFFSET], SHORT_LEN as u8);
+        assert_eq!(desc[20 + REPORT_COUNT_OFFSET], LONG_LEN as u8);
+
+        assert!(has_openinput_collections_with(&desc, 0xFF42, 0x01));
+        assert!(!has_openinput_collections(&desc));
+        assert!(!has_openinput_collections_with(
+            &OPENINPUT_DESC,
+            0xFF42,
+            0x01
+        ));
+    }
+
+    #[test]
+    fn keyboard_with_usage() {
+        let desc = OiKeyboardReport::desc();
+        let mut buf = [0; 512];
+        let len = write_with_usage(desc, 0xFF42, 0x01, &mut buf).unwrap();
+        let moved = &buf[..len];
+        assert_eq!(len, desc.len());
+        assert!(
+            has_openinput_collections_with(moved, 0xFF42, 0x01),
+            "{:x?}",
+            moved
+        );
+        assert!(!has_openinput_collections(moved));
+        // the meta feature collection moves along, usage 1 and the rest stay
+        let meta = top_level_collections(moved).last().unwrap();
+        assert_eq!(
+            &meta[..9],
+            &[0x06, 0x42, 0xff, 0x09, 0x01, 0xa1, 0x01, 0x85, 0x22]
+        );
+        // only the vendor collections changed
+        let keyboard = top_level_collections(desc).next().unwrap();
+        assert!(moved.starts_with(keyboard));
+
+        assert!(matches!(
+            write_with_usage(desc, 0xFF42, 0x01, &mut buf[..len - 1]),
+            Err(OIError::BufferTooSmall)
+        ));
+        assert!(matches!(
+            write_with_usage(keyboard, 0xFF42, 0x01, &mut buf),
+            Err(OIError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn split_collections() {
+        let pieces: std::vec::Vec<_> = top_level_collections(&OPENINPUT_DESC).collect();
+        assert_eq!(
+            pieces,
+            [
+                &OPENINPUT_SHORT_COLLECTION[..],
+                &OPENINPUT_LONG_COLLECTION[..]
+            ]
+        );
+
+        // globals in front stay with the collection, nested collections don't split
+        let desc = [
+            0x15, 0x00, 0xa1, 0x01, 0xa1, 0x00, 0x81, 0x02, 0xc0, 0xc0, 0x26, 0xff, 0x00,
+        ];
+        let pieces: std::vec::Vec<_> = top_level_collections(&desc).collect();
+        assert_eq!(pieces, [&desc[..10], &desc[10..]]);
+
+        // truncated items end the last piece
+        assert_eq!(top_level_collections(&[0x26, 0xff]).count(), 1);
+        assert_eq!(top_level_collections(&[]).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "collection 1 differs at byte 12")]
+    fn snapshot_diff() {
+        let mut golden = OPENINPUT_LONG_COLLECTION;
+        golden[REPORT_COUNT_OFFSET] = 0x40;
+        assert_snapshot(
+            "openinput",
+            &OPENINPUT_DESC,
+            &[&OPENINPUT_SHORT_COLLECTION, &golden],
+        );
+    }
+
+    #[test]
+    fn rejects_broken_collections() {
+        let mut desc = std::vec::Vec::from(OPENINPUT_DESC);
+        assert!(has_openinput_collections(&desc));
+
+        // wrong REPORT_COUNT in the long collection
+        desc[20 + REPORT_COUNT_OFFSET] = 0x40;
+        assert!(!has_openinput_collections(&desc));
+
+        // swapped
+        let swapped: [u8; 40] =
+            concat_desc(&OPENINPUT_LONG_COLLECTION, &OPENINPUT_SHORT_COLLECTION);
+        assert!(!has_openinput_collections(&swapped));
+
+        // duplicated
+        let mut twice = std::vec::Vec::from(OPENINPUT_DESC);
+        twice.extend_from_slice(&OPENINPUT_SHORT_COLLECTION);
+        assert!(!has_openinput_collections(&twice));
+    }
+}
@@ -0,0 +1,291 @@
+//! Pull → dispatch → push in a single call
+//!
+//! [`OpenInputDevice`] owns the HID class and a [`Dispatch`], firmware calls [`OpenInputDevice::poll`] from its
+//! usb loop after polling the usb device. Reports that aren't OpenInput frames (keyboard leds etc.) are handed
+//! back to the caller.
+
+use heapless::Vec;
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+
+use crate::dispatch::pending::PendingToken;
+use crate::dispatch::{Dispatch, DispatchMeta, DispatchResponse, Error};
+use crate::{OIError, OiReport, OpenInputHIDClass, OpenInputHidReport, LONG_LEN};
+
+/// what a single [`OpenInputDevice::poll`] did
+pub enum PollOutcome<'a, R: OpenInputHidReport + 'a> {
+    /// nothing pending from the host
+    Idle,
+    /// request handled and the reply pushed
+    Dispatched { page: u8, fn_id: u8 },
+    /// request failed and an ff_error report was pushed instead
+    ErrorSent { page: u8, fn_id: u8, error: Error },
+    /// the handler deferred its reply, nothing was pushed, see [`Dispatch::resolve`]
+    Deferred {
+        page: u8,
+        fn_id: u8,
+        token: PendingToken,
+    },
+    /// the IN endpoint is busy with an earlier reply, `remaining` reports (the reply or what's left of its
+    /// stream) still wait and the next request is left unread
+    Streaming { remaining: usize },
+    /// not an OpenInput frame, left for the caller
+    Report(R::PullReport<'a>),
+}
+
+pub struct OpenInputDevice<'ep, B: UsbBus, R: OpenInputHidReport> {
+    pub class: OpenInputHIDClass<'ep, B, R>,
+    pub dispatch: Dispatch,
+    /// reply the IN endpoint was busy for, pushed before anything else
+    unsent: Option<Vec<u8, LONG_LEN>>,
+}
+
+impl<'ep, B: UsbBus, R: OpenInputHidReport> OpenInputDevice<'ep, B, R> {
+    pub fn new(class: OpenInputHIDClass<'ep, B, R>, dispatch: Dispatch) -> Self {
+        Self {
+            class,
+            dispatch,
+            unsent: None,
+        }
+    }
+
+    /// firmware vendor, version etc. the host reads from the info page
+    pub fn device_info(&self) -> &DispatchMeta {
+        &self.dispatch.meta
+    }
+
+    /// push what's left of an earlier reply, then handle at most one report from the host
+    ///
+    /// nothing is pulled until the IN endpoint took every report of the earlier reply, a reply (or frames of a
+    /// stream) that doesn't fit the endpoint right away goes out on later polls
+    pub fn poll(&mut self) -> Result<PollOutcome<'_, R>, OIError> {
+        let OpenInputHIDClass { inner, report, .. } = &mut self.class;
+        if let Some(reply) = &self.unsent {
+            match inner.push_raw_input(reply) {
+                Ok(_) => self.unsent = None,
+                Err(UsbError::WouldBlock) => {
+                    return Ok(PollOutcome::Streaming {
+                        remaining: 1 + self.dispatch.queued_count(),
+                    })
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match self
+            .dispatch
+            .flush_queued(|frame| inner.push_raw_input(frame).map(drop))
+        {
+            Ok(()) => (),
+            Err(UsbError::WouldBlock) => {
+                return Ok(PollOutcome::Streaming {
+                    remaining: self.dispatch.queued_count(),
+                })
+            }
+            Err(e) => return Err(e.into()),
+        }
+        let pulled = match report.pull_ep_out(inner) {
+            Ok(pulled) => pulled,
+            Err(OIError::UsbError(UsbError::WouldBlock)) => return Ok(PollOutcome::Idle),
+            Err(e) => return Err(e),
+        };
+        let frame = match R::openinput_frame(pulled) {
+            Ok(frame) => frame,
+            Err(other) => return Ok(PollOutcome::Report(other)),
+        };
+
+        let OiReport {
+            id,
+            function_page: page,
+            function_id: fn_id,
+            ..
+        } = frame;
+        let res = self.dispatch.dispatch_report(&frame);
+        if let Some(token) = res.as_ref().ok().and_then(DispatchResponse::deferred) {
+            return Ok(PollOutcome::Deferred { page, fn_id, token });
+        }
+        let (reply, outcome): (Vec<u8, LONG_LEN>, _) = match res {
+            Ok(res) => (
+                res.reply(id, page, fn_id),
+                PollOutcome::Dispatched { page, fn_id },
+            ),
+            Err(error) => (
+                error.serialize_error(id, page, fn_id),
+                PollOutcome::ErrorSent { page, fn_id, error },
+            ),
+        };
+        #[cfg(feature = "wireless")]
+        let reply = crate::wireless::address_reply(frame.address_and_data().0, reply);
+        match inner.push_raw_input(&reply) {
+            Ok(_) => (),
+            // the stream's queued frames wait behind it
+            Err(UsbError::WouldBlock) => {
+                self.unsent = Some(reply);
+                return Ok(outcome);
+            }
+            Err(e) => {
+                self.dispatch.clear_queued();
+                return Err(e.into());
+            }
+        }
+        match self
+            .dispatch
+            .flush_queued(|frame| inner.push_raw_input(frame).map(drop))
+        {
+            Ok(()) | Err(UsbError::WouldBlock) => Ok(outcome),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+    use usbd_hid::hid_class::HIDClass;
+
+    use super::*;
+    use crate::mock::MockUsbBus;
+    use crate::{OiKeyboardReport, OpenInputKeyboardHID};
+
+    #[test]
+    fn poll_round_trip() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut device = OpenInputDevice::new(
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1)),
+            Dispatch::default(),
+        );
+        assert!(matches!(device.poll(), Ok(PollOutcome::Idle)));
+
+        // info page, protocol version
+        host.send(&[0x20, 0x00, 0x00, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::Dispatched {
+                page: 0x00,
+                fn_id: 0x00
+            })
+        ));
+        let [major, minor, patch] = crate::PROTOCOL_VERSION.to_bytes();
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x20, 0x00, 0x00, major, minor, patch, 0, 0])
+        );
+
+        // nothing registered on page 0x42
+        host.send(&[0x20, 0x42, 0x01, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::ErrorSent {
+                page: 0x42,
+                fn_id: 0x01,
+                error: Error::UnsupportedPage
+            })
+        ));
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x20, 0xFF, 0x04, 0x42, 0x01, 0, 0, 0])
+        );
+
+        assert!(matches!(device.poll(), Ok(PollOutcome::Idle)));
+        assert_eq!(host.recv(), None);
+    }
+
+    fn stream(_: &[u8], ctx: crate::dispatch::DispatchContext) -> crate::dispatch::DispatchReturn {
+        let mut res = crate::dispatch::stream::DispatchResponses::<3>::new();
+        res.extend_from_slice(&[0xAA; 60])?;
+        ctx.stream(res)
+    }
+
+    #[test]
+    fn poll_streamed_reply() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut table = heapless::FnvIndexMap::new();
+        let mut page = heapless::FnvIndexMap::new();
+        page.insert(0x00, crate::dispatch::DispatchHandler::Function(stream))
+            .ok()
+            .unwrap();
+        table.insert(0x42, page).ok().unwrap();
+        let mut device = OpenInputDevice::new(
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1)),
+            Dispatch::new_raw(table, Default::default()),
+        );
+        let header = |seq: u8| vec![0x21, 0x42, 0x00, seq, 3];
+
+        host.send(&[0x20, 0x42, 0x00, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::Dispatched {
+                page: 0x42,
+                fn_id: 0x00
+            })
+        ));
+        for seq in 0..3 {
+            let frame = host.recv().unwrap();
+            assert_eq!(frame[..5], header(seq));
+            assert_eq!(frame.len(), LONG_LEN);
+        }
+        assert_eq!(host.recv(), None);
+
+        // a stream whose first frame went out but the endpoint is busy for the rest, the next request
+        // waits until they're all pushed
+        device
+            .dispatch
+            .dispatch(&OiReport::new_short(0x42, 0x00, &[0; 5]));
+        host.send(&[0x20, 0x42, 0x00, 0, 0, 0, 0, 0]);
+        host.set_in_busy(true);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::Streaming { remaining: 2 })
+        ));
+        host.set_in_busy(false);
+        assert!(matches!(device.poll(), Ok(PollOutcome::Dispatched { .. })));
+        let seqs: std::vec::Vec<_> = core::iter::from_fn(|| host.recv()).map(|f| f[3]).collect();
+        assert_eq!(seqs, [1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn poll_in_busy() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut device = OpenInputDevice::new(
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1)),
+            Dispatch::default(),
+        );
+
+        // the request is handled, its reply waits for the endpoint
+        host.send(&[0x20, 0x00, 0x00, 0, 0, 0, 0, 0]);
+        host.set_in_busy(true);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::Dispatched {
+                page: 0x00,
+                fn_id: 0x00
+            })
+        ));
+        assert_eq!(host.recv(), None);
+
+        // the next request stays unread while the endpoint is busy
+        host.send(&[0x20, 0x42, 0x01, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::Streaming { remaining: 1 })
+        ));
+
+        host.set_in_busy(false);
+        assert!(matches!(
+            device.poll(),
+            Ok(PollOutcome::ErrorSent {
+                page: 0x42,
+                fn_id: 0x01,
+                ..
+            })
+        ));
+        let [major, minor, patch] = crate::PROTOCOL_VERSION.to_bytes();
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x20, 0x00, 0x00, major, minor, patch, 0, 0])
+        );
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x20, 0xFF, 0x04, 0x42, 0x01, 0, 0, 0])
+        );
+    }
+}
@@ -1,84 +1,232 @@
+use core::any::Any;
+use core::cell::Cell;
+
 use heapless::{FnvIndexMap, String, Vec};
+use usb_device::UsbError;
 
-use crate::{OiReport, OPENINPUT_SHORT_REPORT_ID, LONG_LEN, SHORT_LEN};
+use crate::{
+    OIError, OiReport, OiReportId, ProtocolVersion, ReportSize, LONG_LEN, OPENINPUT_SHORT_REPORT_ID,
+};
 
-const ERROR_FUNCTION_PAGE: u8 = 0xFF;
-const INFO_FUNCTION_PAGE: u8 = 0x00;
+#[cfg(feature = "absolute-pointer")]
+pub mod abs_pointer;
+pub mod access;
+pub mod actuation;
+pub mod args;
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "auth")]
+pub mod auth;
+pub mod capabilities;
+#[cfg(feature = "challenge")]
+pub mod challenge;
+#[cfg(any(feature = "auth", feature = "challenge"))]
+mod crypto;
+#[cfg(feature = "debug-page")]
+pub mod debug;
+pub mod fragment;
+pub mod pending;
+pub mod reply;
+pub mod report_rate;
+pub mod scan;
+pub mod stats;
+pub mod stream;
+pub mod vendor;
 
-// fn params may be 6 or 29 bytes
+pub use args::FunctionArgs;
+pub use capabilities::DeviceCapabilityFlags;
+pub use reply::{FunctionReply, ResponseWriter};
+pub use stats::DispatchStats;
+pub use vendor::VendorPageError;
+
+/// function page of ff_error replies, see [`Error`]
+pub const ERROR_FUNCTION_PAGE: u8 = 0xFF;
+const INFO_FUNCTION_PAGE: u8 = 0x00;
 
 // TODO UnsupportedFunction should return what requested page and ID caused the error
 /// https://openinput.readthedocs.io/projects/protocol/en/latest/device-protocol/functions/ff_error.html
-#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
 pub enum Error {
+    /// byte offset of the offending parameter within the function data (0 is the first byte after the function id)
     InvalidValue(u8),
+    /// the page exists but doesn't implement the function
     UnsupportedFunction,
-    Custom([u8; LONG_LEN - ERROR_PREFIX_LEN]),
+    /// request tag missing or wrong, see [`auth`]
+    AuthenticationFailed,
+    /// no page with the requested id, not part of the openinput spec (which only has unsupported function)
+    UnsupportedPage,
+    /// every deferred request slot is taken, see [`pending`]. not part of the openinput spec
+    Busy,
+    /// the function needs an authenticated session, see [`access`]. not part of the openinput spec
+    PermissionDenied,
+    /// nul padded ASCII, build it with [`Error::custom`]
+    Custom([u8; CUSTOM_ERROR_LEN]),
+    /// firmware specific failure with a stable code, sent as a custom error with a structured payload
+    ///
+    /// ```text
+    /// report id | 0xFF | 0xFE | page | fn id | DEVICE_ERROR_MARKER | code | detail...
+    /// ```
+    ///
+    /// custom messages are ASCII so they never start with [`DEVICE_ERROR_MARKER`]. trailing zero bytes of
+    /// `detail` aren't sent and hosts read missing bytes as zero, a detail of up to 1 byte fits a short report
+    Device {
+        code: u8,
+        detail: [u8; DEVICE_ERROR_DETAIL_LEN],
+    },
+}
+
+/// longest [`Error::Custom`] message, messages up to 3 bytes fit a short report
+pub const CUSTOM_ERROR_LEN: usize = LONG_LEN - ERROR_PREFIX_LEN;
+
+/// first payload byte of an [`Error::Device`], not ASCII so it can't start a custom message
+pub const DEVICE_ERROR_MARKER: u8 = 0xFF;
+
+/// room left in a long report after the marker and code of an [`Error::Device`]
+pub const DEVICE_ERROR_DETAIL_LEN: usize = CUSTOM_ERROR_LEN - 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CustomMessageError {
+    /// longer than [`CUSTOM_ERROR_LEN`]
+    TooLong,
+    /// not ASCII or contains a nul, hosts read up to the first nul
+    NotAscii,
 }
 
 impl Error {
+    /// errors if `message` is longer than [`CUSTOM_ERROR_LEN`], isn't ASCII or contains a nul
+    pub fn custom(message: &str) -> Result<Self, CustomMessageError> {
+        if message.len() > CUSTOM_ERROR_LEN {
+            return Err(CustomMessageError::TooLong);
+        }
+        if !message.bytes().all(|c| c.is_ascii() && c != 0) {
+            return Err(CustomMessageError::NotAscii);
+        }
+        Ok(Self::custom_truncating(message))
+    }
+
+    /// never fails, `message` is truncated to [`CUSTOM_ERROR_LEN`] bytes and anything that isn't ASCII (or is
+    /// a nul) becomes `?`
+    pub fn custom_truncating(message: &str) -> Self {
+        let mut ascii = [0; CUSTOM_ERROR_LEN];
+        for (dst, c) in ascii.iter_mut().zip(message.bytes()) {
+            *dst = if c.is_ascii() && c != 0 { c } else { b'?' };
+        }
+        Self::Custom(ascii)
+    }
+
+    /// `detail` is truncated to [`DEVICE_ERROR_DETAIL_LEN`] bytes
+    pub fn device(code: u8, detail: &[u8]) -> Self {
+        let mut padded = [0; DEVICE_ERROR_DETAIL_LEN];
+        for (dst, &b) in padded.iter_mut().zip(detail) {
+            *dst = b;
+        }
+        Self::Device {
+            code,
+            detail: padded,
+        }
+    }
+
+    /// the message of a custom error
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Self::Custom(ascii) => {
+                let len = ascii.iter().position(|&c| c == 0).unwrap_or(ascii.len());
+                // can't fail, custom messages are ASCII
+                core::str::from_utf8(&ascii[..len]).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// not part of the openinput spec, only sent by devices with the `auth` feature
+    pub fn new_auth_error() -> Self {
+        Self::AuthenticationFailed
+    }
+
     pub fn id(&self) -> u8 {
         match self {
             Self::InvalidValue(_) => 0x01,
             Self::UnsupportedFunction => 0x02,
-            Self::Custom(_) => 0xFE,
+            Self::AuthenticationFailed => 0x03,
+            Self::UnsupportedPage => 0x04,
+            Self::Busy => 0x05,
+            Self::PermissionDenied => 0x06,
+            Self::Custom(_) | Self::Device { .. } => 0xFE,
         }
     }
 
-    pub(crate) fn serialize_error(&self, page: u8, id: u8) -> Vec<u8, 32> {
-        let invalid_data = &mut [page, id, 0];
-        let unsupported_data = &[page, id];
-        let o = match *self {
+    /// ff_error report sized like any other reply to `request_id`, so custom messages over 3 bytes are
+    /// promoted to a long report
+    pub(crate) fn serialize_error(&self, request_id: u8, page: u8, id: u8) -> Vec<u8, LONG_LEN> {
+        let mut data: Vec<u8, DISPATCH_LONG_RET_LEN> = Vec::new();
+        // can't fail, page + id + the longest custom message is exactly a long report
+        let _ = data.extend_from_slice(&[page, id]);
+        match self {
             Error::InvalidValue(index) => {
-                invalid_data[2] = index;
-                OiReport {
-                    id: OPENINPUT_SHORT_REPORT_ID,
-                    function_page: ERROR_FUNCTION_PAGE,
-                    function_id: self.id(),
-                    data: invalid_data,
-                }
+                let _ = data.push(*index);
             }
-            Error::UnsupportedFunction => OiReport {
-                id: OPENINPUT_SHORT_REPORT_ID,
-                function_page: ERROR_FUNCTION_PAGE,
-                function_id: self.id(),
-                data: unsupported_data,
-            },
-            // only custom error type might need to fit in a long report
-            Error::Custom(ascii) => {
-                let mut rep = OiReport {
-                    id: OPENINPUT_SHORT_REPORT_ID,
-                    function_page: ERROR_FUNCTION_PAGE,
-                    function_id: self.id(),
-                    data: &[],
-                };
-                // bit too large but whatever
-                let mut buf = [0; LONG_LEN];
-
-                let mut nulls = ascii.iter().enumerate().filter(|(_, &char)| char == 0);
-                // if first null ascii char is at index 3 it can fit in a short report, otherwise long report
-                let len = match nulls.next() {
-                    Some((i, _)) => {
-                        if i <= 3 {
-                            rep.id = super::OPENINPUT_SHORT_REPORT_ID;
-                            i
-                        } else {
-                            i
-                        }
-                    }
-                    None => {
-                        rep.id = super::OPENINPUT_SHORT_REPORT_ID;
-                        // no null means full report
-                        LONG_LEN - ERROR_PREFIX_LEN
-                    }
-                };
-                buf[..len].copy_from_slice(&ascii[..len]);
-
-                rep
+            Error::UnsupportedFunction
+            | Error::AuthenticationFailed
+            | Error::UnsupportedPage
+            | Error::Busy
+            | Error::PermissionDenied => (),
+            Error::Custom(_) => {
+                let _ = data.extend_from_slice(self.message().unwrap_or_default().as_bytes());
             }
-        };
+            Error::Device { code, detail } => {
+                let len = detail.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+                let _ = data.extend_from_slice(&[DEVICE_ERROR_MARKER, *code]);
+                let _ = data.extend_from_slice(&detail[..len]);
+            }
+        }
+        DispatchResponse::from(data).reply(request_id, ERROR_FUNCTION_PAGE, self.id())
+    }
+}
+
+/// custom messages are shown as text
+impl core::fmt::Debug for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::InvalidValue(index) => f.debug_tuple("InvalidValue").field(index).finish(),
+            Self::UnsupportedFunction => f.write_str("UnsupportedFunction"),
+            Self::AuthenticationFailed => f.write_str("AuthenticationFailed"),
+            Self::UnsupportedPage => f.write_str("UnsupportedPage"),
+            Self::Busy => f.write_str("Busy"),
+            Self::PermissionDenied => f.write_str("PermissionDenied"),
+            Self::Custom(_) => f
+                .debug_tuple("Custom")
+                .field(&self.message().unwrap_or_default())
+                .finish(),
+            Self::Device { code, detail } => f
+                .debug_struct("Device")
+                .field("code", code)
+                .field("detail", detail)
+                .finish(),
+        }
+    }
+}
 
-        o.into()
+#[cfg(feature = "defmt")]
+impl defmt::Format for Error {
+    fn format(&self, f: defmt::Formatter) {
+        match self {
+            Self::InvalidValue(index) => defmt::write!(f, "InvalidValue({=u8})", index),
+            Self::UnsupportedFunction => defmt::write!(f, "UnsupportedFunction"),
+            Self::AuthenticationFailed => defmt::write!(f, "AuthenticationFailed"),
+            Self::UnsupportedPage => defmt::write!(f, "UnsupportedPage"),
+            Self::Busy => defmt::write!(f, "Busy"),
+            Self::PermissionDenied => defmt::write!(f, "PermissionDenied"),
+            Self::Custom(_) => {
+                defmt::write!(f, "Custom({=str})", self.message().unwrap_or_default())
+            }
+            Self::Device { code, detail } => defmt::write!(
+                f,
+                "Device {{ code: {=u8}, detail: {=[u8]} }}",
+                code,
+                &detail[..]
+            ),
+        }
     }
 }
 
@@ -87,201 +235,3721 @@ const DISPATCH_PREFIX_LEN: usize = 3;
 /// ReportId, FnPage (0xFF), ErrorId, FnPage, FnId
 const ERROR_PREFIX_LEN: usize = DISPATCH_PREFIX_LEN + 2;
 
-// TODO better names
-const DISPATCH_LONG_RET_LEN: usize = LONG_LEN - DISPATCH_PREFIX_LEN;
-const DISPATCH_SHORT_RET_LEN: usize = SHORT_LEN - DISPATCH_PREFIX_LEN;
+/// function parameters a handler gets from a short request, everything after the 3 byte prefix of the 8 byte report
+pub const DISPATCH_SHORT_PARAM_LEN: usize = ReportSize::Short.payload_len();
+/// function parameters a handler gets from a long request, everything after the 3 byte prefix of the 32 byte report
+pub const DISPATCH_LONG_PARAM_LEN: usize = ReportSize::Long.payload_len();
+
+/// reply payload after the prefix, replies use the request layout so these match the param lengths
+const DISPATCH_SHORT_RET_LEN: usize = ReportSize::Short.payload_len();
+const DISPATCH_LONG_RET_LEN: usize = ReportSize::Long.payload_len();
+/// long reply payload handlers can fill, the `wireless` address takes a byte of it
+pub(crate) const LONG_REPLY_CAPACITY: usize =
+    DISPATCH_LONG_RET_LEN - cfg!(feature = "wireless") as usize;
+
+const _: () =
+    assert!(DISPATCH_PREFIX_LEN + DISPATCH_SHORT_PARAM_LEN == ReportSize::Short.total_len());
+const _: () =
+    assert!(DISPATCH_PREFIX_LEN + DISPATCH_LONG_PARAM_LEN == ReportSize::Long.total_len());
+// the error page, function id and failing page/id pair fill the prefix of a custom error
+const _: () =
+    assert!(ERROR_PREFIX_LEN + CUSTOM_ERROR_LEN == DISPATCH_PREFIX_LEN + DISPATCH_LONG_RET_LEN);
 
 /// newtype to enforce proper output serailization
-pub struct DispatchResponse(Vec<u8, DISPATCH_LONG_RET_LEN>);
+///
+/// the report size is fixed for replies built with [`DispatchResponse::raw`]. a handler that defers its reply
+/// returns the [`PendingToken`](pending::PendingToken) converted into a response, see [`pending`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DispatchResponse(
+    Vec<u8, DISPATCH_LONG_RET_LEN>,
+    Option<ReportSize>,
+    Option<pending::PendingToken>,
+);
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DispatchResponse {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "DispatchResponse({=[u8]})", self.payload())
+    }
+}
 
 impl DispatchResponse {
-    // TODO dont panic
-    /// pad response to fill into report size
-    fn report<'a>(&'a mut self, page: u8, fn_id: u8) -> OiReport<'a> {
-        if self.0.len() > DISPATCH_SHORT_RET_LEN {
-            self.0.resize(DISPATCH_SHORT_RET_LEN, 0).unwrap();
-            OiReport::new_short(page, fn_id, self.0.as_slice().try_into().unwrap())
+    /// reply data without padding, raw replies as they were given, empty for deferred replies
+    pub fn payload(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// the token of a deferred reply, nothing is sent until it's passed to [`Dispatch::resolve`]
+    pub fn deferred(&self) -> Option<pending::PendingToken> {
+        self.2
+    }
+
+    /// reply with `report` as is, e.g. to echo a frame, instead of padding the payload to the smallest
+    /// report it fits in
+    ///
+    /// `report` has to be a whole short or long report for the function `ctx` belongs to, anything else is a
+    /// bug in the handler and panics in debug builds. The reply goes out with the request's page and function id
+    /// either way. With the `wireless` feature the address of a long report is replaced with the request's
+    pub fn raw(report: &OiReport, ctx: &DispatchContext) -> Self {
+        let size = report.size();
+        debug_assert!(
+            size.map_or(false, |size| report.data().len() == size.payload_len())
+                && report.function_page() == ctx.page
+                && report.function_id() == ctx.fn_id,
+            "raw reply framing"
+        );
+        #[cfg(feature = "wireless")]
+        let data = report.address_and_data().1;
+        #[cfg(not(feature = "wireless"))]
+        let data = report.data();
+        // can't truncate a whole report, the payload of a long report is DISPATCH_LONG_RET_LEN bytes
+        let data = &data[..data.len().min(DISPATCH_LONG_RET_LEN)];
+        Self(Vec::from_slice(data).unwrap_or_default(), size, None)
+    }
+
+    /// raw replies keep their size, others are short if the request was short and the payload fits
+    fn size_for(&self, request_short: bool) -> ReportSize {
+        match self.1 {
+            Some(size) => size,
+            None if request_short && self.0.len() <= DISPATCH_SHORT_RET_LEN => ReportSize::Short,
+            None => ReportSize::Long,
+        }
+    }
+
+    /// pad the payload to the smallest report it fits in, short up to 5 bytes and long otherwise
+    pub fn report(&mut self, page: u8, fn_id: u8) -> Result<OiReport<'_>, OIError> {
+        self.report_for(OiReportId::Short, page, fn_id)
+    }
+
+    /// pad the payload to match a request sent as `request`, short only if the request was short and the
+    /// payload fits (see [`DispatchContext::request_report_id`]), fails for deferred replies
+    pub fn report_for(
+        &mut self,
+        request: OiReportId,
+        page: u8,
+        fn_id: u8,
+    ) -> Result<OiReport<'_>, OIError> {
+        if self.2.is_some() {
+            return Err(OIError::SerializationError);
+        }
+        if self.size_for(request == OiReportId::Short) == ReportSize::Short {
+            self.0
+                .resize(DISPATCH_SHORT_RET_LEN, 0)
+                .map_err(|_| OIError::SerializationError)?;
+            let data = self
+                .0
+                .as_slice()
+                .try_into()
+                .map_err(|_| OIError::SerializationError)?;
+            Ok(OiReport::new_short(page, fn_id, data))
         } else {
-            self.0.resize(DISPATCH_LONG_RET_LEN, 0).unwrap();
-            OiReport::new_long(page, fn_id, self.0.as_slice().try_into().unwrap())
+            // never truncates, the payload can't be longer than a long report
+            self.0
+                .resize(DISPATCH_LONG_RET_LEN, 0)
+                .map_err(|_| OIError::SerializationError)?;
+            let data = self
+                .0
+                .as_slice()
+                .try_into()
+                .map_err(|_| OIError::SerializationError)?;
+            Ok(OiReport::new_long(page, fn_id, data))
+        }
+    }
+
+    /// serialize into a padded reply, short if the request was short and the payload fits, long otherwise
+    ///
+    /// the reference firmware answers in the request's report size and switches to a long report when the
+    /// reply doesn't fit a short one, hosts read both input reports so this is always safe
+    ///
+    /// with the `wireless` feature long replies leave a byte for the address in front of the payload, which
+    /// `address_reply` fills in, and payloads that don't fit next to it are answered with an error instead
+    ///
+    /// empty for deferred replies, there is nothing to send yet
+    pub(crate) fn reply(&self, request_id: u8, page: u8, fn_id: u8) -> Vec<u8, LONG_LEN> {
+        if self.2.is_some() {
+            return Vec::new();
+        }
+        let size = self.size_for(request_id == OPENINPUT_SHORT_REPORT_ID);
+        let start = match size {
+            ReportSize::Short => DISPATCH_PREFIX_LEN,
+            ReportSize::Long => DISPATCH_PREFIX_LEN + DISPATCH_LONG_RET_LEN - LONG_REPLY_CAPACITY,
+        };
+        if start + self.0.len() > size.total_len() {
+            // errors name the function they answer after the error id
+            let (page, fn_id) = match page {
+                ERROR_FUNCTION_PAGE => (self.0[0], self.0[1]),
+                _ => (page, fn_id),
+            };
+            return Error::custom_truncating("reply too long")
+                .serialize_error(request_id, page, fn_id);
         }
+        let mut buf = [0; LONG_LEN];
+        buf[..DISPATCH_PREFIX_LEN].copy_from_slice(&[size.report_id(), page, fn_id]);
+        buf[start..start + self.0.len()].copy_from_slice(&self.0);
+        // can't fail, the total length is at most LONG_LEN
+        Vec::from_slice(&buf[..size.total_len()]).unwrap_or_default()
     }
 }
 
 impl From<Vec<u8, DISPATCH_LONG_RET_LEN>> for DispatchResponse {
     fn from(src: Vec<u8, DISPATCH_LONG_RET_LEN>) -> Self {
-        Self(src)
+        Self(src, None, None)
     }
 }
 
-type DispatchReturn = Result<DispatchResponse, Error>;
-type DispatchFn = for<'input, 'ctx> fn(&[u8], DispatchContext<'ctx>) -> DispatchReturn;
+/// defer the reply, see [`pending`]
+impl From<pending::PendingToken> for DispatchResponse {
+    fn from(token: pending::PendingToken) -> Self {
+        Self(Vec::new(), None, Some(token))
+    }
+}
 
-// NOTE: table lookups are O(2) but they need to do hashing before lookup so O(n) without hashing would probably be faster.
-type DispatchTable = FnvIndexMap<u8, FnvIndexMap<u8, DispatchFn, 8>, 8>;
+pub type DispatchReturn = Result<DispatchResponse, Error>;
+pub type DispatchFn = for<'input, 'ctx> fn(&[u8], DispatchContext<'ctx>) -> DispatchReturn;
+/// catch-all of a page for function ids without a handler, gets the function id (without the sequence
+/// number) first, see [`Dispatch::register_page_default`]
+pub type PageDefaultFn = for<'input, 'ctx> fn(u8, &[u8], DispatchContext<'ctx>) -> DispatchReturn;
 
-pub struct DispatchContext<'a> {
-    table: &'a DispatchTable,
-    meta: &'a DispatchMeta,
+/// a whole function page shipped as one unit, e.g. by a third party crate
+///
+/// `call` takes `&self` since dispatching does, pages with state need interior mutability that is `Sync` like
+/// a `critical_section::Mutex` or atomics, so the dispatcher can be a `static` shared with interrupt handlers
+pub trait FunctionPage: Sync {
+    fn page_id(&self) -> u8;
+
+    /// function ids this page implements, reported by `supported_functions`
+    fn functions(&self) -> &'static [u8];
+
+    /// ids from [`FunctionPage::functions`] the spec marks optional, at most 8
+    fn optional_functions(&self) -> &'static [u8] {
+        &[]
+    }
+
+    /// bit `n` is set if the `n`th optional function is implemented, e.g. depending on the hardware the
+    /// firmware runs on. functions with a cleared bit are left out of `supported_functions` and answer
+    /// [`Error::UnsupportedFunction`] without reaching [`FunctionPage::call`]
+    fn capabilities(&self) -> u8 {
+        u8::MAX
+    }
+
+    /// listed in [`FunctionPage::functions`] and not an optional function with its capability bit cleared
+    fn implements(&self, fn_id: u8) -> bool {
+        match self.optional_functions().iter().position(|&id| id == fn_id) {
+            Some(bit) => bit < 8 && self.capabilities() & (1 << bit) != 0,
+            None => self.functions().contains(&fn_id),
+        }
+    }
+
+    /// only called with ids [`FunctionPage::implements`]
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn;
 }
 
-pub struct Dispatch {
-    /// 8 pages, max 8 functions per page (implementation detail)
-    table: DispatchTable,
-    pub meta: DispatchMeta,
+/// sees every request and its result, e.g. to log them over RTT/UART, install with [`Dispatch::set_observer`]
+///
+/// like [`FunctionPage`] the methods take `&self`, observers with state need interior mutability
+pub trait DispatchObserver {
+    /// before the request is handled, `data` is the function parameters as received
+    fn on_request(&self, _page: u8, _id: u8, _data: &[u8]) {}
+
+    /// after the request is handled, also called for requests that failed before reaching a handler
+    fn on_response(&self, _result: &DispatchReturn) {}
 }
 
-/// https://openinput.readthedocs.io/projects/protocol/en/latest/device-protocol/functions/00_info.html
-pub struct DispatchMeta {
-    protocol_version: [u8; 3],
-    firmware_vendor: Vec<u8, DISPATCH_LONG_RET_LEN>,
-    firmware_version: Vec<u8, DISPATCH_LONG_RET_LEN>,
-    device_name: Vec<u8, DISPATCH_LONG_RET_LEN>,
+/// dispatch table entry
+#[derive(Clone, Copy)]
+pub enum DispatchHandler {
+    Function(DispatchFn),
+    Page(&'static dyn FunctionPage),
 }
 
-impl Dispatch {
-    // panics if !(5 <= `data.len()` <= 29)
-    pub fn dispatch_raw(&self, page: u8, id: u8, data: &[u8]) -> DispatchReturn {
-        assert!(data.len() >= DISPATCH_SHORT_RET_LEN && data.len() <= DISPATCH_LONG_RET_LEN);
-        let func = match self.table.get(&page).and_then(|fn_page| fn_page.get(&id)) {
-            Some(func) => func,
-            None => return Err(Error::UnsupportedFunction),
-        };
+impl DispatchHandler {
+    /// pages can leave out optional functions, see [`FunctionPage::capabilities`]
+    fn implements(&self, id: u8) -> bool {
+        match self {
+            Self::Function(_) => true,
+            Self::Page(fn_page) => fn_page.implements(id),
+        }
+    }
+}
 
-        let ctx = DispatchContext {
-            table: &self.table,
-            meta: &self.meta,
-        };
-        func(data, ctx)
+impl From<DispatchFn> for DispatchHandler {
+    fn from(func: DispatchFn) -> Self {
+        Self::Function(func)
     }
+}
 
-    /// construct from raw function table, this will not implement functions required to be compliant with openinput's spec
-    pub const fn new_raw(table: DispatchTable, meta: DispatchMeta) -> Self {
-        Self { table, meta }
+/// why [`Dispatch::register_page`] refused a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum RegisterError {
+    /// 0xFF is reserved for error replies
+    ErrorPage,
+    /// the info page can only be changed through [`Dispatch::override_info`]
+    InfoPage,
+    /// too many pages or functions, or the table is static
+    TableFull,
+    /// another [`FunctionPage`] already registered the page id
+    DuplicatePage,
+}
+
+fn check_protected(page: u8) -> Result<(), RegisterError> {
+    match page {
+        INFO_FUNCTION_PAGE => Err(RegisterError::InfoPage),
+        ERROR_FUNCTION_PAGE => Err(RegisterError::ErrorPage),
+        _ => Ok(()),
     }
 }
 
-impl Default for Dispatch {
-    fn default() -> Self {
-        let mut table = FnvIndexMap::<u8, FnvIndexMap<u8, DispatchFn, 8>, 8>::new();
-
-        let mut info_page = FnvIndexMap::<u8, DispatchFn, 8>::new();
-
-        info_page
-            .insert(info_table::INFO_VERSION, info_table::protocol_version)
-            .ok()
-            .expect("failed to insert version function into dispatch table");
-        info_page
-            .insert(info_table::INFO_FIRMWARE_INFO, info_table::firmware_info)
-            .ok()
-            .expect("failed to insert firmware_info function into dispatch table");
-        info_page
-            .insert(
-                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
-                info_table::supported_fn_pages,
-            )
-            .ok()
-            .expect("failed to insert supported_fn_pages function into dispatch table");
-        info_page
-            .insert(
-                info_table::INFO_SUPPORTED_FUNCTIONS,
-                info_table::supported_fns,
-            )
-            .ok()
-            .expect("failed to insert supported_fns function into dispatch table");
+/// implementation limits, also enforced on static tables so supported pages/functions fit one reply
+const MAX_PAGES: usize = 8;
+const MAX_FUNCTIONS: usize = 8;
+
+// NOTE: table lookups are O(2) but they need to do hashing before lookup so O(n) without hashing would probably be faster.
+type DispatchMap = FnvIndexMap<u8, FnvIndexMap<u8, DispatchHandler, MAX_FUNCTIONS>, MAX_PAGES>;
+
+/// bit per page id
+struct PageSet([u32; 8]);
+
+impl PageSet {
+    const fn new() -> Self {
+        Self([0; 8])
+    }
 
-        match table.insert(INFO_FUNCTION_PAGE, info_page) {
-            Ok(_) => (),
-            Err(_) => panic!("failed to insert info page into dispatch table"),
+    fn contains(&self, page: u8) -> bool {
+        self.0[page as usize / 32] & (1 << (page % 32)) != 0
+    }
+
+    fn set(&mut self, page: u8, present: bool) {
+        let bit = 1 << (page % 32);
+        if present {
+            self.0[page as usize / 32] |= bit;
+        } else {
+            self.0[page as usize / 32] &= !bit;
         }
+    }
+}
 
-        let meta = DispatchMeta {
-            firmware_vendor: Vec::from_slice(b"Unspecified Vendor").unwrap(),
-            firmware_version: Vec::from_slice(b"Unspecified Version").unwrap(),
-            protocol_version: super::PROTOCOL_VERSION,
-            device_name: Vec::from_slice(b"Unspecified Name").unwrap(),
-        };
+/// (page, function id) pairs, room for as many functions as the table holds
+struct FunctionSet(Vec<(u8, u8), { MAX_PAGES * MAX_FUNCTIONS }>);
+
+impl FunctionSet {
+    const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn contains(&self, page: u8, id: u8) -> bool {
+        self.0.contains(&(page, id))
+    }
 
-        Self::new_raw(table, meta)
+    /// false if the set is full
+    fn set(&mut self, page: u8, id: u8, present: bool) -> bool {
+        match (self.0.iter().position(|&f| f == (page, id)), present) {
+            (Some(i), false) => {
+                self.0.swap_remove(i);
+                true
+            }
+            (None, true) => self.0.push((page, id)).is_ok(),
+            _ => true,
+        }
     }
 }
 
-mod info_table {
-    use super::*;
+/// `Cell` behind a critical section, keeps [`Dispatch`] `Sync` so it can be a `static` shared with interrupt
+/// handlers
+pub(crate) struct SyncCell<T>(critical_section::Mutex<Cell<T>>);
 
-    pub const INFO_VERSION: u8 = 0x00;
-    pub const INFO_FIRMWARE_INFO: u8 = 0x01;
-    pub const INFO_SUPPORTED_FUNCTION_PAGES: u8 = 0x02;
-    pub const INFO_SUPPORTED_FUNCTIONS: u8 = 0x03;
+impl<T> SyncCell<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self(critical_section::Mutex::new(Cell::new(value)))
+    }
 
-    pub fn protocol_version(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
-        Ok(Vec::from_slice(&ctx.meta.protocol_version).unwrap().into())
+    pub(crate) fn replace(&self, value: T) -> T {
+        critical_section::with(|cs| self.0.borrow(cs).replace(value))
     }
 
-    pub enum FirmwareInfoParam {
-        Vendor = 0,
-        Version = 1,
-        DeviceName = 2,
+    pub(crate) fn set(&self, value: T) {
+        self.replace(value);
     }
+}
 
-    impl TryFrom<u8> for FirmwareInfoParam {
-        type Error = Error;
+impl<T: Copy> SyncCell<T> {
+    pub(crate) fn get(&self) -> T {
+        critical_section::with(|cs| self.0.borrow(cs).get())
+    }
 
-        fn try_from(value: u8) -> Result<Self, Self::Error> {
-            match value {
-                0 => Ok(Self::Vendor),
-                1 => Ok(Self::Version),
-                2 => Ok(Self::DeviceName),
-                _ => Err(Error::InvalidValue(0)),
+    /// read, modify and write back without another context getting in between
+    pub(crate) fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        critical_section::with(|cs| {
+            let cell = self.0.borrow(cs);
+            let mut value = cell.get();
+            let ret = f(&mut value);
+            cell.set(value);
+            ret
+        })
+    }
+}
+
+impl<T: Default> SyncCell<T> {
+    pub(crate) fn take(&self) -> T {
+        self.replace(T::default())
+    }
+}
+
+impl<T: Copy, const N: usize> SyncCell<[T; N]> {
+    /// element `index` without copying the whole array, `None` if it's out of bounds
+    pub(crate) fn get_at(&self, index: usize) -> Option<T> {
+        critical_section::with(|cs| {
+            let cells: &Cell<[T]> = self.0.borrow(cs);
+            cells.as_slice_of_cells().get(index).map(Cell::get)
+        })
+    }
+
+    /// [`SyncCell::update`] for element `index`, `None` if it's out of bounds
+    pub(crate) fn update_at<R>(&self, index: usize, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        critical_section::with(|cs| {
+            let cells: &Cell<[T]> = self.0.borrow(cs);
+            let cell = cells.as_slice_of_cells().get(index)?;
+            let mut value = cell.get();
+            let ret = f(&mut value);
+            cell.set(value);
+            Some(ret)
+        })
+    }
+}
+
+/// one page of a table built by [`dispatch_table!`]
+pub struct StaticPage {
+    pub id: u8,
+    pub functions: &'static [(u8, DispatchHandler)],
+}
+
+impl StaticPage {
+    /// the handler registered for `id`, optional functions the page doesn't implement included
+    pub fn handler(&self, id: u8) -> Option<DispatchHandler> {
+        self.functions
+            .iter()
+            .find(|(fn_id, _)| *fn_id == id)
+            .map(|&(_, handler)| handler)
+    }
+
+    /// panics on duplicate page or function ids and on tables over the implementation limits,
+    /// [`dispatch_table!`] calls this in a const so mistakes fail to compile
+    pub const fn check_table(pages: &[StaticPage]) {
+        assert!(pages.len() <= MAX_PAGES, "too many function pages");
+        let mut i = 0;
+        while i < pages.len() {
+            let functions = pages[i].functions;
+            assert!(pages[i].id != ERROR_FUNCTION_PAGE, "0xFF is the error page");
+            assert!(
+                functions.len() <= MAX_FUNCTIONS,
+                "too many functions in a page"
+            );
+            let mut j = i + 1;
+            while j < pages.len() {
+                assert!(pages[i].id != pages[j].id, "duplicate function page id");
+                j += 1;
+            }
+            let mut a = 0;
+            while a < functions.len() {
+                let mut b = a + 1;
+                while b < functions.len() {
+                    assert!(functions[a].0 != functions[b].0, "duplicate function id");
+                    b += 1;
+                }
+                a += 1;
             }
+            i += 1;
         }
     }
 
-    pub fn firmware_info(input: &[u8], ctx: DispatchContext) -> DispatchReturn {
-        let info: FirmwareInfoParam = input[0].try_into()?;
-        Ok(match info {
-            FirmwareInfoParam::Vendor => &ctx.meta.firmware_vendor,
-            FirmwareInfoParam::Version => &ctx.meta.firmware_version,
-            FirmwareInfoParam::DeviceName => &ctx.meta.device_name,
+    /// panics on ids [`dispatch_table!`] doesn't accept for user pages, the info page is always included
+    pub const fn check_page_id(id: u8) {
+        assert!(
+            id != INFO_FUNCTION_PAGE,
+            "page 0x00 is the info page, it's always included"
+        );
+        assert!(id != ERROR_FUNCTION_PAGE, "0xFF is the error page");
+    }
+
+    const fn has_info_page(pages: &[StaticPage]) -> bool {
+        let mut i = 0;
+        while i < pages.len() {
+            if pages[i].id == INFO_FUNCTION_PAGE {
+                return true;
+            }
+            i += 1;
         }
-        .clone()
-        .into())
+        false
+    }
+}
+
+/// info page entry for static tables, [`dispatch_table!`] always includes it
+pub const INFO_PAGE: StaticPage = StaticPage {
+    id: INFO_FUNCTION_PAGE,
+    functions: &[
+        (
+            info_table::INFO_VERSION,
+            DispatchHandler::Page(&info_table::InfoPage),
+        ),
+        (
+            info_table::INFO_FIRMWARE_INFO,
+            DispatchHandler::Page(&info_table::InfoPage),
+        ),
+        (
+            info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+            DispatchHandler::Page(&info_table::InfoPage),
+        ),
+        (
+            info_table::INFO_SUPPORTED_FUNCTIONS,
+            DispatchHandler::Page(&info_table::InfoPage),
+        ),
+    ],
+};
+
+/// function table compiled to a `match` on page and function id, see [`Dispatch::new_match`]
+///
+/// Implemented by `#[openinput_dispatch_table]` (the `macros` feature) on an inherent impl whose handlers are
+/// marked with `#[function(page = .., id = ..)]`. The info page is always included, duplicate ids, ids that
+/// aren't `u8` and the 0x00/0xFF pages fail to compile.
+///
+/// ```ignore
+/// struct Table;
+///
+/// #[openinput_rust::openinput_dispatch_table]
+/// impl Table {
+///     #[function(page = 0xA0, id = 0x00)]
+///     fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn {
+///         Ok(heapless::Vec::from_slice(&[0x06, 0x40]).unwrap().into())
+///     }
+/// }
+///
+/// static DISPATCH: Dispatch = Dispatch::new_match::<Table>(openinput_rust::dispatch_meta!("Dekanova"));
+/// ```
+///
+/// Like [`dispatch_table!`] the table lives in flash and can't change at runtime, a lookup is a jump table
+/// or a compare chain picked by the compiler instead of a scan. `tests/cortex-m4-bench` times a call through
+/// each of the three tables with the DWT cycle counter on an STM32F411. It needs a board, QEMU doesn't
+/// implement the counter, and no cycle counts have been recorded from it yet.
+pub trait MatchTable {
+    /// the pages `lookup` resolves, for the supported pages and functions lists
+    const PAGES: &'static [StaticPage];
+
+    /// the handler for `page` and function `id`, `None` for unknown ids
+    fn lookup(page: u8, id: u8) -> Option<DispatchHandler>;
+}
+
+/// build a static function table at compile time, duplicate page or function ids fail to compile
+///
+/// the built-in info page is always included so the table answers the protocol version like any other,
+/// a leading `@info` is accepted and changes nothing. The result is passed to [`Dispatch::new_static`]
+///
+/// Compared to the runtime table a lookup is a linear scan over at most 8 pages and 8 functions instead of
+/// two FNV hashes and the table needs no RAM (it lives in flash). The trade-off is that pages can't be
+/// registered, overridden or removed at runtime. Page and function ids are `u8` literals, anything out of
+/// range fails to compile. For a `match` instead of a scan see [`MatchTable`].
+///
+/// ```
+/// use openinput_rust::dispatch::{Dispatch, DispatchContext, DispatchReturn, DispatchMeta};
+///
+/// fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn {
+///     Ok(heapless::Vec::from_slice(&[0x06, 0x40]).unwrap().into())
+/// }
+///
+/// fn dpi_set(_: &[u8], _: DispatchContext) -> DispatchReturn {
+///     Ok(heapless::Vec::new().into())
+/// }
+///
+/// let table = openinput_rust::dispatch_table! {
+///     0xA0 => { 0x00 => dpi_get, 0x01 => dpi_set },
+/// };
+/// let dispatch = Dispatch::new_static(table, DispatchMeta::default());
+/// ```
+///
+/// ```compile_fail
+/// # use openinput_rust::dispatch::{DispatchContext, DispatchReturn};
+/// # fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn { unimplemented!() }
+/// let table = openinput_rust::dispatch_table! {
+///     0xA0 => { 0x00 => dpi_get, 0x00 => dpi_get },
+/// };
+/// ```
+///
+/// ```compile_fail
+/// # use openinput_rust::dispatch::{DispatchContext, DispatchReturn};
+/// # fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn { unimplemented!() }
+/// // 0xFF only carries errors
+/// let table = openinput_rust::dispatch_table! {
+///     0xFF => { 0x00 => dpi_get },
+/// };
+/// ```
+///
+/// ```compile_fail
+/// # use openinput_rust::dispatch::{DispatchContext, DispatchReturn};
+/// # fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn { unimplemented!() }
+/// // the info page already claims 0x00
+/// let table = openinput_rust::dispatch_table! {
+///     0x00 => { 0x10 => dpi_get },
+/// };
+/// ```
+#[macro_export]
+macro_rules! dispatch_table {
+    (@info $(, $page:expr => { $($id:expr => $handler:expr),* $(,)? })* $(,)?) => {
+        $crate::dispatch_table!(@pages [$crate::dispatch::INFO_PAGE,] $($page => { $($id => $handler),* })*)
+    };
+    (@pages [$($pre:expr,)*] $($page:expr => { $($id:expr => $handler:expr),* })*) => {{
+        const PAGES: &[$crate::dispatch::StaticPage] = &[
+            $($pre,)*
+            $($crate::dispatch::StaticPage {
+                id: $page,
+                functions: &[$((
+                    $id,
+                    $crate::dispatch::DispatchHandler::Function($handler),
+                )),*],
+            },)*
+        ];
+        const _: () = $crate::dispatch::StaticPage::check_table(PAGES);
+        $(const _: () = $crate::dispatch::StaticPage::check_page_id($page);)*
+        PAGES
+    }};
+    ($($page:expr => { $($id:expr => $handler:expr),* $(,)? }),* $(,)?) => {
+        $crate::dispatch_table!(@info, $($page => { $($id => $handler),* }),*)
+    };
+}
+
+/// with `sequence-tracking` the high nibble of a function id is the sequence number, higher ids can't be called
+#[cfg(feature = "sequence-tracking")]
+fn reachable(id: u8) -> bool {
+    id <= crate::sequence::FUNCTION_ID_MASK
+}
+
+#[cfg(not(feature = "sequence-tracking"))]
+fn reachable(_: u8) -> bool {
+    true
+}
+
+/// function table, either filled at runtime or a static table from [`dispatch_table!`]
+enum DispatchTable {
+    Map(DispatchMap),
+    Static(&'static [StaticPage]),
+    Match {
+        pages: &'static [StaticPage],
+        lookup: fn(u8, u8) -> Option<DispatchHandler>,
+    },
+}
+
+impl DispatchTable {
+    /// `None` for optional functions a page doesn't implement
+    fn get(&self, page: u8, id: u8) -> Option<DispatchHandler> {
+        let handler = match self {
+            Self::Map(map) => map.get(&page)?.get(&id).copied(),
+            Self::Static(pages) => pages.iter().find(|fn_page| fn_page.id == page)?.handler(id),
+            Self::Match { lookup, .. } => lookup(page, id),
+        }?;
+        (handler.implements(id) && reachable(id)).then_some(handler)
+    }
+
+    fn has_page(&self, page: u8) -> bool {
+        match self {
+            Self::Map(map) => map.contains_key(&page),
+            Self::Static(pages) | Self::Match { pages, .. } => {
+                pages.iter().any(|fn_page| fn_page.id == page)
+            }
+        }
+    }
+
+    /// registered page ids in insertion order
+    fn iter_pages(&self) -> impl Iterator<Item = u8> + '_ {
+        let (map, pages) = match self {
+            Self::Map(map) => (Some(map), None),
+            Self::Static(pages) | Self::Match { pages, .. } => (None, Some(*pages)),
+        };
+        let map = map.into_iter().flat_map(|map| map.keys().copied());
+        let pages = pages.into_iter().flatten().map(|fn_page| fn_page.id);
+        map.chain(pages)
+    }
+
+    /// implemented and reachable function ids of `page` in insertion order, `None` if the page doesn't exist
+    fn iter_functions(&self, page: u8) -> Option<impl Iterator<Item = u8> + '_> {
+        let (map, functions) = match self {
+            Self::Map(map) => (Some(map.get(&page)?), None),
+            Self::Static(pages) | Self::Match { pages, .. } => (
+                None,
+                Some(pages.iter().find(|fn_page| fn_page.id == page)?.functions),
+            ),
+        };
+        let map = map
+            .into_iter()
+            .flatten()
+            .map(|(&id, &handler)| (id, handler));
+        let functions = functions.into_iter().flatten().copied();
+        Some(
+            map.chain(functions)
+                .filter(|(id, handler)| handler.implements(*id) && reachable(*id))
+                .map(|(id, _)| id),
+        )
+    }
+
+    fn page_count(&self) -> usize {
+        self.iter_pages().count()
     }
 
-    pub fn supported_fn_pages(input: &[u8], ctx: DispatchContext) -> DispatchReturn {
-        let start = input[0] as usize;
+    /// implemented functions only, `None` if the page doesn't exist
+    fn page_function_count(&self, page: u8) -> Option<usize> {
+        self.iter_functions(page).map(Iterator::count)
+    }
 
-        let mut pages: Vec<u8, 8> = Vec::from_iter(ctx.table.iter().map(|(&k, _)| k));
+    /// sorted, the underlying structures iterate in insertion order
+    fn page_ids(&self) -> Vec<u8, MAX_PAGES> {
+        let mut pages: Vec<u8, MAX_PAGES> = self.iter_pages().collect();
         pages.sort_unstable();
-        // NOTE: implementation limits to 8 pages, if we use a long report we don't need to worry about partial sets
-        let element_list = pages.get(start..).ok_or(Error::InvalidValue(0))?;
+        pages
+    }
 
-        let mut output = Vec::new();
-        output
-            .extend_from_slice(&[element_list.len() as u8, 0])
-            .unwrap();
-        output.extend_from_slice(&element_list).unwrap();
+    /// sorted and only implemented functions, `None` if the page doesn't exist
+    fn function_ids(&self, page: u8) -> Option<Vec<u8, MAX_FUNCTIONS>> {
+        let mut functions: Vec<u8, MAX_FUNCTIONS> = self.iter_functions(page)?.collect();
+        functions.sort_unstable();
+        Some(functions)
+    }
+
+    /// static tables can't be changed at runtime
+    fn map_mut(&mut self) -> Option<&mut DispatchMap> {
+        match self {
+            Self::Map(map) => Some(map),
+            Self::Static(_) | Self::Match { .. } => None,
+        }
+    }
+}
+
+pub struct DispatchContext<'a> {
+    table: &'a DispatchTable,
+    meta: &'a DispatchMeta,
+    hidden: &'a PageSet,
+    hidden_functions: &'a FunctionSet,
+    disabled: &'a PageSet,
+    pending: &'a [pending::PendingSlot],
+    queue: &'a stream::FrameQueue,
+    stats: &'a stats::StatsCell,
+    session: &'a access::Session,
+    page: u8,
+    /// as sent, including the sequence number
+    fn_id: u8,
+    request_report_id: OiReportId,
+    device_address: Option<u8>,
+    user: &'a dyn Any,
+}
+
+impl<'a> DispatchContext<'a> {
+    pub fn meta(&self) -> &'a DispatchMeta {
+        self.meta
+    }
+
+    /// device state the caller passed to [`Dispatch::dispatch_with`] (or the other `_with` methods), `None` if
+    /// it isn't a `U` or the request came through a method without state
+    ///
+    /// the same dispatcher serves callers with and without state, handlers that need it fail with
+    /// [`Error::UnsupportedFunction`] or similar when it is missing
+    pub fn user<U: Any>(&self) -> Option<&'a U> {
+        self.user.downcast_ref()
+    }
+
+    /// report the request came in, short or long (reassembled fragments count as long)
+    pub fn request_report_id(&self) -> OiReportId {
+        self.request_report_id
+    }
+
+    /// paired device the request is for, `None` for the receiver itself and always without the `wireless`
+    /// feature, see the `wireless` module
+    pub fn device_address(&self) -> Option<u8> {
+        self.device_address
+    }
+
+    /// counters of the dispatcher, not including the request being handled
+    pub fn stats(&self) -> DispatchStats {
+        self.stats.get()
+    }
+
+    /// reply payload that fits the request's report size, handlers that can split a reply across requests
+    /// (like the supported pages/functions lists) stay within it instead of forcing a long report
+    pub fn reply_capacity(&self) -> usize {
+        match self.request_report_id {
+            OiReportId::Short => DISPATCH_SHORT_RET_LEN,
+            _ => LONG_REPLY_CAPACITY,
+        }
+    }
+
+    /// number of pages the supported pages reply lists
+    pub fn page_count(&self) -> usize {
+        self.table
+            .iter_pages()
+            .filter(|&page| self.is_visible(page))
+            .count()
+    }
+
+    /// number of functions the supported functions reply lists for `page`, `None` for pages it rejects
+    /// (unknown, hidden or disabled ones)
+    pub fn function_count(&self, page: u8) -> Option<usize> {
+        self.visible_function_ids(page)
+            .map(|functions| functions.len())
+    }
 
-        Ok(output.into())
+    /// pages reported to the host, hidden and disabled pages and the error page are left out
+    fn visible_page_ids(&self) -> Vec<u8, MAX_PAGES> {
+        let mut pages: Vec<u8, MAX_PAGES> = self
+            .table
+            .iter_pages()
+            .filter(|&page| self.is_visible(page))
+            .collect();
+        pages.sort_unstable();
+        pages
     }
 
-    pub fn supported_fns(input: &[u8], ctx: DispatchContext) -> DispatchReturn {
-        let page = input[0];
-        let start = input[1] as usize;
+    fn is_visible(&self, page: u8) -> bool {
+        page != ERROR_FUNCTION_PAGE && !self.hidden.contains(page) && !self.disabled.contains(page)
+    }
 
-        // TODO is this error invalid input or unsupported function?
-        let page = ctx.table.get(&page).ok_or(Error::UnsupportedFunction)?;
-        let mut functions: Vec<u8, 8> = Vec::from_iter(page.iter().map(|(&k, _)| k));
+    /// functions of a visible page reported to the host, sorted, hidden functions are left out
+    fn visible_function_ids(&self, page: u8) -> Option<Vec<u8, MAX_FUNCTIONS>> {
+        if !self.is_visible(page) {
+            return None;
+        }
+        let mut functions: Vec<u8, MAX_FUNCTIONS> = self
+            .table
+            .iter_functions(page)?
+            .filter(|&id| !self.hidden_functions.contains(page, id))
+            .collect();
         functions.sort_unstable();
-        // NOTE: implementation limits 8 functions/page, if we use a long report we don't need to worry about partial sets
-        let element_list = functions.get(start..).ok_or(Error::InvalidValue(0))?;
+        Some(functions)
+    }
+}
 
-        let mut output = Vec::new();
-        output
-            .extend_from_slice(&[element_list.len() as u8, 0])
-            .unwrap();
-        output.extend_from_slice(&element_list).unwrap();
+/// `MAX_PENDING` is the number of requests that can be deferred at once, see [`pending`]
+pub struct Dispatch<const MAX_PENDING: usize = { pending::DEFAULT_MAX_PENDING }> {
+    /// 8 pages, max 8 functions per page (implementation detail)
+    table: DispatchTable,
+    /// callable but left out of supported pages/functions
+    hidden: PageSet,
+    /// callable but left out of supported functions
+    hidden_functions: FunctionSet,
+    /// neither callable nor listed, see [`Dispatch::set_page_enabled`]
+    disabled: PageSet,
+    pub meta: DispatchMeta,
+    pending: [pending::PendingSlot; MAX_PENDING],
+    /// frames of a streamed reply after the first
+    queue: stream::FrameQueue,
+    stats: stats::StatsCell,
+    protected: access::Protected,
+    session: access::Session,
+    observer: Option<&'static (dyn DispatchObserver + Sync)>,
+    /// page defaults, kept apart from the table so static tables can have them too
+    defaults: Vec<(u8, PageDefaultFn), MAX_PAGES>,
+}
+
+/// max length of the info strings
+///
+/// longer than the 29 bytes (28 with `wireless`) a single long reply carries on purpose, firmware_info takes a
+/// byte offset and reads the rest in chunks. 64 fits the product names USB string descriptors usually carry without making
+/// every [`DispatchMeta`] much bigger
+pub const META_STRING_LEN: usize = 64;
+
+/// info string either borrowed from flash or copied in at runtime
+#[derive(Clone)]
+enum MetaString {
+    Static(&'static [u8]),
+    Owned(Vec<u8, META_STRING_LEN>),
+}
+
+impl core::ops::Deref for MetaString {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MetaString::Static(s) => s,
+            MetaString::Owned(s) => s,
+        }
+    }
+}
+
+/// ASCII info string (vendor, version, device name) of at most [`META_STRING_LEN`] bytes
+#[derive(Clone)]
+pub struct FirmwareString(MetaString);
+
+impl FirmwareString {
+    /// errors if `s` isn't ASCII or is longer than [`META_STRING_LEN`]
+    pub fn new(s: &str) -> Result<Self, MetaError> {
+        if !s.is_ascii() {
+            return Err(MetaError::NotAscii);
+        }
+        meta_string(s.as_bytes()).map(Self)
+    }
 
-        Ok(output.into())
+    /// borrow a string from flash, panics (or fails to compile in a const context) if it isn't ASCII or is
+    /// longer than [`META_STRING_LEN`]
+    pub const fn from_static(s: &'static str) -> Self {
+        assert!(s.len() <= META_STRING_LEN, "info string is too long");
+        let bytes = s.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            assert!(bytes[i].is_ascii(), "info string isn't ASCII");
+            i += 1;
+        }
+        Self(MetaString::Static(bytes))
+    }
+
+    /// truncates to [`META_STRING_LEN`] and replaces anything that isn't ASCII with `?`
+    pub fn new_lossy(s: &str) -> Self {
+        let bytes = s
+            .bytes()
+            .take(META_STRING_LEN)
+            .map(|b| if b.is_ascii() { b } else { b'?' })
+            .collect();
+        Self(MetaString::Owned(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // can't fail, only ASCII gets in
+        core::str::from_utf8(&self.0).unwrap_or_default()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl core::ops::Deref for FirmwareString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for FirmwareString {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl core::fmt::Debug for FirmwareString {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl core::fmt::Display for FirmwareString {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for FirmwareString {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=str}", self.as_str())
+    }
+}
+
+impl PartialEq for FirmwareString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for FirmwareString {}
+
+impl PartialEq<str> for FirmwareString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for FirmwareString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// reads the serial number at runtime (e.g. from OTP) into the buffer and returns its length, see
+/// [`DispatchMeta::set_serial_fn`]
+pub type SerialFn = fn(&mut [u8; META_STRING_LEN]) -> usize;
+
+/// https://openinput.readthedocs.io/projects/protocol/en/latest/device-protocol/functions/00_info.html
+pub struct DispatchMeta {
+    protocol_version: ProtocolVersion,
+    firmware_vendor: FirmwareString,
+    firmware_version: FirmwareString,
+    device_name: FirmwareString,
+    hardware_revision: FirmwareString,
+    serial: MetaString,
+    // takes precedence over `serial`
+    serial_fn: Option<SerialFn>,
+    capabilities: DeviceCapabilityFlags,
+    matrix: Option<scan::MatrixConfig>,
+    // the host changes the input mode through a shared reference
+    #[cfg(feature = "absolute-pointer")]
+    abs_pointer: SyncCell<Option<abs_pointer::AbsolutePointerConfig>>,
+    #[cfg(feature = "debug-page")]
+    log: debug::DebugLog,
+    #[cfg(feature = "auth")]
+    auth_key: SyncCell<Option<auth::AuthKey>>,
+}
+
+/// info strings are shown as text, the debug log and the auth key are left out
+impl core::fmt::Debug for DispatchMeta {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let mut s = f.debug_struct("DispatchMeta");
+        s.field("protocol_version", &self.protocol_version)
+            .field("firmware_vendor", &self.firmware_vendor)
+            .field("firmware_version", &self.firmware_version)
+            .field("device_name", &self.device_name)
+            .field("hardware_revision", &self.hardware_revision)
+            .field("serial", &self.serial())
+            .field("capabilities", &self.capabilities)
+            .field("matrix", &self.matrix);
+        #[cfg(feature = "absolute-pointer")]
+        s.field("abs_pointer", &self.abs_pointer.get());
+        s.finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DispatchMeta {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "DispatchMeta {{ protocol_version: {}, firmware_vendor: {}, firmware_version: {}, device_name: {}, serial: {=[u8]}, capabilities: {}, .. }}",
+            self.protocol_version,
+            self.firmware_vendor,
+            self.firmware_version,
+            self.device_name,
+            self.serial(),
+            self.capabilities,
+        )
+    }
+}
+
+/// same info strings and page configs, the debug log and the auth key aren't compared
+impl PartialEq for DispatchMeta {
+    fn eq(&self, other: &Self) -> bool {
+        #[cfg(feature = "absolute-pointer")]
+        if self.abs_pointer.get() != other.abs_pointer.get() {
+            return false;
+        }
+        self.protocol_version == other.protocol_version
+            && self.firmware_vendor == other.firmware_vendor
+            && self.firmware_version == other.firmware_version
+            && self.device_name == other.device_name
+            && self.hardware_revision == other.hardware_revision
+            && self.serial() == other.serial()
+            && self.capabilities == other.capabilities
+            && self.matrix == other.matrix
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MetaError {
+    /// string is longer than [`META_STRING_LEN`]
+    TooLong,
+    /// vendor, version and device name have to be ASCII
+    NotAscii,
+    /// the matrix has more than [`MAX_MATRIX_COLS`](scan::MAX_MATRIX_COLS) columns
+    MatrixTooWide,
+}
+
+fn non_empty(s: &FirmwareString) -> Option<&str> {
+    (!s.is_empty()).then(|| s.as_str())
+}
+
+fn meta_string(src: &[u8]) -> Result<MetaString, MetaError> {
+    Vec::from_slice(src)
+        .map(MetaString::Owned)
+        .map_err(|_| MetaError::TooLong)
+}
+
+/// build a [`DispatchMeta`] at compile time, strings longer than [`META_STRING_LEN`] fail to compile
+///
+/// ```
+/// let meta = openinput_rust::dispatch_meta!("Dekanova", "1.0.0", "Keyboard");
+/// // version and name from Cargo.toml
+/// let meta = openinput_rust::dispatch_meta!("Dekanova");
+/// // exactly META_STRING_LEN bytes
+/// let meta = openinput_rust::dispatch_meta!(
+///     "Dekanova",
+///     "1.0.0",
+///     "Openinput Wireless Gaming Mouse rev B with a name that just fits"
+/// );
+/// ```
+///
+/// ```compile_fail
+/// // one byte more
+/// let meta = openinput_rust::dispatch_meta!(
+///     "Dekanova",
+///     "1.0.0",
+///     "Openinput Wireless Gaming Mouse rev B with a name that just fits!"
+/// );
+/// ```
+#[macro_export]
+macro_rules! dispatch_meta {
+    ($vendor:expr) => {
+        $crate::dispatch_meta!($vendor, env!("CARGO_PKG_VERSION"), env!("CARGO_PKG_NAME"))
+    };
+    ($vendor:expr, $version:expr, $name:expr $(,)?) => {{
+        const META: $crate::dispatch::DispatchMeta =
+            $crate::dispatch::DispatchMeta::from_static($vendor, $version, $name);
+        META
+    }};
+}
+
+impl DispatchMeta {
+    /// errors if any string isn't ASCII or is longer than [`META_STRING_LEN`]
+    pub fn new(vendor: &str, version: &str, name: &str) -> Result<Self, MetaError> {
+        Ok(Self {
+            protocol_version: super::PROTOCOL_VERSION,
+            firmware_vendor: FirmwareString::new(vendor)?,
+            firmware_version: FirmwareString::new(version)?,
+            device_name: FirmwareString::new(name)?,
+            hardware_revision: FirmwareString(MetaString::Static(&[])),
+            serial: MetaString::Static(&[]),
+            serial_fn: None,
+            capabilities: DeviceCapabilityFlags::empty(),
+            matrix: None,
+            #[cfg(feature = "absolute-pointer")]
+            abs_pointer: SyncCell::new(None),
+            #[cfg(feature = "debug-page")]
+            log: debug::DebugLog::new(),
+            #[cfg(feature = "auth")]
+            auth_key: SyncCell::new(None),
+        })
+    }
+
+    /// borrow the strings instead of copying them, panics if any string is longer than [`META_STRING_LEN`]
+    ///
+    /// use in a const context (or [`dispatch_meta!`]) to turn the length check into a compile error
+    pub const fn from_static(
+        vendor: &'static str,
+        version: &'static str,
+        name: &'static str,
+    ) -> Self {
+        assert!(
+            vendor.len() <= META_STRING_LEN,
+            "firmware vendor is too long"
+        );
+        assert!(
+            version.len() <= META_STRING_LEN,
+            "firmware version is too long"
+        );
+        assert!(name.len() <= META_STRING_LEN, "device name is too long");
+        Self {
+            protocol_version: super::PROTOCOL_VERSION,
+            firmware_vendor: FirmwareString::from_static(vendor),
+            firmware_version: FirmwareString::from_static(version),
+            device_name: FirmwareString::from_static(name),
+            hardware_revision: FirmwareString(MetaString::Static(&[])),
+            serial: MetaString::Static(&[]),
+            serial_fn: None,
+            capabilities: DeviceCapabilityFlags::empty(),
+            matrix: None,
+            #[cfg(feature = "absolute-pointer")]
+            abs_pointer: SyncCell::new(None),
+            #[cfg(feature = "debug-page")]
+            log: debug::DebugLog::new(),
+            #[cfg(feature = "auth")]
+            auth_key: SyncCell::new(None),
+        }
+    }
+
+    /// same as [`DispatchMeta::new`] but truncates strings that don't fit (see [`FirmwareString::new_lossy`])
+    pub fn new_lossy(vendor: &str, version: &str, name: &str) -> Self {
+        Self {
+            protocol_version: super::PROTOCOL_VERSION,
+            firmware_vendor: FirmwareString::new_lossy(vendor),
+            firmware_version: FirmwareString::new_lossy(version),
+            device_name: FirmwareString::new_lossy(name),
+            hardware_revision: FirmwareString(MetaString::Static(&[])),
+            serial: MetaString::Static(&[]),
+            serial_fn: None,
+            capabilities: DeviceCapabilityFlags::empty(),
+            matrix: None,
+            #[cfg(feature = "absolute-pointer")]
+            abs_pointer: SyncCell::new(None),
+            #[cfg(feature = "debug-page")]
+            log: debug::DebugLog::new(),
+            #[cfg(feature = "auth")]
+            auth_key: SyncCell::new(None),
+        }
+    }
+
+    pub fn builder<'a>() -> DispatchMetaBuilder<'a> {
+        DispatchMetaBuilder::default()
+    }
+
+    pub fn protocol_version(&self) -> ProtocolVersion {
+        self.protocol_version
+    }
+
+    pub fn firmware_vendor(&self) -> &FirmwareString {
+        &self.firmware_vendor
+    }
+
+    pub fn firmware_version(&self) -> &FirmwareString {
+        &self.firmware_version
+    }
+
+    pub fn device_name(&self) -> &FirmwareString {
+        &self.device_name
+    }
+
+    /// `None` if the vendor is empty, the info strings are always ASCII
+    pub fn firmware_vendor_str(&self) -> Option<&str> {
+        non_empty(&self.firmware_vendor)
+    }
+
+    /// `None` if the version is empty
+    pub fn firmware_version_str(&self) -> Option<&str> {
+        non_empty(&self.firmware_version)
+    }
+
+    /// `None` if the name is empty
+    pub fn device_name_str(&self) -> Option<&str> {
+        non_empty(&self.device_name)
+    }
+
+    /// empty if it wasn't set
+    pub fn hardware_revision(&self) -> &FirmwareString {
+        &self.hardware_revision
+    }
+
+    /// the serial from [`set_serial`](Self::set_serial), use [`read_serial`](Self::read_serial) to include
+    /// a [`SerialFn`]
+    pub fn serial(&self) -> &[u8] {
+        &self.serial
+    }
+
+    /// the serial from the [`SerialFn`] if there is one, the one from [`set_serial`](Self::set_serial) otherwise
+    pub fn read_serial<'b>(&'b self, buf: &'b mut [u8; META_STRING_LEN]) -> &'b [u8] {
+        match self.serial_fn {
+            Some(serial_fn) => {
+                let len = serial_fn(buf).min(META_STRING_LEN);
+                &buf[..len]
+            }
+            None => &self.serial,
+        }
+    }
+
+    pub fn capabilities(&self) -> DeviceCapabilityFlags {
+        self.capabilities
+    }
+
+    pub fn set_firmware_vendor(&mut self, vendor: &str) -> Result<(), MetaError> {
+        self.firmware_vendor = FirmwareString::new(vendor)?;
+        Ok(())
+    }
+
+    pub fn set_firmware_version(&mut self, version: &str) -> Result<(), MetaError> {
+        self.firmware_version = FirmwareString::new(version)?;
+        Ok(())
+    }
+
+    pub fn set_device_name(&mut self, name: &str) -> Result<(), MetaError> {
+        self.device_name = FirmwareString::new(name)?;
+        Ok(())
+    }
+
+    pub fn set_hardware_revision(&mut self, revision: &str) -> Result<(), MetaError> {
+        self.hardware_revision = FirmwareString::new(revision)?;
+        Ok(())
+    }
+
+    pub fn set_serial(&mut self, serial: &[u8]) -> Result<(), MetaError> {
+        self.serial = meta_string(serial)?;
+        Ok(())
+    }
+
+    /// read the serial with `serial_fn` on every request instead, `None` goes back to the stored one
+    pub fn set_serial_fn(&mut self, serial_fn: Option<SerialFn>) {
+        self.serial_fn = serial_fn;
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: DeviceCapabilityFlags) {
+        self.capabilities = capabilities;
+    }
+}
+
+impl Default for DispatchMeta {
+    fn default() -> Self {
+        Self::new_lossy(
+            "Unspecified Vendor",
+            "Unspecified Version",
+            "Unspecified Name",
+        )
+    }
+}
+
+/// unset fields fall back to [`DispatchMeta::default`]
+#[derive(Default)]
+pub struct DispatchMetaBuilder<'a> {
+    vendor: Option<&'a str>,
+    version: Option<&'a str>,
+    name: Option<&'a str>,
+    hardware_revision: Option<&'a str>,
+    serial: Option<&'a [u8]>,
+    serial_fn: Option<SerialFn>,
+    capabilities: DeviceCapabilityFlags,
+    matrix: Option<scan::MatrixConfig>,
+    #[cfg(feature = "absolute-pointer")]
+    abs_pointer: Option<abs_pointer::AbsolutePointerConfig>,
+}
+
+impl<'a> DispatchMetaBuilder<'a> {
+    pub fn vendor(mut self, vendor: &'a str) -> Self {
+        self.vendor = Some(vendor);
+        self
+    }
+
+    pub fn version(mut self, version: &'a str) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn name(mut self, name: &'a str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    pub fn hardware_revision(mut self, revision: &'a str) -> Self {
+        self.hardware_revision = Some(revision);
+        self
+    }
+
+    pub fn serial(mut self, serial: &'a [u8]) -> Self {
+        self.serial = Some(serial);
+        self
+    }
+
+    /// see [`DispatchMeta::set_serial_fn`]
+    pub fn serial_fn(mut self, serial_fn: SerialFn) -> Self {
+        self.serial_fn = Some(serial_fn);
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: DeviceCapabilityFlags) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_matrix(mut self, matrix: scan::MatrixConfig) -> Self {
+        self.matrix = Some(matrix);
+        self
+    }
+
+    #[cfg(feature = "absolute-pointer")]
+    pub fn with_abs_pointer(mut self, config: abs_pointer::AbsolutePointerConfig) -> Self {
+        self.abs_pointer = Some(config);
+        self
+    }
+
+    pub fn build(self) -> Result<DispatchMeta, MetaError> {
+        let mut meta = DispatchMeta::default();
+        if let Some(vendor) = self.vendor {
+            meta.set_firmware_vendor(vendor)?;
+        }
+        if let Some(version) = self.version {
+            meta.set_firmware_version(version)?;
+        }
+        if let Some(name) = self.name {
+            meta.set_device_name(name)?;
+        }
+        if let Some(revision) = self.hardware_revision {
+            meta.set_hardware_revision(revision)?;
+        }
+        if let Some(serial) = self.serial {
+            meta.set_serial(serial)?;
+        }
+        meta.set_serial_fn(self.serial_fn);
+        meta.set_capabilities(self.capabilities);
+        meta.set_matrix(self.matrix)?;
+        #[cfg(feature = "absolute-pointer")]
+        meta.set_abs_pointer(self.abs_pointer);
+        Ok(meta)
+    }
+}
+
+/// report size, device address and params of a parsed report, `InvalidValue` if the params are too short or
+/// too long
+fn split_report<'d>(report: &OiReport<'d>) -> Result<(OiReportId, Option<u8>, &'d [u8]), Error> {
+    #[cfg(feature = "wireless")]
+    let (address, data) = (report.device_address(), report.address_and_data().1);
+    #[cfg(not(feature = "wireless"))]
+    let (address, data) = (None, report.data);
+    if data.len() < DISPATCH_SHORT_PARAM_LEN || data.len() > DISPATCH_LONG_PARAM_LEN {
+        return Err(Error::InvalidValue(0));
+    }
+    let report_id = OiReportId::try_from(report.id).unwrap_or_else(|_| report_id_for(data));
+    Ok((report_id, address, data))
+}
+
+/// short for up to 5 bytes of params, long otherwise
+fn report_id_for(data: &[u8]) -> OiReportId {
+    if data.len() <= DISPATCH_SHORT_PARAM_LEN {
+        OiReportId::Short
+    } else {
+        OiReportId::Long
+    }
+}
+
+enum Target {
+    Handler(DispatchHandler),
+    Default(PageDefaultFn),
+}
+
+/// a request that passed [`Dispatch::route`]'s checks, `id` without the sequence number
+struct Route<'a, 'd> {
+    target: Target,
+    id: u8,
+    data: &'d [u8],
+    ctx: DispatchContext<'a>,
+}
+
+impl Route<'_, '_> {
+    fn call(self) -> DispatchReturn {
+        let Self {
+            target,
+            id,
+            data,
+            ctx,
+        } = self;
+        match target {
+            Target::Handler(DispatchHandler::Function(func)) => func(data, ctx),
+            Target::Handler(DispatchHandler::Page(fn_page)) => fn_page.call(id, data, ctx),
+            Target::Default(default) => default(id, data, ctx),
+        }
+    }
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// handle a single OpenInput frame and return the serialized reply (or ff_error report) ready to be pushed to the host
+    ///
+    /// empty if the handler deferred its reply, see [`pending`]
+    pub fn dispatch(&self, request: &OiReport) -> Vec<u8, LONG_LEN> {
+        self.dispatch_with(request, &())
+    }
+
+    /// same as [`Dispatch::dispatch`], handlers can borrow `user` with [`DispatchContext::user`]
+    pub fn dispatch_with(&self, request: &OiReport, user: &dyn Any) -> Vec<u8, LONG_LEN> {
+        let OiReport {
+            id,
+            function_page,
+            function_id,
+            ..
+        } = *request;
+        let reply = match self.dispatch_report_with(request, user) {
+            Ok(res) => res.reply(id, function_page, function_id),
+            Err(e) => e.serialize_error(id, function_page, function_id),
+        };
+        #[cfg(feature = "wireless")]
+        let reply = crate::wireless::address_reply(request.address_and_data().0, reply);
+        reply
+    }
+
+    /// parse a frame straight off the wire and dispatch it, never panics on any input
+    ///
+    /// `ParseError` unless `bytes` is a whole short or long report with the matching report id, otherwise the
+    /// same as [`Dispatch::dispatch`]
+    pub fn parse_and_dispatch(&self, bytes: &[u8]) -> Result<Vec<u8, LONG_LEN>, OIError> {
+        let report = OiReport::read(bytes).map_err(|_| UsbError::ParseError)?;
+        if report.size() != ReportSize::from_total_len(bytes.len()) {
+            return Err(UsbError::ParseError.into());
+        }
+        Ok(self.dispatch(&report))
+    }
+
+    /// dispatch a parsed report, returns `InvalidValue` instead of panicking when the payload length is out of range
+    pub fn dispatch_report(&self, report: &OiReport) -> DispatchReturn {
+        self.dispatch_report_with(report, &())
+    }
+
+    /// same as [`Dispatch::dispatch_report`], handlers can borrow `user` with [`DispatchContext::user`]
+    pub fn dispatch_report_with(&self, report: &OiReport, user: &dyn Any) -> DispatchReturn {
+        let (report_id, address, data) = split_report(report)?;
+        self.dispatch_payload(
+            report_id,
+            address,
+            report.function_page,
+            report.function_id,
+            data,
+            user,
+        )
+    }
+
+    /// the request's report size is taken from the length of `data`, panics unless
+    /// `DISPATCH_SHORT_PARAM_LEN <= data.len() <= DISPATCH_LONG_PARAM_LEN`
+    pub fn dispatch_raw(&self, page: u8, id: u8, data: &[u8]) -> DispatchReturn {
+        self.dispatch_raw_with(page, id, data, &())
+    }
+
+    /// same as [`Dispatch::dispatch_raw`], handlers can borrow `user` with [`DispatchContext::user`]
+    pub fn dispatch_raw_with(
+        &self,
+        page: u8,
+        id: u8,
+        data: &[u8],
+        user: &dyn Any,
+    ) -> DispatchReturn {
+        assert!((DISPATCH_SHORT_PARAM_LEN..=DISPATCH_LONG_PARAM_LEN).contains(&data.len()));
+        self.dispatch_payload(report_id_for(data), None, page, id, data, user)
+    }
+
+    /// no length checks, reassembled fragments can be longer than a long report
+    fn dispatch_payload(
+        &self,
+        request_report_id: OiReportId,
+        device_address: Option<u8>,
+        page: u8,
+        id: u8,
+        data: &[u8],
+        user: &dyn Any,
+    ) -> DispatchReturn {
+        if let Some(observer) = self.observer {
+            observer.on_request(page, id, data);
+        }
+        let res = self.call_handler(request_report_id, device_address, page, id, data, user);
+        self.finish_request(page, id, res)
+    }
+
+    /// stats and observer for a handled request
+    fn finish_request(&self, page: u8, id: u8, res: DispatchReturn) -> DispatchReturn {
+        self.record_stats(page, id, &res);
+        if let Some(observer) = self.observer {
+            observer.on_response(&res);
+        }
+        res
+    }
+
+    fn call_handler(
+        &self,
+        request_report_id: OiReportId,
+        device_address: Option<u8>,
+        page: u8,
+        id: u8,
+        data: &[u8],
+        user: &dyn Any,
+    ) -> DispatchReturn {
+        self.route(request_report_id, device_address, page, id, data, user)?
+            .call()
+    }
+
+    /// look up the handler and run the checks in front of it
+    fn route<'a, 'd>(
+        &'a self,
+        request_report_id: OiReportId,
+        device_address: Option<u8>,
+        page: u8,
+        id: u8,
+        data: &'d [u8],
+        user: &'a dyn Any,
+    ) -> Result<Route<'a, 'd>, Error> {
+        #[cfg(feature = "auth")]
+        let data = auth::authenticate(&self.meta, page, id, data)?;
+        let fn_id = id;
+        // the high nibble is the sequence number (covered by the tag), replies echo it since they reuse the
+        // request's id
+        #[cfg(feature = "sequence-tracking")]
+        let id = id & crate::sequence::FUNCTION_ID_MASK;
+        if self.disabled.contains(page) {
+            return Err(Error::UnsupportedFunction);
+        }
+        let handler = self.table.get(page, id);
+        let default = self.page_default(page);
+        if handler.is_none() {
+            if !self.table.has_page(page) {
+                return Err(Error::UnsupportedPage);
+            }
+            if default.is_none() {
+                return Err(Error::UnsupportedFunction);
+            }
+        }
+        self.check_access(page, id)?;
+
+        let ctx = DispatchContext {
+            table: &self.table,
+            meta: &self.meta,
+            hidden: &self.hidden,
+            hidden_functions: &self.hidden_functions,
+            disabled: &self.disabled,
+            pending: &self.pending,
+            queue: &self.queue,
+            stats: &self.stats,
+            session: &self.session,
+            page,
+            fn_id,
+            request_report_id,
+            device_address,
+            user,
+        };
+        let target = match (handler, default) {
+            (Some(handler), _) => Target::Handler(handler),
+            (None, Some(default)) => Target::Default(default),
+            (None, None) => return Err(Error::UnsupportedFunction),
+        };
+        Ok(Route {
+            target,
+            id,
+            data,
+            ctx,
+        })
+    }
+
+    fn page_default(&self, page: u8) -> Option<PageDefaultFn> {
+        self.defaults
+            .iter()
+            .find(|&&(id, _)| id == page)
+            .map(|&(_, handler)| handler)
+    }
+
+    /// registered page ids, sorted like the supported pages reply but including hidden pages
+    pub fn pages(&self) -> impl Iterator<Item = u8> {
+        self.table.page_ids().into_iter()
+    }
+
+    /// function ids of `page` sorted like the supported functions reply, `None` if the page isn't registered
+    pub fn functions(&self, page: u8) -> Option<impl Iterator<Item = u8>> {
+        self.table.function_ids(page).map(IntoIterator::into_iter)
+    }
+
+    /// registered page ids in insertion order, hidden and disabled pages included
+    pub fn iter_pages(&self) -> impl Iterator<Item = u8> + '_ {
+        self.table.iter_pages()
+    }
+
+    /// implemented function ids of `page` in insertion order, `None` if the page isn't registered
+    pub fn iter_functions(&self, page: u8) -> Option<impl Iterator<Item = u8> + '_> {
+        self.table.iter_functions(page)
+    }
+
+    /// registered pages, hidden and disabled ones included, e.g. to check the table limits
+    pub fn page_count(&self) -> usize {
+        self.table.page_count()
+    }
+
+    /// implemented functions of `page`, `None` if the page isn't registered
+    pub fn page_function_count(&self, page: u8) -> Option<usize> {
+        self.table.page_function_count(page)
+    }
+
+    /// `id` is the function id without the sequence number
+    pub fn contains(&self, page: u8, id: u8) -> bool {
+        self.table.get(page, id).is_some()
+    }
+
+    /// install or remove the [`DispatchObserver`], without one dispatching doesn't do any extra work
+    pub fn set_observer(&mut self, observer: Option<&'static (dyn DispatchObserver + Sync)>) {
+        self.observer = observer;
+    }
+
+    /// add every function of `page`, replacing existing entries with the same ids
+    ///
+    /// the error page is refused, and so is the info page outside of [`Dispatch::override_info`]. a page id
+    /// another [`FunctionPage`] registered is refused too, pages aren't merged. the table is left untouched on
+    /// errors
+    pub fn register_page(&mut self, page: &'static dyn FunctionPage) -> Result<(), RegisterError> {
+        check_protected(page.page_id())?;
+        self.insert_page(page)
+    }
+
+    fn insert_page(&mut self, page: &'static dyn FunctionPage) -> Result<(), RegisterError> {
+        let table = self.table.map_mut().ok_or(RegisterError::TableFull)?;
+        // replacing the info page is what `override_info` is for
+        let taken = table.get(&page.page_id()).map_or(false, |functions| {
+            functions
+                .values()
+                .any(|handler| matches!(handler, DispatchHandler::Page(_)))
+        });
+        if taken && page.page_id() != INFO_FUNCTION_PAGE {
+            return Err(RegisterError::DuplicatePage);
+        }
+        if !table.contains_key(&page.page_id())
+            && table.insert(page.page_id(), FnvIndexMap::new()).is_err()
+        {
+            return Err(RegisterError::TableFull);
+        }
+        let fn_page = table.get_mut(&page.page_id()).unwrap();
+        let new = page
+            .functions()
+            .iter()
+            .filter(|&&id| !fn_page.contains_key(&id))
+            .count();
+        if fn_page.len() + new > fn_page.capacity() {
+            if fn_page.is_empty() {
+                table.remove(&page.page_id());
+            }
+            return Err(RegisterError::TableFull);
+        }
+        for &id in page.functions() {
+            // can't fail, checked capacity above
+            let _ = fn_page.insert(id, DispatchHandler::Page(page));
+        }
+        Ok(())
+    }
+
+    /// same as [`Dispatch::register_page`] but the page is left out of supported pages/functions, hosts
+    /// that know the page id can still call it
+    pub fn register_page_hidden(
+        &mut self,
+        page: &'static dyn FunctionPage,
+    ) -> Result<(), RegisterError> {
+        self.register_page(page)?;
+        self.set_page_hidden(page.page_id(), true);
+        Ok(())
+    }
+
+    /// change the info page, which [`Dispatch::register_page`], [`Dispatch::override_function`] and
+    /// [`Dispatch::remove_function`] refuse to touch
+    ///
+    /// hosts start every session with the protocol version (0x00, 0x00), a device that breaks it can't be
+    /// configured anymore, so only do this on purpose
+    pub fn override_info(&mut self) -> InfoOverride<'_, MAX_PENDING> {
+        InfoOverride { dispatch: self }
+    }
+
+    /// hide or show a page in supported pages/functions, works on static tables and on pages that
+    /// aren't registered (yet)
+    pub fn set_page_hidden(&mut self, page: u8, hidden: bool) {
+        self.hidden.set(page, hidden);
+    }
+
+    /// hide or show a single function in supported functions, hosts that know its id can still call it
+    ///
+    /// like [`Dispatch::set_page_hidden`] the function doesn't have to be registered (yet). returns false if
+    /// 64 functions (as many as the table holds) are hidden already
+    pub fn set_function_hidden(&mut self, page: u8, id: u8, hidden: bool) -> bool {
+        self.hidden_functions.set(page, id, hidden)
+    }
+
+    /// turn a page off and on again without touching the table, e.g. to refuse configuration while a
+    /// gaming mode is active
+    ///
+    /// disabled pages answer [`Error::UnsupportedFunction`] and are left out of supported pages/functions so
+    /// hosts see what the device can do right now. works on static tables and on pages that aren't registered
+    /// (yet), the info page can't be disabled
+    pub fn set_page_enabled(&mut self, page: u8, enabled: bool) {
+        if page != INFO_FUNCTION_PAGE {
+            self.disabled.set(page, !enabled);
+        }
+    }
+
+    pub fn is_page_enabled(&self, page: u8) -> bool {
+        !self.disabled.contains(page)
+    }
+
+    /// replace the handler of an existing function, returns false (and does nothing) if there is none,
+    /// the table is static or `page` is the info or error page
+    pub fn override_function(&mut self, page: u8, id: u8, handler: DispatchFn) -> bool {
+        check_protected(page).is_ok() && self.replace_function(page, id, handler)
+    }
+
+    fn replace_function(&mut self, page: u8, id: u8, handler: DispatchFn) -> bool {
+        match self
+            .table
+            .map_mut()
+            .and_then(|table| table.get_mut(&page))
+            .and_then(|fn_page| fn_page.get_mut(&id))
+        {
+            Some(entry) => {
+                *entry = DispatchHandler::Function(handler);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// handle function ids of `page` that have no handler with `handler` instead of answering
+    /// [`Error::UnsupportedFunction`], e.g. to proxy them to a coprocessor
+    ///
+    /// replaces an earlier default. returns false (and does nothing) for the info page and pages that aren't
+    /// registered, works on static tables. supported functions only lists the registered ids
+    pub fn register_page_default(&mut self, page: u8, handler: PageDefaultFn) -> bool {
+        if page == INFO_FUNCTION_PAGE || !self.table.has_page(page) {
+            return false;
+        }
+        match self.defaults.iter_mut().find(|(id, _)| *id == page) {
+            Some(entry) => {
+                entry.1 = handler;
+                true
+            }
+            None => self.defaults.push((page, handler)).is_ok(),
+        }
+    }
+
+    /// remove a single function, the page is dropped as well once it is empty, always false for static tables
+    /// and the info and error pages
+    pub fn remove_function(&mut self, page: u8, id: u8) -> bool {
+        check_protected(page).is_ok() && self.drop_function(page, id)
+    }
+
+    fn drop_function(&mut self, page: u8, id: u8) -> bool {
+        let table = match self.table.map_mut() {
+            Some(table) => table,
+            None => return false,
+        };
+        let fn_page = match table.get_mut(&page) {
+            Some(fn_page) => fn_page,
+            None => return false,
+        };
+        let existed = fn_page.remove(&id).is_some();
+        if fn_page.is_empty() {
+            table.remove(&page);
+        }
+        existed
+    }
+}
+
+/// the info page through [`Dispatch::override_info`], the error page stays off limits
+pub struct InfoOverride<'a, const MAX_PENDING: usize> {
+    dispatch: &'a mut Dispatch<MAX_PENDING>,
+}
+
+impl<const MAX_PENDING: usize> InfoOverride<'_, MAX_PENDING> {
+    /// same as [`Dispatch::register_page`], also for a page with id 0x00
+    pub fn register_page(&mut self, page: &'static dyn FunctionPage) -> Result<(), RegisterError> {
+        match page.page_id() {
+            ERROR_FUNCTION_PAGE => Err(RegisterError::ErrorPage),
+            _ => self.dispatch.insert_page(page),
+        }
+    }
+
+    /// same as [`Dispatch::override_function`] on the info page
+    pub fn override_function(&mut self, id: u8, handler: DispatchFn) -> bool {
+        self.dispatch
+            .replace_function(INFO_FUNCTION_PAGE, id, handler)
+    }
+
+    /// same as [`Dispatch::remove_function`] on the info page
+    pub fn remove_function(&mut self, id: u8) -> bool {
+        self.dispatch.drop_function(INFO_FUNCTION_PAGE, id)
+    }
+}
+
+impl Dispatch {
+    /// construct from raw function table, this will not implement functions required to be compliant with openinput's spec
+    ///
+    /// the unchecked path: `table` is used as is, including whatever it puts on the info and error pages
+    pub const fn new_raw(table: DispatchMap, meta: DispatchMeta) -> Self {
+        Self::with_table(DispatchTable::Map(table), meta)
+    }
+
+    /// construct from a table built by [`dispatch_table!`]
+    ///
+    /// panics if `table` has no page 0x00 (a compile error in a `static`), tables put together by hand have to
+    /// include [`INFO_PAGE`] like the macro does
+    pub const fn new_static(table: &'static [StaticPage], meta: DispatchMeta) -> Self {
+        assert!(
+            StaticPage::has_info_page(table),
+            "static tables need the info page, see INFO_PAGE"
+        );
+        Self::with_table(DispatchTable::Static(table), meta)
+    }
+
+    /// construct from a table generated by `#[openinput_dispatch_table]`, see [`MatchTable`]
+    pub const fn new_match<T: MatchTable>(meta: DispatchMeta) -> Self {
+        Self::with_table(
+            DispatchTable::Match {
+                pages: T::PAGES,
+                lookup: T::lookup,
+            },
+            meta,
+        )
+    }
+
+    const fn with_table(table: DispatchTable, meta: DispatchMeta) -> Self {
+        Self {
+            table,
+            hidden: PageSet::new(),
+            hidden_functions: FunctionSet::new(),
+            disabled: PageSet::new(),
+            meta,
+            pending: pending::slots(),
+            queue: stream::queue(),
+            stats: stats::stats(),
+            protected: Vec::new(),
+            session: access::Session::new(),
+            observer: None,
+            defaults: Vec::new(),
+        }
+    }
+}
+
+impl Dispatch {
+    /// the four info functions, the table behind [`Dispatch::default_const`] and [`Dispatch::default`]
+    pub const DEFAULT_TABLE: &'static [StaticPage] = &[INFO_PAGE];
+
+    /// same replies as [`Dispatch::default`] but built at compile time with nothing to panic, pages can't be
+    /// registered on it though
+    ///
+    /// ```
+    /// use openinput_rust::dispatch::Dispatch;
+    ///
+    /// static DISPATCH: Dispatch = Dispatch::default_const(openinput_rust::dispatch_meta!("Dekanova"));
+    ///
+    /// // e.g. from the USB interrupt
+    /// let reply = DISPATCH.dispatch_raw(0x00, 0x00, &[0; 5]);
+    /// ```
+    pub const fn default_const(meta: DispatchMeta) -> Self {
+        Self::new_static(Self::DEFAULT_TABLE, meta)
+    }
+}
+
+/// [`Dispatch::parse_and_dispatch`] with the default table, e.g. as a cargo-fuzz target
+///
+/// ```ignore
+/// fuzz_target!(|data: &[u8]| {
+///     let _ = openinput_rust::dispatch::parse_and_dispatch(data);
+/// });
+/// ```
+pub fn parse_and_dispatch(bytes: &[u8]) -> Result<Vec<u8, LONG_LEN>, OIError> {
+    Dispatch::default().parse_and_dispatch(bytes)
+}
+
+impl Default for Dispatch {
+    /// a runtime copy of [`Dispatch::DEFAULT_TABLE`] that more pages can be registered in
+    fn default() -> Self {
+        let mut table = DispatchMap::new();
+        for page in Self::DEFAULT_TABLE {
+            let mut functions = FnvIndexMap::new();
+            for &(id, handler) in page.functions {
+                // can't fail, the table is within MAX_PAGES/MAX_FUNCTIONS
+                let _ = functions.insert(id, handler);
+            }
+            let _ = table.insert(page.id, functions);
+        }
+        Self::new_raw(table, DispatchMeta::default())
+    }
+}
+
+const _: () = StaticPage::check_table(Dispatch::DEFAULT_TABLE);
+
+// a dispatcher can be a `static` shared with interrupt handlers
+const _: () = {
+    const fn assert_sync<T: Sync>() {}
+    assert_sync::<Dispatch>();
+};
+
+mod info_table {
+    use super::args::call_typed;
+    use super::reply::{ElementList, FirmwareInfoChunk, VersionReply};
+    use super::*;
+
+    pub const INFO_VERSION: u8 = 0x00;
+    pub const INFO_FIRMWARE_INFO: u8 = 0x01;
+    pub const INFO_SUPPORTED_FUNCTION_PAGES: u8 = 0x02;
+    pub const INFO_SUPPORTED_FUNCTIONS: u8 = 0x03;
+
+    /// https://openinput.readthedocs.io/projects/protocol/en/latest/device-protocol/functions/00_info.html
+    pub struct InfoPage;
+
+    impl FunctionPage for InfoPage {
+        fn page_id(&self) -> u8 {
+            INFO_FUNCTION_PAGE
+        }
+
+        fn functions(&self) -> &'static [u8] {
+            &[
+                INFO_VERSION,
+                INFO_FIRMWARE_INFO,
+                INFO_SUPPORTED_FUNCTION_PAGES,
+                INFO_SUPPORTED_FUNCTIONS,
+            ]
+        }
+
+        fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+            match fn_id {
+                INFO_VERSION => call_typed(data, ctx, protocol_version),
+                INFO_FIRMWARE_INFO => call_typed(data, ctx, firmware_info),
+                INFO_SUPPORTED_FUNCTION_PAGES => call_typed(data, ctx, supported_fn_pages),
+                INFO_SUPPORTED_FUNCTIONS => call_typed(data, ctx, supported_fns),
+                _ => Err(Error::UnsupportedFunction),
+            }
+        }
+    }
+
+    pub fn protocol_version(_: (), ctx: DispatchContext) -> DispatchReturn {
+        Ok(VersionReply(ctx.meta.protocol_version).into())
+    }
+
+    /// 0 to 2 are the spec's, serial and hardware revision aren't assigned by it yet. unset strings are
+    /// returned empty
+    pub enum FirmwareInfoParam {
+        Vendor = 0,
+        Version = 1,
+        DeviceName = 2,
+        Serial = 3,
+        HardwareRevision = 4,
+    }
+
+    impl TryFrom<u8> for FirmwareInfoParam {
+        type Error = ();
+
+        fn try_from(value: u8) -> Result<Self, Self::Error> {
+            match value {
+                0 => Ok(Self::Vendor),
+                1 => Ok(Self::Version),
+                2 => Ok(Self::DeviceName),
+                3 => Ok(Self::Serial),
+                4 => Ok(Self::HardwareRevision),
+                _ => Err(()),
+            }
+        }
+    }
+
+    /// params: info, byte offset into the string (0 if the host doesn't know about chunking)
+    ///
+    /// strings longer than a long report are returned in chunks of [`DispatchContext::reply_capacity`] bytes
+    /// (29, 28 with the `wireless` feature), the string is complete once a chunk is null padded (shorter than
+    /// that), an offset past the end returns an empty chunk
+    pub fn firmware_info((info, offset): (u8, Option<u8>), ctx: DispatchContext) -> DispatchReturn {
+        let info = FirmwareInfoParam::try_from(info).map_err(|_| Error::InvalidValue(0))?;
+        let offset = offset.unwrap_or(0) as usize;
+        let mut buf = [0; META_STRING_LEN];
+        let string = match info {
+            FirmwareInfoParam::Vendor => ctx.meta.firmware_vendor.as_bytes(),
+            FirmwareInfoParam::Version => ctx.meta.firmware_version.as_bytes(),
+            FirmwareInfoParam::DeviceName => ctx.meta.device_name.as_bytes(),
+            FirmwareInfoParam::Serial => ctx.meta.read_serial(&mut buf),
+            FirmwareInfoParam::HardwareRevision => ctx.meta.hardware_revision.as_bytes(),
+        };
+        Ok(FirmwareInfoChunk { string, offset }.into())
+    }
+
+    /// the part of `items` that fits the reply, the host asks again with a later start index for the rest
+    fn window<'a>(items: &'a [u8], ctx: &DispatchContext) -> &'a [u8] {
+        // total and count come first
+        let fit = ctx.reply_capacity().saturating_sub(2);
+        &items[..items.len().min(fit)]
+    }
+
+    /// params: start index, reply is an [`ElementList`] sized to the request's report, starting at the end
+    /// returns an empty list
+    ///
+    /// lists the device relative set: the pages this device exposes to hosts, which leaves out pages
+    /// registered hidden and the error page (0xFF, it's only ever a reply). Ids are the absolute page ids
+    /// hosts call, not indices into the list
+    pub fn supported_fn_pages(start: u8, ctx: DispatchContext) -> DispatchReturn {
+        let pages = ctx.visible_page_ids();
+        let items = pages.get(start as usize..).ok_or(Error::InvalidValue(0))?;
+
+        Ok(ElementList {
+            items: window(items, &ctx),
+            total: pages.len() as u8,
+        }
+        .into())
+    }
+
+    /// params: page, start index, same reply as [`supported_fn_pages`], an unknown (or hidden) page is an
+    /// invalid first parameter. functions hidden with [`Dispatch::set_function_hidden`] are left out
+    pub fn supported_fns((page, start): (u8, u8), ctx: DispatchContext) -> DispatchReturn {
+        let functions = ctx
+            .visible_function_ids(page)
+            .ok_or(Error::InvalidValue(0))?;
+        let items = functions
+            .get(start as usize..)
+            .ok_or(Error::InvalidValue(1))?;
+
+        Ok(ElementList {
+            items: window(items, &ctx),
+            total: functions.len() as u8,
+        }
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::{OPENINPUT_LONG_REPORT_ID, SHORT_LEN};
+
+    #[test]
+    fn dispatch_short_report() {
+        let dispatch = Dispatch::default();
+        let report = OiReport::new_short(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5]);
+        let res = dispatch.dispatch_report(&report).unwrap();
+        assert_eq!(res.payload(), &crate::PROTOCOL_VERSION.to_bytes());
+    }
+
+    #[test]
+    fn dispatch_long_report() {
+        let dispatch = Dispatch::default();
+        let mut data = [0; 29];
+        data[0] = info_table::FirmwareInfoParam::DeviceName as u8;
+        let report = OiReport::new_long(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &data);
+        let res = dispatch.dispatch_report(&report).unwrap();
+        assert_eq!(res.payload(), b"Unspecified Name");
+    }
+
+    #[test]
+    fn parse_and_dispatch_malformed() {
+        let mut oversized = [0; LONG_LEN + 1];
+        oversized[0] = OPENINPUT_LONG_REPORT_ID;
+        let mut mislabeled = [0; SHORT_LEN];
+        mislabeled[0] = OPENINPUT_LONG_REPORT_ID;
+        for bytes in [
+            &[][..],
+            &[0x20],
+            &oversized,
+            &mislabeled,
+            &[0x55; SHORT_LEN],
+        ] {
+            assert!(
+                matches!(
+                    parse_and_dispatch(bytes),
+                    Err(OIError::UsbError(UsbError::ParseError))
+                ),
+                "{:x?}",
+                bytes
+            );
+        }
+
+        // anything that parses gets a reply, errors included
+        let reply = parse_and_dispatch(&[0x20, 0x42, 0x00, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(reply[..2], [0x20, ERROR_FUNCTION_PAGE]);
+        let reply = parse_and_dispatch(&[0x20, 0x00, 0x00, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(reply[..3], [0x20, 0x00, 0x00]);
+    }
+
+    fn echo(data: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(data).unwrap().into())
+    }
+
+    #[test]
+    fn request_param_len() {
+        // frames laid out like libratbag's openinput driver sends them: report id, page, function id, then the
+        // params. keyboard's openinput_request_params pulls the same frames off the wire
+        let dispatch = Dispatch::default();
+        let version = dispatch
+            .parse_and_dispatch(&[0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(version[..3], [0x20, 0x00, 0x00]);
+        assert_eq!(version[3..6], crate::PROTOCOL_VERSION.to_bytes());
+
+        // the first param byte is right after the function id
+        let name = dispatch
+            .parse_and_dispatch(&[0x20, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00])
+            .unwrap();
+        assert_eq!(name[..3], [0x21, 0x00, 0x01]);
+        assert!(name[3..].starts_with(b"Unspecified Name"));
+
+        // handlers see every byte after the prefix, the echo comes back as the same frame
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(0x80, &[(0x00, DispatchHandler::Function(echo))])
+            .unwrap();
+        let short = [0x20, 0x80, 0x00, 0xA1, 0xA2, 0xA3, 0xA4, 0xA5];
+        assert_eq!(dispatch.parse_and_dispatch(&short).unwrap(), short);
+        let mut long = [0; LONG_LEN];
+        long[..3].copy_from_slice(&[0x21, 0x80, 0x00]);
+        for (i, b) in long[3..].iter_mut().enumerate() {
+            *b = i as u8 + 1;
+        }
+        assert_eq!(dispatch.parse_and_dispatch(&long).unwrap(), long);
+        assert_eq!(DISPATCH_SHORT_PARAM_LEN, 5);
+        assert_eq!(DISPATCH_LONG_PARAM_LEN, 29);
+    }
+
+    fn raw_short(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        Ok(DispatchResponse::raw(
+            &OiReport::new_short(0x80, 0x00, &[1, 2, 3, 4, 5]),
+            &ctx,
+        ))
+    }
+
+    fn raw_wrong_id(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        Ok(DispatchResponse::raw(
+            &OiReport::new_short(0x80, 0x07, &[0; 5]),
+            &ctx,
+        ))
+    }
+
+    fn raw_truncated(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let report = OiReport {
+            id: OPENINPUT_SHORT_REPORT_ID,
+            function_page: 0x80,
+            function_id: 0x02,
+            data: &[1],
+        };
+        Ok(DispatchResponse::raw(&report, &ctx))
+    }
+
+    fn raw_dispatch() -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0x80,
+                &[
+                    (0x00, DispatchHandler::Function(raw_short)),
+                    (0x01, DispatchHandler::Function(raw_wrong_id)),
+                    (0x02, DispatchHandler::Function(raw_truncated)),
+                ],
+            )
+            .unwrap();
+        dispatch
+    }
+
+    #[test]
+    fn raw_response() {
+        let dispatch = raw_dispatch();
+        // a long request would get a long reply, the raw one reaches the wire unchanged
+        let mut request = [0; LONG_LEN];
+        request[..3].copy_from_slice(&[0x21, 0x80, 0x00]);
+        assert_eq!(
+            &dispatch.parse_and_dispatch(&request).unwrap()[..],
+            &[0x20, 0x80, 0x00, 1, 2, 3, 4, 5]
+        );
+    }
+
+    // framing that doesn't match the request is a bug in the handler
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "raw reply framing")]
+    fn raw_response_wrong_id() {
+        let _ = raw_dispatch().dispatch_raw(0x80, 0x01, &[0; 5]);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "raw reply framing")]
+    fn raw_response_truncated() {
+        let _ = raw_dispatch().dispatch_raw(0x80, 0x02, &[0; 5]);
+    }
+
+    #[test]
+    fn response_report_size() {
+        for (len, id, size) in [
+            (0, OPENINPUT_SHORT_REPORT_ID, SHORT_LEN),
+            (3, OPENINPUT_SHORT_REPORT_ID, SHORT_LEN),
+            (5, OPENINPUT_SHORT_REPORT_ID, SHORT_LEN),
+            (6, OPENINPUT_LONG_REPORT_ID, LONG_LEN),
+            (10, OPENINPUT_LONG_REPORT_ID, LONG_LEN),
+            (29, OPENINPUT_LONG_REPORT_ID, LONG_LEN),
+        ] {
+            let payload: Vec<u8, DISPATCH_LONG_RET_LEN> = (1..=len as u8).collect();
+            let mut res = DispatchResponse::from(payload.clone());
+            let bytes: Vec<u8, LONG_LEN> = res.report(0x10, 0x02).unwrap().into();
+            assert_eq!(bytes.len(), size, "{} byte payload", len);
+            assert_eq!(&bytes[..DISPATCH_PREFIX_LEN], &[id, 0x10, 0x02]);
+            assert_eq!(&bytes[DISPATCH_PREFIX_LEN..][..len], payload.as_slice());
+            assert!(bytes[DISPATCH_PREFIX_LEN + len..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn error_report_bytes() {
+        assert_eq!(
+            Error::InvalidValue(2)
+                .serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x00, 0x01)
+                .as_slice(),
+            &[0x20, 0xFF, 0x01, 0x00, 0x01, 0x02, 0x00, 0x00]
+        );
+        assert_eq!(
+            Error::UnsupportedFunction
+                .serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x42, 0x07)
+                .as_slice(),
+            &[0x20, 0xFF, 0x02, 0x42, 0x07, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            Error::custom("bad")
+                .unwrap()
+                .serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x10, 0x01)
+                .as_slice(),
+            &[0x20, 0xFF, 0xFE, 0x10, 0x01, b'b', b'a', b'd']
+        );
+
+        let report = Error::custom("flash busy").unwrap().serialize_error(
+            OPENINPUT_SHORT_REPORT_ID,
+            0x10,
+            0x01,
+        );
+        assert_eq!(report.len(), LONG_LEN);
+        assert_eq!(&report[..ERROR_PREFIX_LEN], &[0x21, 0xFF, 0xFE, 0x10, 0x01]);
+        assert_eq!(&report[ERROR_PREFIX_LEN..][..10], b"flash busy");
+        assert!(report[ERROR_PREFIX_LEN + 10..].iter().all(|&b| b == 0));
+
+        // message that fills the whole report
+        let message = [b'x'; LONG_LEN - ERROR_PREFIX_LEN];
+        let report = Error::custom(core::str::from_utf8(&message).unwrap())
+            .unwrap()
+            .serialize_error(OPENINPUT_SHORT_REPORT_ID, 0, 0);
+        assert_eq!(&report[ERROR_PREFIX_LEN..], &message);
+    }
+
+    #[test]
+    fn custom_error_lengths() {
+        let message = "abcdefghijklmnopqrstuvwxyz01";
+        for len in [3, 4, 26, 27] {
+            let error = Error::custom(&message[..len]).unwrap();
+            assert_eq!(error.message(), Some(&message[..len]));
+            let report = error.serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x10, 0x01);
+            // only 3 bytes fit a short report
+            let expected = if len <= 3 { SHORT_LEN } else { LONG_LEN };
+            assert_eq!(report.len(), expected, "{} byte message", len);
+            assert_eq!(
+                &report[ERROR_PREFIX_LEN..][..len],
+                message[..len].as_bytes()
+            );
+            assert!(report[ERROR_PREFIX_LEN + len..].iter().all(|&b| b == 0));
+        }
+        assert_eq!(
+            Error::custom(message).err(),
+            Some(CustomMessageError::TooLong)
+        );
+        assert_eq!(
+            Error::custom_truncating(message).message(),
+            Some(&message[..CUSTOM_ERROR_LEN])
+        );
+
+        assert_eq!(
+            Error::custom("caf\u{e9}").err(),
+            Some(CustomMessageError::NotAscii)
+        );
+        assert_eq!(
+            Error::custom("a\0b").err(),
+            Some(CustomMessageError::NotAscii)
+        );
+        assert_eq!(Error::custom_truncating("a\0b").message(), Some("a?b"));
+        assert_eq!(Error::UnsupportedFunction.message(), None);
+    }
+
+    #[test]
+    fn device_error_layout() {
+        // a single detail byte fits a short report
+        let report =
+            Error::device(0x12, &[0xAB]).serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x10, 0x01);
+        assert_eq!(
+            report.as_slice(),
+            &[0x20, 0xFF, 0xFE, 0x10, 0x01, 0xFF, 0x12, 0xAB]
+        );
+
+        // no detail
+        let report =
+            Error::device(0x01, &[]).serialize_error(OPENINPUT_SHORT_REPORT_ID, 0x10, 0x01);
+        assert_eq!(
+            report.as_slice(),
+            &[0x20, 0xFF, 0xFE, 0x10, 0x01, 0xFF, 0x01, 0x00]
+        );
+
+        // more is promoted to a long report, trailing zeros of the detail don't count
+        let report = Error::device(0x02, &[0x00, 0xCD, 0x00]).serialize_error(
+            OPENINPUT_SHORT_REPORT_ID,
+            0x10,
+            0x01,
+        );
+        let mut expected = [0; LONG_LEN];
+        expected[..9].copy_from_slice(&[0x21, 0xFF, 0xFE, 0x10, 0x01, 0xFF, 0x02, 0x00, 0xCD]);
+        assert_eq!(report.as_slice(), &expected);
+
+        // the full detail exactly fills a long report
+        let detail = [0x5A; DEVICE_ERROR_DETAIL_LEN + 1];
+        let report =
+            Error::device(0x03, &detail).serialize_error(OPENINPUT_LONG_REPORT_ID, 0x10, 0x01);
+        assert_eq!(&report[..7], &[0x21, 0xFF, 0xFE, 0x10, 0x01, 0xFF, 0x03]);
+        assert_eq!(&report[7..], &detail[..DEVICE_ERROR_DETAIL_LEN]);
+        assert_eq!(Error::device(0x03, &detail).message(), None);
+    }
+
+    fn firmware_info(
+        dispatch: &Dispatch,
+        param: info_table::FirmwareInfoParam,
+    ) -> DispatchResponse {
+        let mut data = [0; 29];
+        data[0] = param as u8;
+        let report = OiReport::new_long(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &data);
+        dispatch.dispatch_report(&report).unwrap()
+    }
+
+    #[test]
+    fn firmware_info_serial() {
+        let meta = DispatchMeta::builder().serial(b"OI-0001").build().unwrap();
+        let dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+        let res = firmware_info(&dispatch, info_table::FirmwareInfoParam::Serial);
+        assert_eq!(res.payload(), b"OI-0001");
+    }
+
+    #[test]
+    fn meta_lengths() {
+        let long = "abcdefghijklmnopqrstuvwxyz012";
+        assert_eq!(long.len(), DISPATCH_LONG_RET_LEN);
+        let meta = DispatchMeta::new(long, long, long).unwrap();
+        assert_eq!(meta.device_name(), long);
+
+        let max = "abcdefghijklmnopqrstuvwxyz012345abcdefghijklmnopqrstuvwxyz012345";
+        let over = "abcdefghijklmnopqrstuvwxyz012345abcdefghijklmnopqrstuvwxyz0123456";
+        assert_eq!(max.len(), META_STRING_LEN);
+
+        let meta = DispatchMeta::new(max, max, max).unwrap();
+        assert_eq!(meta.device_name(), max);
+
+        assert!(matches!(
+            DispatchMeta::new("vendor", "1.0.0", over),
+            Err(MetaError::TooLong)
+        ));
+        assert!(matches!(
+            DispatchMeta::builder().vendor(over).build(),
+            Err(MetaError::TooLong)
+        ));
+
+        let meta = DispatchMeta::new_lossy("vendor", "1.0.0", over);
+        assert_eq!(meta.device_name(), max);
+    }
+
+    #[test]
+    fn firmware_string() {
+        let s = FirmwareString::new("Dekanova 1.0").unwrap();
+        assert_eq!(&*s, "Dekanova 1.0");
+        assert_eq!(s.as_bytes(), b"Dekanova 1.0");
+        assert_eq!(s.to_string(), "Dekanova 1.0");
+        assert!(matches!(
+            FirmwareString::new("Dekanöva"),
+            Err(MetaError::NotAscii)
+        ));
+        assert_eq!(FirmwareString::new_lossy("Dekanöva"), "Dekan??va");
+
+        let meta = DispatchMeta::builder().name("Keyboard").build().unwrap();
+        let dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+        let res = firmware_info(&dispatch, info_table::FirmwareInfoParam::DeviceName);
+        let name = FirmwareString::new(core::str::from_utf8(res.payload()).unwrap()).unwrap();
+        assert_eq!(name.as_str(), dispatch.meta.device_name().as_str());
+    }
+
+    #[test]
+    fn builder_ascii() {
+        let meta = DispatchMeta::builder()
+            .vendor("Dekanova")
+            .version("1.2.3")
+            .name("Keyboard ~65% (ISO)")
+            .build()
+            .unwrap();
+        assert_eq!(meta.firmware_vendor_str(), Some("Dekanova"));
+        assert_eq!(meta.firmware_version_str(), Some("1.2.3"));
+        assert_eq!(meta.device_name_str(), Some("Keyboard ~65% (ISO)"));
+
+        for builder in [
+            DispatchMeta::builder().vendor("Dekan\u{f6}va"),
+            DispatchMeta::builder().version("1.2.3\u{a0}"),
+            DispatchMeta::builder().name("\u{2328} Keyboard"),
+        ] {
+            assert!(matches!(builder.build(), Err(MetaError::NotAscii)));
+        }
+
+        let meta = DispatchMeta::builder().name("").build().unwrap();
+        assert_eq!(meta.device_name_str(), None);
+    }
+
+    #[test]
+    fn firmware_info_static() {
+        let meta = crate::dispatch_meta!("Dekanova", "1.2.3", "Test Keyboard");
+        let dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+
+        use info_table::FirmwareInfoParam::*;
+        assert_eq!(firmware_info(&dispatch, Vendor).payload(), b"Dekanova");
+        assert_eq!(firmware_info(&dispatch, Version).payload(), b"1.2.3");
+        assert!(firmware_info(&dispatch, Serial).payload().is_empty());
+
+        let meta = crate::dispatch_meta!("Dekanova");
+        assert_eq!(meta.firmware_version(), env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn firmware_info_configured() {
+        let meta = DispatchMeta::builder()
+            .vendor("Dekanova")
+            .version("1.2.3")
+            .name("Test Keyboard")
+            .build()
+            .unwrap();
+        let dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+
+        use info_table::FirmwareInfoParam::*;
+        assert_eq!(firmware_info(&dispatch, Vendor).payload(), b"Dekanova");
+        assert_eq!(firmware_info(&dispatch, Version).payload(), b"1.2.3");
+        assert_eq!(
+            firmware_info(&dispatch, DeviceName).payload(),
+            b"Test Keyboard"
+        );
+    }
+
+    #[test]
+    fn firmware_info_params() {
+        fn otp_serial(buf: &mut [u8; META_STRING_LEN]) -> usize {
+            buf[..4].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+            4
+        }
+
+        let meta = DispatchMeta::builder()
+            .vendor("Dekanova")
+            .version("1.2.3")
+            .name("Test Keyboard")
+            .hardware_revision("rev B")
+            .serial(b"OI-0001")
+            .build()
+            .unwrap();
+        let mut dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+
+        use info_table::FirmwareInfoParam::*;
+        assert_eq!(firmware_info(&dispatch, Vendor).payload(), b"Dekanova");
+        assert_eq!(firmware_info(&dispatch, Version).payload(), b"1.2.3");
+        assert_eq!(
+            firmware_info(&dispatch, DeviceName).payload(),
+            b"Test Keyboard"
+        );
+        assert_eq!(firmware_info(&dispatch, Serial).payload(), b"OI-0001");
+        assert_eq!(
+            firmware_info(&dispatch, HardwareRevision).payload(),
+            b"rev B"
+        );
+
+        dispatch.meta.set_serial_fn(Some(otp_serial));
+        assert_eq!(
+            firmware_info(&dispatch, Serial).payload(),
+            [0xde, 0xad, 0xbe, 0xef]
+        );
+
+        let mut data = [0; 29];
+        data[0] = 5;
+        let report = OiReport::new_long(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &data);
+        assert_eq!(
+            dispatch.dispatch_report(&report),
+            Err(Error::InvalidValue(0))
+        );
+    }
+
+    #[test]
+    fn firmware_info_unset() {
+        let dispatch = Dispatch::default();
+        use info_table::FirmwareInfoParam::*;
+        assert!(firmware_info(&dispatch, Serial).payload().is_empty());
+        assert!(firmware_info(&dispatch, HardwareRevision)
+            .payload()
+            .is_empty());
+    }
+
+    fn firmware_info_chunk(dispatch: &Dispatch, offset: u8) -> DispatchResponse {
+        let mut data = [0; 29];
+        data[0] = info_table::FirmwareInfoParam::DeviceName as u8;
+        data[1] = offset;
+        let report = OiReport::new_long(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &data);
+        dispatch.dispatch_report(&report).unwrap()
+    }
+
+    #[test]
+    fn firmware_info_chunked() {
+        let name = "Openinput Wireless Gaming Mouse rev B";
+        let meta = DispatchMeta::builder().name(name).build().unwrap();
+        let dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+
+        let first = firmware_info_chunk(&dispatch, 0);
+        assert_eq!(first.payload(), &name.as_bytes()[..29]);
+        let rest = firmware_info_chunk(&dispatch, 29);
+        assert_eq!(rest.payload(), &name.as_bytes()[29..]);
+        let mid = firmware_info_chunk(&dispatch, 10);
+        assert_eq!(mid.payload(), &name.as_bytes()[10..]);
+        let past = firmware_info_chunk(&dispatch, 200);
+        assert!(past.payload().is_empty());
+
+        let mut reader = crate::host::FirmwareInfoReader::<64>::new();
+        while !reader.is_complete() {
+            let mut res = firmware_info_chunk(&dispatch, reader.next_offset());
+            res.0.resize(DISPATCH_LONG_RET_LEN, 0).unwrap();
+            reader.push(&res.0).unwrap();
+        }
+        assert_eq!(reader.as_bytes(), name.as_bytes());
+    }
+
+    #[test]
+    fn dispatch_protocol_version_frame() {
+        let dispatch = Dispatch::default();
+        let request = [0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let reply = dispatch.dispatch(&OiReport::read(&request).unwrap());
+        assert_eq!(
+            reply.as_slice(),
+            &[0x20, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn dispatch_long_frame() {
+        let dispatch = Dispatch::default();
+        let mut request = [0; LONG_LEN];
+        request[..4].copy_from_slice(&[0x21, 0x00, 0x01, 0x00]);
+        let reply = dispatch.dispatch(&OiReport::read(&request).unwrap());
+        assert_eq!(reply.len(), LONG_LEN);
+        assert_eq!(&reply[..3], &[0x21, 0x00, 0x01]);
+        assert_eq!(&reply[3..21], b"Unspecified Vendor");
+        assert!(reply[21..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn reply_mirrors_request() {
+        let short = DispatchResponse::from(Vec::from_slice(&[1, 2, 3, 4, 5]).unwrap());
+        let long = DispatchResponse::from(Vec::from_slice(&[1, 2, 3, 4, 5, 6]).unwrap());
+        let size = |reply: Vec<u8, LONG_LEN>| (reply[0], reply.len());
+
+        assert_eq!(
+            size(short.reply(OPENINPUT_SHORT_REPORT_ID, 0, 0)),
+            (OPENINPUT_SHORT_REPORT_ID, SHORT_LEN)
+        );
+        // promoted, the payload doesn't fit a short report
+        assert_eq!(
+            size(long.reply(OPENINPUT_SHORT_REPORT_ID, 0, 0)),
+            (OPENINPUT_LONG_REPORT_ID, LONG_LEN)
+        );
+        assert_eq!(
+            size(short.reply(OPENINPUT_LONG_REPORT_ID, 0, 0)),
+            (OPENINPUT_LONG_REPORT_ID, LONG_LEN)
+        );
+        assert_eq!(
+            size(long.reply(OPENINPUT_LONG_REPORT_ID, 0, 0)),
+            (OPENINPUT_LONG_REPORT_ID, LONG_LEN)
+        );
+
+        // errors follow the same rule
+        let dispatch = Dispatch::default();
+        let reply = dispatch.dispatch(&OiReport::new_short(0x42, 0x00, &[0; 5]));
+        assert_eq!(size(reply), (OPENINPUT_SHORT_REPORT_ID, SHORT_LEN));
+        let reply = dispatch.dispatch(&OiReport::new_long(0x42, 0x00, &[0; 29]));
+        assert_eq!(size(reply), (OPENINPUT_LONG_REPORT_ID, LONG_LEN));
+    }
+
+    /// replies with the request's report id
+    fn request_id(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&[ctx.request_report_id().id()])
+            .unwrap()
+            .into())
+    }
+
+    #[test]
+    fn request_report_id() {
+        let mut dispatch = Dispatch::default();
+        assert!(dispatch
+            .override_info()
+            .override_function(info_table::INFO_VERSION, request_id));
+
+        let short = OiReport::new_short(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5]);
+        let mut res = dispatch.dispatch_report(&short).unwrap();
+        assert_eq!(res.payload(), &[OPENINPUT_SHORT_REPORT_ID]);
+        let report = res
+            .report_for(
+                OiReportId::Short,
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_VERSION,
+            )
+            .unwrap();
+        assert_eq!(report.id, OPENINPUT_SHORT_REPORT_ID);
+        assert_eq!(report.data, &[OPENINPUT_SHORT_REPORT_ID, 0, 0, 0, 0]);
+
+        // a long request gets a long reply even though the payload would fit a short one
+        let long = OiReport::new_long(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 29]);
+        let mut res = dispatch.dispatch_report(&long).unwrap();
+        assert_eq!(res.payload(), &[OPENINPUT_LONG_REPORT_ID]);
+        let report = res
+            .report_for(
+                OiReportId::Long,
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_VERSION,
+            )
+            .unwrap();
+        assert_eq!(report.id, OPENINPUT_LONG_REPORT_ID);
+        assert_eq!(report.data.len(), DISPATCH_LONG_RET_LEN);
+    }
+
+    #[test]
+    fn info_handlers_short_input() {
+        let dispatch = Dispatch::default();
+        let call = |id, data: &[u8]| {
+            let ctx = DispatchContext {
+                table: &dispatch.table,
+                meta: &dispatch.meta,
+                hidden: &dispatch.hidden,
+                hidden_functions: &dispatch.hidden_functions,
+                disabled: &dispatch.disabled,
+                pending: &[],
+                queue: &dispatch.queue,
+                stats: &dispatch.stats,
+                session: &dispatch.session,
+                page: INFO_FUNCTION_PAGE,
+                fn_id: id,
+                request_report_id: OiReportId::Long,
+                device_address: None,
+                user: &(),
+            };
+            info_table::InfoPage.call(id, data, ctx)
+        };
+        use info_table::*;
+        assert_eq!(call(INFO_FIRMWARE_INFO, &[]), Err(Error::InvalidValue(0)));
+        assert_eq!(
+            call(INFO_SUPPORTED_FUNCTION_PAGES, &[]),
+            Err(Error::InvalidValue(0))
+        );
+        assert_eq!(
+            call(INFO_SUPPORTED_FUNCTIONS, &[]),
+            Err(Error::InvalidValue(0))
+        );
+        assert_eq!(
+            call(INFO_SUPPORTED_FUNCTIONS, &[INFO_FUNCTION_PAGE]),
+            Err(Error::InvalidValue(1))
+        );
+        // offset is optional for hosts that predate chunking
+        let res = call(INFO_FIRMWARE_INFO, &[0]).unwrap();
+        assert_eq!(res.payload(), b"Unspecified Vendor");
+    }
+
+    fn dpi_set(dpi: u16, _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&dpi.to_be_bytes()).unwrap().into())
+    }
+
+    struct Sensor {
+        dpi: u16,
+    }
+
+    fn get_dpi(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let sensor = ctx.user::<Sensor>().ok_or(Error::UnsupportedFunction)?;
+        Ok(Vec::from_slice(&sensor.dpi.to_le_bytes()).unwrap().into())
+    }
+
+    #[test]
+    fn user_state() {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(0x80, &[(0x00, DispatchHandler::Function(get_dpi))])
+            .unwrap();
+        let sensor = Sensor { dpi: 1600 };
+
+        let request = OiReport::new_short(0x80, 0x00, &[0; 5]);
+        let reply = dispatch.dispatch_with(&request, &sensor);
+        assert_eq!(&reply[..], &[0x20, 0x80, 0x00, 0x40, 0x06, 0, 0, 0]);
+        assert_eq!(
+            dispatch
+                .dispatch_raw_with(0x80, 0x00, &[0; 5], &sensor)
+                .unwrap()
+                .payload(),
+            1600u16.to_le_bytes()
+        );
+
+        // the same dispatcher without state, or with the wrong type
+        assert_eq!(
+            dispatch.dispatch_raw(0x80, 0x00, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert_eq!(
+            dispatch.dispatch_raw_with(0x80, 0x00, &[0; 5], &1600u16),
+            Err(Error::UnsupportedFunction)
+        );
+
+        // the info page ignores it
+        let version = OiReport::new_short(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5]);
+        assert_eq!(
+            dispatch.dispatch_with(&version, &sensor),
+            dispatch.dispatch(&version)
+        );
+    }
+
+    #[test]
+    fn typed_handler() {
+        let handler = crate::typed_fn!(dpi_set);
+        let dispatch = Dispatch::default();
+        let ctx = || DispatchContext {
+            table: &dispatch.table,
+            meta: &dispatch.meta,
+            hidden: &dispatch.hidden,
+            hidden_functions: &dispatch.hidden_functions,
+            disabled: &dispatch.disabled,
+            pending: &[],
+            queue: &dispatch.queue,
+            stats: &dispatch.stats,
+            session: &dispatch.session,
+            page: 0x00,
+            fn_id: 0x00,
+            request_report_id: OiReportId::Long,
+            device_address: None,
+            user: &(),
+        };
+        assert_eq!(handler(&[0x40], ctx()), Err(Error::InvalidValue(0)));
+        let res = handler(&[0x40, 0x06, 0, 0, 0], ctx()).unwrap();
+        assert_eq!(res.payload(), &[0x06, 0x40]);
+    }
+
+    /// counts how often the host pinged it
+    #[derive(Default)]
+    struct CounterPage {
+        count: std::sync::atomic::AtomicU8,
+    }
+
+    impl FunctionPage for CounterPage {
+        fn page_id(&self) -> u8 {
+            0x42
+        }
+
+        fn functions(&self) -> &'static [u8] {
+            &[0x00, 0x01]
+        }
+
+        fn call(&self, fn_id: u8, _: &[u8], _: DispatchContext) -> DispatchReturn {
+            match fn_id {
+                0x00 => {
+                    self.count.fetch_add(1, Ordering::Relaxed);
+                }
+                0x01 => (),
+                _ => return Err(Error::UnsupportedFunction),
+            }
+            Ok(Vec::from_slice(&[self.count.load(Ordering::Relaxed)])
+                .unwrap()
+                .into())
+        }
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        requests: std::sync::Mutex<std::vec::Vec<(u8, u8, std::vec::Vec<u8>)>>,
+        /// reply payload or error id
+        responses: std::sync::Mutex<std::vec::Vec<Result<std::vec::Vec<u8>, u8>>>,
+    }
+
+    impl DispatchObserver for Recorder {
+        fn on_request(&self, page: u8, id: u8, data: &[u8]) {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((page, id, data.to_vec()));
+        }
+
+        fn on_response(&self, result: &DispatchReturn) {
+            let result = match result {
+                Ok(res) => Ok(res.payload().to_vec()),
+                Err(e) => Err(e.id()),
+            };
+            self.responses.lock().unwrap().push(result);
+        }
+    }
+
+    #[test]
+    fn observer() {
+        let recorder: &'static Recorder = Box::leak(Box::default());
+        let mut dispatch = Dispatch::default();
+        dispatch.set_observer(Some(recorder));
+
+        dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(
+            recorder.requests.lock().unwrap().as_slice(),
+            &[(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, vec![0; 5])]
+        );
+        assert_eq!(
+            recorder.responses.lock().unwrap().as_slice(),
+            &[Ok(crate::PROTOCOL_VERSION.to_bytes().to_vec())]
+        );
+
+        // removed again
+        dispatch.set_observer(None);
+        dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(recorder.requests.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn register_page() {
+        let page: &'static CounterPage = Box::leak(Box::default());
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(page).ok().unwrap();
+
+        dispatch.dispatch_raw(0x42, 0x00, &[0; 5]).unwrap();
+        let res = dispatch.dispatch_raw(0x42, 0x01, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[1]);
+        assert_eq!(
+            dispatch.dispatch_raw(0x42, 0x02, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+
+        let pages = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[0; 5],
+            )
+            .unwrap();
+        assert_eq!(pages.payload(), &[2, 2, INFO_FUNCTION_PAGE, 0x42]);
+        let fns = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0x42, 0, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(fns.payload(), &[2, 2, 0x00, 0x01]);
+    }
+
+    fn counts(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let count = |page| ctx.function_count(page).map_or(0xFF, |count| count as u8);
+        Ok(Vec::from_slice(&[
+            ctx.page_count() as u8,
+            count(0x00),
+            count(0xA0),
+            count(0xA1),
+        ])
+        .unwrap()
+        .into())
+    }
+
+    #[test]
+    fn page_and_function_counts() {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0xA0,
+                &[
+                    (0x00, DispatchHandler::Function(counts)),
+                    (0x01, DispatchHandler::Function(dpi_get)),
+                ],
+            )
+            .unwrap();
+        dispatch
+            .register_vendor_page(0xA1, &[(0x00, DispatchHandler::Function(dpi_get))])
+            .unwrap();
+        let info_fns = dispatch.functions(INFO_FUNCTION_PAGE).unwrap().count() as u8;
+        let res = dispatch.dispatch_raw(0xA0, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[3, info_fns, 2, 1]);
+
+        // same view as the supported pages/functions replies, disabled pages aren't counted
+        dispatch.set_page_enabled(0xA1, false);
+        let res = dispatch.dispatch_raw(0xA0, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[2, info_fns, 2, 0xFF]);
+
+        let table = crate::dispatch_table! {
+            @info,
+            0xA0 => { 0x00 => counts },
+            0xA1 => { 0x00 => dpi_get },
+        };
+        let dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        let res = dispatch.dispatch_raw(0xA0, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[3, info_fns, 1, 1]);
+    }
+
+    fn proxy(fn_id: u8, data: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&[fn_id, data[0]]).unwrap().into())
+    }
+
+    #[test]
+    fn page_default() {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(0xA0, &[(0x00, DispatchHandler::Function(dpi_get))])
+            .unwrap();
+        dispatch
+            .register_vendor_page(0xA1, &[(0x00, DispatchHandler::Function(dpi_get))])
+            .unwrap();
+        assert!(dispatch.register_page_default(0xA0, proxy));
+        assert!(!dispatch.register_page_default(INFO_FUNCTION_PAGE, proxy));
+        assert!(!dispatch.register_page_default(0xA2, proxy));
+
+        // registered ids keep their handler
+        assert_eq!(
+            dispatch
+                .dispatch_raw(0xA0, 0x00, &[0; 5])
+                .unwrap()
+                .payload(),
+            dispatch
+                .dispatch_raw(0xA1, 0x00, &[0; 5])
+                .unwrap()
+                .payload()
+        );
+        // unknown ids go to the default with their id
+        assert_eq!(
+            dispatch
+                .dispatch_raw(0xA0, 0x07, &[0x42, 0, 0, 0, 0])
+                .unwrap()
+                .payload(),
+            &[0x07, 0x42]
+        );
+        // pages without one behave as before
+        assert_eq!(
+            dispatch.dispatch_raw(0xA1, 0x07, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert_eq!(
+            dispatch.dispatch_raw(INFO_FUNCTION_PAGE, 0x07, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert_eq!(
+            dispatch.dispatch_raw(0xA2, 0x07, &[0; 5]),
+            Err(Error::UnsupportedPage)
+        );
+
+        // the default doesn't add ids to supported functions
+        let fns = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0xA0, 0, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(fns.payload(), &[1, 1, 0x00]);
+    }
+
+    #[test]
+    fn invalid_value_index() {
+        let dispatch = Dispatch::default();
+        let error = |id, params: [u8; 5]| {
+            let reply = dispatch.dispatch(&OiReport::new_short(INFO_FUNCTION_PAGE, id, &params));
+            // ReportId, error page, InvalidValue, failing page and function, then the index
+            assert_eq!(reply[..5], [0x20, 0xFF, 0x01, INFO_FUNCTION_PAGE, id]);
+            reply[5]
+        };
+
+        // unknown FirmwareInfoParam is the first param
+        assert_eq!(error(info_table::INFO_FIRMWARE_INFO, [0x7F, 0, 0, 0, 0]), 0);
+        // supported_fns: unknown page first, start past the end second
+        assert_eq!(
+            error(info_table::INFO_SUPPORTED_FUNCTIONS, [0xA0, 0, 0, 0, 0]),
+            0
+        );
+        assert_eq!(
+            error(
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                [INFO_FUNCTION_PAGE, 9, 0, 0, 0]
+            ),
+            1
+        );
+        assert_eq!(
+            error(info_table::INFO_SUPPORTED_FUNCTION_PAGES, [9, 0, 0, 0, 0]),
+            0
+        );
+    }
+
+    #[test]
+    fn table_counts() {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0xA1,
+                &[
+                    (0x00, DispatchHandler::Function(dpi_get)),
+                    (0x01, DispatchHandler::Function(dpi_get)),
+                ],
+            )
+            .unwrap();
+        dispatch.register_vendor_page(0xA0, &[]).unwrap();
+        assert_eq!(dispatch.page_count(), 3);
+        assert!(dispatch.iter_pages().eq([INFO_FUNCTION_PAGE, 0xA1, 0xA0]));
+        assert_eq!(dispatch.page_function_count(0xA1), Some(2));
+        assert_eq!(dispatch.page_function_count(0xA0), Some(0));
+        assert_eq!(dispatch.page_function_count(0xA2), None);
+        assert!(dispatch.iter_functions(0xA1).unwrap().eq([0x00, 0x01]));
+        assert_eq!(
+            dispatch.page_function_count(INFO_FUNCTION_PAGE),
+            Some(INFO_PAGE.functions.len())
+        );
+
+        // hidden pages are still registered
+        dispatch.set_page_hidden(0xA0, true);
+        assert_eq!(dispatch.page_count(), 3);
+
+        let table = crate::dispatch_table! {
+            0xA0 => { 0x01 => dpi_get, 0x00 => dpi_get },
+            0xA1 => {},
+        };
+        let dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        assert_eq!(dispatch.page_count(), 3);
+        assert!(dispatch.iter_pages().eq([INFO_FUNCTION_PAGE, 0xA0, 0xA1]));
+        assert!(dispatch.iter_functions(0xA0).unwrap().eq([0x01, 0x00]));
+        assert_eq!(dispatch.page_function_count(0xA1), Some(0));
+        assert!(dispatch.iter_functions(0xA2).is_none());
+    }
+
+    #[test]
+    fn supported_pages_continuation() {
+        let table = crate::dispatch_table! {
+            @info,
+            0xA0 => { 0x00 => dpi_get },
+            0xA1 => { 0x00 => dpi_get },
+            0xA2 => { 0x00 => dpi_get },
+            0xA3 => { 0x00 => dpi_get },
+        };
+        let dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        let query = |start| {
+            let request = OiReport::new_short(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[start, 0, 0, 0, 0],
+            );
+            dispatch.dispatch(&request)
+        };
+
+        // five pages don't fit a short reply, the host continues from start + count
+        let first = query(0);
+        assert_eq!(
+            first.as_slice(),
+            &[0x20, 0x00, 0x02, 5, 3, INFO_FUNCTION_PAGE, 0xA0, 0xA1]
+        );
+        let second = query(3);
+        assert_eq!(second.as_slice(), &[0x20, 0x00, 0x02, 5, 2, 0xA2, 0xA3, 0]);
+
+        // a long request gets them all at once
+        let res = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[0; 29],
+            )
+            .unwrap();
+        assert_eq!(
+            res.payload(),
+            &[5, 5, INFO_FUNCTION_PAGE, 0xA0, 0xA1, 0xA2, 0xA3]
+        );
+    }
+
+    /// supported pages/functions payload of a long request, without the total and count
+    fn wire_list(dispatch: &Dispatch, id: u8, params: &[u8]) -> std::vec::Vec<u8> {
+        let mut data = [0; 29];
+        data[..params.len()].copy_from_slice(params);
+        let res = dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, id, &data)
+            .unwrap();
+        res.payload()[2..].to_vec()
+    }
+
+    #[test]
+    fn introspection_matches_info() {
+        let counter: &'static CounterPage = Box::leak(Box::default());
+        let mut map = Dispatch::default();
+        map.register_page(counter).ok().unwrap();
+        // registered out of order
+        let table = crate::dispatch_table! {
+            @info,
+            0xA3 => { 0x01 => dpi_get, 0x00 => dpi_get },
+            0xA0 => { 0x00 => dpi_get },
+        };
+        let array = Dispatch::new_static(table, DispatchMeta::default());
+
+        for dispatch in [&map, &array] {
+            let pages: std::vec::Vec<u8> = dispatch.pages().collect();
+            assert!(pages.windows(2).all(|w| w[0] < w[1]), "{:x?}", pages);
+            assert_eq!(
+                pages,
+                wire_list(dispatch, info_table::INFO_SUPPORTED_FUNCTION_PAGES, &[0])
+            );
+            for page in pages {
+                let functions: std::vec::Vec<u8> = dispatch.functions(page).unwrap().collect();
+                assert_eq!(
+                    functions,
+                    wire_list(dispatch, info_table::INFO_SUPPORTED_FUNCTIONS, &[page, 0])
+                );
+                assert!(functions.iter().all(|&id| dispatch.contains(page, id)));
+            }
+            assert!(dispatch.functions(0x43).is_none());
+            assert!(!dispatch.contains(INFO_FUNCTION_PAGE, 0x43));
+        }
+        assert!(array.contains(0xA3, 0x01));
+    }
+
+    /// 0x00 is required, 0x01 and 0x02 are optional and only 0x02 is implemented
+    struct OptionalPage;
+
+    impl FunctionPage for OptionalPage {
+        fn page_id(&self) -> u8 {
+            0xA5
+        }
+
+        fn functions(&self) -> &'static [u8] {
+            &[0x00, 0x01, 0x02]
+        }
+
+        fn optional_functions(&self) -> &'static [u8] {
+            &[0x01, 0x02]
+        }
+
+        fn capabilities(&self) -> u8 {
+            0b10
+        }
+
+        fn call(&self, fn_id: u8, _: &[u8], _: DispatchContext) -> DispatchReturn {
+            assert_ne!(fn_id, 0x01, "called an unimplemented function");
+            Ok(Vec::new().into())
+        }
+    }
+
+    #[test]
+    fn optional_functions() {
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(&OptionalPage).ok().unwrap();
+
+        assert_eq!(
+            wire_list(&dispatch, info_table::INFO_SUPPORTED_FUNCTIONS, &[0xA5, 0]),
+            [0x00, 0x02]
+        );
+        assert!(!dispatch.contains(0xA5, 0x01));
+        assert_eq!(
+            dispatch.dispatch_raw(0xA5, 0x01, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert!(dispatch.dispatch_raw(0xA5, 0x02, &[0; 5]).is_ok());
+    }
+
+    #[test]
+    fn hidden_pages() {
+        // by hand, dispatch_table! doesn't take the error page
+        const TABLE: &[StaticPage] = &[
+            INFO_PAGE,
+            StaticPage {
+                id: 0xA0,
+                functions: &[(0x00, DispatchHandler::Function(dpi_get))],
+            },
+            StaticPage {
+                id: 0xA1,
+                functions: &[(0x00, DispatchHandler::Function(dpi_get))],
+            },
+            StaticPage {
+                id: ERROR_FUNCTION_PAGE,
+                functions: &[(0x00, DispatchHandler::Function(dpi_get))],
+            },
+        ];
+        let mut dispatch = Dispatch::new_static(TABLE, DispatchMeta::default());
+        dispatch.set_page_hidden(0xA1, true);
+        let pages = |dispatch: &Dispatch| {
+            dispatch
+                .dispatch_raw(
+                    INFO_FUNCTION_PAGE,
+                    info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                    &[0; 5],
+                )
+                .unwrap()
+                .0
+        };
+        // the error page is never listed
+        assert_eq!(
+            pages(&dispatch).as_slice(),
+            &[2, 2, INFO_FUNCTION_PAGE, 0xA0]
+        );
+        assert_eq!(
+            dispatch.dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0xA1, 0, 0, 0, 0]
+            ),
+            Err(Error::InvalidValue(0))
+        );
+        // still callable
+        assert!(dispatch.dispatch_raw(0xA1, 0x00, &[0; 5]).is_ok());
+
+        dispatch.set_page_hidden(0xA1, false);
+        assert_eq!(
+            pages(&dispatch).as_slice(),
+            &[3, 3, INFO_FUNCTION_PAGE, 0xA0, 0xA1]
+        );
+
+        let counter: &'static CounterPage = Box::leak(Box::default());
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page_hidden(counter).ok().unwrap();
+        assert_eq!(pages(&dispatch).as_slice(), &[1, 1, INFO_FUNCTION_PAGE]);
+        let res = dispatch.dispatch_raw(0x42, 0x01, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[0]);
+    }
+
+    #[test]
+    fn disabled_pages() {
+        let table = crate::dispatch_table! {
+            @info,
+            0xA0 => { 0x00 => dpi_get },
+            0xA1 => { 0x00 => dpi_get },
+        };
+        let mut dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        let pages = |dispatch: &Dispatch| {
+            wire_list(dispatch, info_table::INFO_SUPPORTED_FUNCTION_PAGES, &[0])
+        };
+
+        dispatch.set_page_enabled(0xA1, false);
+        assert!(!dispatch.is_page_enabled(0xA1));
+        assert_eq!(pages(&dispatch), [INFO_FUNCTION_PAGE, 0xA0]);
+        assert_eq!(
+            dispatch.dispatch_raw(0xA1, 0x00, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert_eq!(
+            dispatch.dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0xA1, 0, 0, 0, 0]
+            ),
+            Err(Error::InvalidValue(0))
+        );
+
+        dispatch.set_page_enabled(0xA1, true);
+        assert_eq!(pages(&dispatch), [INFO_FUNCTION_PAGE, 0xA0, 0xA1]);
+        assert!(dispatch.dispatch_raw(0xA1, 0x00, &[0; 5]).is_ok());
+
+        // the info page stays
+        dispatch.set_page_enabled(INFO_FUNCTION_PAGE, false);
+        assert!(dispatch.is_page_enabled(INFO_FUNCTION_PAGE));
+        assert_eq!(pages(&dispatch), [INFO_FUNCTION_PAGE, 0xA0, 0xA1]);
+    }
+
+    #[test]
+    fn hidden_functions() {
+        let table = crate::dispatch_table! {
+            @info,
+            0xA0 => { 0x00 => dpi_get, 0x01 => dpi_get, 0x02 => dpi_get },
+        };
+        let mut dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        let functions = |dispatch: &Dispatch| {
+            wire_list(dispatch, info_table::INFO_SUPPORTED_FUNCTIONS, &[0xA0, 0])
+        };
+
+        assert!(dispatch.set_function_hidden(0xA0, 0x01, true));
+        assert_eq!(functions(&dispatch), [0x00, 0x02]);
+        let res = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0xA0, 0, 0, 0, 0],
+            )
+            .unwrap();
+        // count and total leave it out too
+        assert_eq!(res.payload(), &[2, 2, 0x00, 0x02]);
+        // still callable
+        assert!(dispatch.dispatch_raw(0xA0, 0x01, &[0; 5]).is_ok());
+
+        assert!(dispatch.set_function_hidden(0xA0, 0x01, false));
+        assert_eq!(functions(&dispatch), [0x00, 0x01, 0x02]);
+
+        // functions that aren't registered can be hidden ahead of time, up to the table size
+        for id in 0..64 {
+            assert!(dispatch.set_function_hidden(0xA1, id, true));
+        }
+        assert!(!dispatch.set_function_hidden(0xA1, 64, true));
+        assert!(dispatch.set_function_hidden(0xA1, 0, true));
+    }
+
+    #[test]
+    fn debug_output() {
+        let error = Error::custom("no dice").unwrap();
+        assert_eq!(format!("{:?}", error), r#"Custom("no dice")"#);
+
+        let meta = DispatchMeta::new("Dekanova", "1.0.0", "Keyboard").unwrap();
+        let debug = format!("{:?}", meta);
+        assert!(
+            debug.starts_with(r#"DispatchMeta { protocol_version: "#),
+            "{}",
+            debug
+        );
+        assert!(
+            debug.contains(r#"firmware_vendor: "Dekanova""#),
+            "{}",
+            debug
+        );
+        assert_eq!(
+            meta,
+            DispatchMeta::from_static("Dekanova", "1.0.0", "Keyboard")
+        );
+        assert_ne!(meta, DispatchMeta::default());
+    }
+
+    #[test]
+    fn unsupported_page_or_function() {
+        let page: &'static CounterPage = Box::leak(Box::default());
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(page).ok().unwrap();
+
+        // no such page
+        let reply = dispatch.dispatch(&OiReport::new_short(0x43, 0x00, &[0; 5]));
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x04, 0x43, 0x00, 0, 0, 0]);
+        // page exists, function doesn't
+        let reply = dispatch.dispatch(&OiReport::new_short(0x42, 0x07, &[0; 5]));
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x02, 0x42, 0x07, 0, 0, 0]);
+        // supported functions of a page that doesn't exist, parameter 0 is invalid
+        let reply = dispatch.dispatch(&OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            info_table::INFO_SUPPORTED_FUNCTIONS,
+            &[0x43, 0, 0, 0, 0],
+        ));
+        assert_eq!(
+            reply.as_slice(),
+            &[
+                0x20,
+                0xFF,
+                0x01,
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                0,
+                0,
+                0
+            ]
+        );
+    }
+
+    fn version_stub(_: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&[9, 9, 9]).unwrap().into())
+    }
+
+    #[test]
+    fn override_and_remove_function() {
+        let mut dispatch = Dispatch::default();
+        assert!(dispatch
+            .override_info()
+            .override_function(info_table::INFO_VERSION, version_stub));
+        let res = dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[9, 9, 9]);
+
+        assert!(dispatch
+            .override_info()
+            .remove_function(info_table::INFO_VERSION));
+        assert_eq!(
+            dispatch.dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        // the rest of the page is still there
+        assert!(dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &[0; 5])
+            .is_ok());
+
+        assert!(!dispatch
+            .override_info()
+            .remove_function(info_table::INFO_VERSION));
+        assert!(!dispatch.override_function(0x42, 0x00, version_stub));
+    }
+
+    /// a page claiming to be `.0`
+    struct PageAt(u8);
+
+    impl FunctionPage for PageAt {
+        fn page_id(&self) -> u8 {
+            self.0
+        }
+
+        fn functions(&self) -> &'static [u8] {
+            &[0x00, 0x10]
+        }
+
+        fn call(&self, _: u8, _: &[u8], _: DispatchContext) -> DispatchReturn {
+            Ok(Vec::from_slice(&[0xEE]).unwrap().into())
+        }
+    }
+
+    #[test]
+    fn protected_pages() {
+        let version = |dispatch: &Dispatch| {
+            dispatch
+                .dispatch(&OiReport::new_short(
+                    INFO_FUNCTION_PAGE,
+                    info_table::INFO_VERSION,
+                    &[0; 5],
+                ))
+                .to_vec()
+        };
+        let expected = version(&Dispatch::default_const(DispatchMeta::default()));
+        assert_eq!(&expected[3..6], &crate::PROTOCOL_VERSION.to_bytes());
+
+        let mut dispatch = Dispatch::default();
+        let info: &'static PageAt = Box::leak(Box::new(PageAt(INFO_FUNCTION_PAGE)));
+        let error: &'static PageAt = Box::leak(Box::new(PageAt(ERROR_FUNCTION_PAGE)));
+        assert_eq!(dispatch.register_page(info), Err(RegisterError::InfoPage));
+        assert_eq!(
+            dispatch.register_page_hidden(info),
+            Err(RegisterError::InfoPage)
+        );
+        assert_eq!(dispatch.register_page(error), Err(RegisterError::ErrorPage));
+        assert!(!dispatch.override_function(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, dpi_get));
+        assert!(!dispatch.remove_function(INFO_FUNCTION_PAGE, info_table::INFO_VERSION));
+        assert!(!dispatch.override_function(ERROR_FUNCTION_PAGE, 0x00, dpi_get));
+        assert!(!dispatch.remove_function(ERROR_FUNCTION_PAGE, 0x00));
+        let counter: &'static CounterPage = Box::leak(Box::default());
+        dispatch.register_page(counter).unwrap();
+        // whatever else gets registered, the version is untouched
+        assert_eq!(version(&dispatch), expected);
+        assert!(!dispatch.contains(INFO_FUNCTION_PAGE, 0x10));
+        assert!(!dispatch.contains(ERROR_FUNCTION_PAGE, 0x00));
+
+        // on purpose
+        assert_eq!(
+            dispatch.override_info().register_page(error),
+            Err(RegisterError::ErrorPage)
+        );
+        dispatch.override_info().register_page(info).unwrap();
+        assert_eq!(version(&dispatch), &[0x20, 0x00, 0x00, 0xEE, 0, 0, 0, 0]);
+        assert!(dispatch.contains(INFO_FUNCTION_PAGE, 0x10));
+    }
+
+    #[test]
+    fn page_taken() {
+        let mut dispatch = Dispatch::default();
+        let counter: &'static CounterPage = Box::leak(Box::default());
+        dispatch.register_page(counter).unwrap();
+
+        // same id, the counter's handlers stay
+        let other: &'static PageAt = Box::leak(Box::new(PageAt(0x42)));
+        assert_eq!(
+            dispatch.register_page(other),
+            Err(RegisterError::DuplicatePage)
+        );
+        let res = dispatch.dispatch_raw(0x42, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[1]);
+        assert!(!dispatch.contains(0x42, 0x10));
+
+        // functions on their own don't own the page
+        dispatch
+            .register_vendor_page(0x80, &[(0x00, DispatchHandler::Function(dpi_get))])
+            .unwrap();
+        let vendor: &'static PageAt = Box::leak(Box::new(PageAt(0x80)));
+        dispatch.register_page(vendor).unwrap();
+        assert!(dispatch.contains(0x80, 0x10));
+    }
+
+    fn dpi_get(_: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&[0x06, 0x40]).unwrap().into())
+    }
+
+    #[test]
+    fn static_table() {
+        let table = crate::dispatch_table! {
+            @info,
+            0xA0 => { 0x00 => dpi_get, 0x01 => version_stub },
+        };
+        let mut dispatch = Dispatch::new_static(table, DispatchMeta::default());
+
+        let res = dispatch.dispatch_raw(0xA0, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[0x06, 0x40]);
+        let res = dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &crate::PROTOCOL_VERSION.to_bytes());
+        let pages = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[0; 5],
+            )
+            .unwrap();
+        assert_eq!(pages.payload(), &[2, 2, INFO_FUNCTION_PAGE, 0xA0]);
+        assert_eq!(
+            dispatch.dispatch_raw(0xA0, 0x02, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+
+        // static tables are read only
+        assert!(!dispatch.remove_function(0xA0, 0x00));
+        assert!(!dispatch.override_function(0xA0, 0x00, version_stub));
+
+        // the info page is there without @info
+        let table = crate::dispatch_table! {
+            0xA0 => { 0x00 => dpi_get },
+        };
+        let dispatch = Dispatch::new_static(table, DispatchMeta::default());
+        let res = dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &crate::PROTOCOL_VERSION.to_bytes());
+    }
+
+    #[test]
+    #[should_panic(expected = "static tables need the info page")]
+    fn static_table_without_info() {
+        const TABLE: &[StaticPage] = &[StaticPage {
+            id: 0xA0,
+            functions: &[(0x00, DispatchHandler::Function(dpi_get))],
+        }];
+        Dispatch::new_static(TABLE, DispatchMeta::default());
+    }
+
+    #[cfg(feature = "macros")]
+    #[test]
+    fn match_table() {
+        struct Table;
+
+        #[crate::openinput_dispatch_table]
+        impl Table {
+            #[function(page = 0xA0, id = 0x01)]
+            fn dpi_set(_: &[u8], _: DispatchContext) -> DispatchReturn {
+                Ok(Vec::new().into())
+            }
+
+            #[function(page = 0xA0, id = 0x00)]
+            fn dpi_get(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+                dpi_get(data, ctx)
+            }
+        }
+
+        let dispatch = Dispatch::new_match::<Table>(DispatchMeta::default());
+        let res = dispatch.dispatch_raw(0xA0, 0x00, &[0; 5]).unwrap();
+        assert_eq!(res.payload(), &[0x06, 0x40]);
+        assert_eq!(
+            dispatch.dispatch_raw(0xA0, 0x02, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        assert_eq!(
+            dispatch.dispatch_raw(0xA1, 0x00, &[0; 5]),
+            Err(Error::UnsupportedPage)
+        );
+
+        // the info page is always there, functions are listed in id order
+        let res = dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &crate::PROTOCOL_VERSION.to_bytes());
+        let pages = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[0; 5],
+            )
+            .unwrap();
+        assert_eq!(pages.payload(), &[2, 2, INFO_FUNCTION_PAGE, 0xA0]);
+        let functions = dispatch
+            .dispatch_raw(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[0xA0, 0, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(functions.payload(), &[2, 2, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn default_const() {
+        // the doc test checks this compiles in a static
+        let constant = Dispatch::default_const(crate::dispatch_meta!(
+            "Unspecified Vendor",
+            "Unspecified Version",
+            "Unspecified Name"
+        ));
+        let runtime = Dispatch::default();
+        assert_eq!(constant.meta, runtime.meta);
+
+        let requests = [
+            OiReport::new_short(INFO_FUNCTION_PAGE, info_table::INFO_VERSION, &[0; 5]),
+            OiReport::new_short(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+                &[0; 5],
+            ),
+            OiReport::new_short(
+                INFO_FUNCTION_PAGE,
+                info_table::INFO_SUPPORTED_FUNCTIONS,
+                &[INFO_FUNCTION_PAGE, 0, 0, 0, 0],
+            ),
+        ];
+        for request in &requests {
+            assert_eq!(constant.dispatch(request), runtime.dispatch(request));
+        }
+        assert_eq!(
+            constant.pages().collect::<std::vec::Vec<_>>(),
+            runtime.pages().collect::<std::vec::Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate function id")]
+    fn static_table_duplicate_function() {
+        // same table the macro would build, checked at runtime to see the panic
+        const PAGES: &[StaticPage] = &[StaticPage {
+            id: 0xA0,
+            functions: &[
+                (0x00, DispatchHandler::Function(dpi_get)),
+                (0x00, DispatchHandler::Function(dpi_get)),
+            ],
+        }];
+        StaticPage::check_table(PAGES);
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate function page id")]
+    fn static_table_duplicate_page() {
+        StaticPage::check_table(&[
+            INFO_PAGE,
+            StaticPage {
+                id: INFO_FUNCTION_PAGE,
+                functions: &[],
+            },
+        ]);
+    }
+
+    #[test]
+    fn invalid_value_index() {
+        let dispatch = Dispatch::default();
+        // param byte is the first one after the function id
+        let report =
+            OiReport::new_short(INFO_FUNCTION_PAGE, info_table::INFO_FIRMWARE_INFO, &[7; 5]);
+        assert_eq!(
+            dispatch.dispatch_report(&report),
+            Err(Error::InvalidValue(0))
+        );
+
+        let report = OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+            &[9, 0, 0, 0, 0],
+        );
+        assert_eq!(
+            dispatch.dispatch_report(&report),
+            Err(Error::InvalidValue(0))
+        );
+
+        // start index is the second param
+        let report = OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            info_table::INFO_SUPPORTED_FUNCTIONS,
+            &[INFO_FUNCTION_PAGE, 9, 0, 0, 0],
+        );
+        assert_eq!(
+            dispatch.dispatch_report(&report),
+            Err(Error::InvalidValue(1))
+        );
+
+        // starting right at the end isn't an error, just an empty list
+        let report = OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            info_table::INFO_SUPPORTED_FUNCTION_PAGES,
+            &[1, 0, 0, 0, 0],
+        );
+        let res = dispatch.dispatch_report(&report).unwrap();
+        assert_eq!(res.payload(), &[1, 0]);
+        let report = OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            info_table::INFO_SUPPORTED_FUNCTIONS,
+            &[INFO_FUNCTION_PAGE, 4, 0, 0, 0],
+        );
+        let res = dispatch.dispatch_report(&report).unwrap();
+        assert_eq!(res.payload(), &[4, 0]);
+    }
+
+    #[test]
+    fn dispatch_bad_length() {
+        let dispatch = Dispatch::default();
+        let report = OiReport {
+            id: OPENINPUT_SHORT_REPORT_ID,
+            function_page: INFO_FUNCTION_PAGE,
+            function_id: info_table::INFO_VERSION,
+            data: &[0; 2],
+        };
+        assert_eq!(
+            dispatch.dispatch_report(&report),
+            Err(Error::InvalidValue(0))
+        );
     }
 }
@@ -0,0 +1,183 @@
+//! Logical bounds and input mode of absolute pointing devices (graphics tablets, drawing pads)
+//!
+//! Firmware sets an [`AbsolutePointerConfig`] in [`DispatchMeta`], the host can read the coordinate range and
+//! resolution and switch between relative and absolute reporting. The page only stores the mode, firmware
+//! reads it back from [`DispatchMeta::abs_pointer`] to pick which reports to send.
+
+use heapless::Vec;
+
+use super::args::call_typed;
+use super::{DispatchContext, DispatchMeta, DispatchReturn, Error, FunctionPage};
+use crate::absolute_pointer::{self, ABS_POINTER_DESC_LEN};
+
+/// not assigned by the openinput spec
+pub const ABS_POINTER_FUNCTION_PAGE: u8 = 0x11;
+
+pub const GET_LOGICAL_BOUNDS: u8 = 0x00;
+pub const GET_RESOLUTION_DPI: u8 = 0x01;
+pub const SET_INPUT_MODE: u8 = 0x02;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum PointerInputMode {
+    /// deltas like a mouse
+    Relative = 0,
+    /// coordinates in `0..=max_x` / `0..=max_y`
+    #[default]
+    Absolute = 1,
+}
+
+impl TryFrom<u8> for PointerInputMode {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(PointerInputMode::Relative),
+            1 => Ok(PointerInputMode::Absolute),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AbsolutePointerConfig {
+    /// largest x coordinate, sent as LOGICAL_MAXIMUM in the descriptor
+    pub max_x: u16,
+    /// largest y coordinate
+    pub max_y: u16,
+    pub dpi: u16,
+    pub mode: PointerInputMode,
+}
+
+impl AbsolutePointerConfig {
+    /// [`OiAbsolutePointerReport`](crate::absolute_pointer::OiAbsolutePointerReport) descriptor with these
+    /// bounds, see [`absolute_pointer::descriptor`]
+    pub const fn descriptor(&self) -> [u8; ABS_POINTER_DESC_LEN] {
+        absolute_pointer::descriptor(self.max_x, self.max_y)
+    }
+}
+
+fn config(ctx: &DispatchContext) -> Result<AbsolutePointerConfig, Error> {
+    ctx.meta().abs_pointer().ok_or(Error::UnsupportedFunction)
+}
+
+/// max x, max y as little endian u16s
+fn get_logical_bounds(_: (), ctx: DispatchContext) -> DispatchReturn {
+    let config = config(&ctx)?;
+    let [x0, x1] = config.max_x.to_le_bytes();
+    let [y0, y1] = config.max_y.to_le_bytes();
+    Ok(Vec::from_slice(&[x0, x1, y0, y1]).unwrap().into())
+}
+
+fn get_resolution_dpi(_: (), ctx: DispatchContext) -> DispatchReturn {
+    let dpi = config(&ctx)?.dpi;
+    Ok(Vec::from_slice(&dpi.to_le_bytes()).unwrap().into())
+}
+
+fn set_input_mode(mode: u8, ctx: DispatchContext) -> DispatchReturn {
+    let mut config = config(&ctx)?;
+    config.mode = PointerInputMode::try_from(mode).map_err(|_| Error::InvalidValue(0))?;
+    ctx.meta().set_abs_pointer(Some(config));
+    Ok(Vec::new().into())
+}
+
+/// exposes [`DispatchMeta::abs_pointer`] to the host, every function fails with
+/// [`Error::UnsupportedFunction`] if no config is set
+pub struct AbsolutePointerPage;
+
+impl FunctionPage for AbsolutePointerPage {
+    fn page_id(&self) -> u8 {
+        ABS_POINTER_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[GET_LOGICAL_BOUNDS, GET_RESOLUTION_DPI, SET_INPUT_MODE]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_LOGICAL_BOUNDS => call_typed(data, ctx, get_logical_bounds),
+            GET_RESOLUTION_DPI => call_typed(data, ctx, get_resolution_dpi),
+            SET_INPUT_MODE => call_typed(data, ctx, set_input_mode),
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+impl DispatchMeta {
+    pub fn abs_pointer(&self) -> Option<AbsolutePointerConfig> {
+        self.abs_pointer.get()
+    }
+
+    pub fn set_abs_pointer(&self, config: Option<AbsolutePointerConfig>) {
+        self.abs_pointer.set(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    const CONFIG: AbsolutePointerConfig = AbsolutePointerConfig {
+        max_x: 32000,
+        max_y: 18000,
+        dpi: 5080,
+        mode: PointerInputMode::Absolute,
+    };
+
+    fn dispatch(config: Option<AbsolutePointerConfig>) -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch.meta.set_abs_pointer(config);
+        dispatch.register_page(&AbsolutePointerPage).ok().unwrap();
+        dispatch
+    }
+
+    #[test]
+    fn bounds_and_resolution() {
+        let dispatch = dispatch(Some(CONFIG));
+        let res = dispatch
+            .dispatch_raw(ABS_POINTER_FUNCTION_PAGE, GET_LOGICAL_BOUNDS, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[0x00, 0x7d, 0x50, 0x46]);
+
+        let res = dispatch
+            .dispatch_raw(ABS_POINTER_FUNCTION_PAGE, GET_RESOLUTION_DPI, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[0xd8, 0x13]);
+    }
+
+    #[test]
+    fn input_mode() {
+        let dispatch = dispatch(Some(CONFIG));
+        dispatch
+            .dispatch_raw(ABS_POINTER_FUNCTION_PAGE, SET_INPUT_MODE, &[0; 5])
+            .unwrap();
+        assert_eq!(
+            dispatch.meta.abs_pointer().unwrap().mode,
+            PointerInputMode::Relative
+        );
+        dispatch
+            .dispatch_raw(ABS_POINTER_FUNCTION_PAGE, SET_INPUT_MODE, &[1, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(dispatch.meta.abs_pointer(), Some(CONFIG));
+
+        assert_eq!(
+            dispatch.dispatch_raw(ABS_POINTER_FUNCTION_PAGE, SET_INPUT_MODE, &[2, 0, 0, 0, 0]),
+            Err(Error::InvalidValue(0))
+        );
+        assert_eq!(dispatch.meta.abs_pointer(), Some(CONFIG));
+    }
+
+    #[test]
+    fn no_config() {
+        let dispatch = dispatch(None);
+        for fn_id in [GET_LOGICAL_BOUNDS, GET_RESOLUTION_DPI, SET_INPUT_MODE] {
+            assert_eq!(
+                dispatch.dispatch_raw(ABS_POINTER_FUNCTION_PAGE, fn_id, &[0; 5]),
+                Err(Error::UnsupportedFunction)
+            );
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Functions that need an authenticated session
+//!
+//! Every function is [`AccessLevel::Open`] unless marked otherwise with [`Dispatch::set_access`]. Protected
+//! functions (keymap writes, DFU entry, setting the serial number) answer [`Error::PermissionDenied`] until
+//! a session is open, and again after it is closed on timeout or disconnect.
+//!
+//! Firmware opens and closes sessions with [`Dispatch::set_session`], auth pages from their handler with
+//! [`DispatchContext::open_session`]. Timeouts are counted in ticks of whatever length the firmware feeds to
+//! [`Dispatch::tick`], e.g. from a 1ms timer:
+//!
+//! ```ignore
+//! dispatch.tick(elapsed_ms);
+//! ```
+
+use heapless::Vec;
+
+use super::{Dispatch, DispatchContext, Error, SyncCell};
+
+/// functions that can be marked [`AccessLevel::Authenticated`], one per function the table can hold
+pub const MAX_PROTECTED: usize = super::MAX_PAGES * super::MAX_FUNCTIONS;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AccessLevel {
+    #[default]
+    Open,
+    /// only callable in an authenticated session
+    Authenticated,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SessionState {
+    #[default]
+    Closed,
+    Authenticated,
+}
+
+/// page and function id of every protected function
+pub(crate) type Protected = Vec<(u8, u8), MAX_PROTECTED>;
+
+/// session state and the clock its timeout runs on, shared with handlers
+pub(crate) struct Session {
+    state: SyncCell<SessionState>,
+    /// ticks since [`Dispatch`] was created, wrapping
+    now: SyncCell<u32>,
+    opened_at: SyncCell<u32>,
+    timeout: SyncCell<Option<u32>>,
+}
+
+impl Session {
+    pub(crate) const fn new() -> Self {
+        Self {
+            state: SyncCell::new(SessionState::Closed),
+            now: SyncCell::new(0),
+            opened_at: SyncCell::new(0),
+            timeout: SyncCell::new(None),
+        }
+    }
+
+    pub(crate) fn state(&self) -> SessionState {
+        self.state.get()
+    }
+
+    /// `timeout` in ticks from now, `None` to stay in `state` until changed again
+    fn set(&self, state: SessionState, timeout: Option<u32>) {
+        critical_section::with(|_| {
+            self.state.set(state);
+            self.opened_at.set(self.now.get());
+            self.timeout.set(timeout);
+        })
+    }
+
+    fn tick(&self, ticks: u32) {
+        critical_section::with(|_| {
+            self.now.update(|now| *now = now.wrapping_add(ticks));
+            let expired = self.timeout.get().map_or(false, |timeout| {
+                self.now.get().wrapping_sub(self.opened_at.get()) >= timeout
+            });
+            if expired {
+                self.set(SessionState::Closed, None);
+            }
+        })
+    }
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// change who can call a function, works for static tables and for functions registered later
+    ///
+    /// `id` is the function id without the sequence number. returns false (and does nothing) if
+    /// [`MAX_PROTECTED`] functions are protected already
+    pub fn set_access(&mut self, page: u8, id: u8, level: AccessLevel) -> bool {
+        let pos = self.protected.iter().position(|&entry| entry == (page, id));
+        match (level, pos) {
+            (AccessLevel::Open, Some(pos)) => {
+                self.protected.swap_remove(pos);
+                true
+            }
+            (AccessLevel::Authenticated, None) => self.protected.push((page, id)).is_ok(),
+            _ => true,
+        }
+    }
+
+    pub fn access(&self, page: u8, id: u8) -> AccessLevel {
+        if self.protected.contains(&(page, id)) {
+            AccessLevel::Authenticated
+        } else {
+            AccessLevel::Open
+        }
+    }
+
+    /// open a session on successful authentication, close it on timeout or disconnect
+    ///
+    /// sessions opened here don't time out, this also cancels the timeout of one opened by a handler
+    pub fn set_session(&mut self, session: SessionState) {
+        self.session.set(session, None);
+    }
+
+    pub fn session(&self) -> SessionState {
+        self.session.state()
+    }
+
+    /// advance the clock session timeouts are counted in, closes a session that timed out
+    pub fn tick(&self, ticks: u32) {
+        self.session.tick(ticks);
+    }
+
+    /// [`Error::PermissionDenied`] for protected functions outside a session
+    pub(crate) fn check_access(&self, page: u8, id: u8) -> Result<(), Error> {
+        match (self.access(page, id), self.session.state()) {
+            (AccessLevel::Authenticated, SessionState::Closed) => Err(Error::PermissionDenied),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl DispatchContext<'_> {
+    pub fn session(&self) -> SessionState {
+        self.session.state()
+    }
+
+    /// open an authenticated session that [`Dispatch::tick`] closes again after `timeout` ticks
+    pub fn open_session(&self, timeout: u32) {
+        self.session.set(SessionState::Authenticated, Some(timeout));
+    }
+
+    pub fn close_session(&self) {
+        self.session.set(SessionState::Closed, None);
+    }
+
+    /// the clock [`Dispatch::tick`] advances, wrapping
+    pub fn ticks(&self) -> u32 {
+        self.session.now.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::info_table::INFO_VERSION;
+    use crate::dispatch::{DispatchHandler, DispatchReturn, INFO_FUNCTION_PAGE};
+
+    #[test]
+    fn protected_function() {
+        let mut dispatch = Dispatch::default();
+        let call =
+            |dispatch: &Dispatch| dispatch.dispatch_raw(INFO_FUNCTION_PAGE, INFO_VERSION, &[0; 5]);
+        assert!(call(&dispatch).is_ok());
+
+        assert!(dispatch.set_access(INFO_FUNCTION_PAGE, INFO_VERSION, AccessLevel::Authenticated));
+        assert_eq!(call(&dispatch), Err(Error::PermissionDenied));
+
+        dispatch.set_session(SessionState::Authenticated);
+        assert!(call(&dispatch).is_ok());
+
+        dispatch.set_session(SessionState::Closed);
+        assert_eq!(call(&dispatch), Err(Error::PermissionDenied));
+
+        // unknown functions still say so
+        assert!(dispatch.set_access(INFO_FUNCTION_PAGE, 0x42, AccessLevel::Authenticated));
+        assert_eq!(
+            dispatch.dispatch_raw(INFO_FUNCTION_PAGE, 0x42, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+
+        assert!(dispatch.set_access(INFO_FUNCTION_PAGE, INFO_VERSION, AccessLevel::Open));
+        assert!(call(&dispatch).is_ok());
+    }
+
+    fn login(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        ctx.open_session(10);
+        Ok(Vec::new().into())
+    }
+
+    #[test]
+    fn session_timeout() {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(0x80, &[(0x00, DispatchHandler::Function(login))])
+            .unwrap();
+        assert!(dispatch.set_access(INFO_FUNCTION_PAGE, INFO_VERSION, AccessLevel::Authenticated));
+
+        dispatch.tick(5);
+        dispatch.dispatch_raw(0x80, 0x00, &[0; 5]).unwrap();
+        assert_eq!(dispatch.session(), SessionState::Authenticated);
+        dispatch.tick(9);
+        assert!(dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, INFO_VERSION, &[0; 5])
+            .is_ok());
+        dispatch.tick(1);
+        assert_eq!(dispatch.session(), SessionState::Closed);
+        assert_eq!(
+            dispatch.dispatch_raw(INFO_FUNCTION_PAGE, INFO_VERSION, &[0; 5]),
+            Err(Error::PermissionDenied)
+        );
+
+        // firmware sessions stay open, and the clock wraps
+        dispatch.tick(u32::MAX);
+        dispatch.set_session(SessionState::Authenticated);
+        dispatch.tick(u32::MAX);
+        assert_eq!(dispatch.session(), SessionState::Authenticated);
+    }
+
+    #[test]
+    fn error_report() {
+        let report = Error::PermissionDenied.serialize_error(0x20, 0x10, 0x03);
+        assert_eq!(report.as_slice(), &[0x20, 0xFF, 0x06, 0x10, 0x03, 0, 0, 0]);
+    }
+}
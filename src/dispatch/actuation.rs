@@ -0,0 +1,231 @@
+//! Actuation point configuration for analog (hall-effect) switches
+//!
+//! Depths are a single byte covering 0-4mm in 16µm steps. Firmware adds the keys the host may configure to a
+//! `static` [`ActuationPointPage`] and registers it:
+//!
+//! ```ignore
+//! static ACTUATION: ActuationPointPage = ActuationPointPage::new();
+//!
+//! for key in 0..KEY_COUNT {
+//!     ACTUATION.add_key(key, ActuationConfig::default()).unwrap();
+//! }
+//! dispatch.register_page(&ACTUATION)?;
+//! ```
+
+use heapless::Vec;
+
+use super::{DispatchContext, DispatchReturn, Error, FunctionPage, SyncCell};
+
+/// not assigned by the openinput spec
+pub const ACTUATION_FUNCTION_PAGE: u8 = 0x10;
+
+pub const GET_ACTUATION_POINT: u8 = 0x00;
+pub const SET_ACTUATION_POINT: u8 = 0x01;
+pub const GET_RAPID_TRIGGER_ENABLED: u8 = 0x02;
+pub const SET_RAPID_TRIGGER_ENABLED: u8 = 0x03;
+pub const GET_RAPID_TRIGGER_SENSITIVITY: u8 = 0x04;
+pub const SET_RAPID_TRIGGER_SENSITIVITY: u8 = 0x05;
+
+/// key ids a page can track, `0..MAX_KEYS`
+pub const MAX_KEYS: usize = 128;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ActuationConfig {
+    /// 0-255 maps to 0-4mm
+    pub actuation_point: u8,
+    pub rapid_trigger: bool,
+    pub rapid_trigger_sensitivity: u8,
+}
+
+/// per-key actuation config, keys must be added with [`ActuationPointPage::add_key`] before the host can configure them
+pub struct ActuationPointPage {
+    // the host changes the config through a shared reference, `None` for keys that weren't added
+    keys: SyncCell<[Option<ActuationConfig>; MAX_KEYS]>,
+}
+
+impl Default for ActuationPointPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActuationPointPage {
+    pub const fn new() -> Self {
+        Self {
+            keys: SyncCell::new([None; MAX_KEYS]),
+        }
+    }
+
+    /// returns the config back if `key_id` isn't below [`MAX_KEYS`]
+    pub fn add_key(&self, key_id: u8, config: ActuationConfig) -> Result<(), ActuationConfig> {
+        self.keys
+            .update_at(key_id as usize, |key| *key = Some(config))
+            .ok_or(config)
+    }
+
+    pub fn config(&self, key_id: u8) -> Result<ActuationConfig, Error> {
+        self.keys
+            .get_at(key_id as usize)
+            .flatten()
+            .ok_or(Error::InvalidValue(0))
+    }
+
+    /// change the config of an added key, the check and the write happen in one critical section
+    fn update(&self, key_id: u8, f: impl FnOnce(&mut ActuationConfig)) -> Result<(), Error> {
+        self.keys
+            .update_at(key_id as usize, |key| key.as_mut().map(f))
+            .flatten()
+            .ok_or(Error::InvalidValue(0))
+    }
+
+    pub fn get_actuation_point(&self, key_id: u8) -> Result<u8, Error> {
+        Ok(self.config(key_id)?.actuation_point)
+    }
+
+    pub fn set_actuation_point(&self, key_id: u8, depth: u8) -> Result<(), Error> {
+        self.update(key_id, |config| config.actuation_point = depth)
+    }
+
+    pub fn get_rapid_trigger_enabled(&self, key_id: u8) -> Result<bool, Error> {
+        Ok(self.config(key_id)?.rapid_trigger)
+    }
+
+    pub fn set_rapid_trigger_enabled(&self, key_id: u8, enabled: bool) -> Result<(), Error> {
+        self.update(key_id, |config| config.rapid_trigger = enabled)
+    }
+
+    pub fn get_rapid_trigger_sensitivity(&self, key_id: u8) -> Result<u8, Error> {
+        Ok(self.config(key_id)?.rapid_trigger_sensitivity)
+    }
+
+    pub fn set_rapid_trigger_sensitivity(&self, key_id: u8, sens: u8) -> Result<(), Error> {
+        self.update(key_id, |config| config.rapid_trigger_sensitivity = sens)
+    }
+}
+
+impl FunctionPage for ActuationPointPage {
+    fn page_id(&self) -> u8 {
+        ACTUATION_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[
+            GET_ACTUATION_POINT,
+            SET_ACTUATION_POINT,
+            GET_RAPID_TRIGGER_ENABLED,
+            SET_RAPID_TRIGGER_ENABLED,
+            GET_RAPID_TRIGGER_SENSITIVITY,
+            SET_RAPID_TRIGGER_SENSITIVITY,
+        ]
+    }
+
+    /// params: key id, then the value for the setters
+    fn call(&self, fn_id: u8, data: &[u8], _: DispatchContext) -> DispatchReturn {
+        let key_id = *data.first().ok_or(Error::InvalidValue(0))?;
+        let value = || data.get(1).copied().ok_or(Error::InvalidValue(1));
+
+        let ret = match fn_id {
+            GET_ACTUATION_POINT => self.get_actuation_point(key_id)?,
+            SET_ACTUATION_POINT => {
+                self.set_actuation_point(key_id, value()?)?;
+                0
+            }
+            GET_RAPID_TRIGGER_ENABLED => self.get_rapid_trigger_enabled(key_id)? as u8,
+            SET_RAPID_TRIGGER_ENABLED => {
+                let enabled = match value()? {
+                    0 => false,
+                    1 => true,
+                    _ => return Err(Error::InvalidValue(1)),
+                };
+                self.set_rapid_trigger_enabled(key_id, enabled)?;
+                0
+            }
+            GET_RAPID_TRIGGER_SENSITIVITY => self.get_rapid_trigger_sensitivity(key_id)?,
+            SET_RAPID_TRIGGER_SENSITIVITY => {
+                self.set_rapid_trigger_sensitivity(key_id, value()?)?;
+                0
+            }
+            _ => return Err(Error::UnsupportedFunction),
+        };
+
+        Ok(Vec::from_slice(&[ret]).unwrap().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    fn dispatch() -> Dispatch {
+        let page: &'static ActuationPointPage = Box::leak(Box::default());
+        page.add_key(4, ActuationConfig::default()).unwrap();
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(page).unwrap();
+        dispatch
+    }
+
+    #[test]
+    fn round_trip() {
+        let page = ActuationPointPage::new();
+        page.add_key(4, ActuationConfig::default()).unwrap();
+
+        page.set_actuation_point(4, 125).unwrap();
+        page.set_rapid_trigger_enabled(4, true).unwrap();
+        page.set_rapid_trigger_sensitivity(4, 10).unwrap();
+
+        assert_eq!(page.get_actuation_point(4).unwrap(), 125);
+        assert!(page.get_rapid_trigger_enabled(4).unwrap());
+        assert_eq!(page.get_rapid_trigger_sensitivity(4).unwrap(), 10);
+    }
+
+    #[test]
+    fn round_trip_dispatch() {
+        let dispatch = dispatch();
+        let call = |fn_id, data: &[u8]| {
+            let mut params = [0; 5];
+            params[..data.len()].copy_from_slice(data);
+            dispatch.dispatch_raw(ACTUATION_FUNCTION_PAGE, fn_id, &params)
+        };
+
+        call(SET_ACTUATION_POINT, &[4, 200]).unwrap();
+        let res = call(GET_ACTUATION_POINT, &[4]).unwrap();
+        assert_eq!(res.payload(), &[200]);
+
+        call(SET_RAPID_TRIGGER_ENABLED, &[4, 1]).unwrap();
+        let res = call(GET_RAPID_TRIGGER_ENABLED, &[4]).unwrap();
+        assert_eq!(res.payload(), &[1]);
+        assert_eq!(
+            call(SET_RAPID_TRIGGER_ENABLED, &[4, 2]),
+            Err(Error::InvalidValue(1))
+        );
+
+        call(SET_RAPID_TRIGGER_SENSITIVITY, &[4, 10]).unwrap();
+        let res = call(GET_RAPID_TRIGGER_SENSITIVITY, &[4]).unwrap();
+        assert_eq!(res.payload(), &[10]);
+    }
+
+    #[test]
+    fn unknown_key() {
+        let page = ActuationPointPage::new();
+        assert!(matches!(
+            page.get_actuation_point(7),
+            Err(Error::InvalidValue(0))
+        ));
+        assert_eq!(
+            page.add_key(MAX_KEYS as u8, ActuationConfig::default()),
+            Err(ActuationConfig::default())
+        );
+
+        let dispatch = dispatch();
+        assert_eq!(
+            dispatch.dispatch_raw(
+                ACTUATION_FUNCTION_PAGE,
+                SET_RAPID_TRIGGER_ENABLED,
+                &[7, 1, 0, 0, 0]
+            ),
+            Err(Error::InvalidValue(0))
+        );
+    }
+}
@@ -0,0 +1,131 @@
+//! Typed handler arguments
+//!
+//! Handlers taking a [`FunctionArgs`] type instead of the raw function data are wrapped into a
+//! [`DispatchFn`](super::DispatchFn) with [`typed_fn!`](crate::typed_fn), missing or malformed
+//! arguments become `InvalidValue(offset)` before the handler runs.
+
+use super::{DispatchContext, DispatchReturn, Error};
+
+/// parse handler arguments out of the function data, errors carry the byte offset of the bad argument
+pub trait FunctionArgs<'a>: Sized {
+    fn parse(data: &'a [u8]) -> Result<Self, Error>;
+}
+
+/// no arguments
+impl<'a> FunctionArgs<'a> for () {
+    fn parse(_: &'a [u8]) -> Result<Self, Error> {
+        Ok(())
+    }
+}
+
+impl<'a> FunctionArgs<'a> for u8 {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        data.first().copied().ok_or(Error::InvalidValue(0))
+    }
+}
+
+/// little endian
+impl<'a> FunctionArgs<'a> for u16 {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        <[u8; 2]>::parse(data).map(u16::from_le_bytes)
+    }
+}
+
+impl<'a, const N: usize> FunctionArgs<'a> for [u8; N] {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        data.get(..N)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(Error::InvalidValue(0))
+    }
+}
+
+/// raw function data, never fails
+impl<'a> FunctionArgs<'a> for &'a [u8] {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        Ok(data)
+    }
+}
+
+/// trailing optional argument, `None` once the data runs out
+impl<'a, T: FunctionArgs<'a>> FunctionArgs<'a> for Option<T> {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        if data.is_empty() {
+            Ok(None)
+        } else {
+            T::parse(data).map(Some)
+        }
+    }
+}
+
+/// leading byte followed by the rest of the arguments, e.g. `(u8, u8)` or `(u8, Option<u8>)`
+impl<'a, T: FunctionArgs<'a>> FunctionArgs<'a> for (u8, T) {
+    fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let first = u8::parse(data)?;
+        let rest = T::parse(&data[1..]).map_err(|e| match e {
+            Error::InvalidValue(index) => Error::InvalidValue(index + 1),
+            e => e,
+        })?;
+        Ok((first, rest))
+    }
+}
+
+/// parse `data` and call a typed handler, used by [`typed_fn!`](crate::typed_fn)
+pub fn call_typed<'a, A: FunctionArgs<'a>>(
+    data: &'a [u8],
+    ctx: DispatchContext,
+    handler: fn(A, DispatchContext) -> DispatchReturn,
+) -> DispatchReturn {
+    handler(A::parse(data)?, ctx)
+}
+
+/// wrap a `fn(impl FunctionArgs, DispatchContext) -> DispatchReturn` into a [`DispatchFn`](crate::dispatch::DispatchFn)
+///
+/// ```
+/// use openinput_rust::dispatch::{DispatchContext, DispatchFn, DispatchReturn};
+///
+/// fn set_dpi(dpi: u16, _: DispatchContext) -> DispatchReturn {
+///     Ok(heapless::Vec::from_slice(&dpi.to_le_bytes()).unwrap().into())
+/// }
+///
+/// let handler: DispatchFn = openinput_rust::typed_fn!(set_dpi);
+/// ```
+#[macro_export]
+macro_rules! typed_fn {
+    ($handler:path) => {{
+        let handler: $crate::dispatch::DispatchFn =
+            |data, ctx| $crate::dispatch::args::call_typed(data, ctx, $handler);
+        handler
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid<T: core::fmt::Debug>(res: Result<T, Error>) -> u8 {
+        match res {
+            Err(Error::InvalidValue(index)) => index,
+            res => panic!("expected InvalidValue, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn short_input() {
+        assert_eq!(invalid(u8::parse(&[])), 0);
+        assert_eq!(invalid(u16::parse(&[1])), 0);
+        assert_eq!(invalid(<[u8; 3]>::parse(&[1, 2])), 0);
+        assert_eq!(invalid(<(u8, u8)>::parse(&[1])), 1);
+        assert_eq!(invalid(<(u8, (u8, u16))>::parse(&[1, 2, 3])), 2);
+        assert_eq!(invalid(<(u8, Option<u8>)>::parse(&[])), 0);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(u16::parse(&[0x34, 0x12, 0xff]).unwrap(), 0x1234);
+        assert_eq!(<(u8, u8)>::parse(&[1, 2, 3]).unwrap(), (1, 2));
+        assert_eq!(<(u8, Option<u8>)>::parse(&[1]).unwrap(), (1, None));
+        assert_eq!(<(u8, Option<u8>)>::parse(&[1, 2]).unwrap(), (1, Some(2)));
+        assert_eq!(<[u8; 2]>::parse(&[1, 2, 3]).unwrap(), [1, 2]);
+        assert_eq!(<(u8, &[u8])>::parse(&[1, 2, 3]).unwrap(), (1, &[2, 3][..]));
+    }
+}
@@ -0,0 +1,280 @@
+//! Dispatch with `async` handlers for executors like embassy
+//!
+//! [`AsyncDispatch`] pairs a [`Dispatch`] with an [`AsyncHandler`]. Requests go through the same table,
+//! checks, stats and reply serialization as the sync path, only the call to the handler is awaited, so the info
+//! page and every sync handler keep working unchanged.
+//!
+//! Functions the async handler answers still have to be registered in the table, that's what lists them on the
+//! info page. Their sync handler runs when the plain [`Dispatch`] gets the request, e.g. one that answers
+//! [`Error::Busy`](super::Error::Busy):
+//!
+//! ```ignore
+//! struct Battery<'a>(&'a Adc);
+//!
+//! impl AsyncHandler for Battery<'_> {
+//!     type Future<'f> = impl Future<Output = DispatchReturn> + 'f where Self: 'f;
+//!
+//!     fn handles(&self, page: u8, id: u8) -> bool {
+//!         (page, id) == (BATTERY_PAGE, READ_LEVEL)
+//!     }
+//!
+//!     fn call<'f>(&'f self, _page: u8, _id: u8, _data: &'f [u8], _ctx: DispatchContext<'f>) -> Self::Future<'f> {
+//!         async move { Ok(Vec::from_slice(&[self.0.read().await]).unwrap().into()) }
+//!     }
+//! }
+//!
+//! let dispatch = AsyncDispatch::new(dispatch, Battery(&adc));
+//! let reply = dispatch.dispatch(&request).await;
+//! ```
+//!
+//! Naming an `async` block's type needs `type_alias_impl_trait` (nightly, which embassy uses anyway), on stable
+//! the future has to be a type that implements [`Future`] by hand. Nothing is boxed either way.
+
+use core::any::Any;
+use core::future::Future;
+
+use heapless::Vec;
+
+use super::pending::DEFAULT_MAX_PENDING;
+use super::{
+    report_id_for, split_report, Dispatch, DispatchContext, DispatchReturn,
+    DISPATCH_LONG_PARAM_LEN, DISPATCH_SHORT_PARAM_LEN,
+};
+use crate::{OiReport, OiReportId, LONG_LEN};
+
+/// handlers awaited by [`AsyncDispatch`], see the [module docs](self)
+pub trait AsyncHandler {
+    type Future<'a>: Future<Output = DispatchReturn> + 'a
+    where
+        Self: 'a;
+
+    /// true if [`call`](Self::call) answers `id` on `page` instead of the table's handler
+    fn handles(&self, page: u8, id: u8) -> bool;
+
+    /// `id` is without the sequence number, like for [`FunctionPage::call`](super::FunctionPage::call)
+    fn call<'a>(
+        &'a self,
+        page: u8,
+        id: u8,
+        data: &'a [u8],
+        ctx: DispatchContext<'a>,
+    ) -> Self::Future<'a>;
+}
+
+pub struct AsyncDispatch<H, const MAX_PENDING: usize = DEFAULT_MAX_PENDING> {
+    pub dispatch: Dispatch<MAX_PENDING>,
+    pub handler: H,
+}
+
+impl<H: AsyncHandler, const MAX_PENDING: usize> AsyncDispatch<H, MAX_PENDING> {
+    pub fn new(dispatch: Dispatch<MAX_PENDING>, handler: H) -> Self {
+        Self { dispatch, handler }
+    }
+
+    /// same as [`Dispatch::dispatch`]
+    pub async fn dispatch(&self, request: &OiReport<'_>) -> Vec<u8, LONG_LEN> {
+        let OiReport {
+            id,
+            function_page,
+            function_id,
+            ..
+        } = *request;
+        let reply = match self.dispatch_report(request).await {
+            Ok(res) => res.reply(id, function_page, function_id),
+            Err(e) => e.serialize_error(id, function_page, function_id),
+        };
+        #[cfg(feature = "wireless")]
+        let reply = crate::wireless::address_reply(request.address_and_data().0, reply);
+        reply
+    }
+
+    /// same as [`Dispatch::dispatch_report`]
+    pub async fn dispatch_report(&self, report: &OiReport<'_>) -> DispatchReturn {
+        let (report_id, address, data) = split_report(report)?;
+        self.dispatch_payload(
+            report_id,
+            address,
+            report.function_page,
+            report.function_id,
+            data,
+            &(),
+        )
+        .await
+    }
+
+    /// same as [`Dispatch::dispatch_raw`], panics on the same lengths
+    pub async fn dispatch_raw(&self, page: u8, id: u8, data: &[u8]) -> DispatchReturn {
+        assert!((DISPATCH_SHORT_PARAM_LEN..=DISPATCH_LONG_PARAM_LEN).contains(&data.len()));
+        self.dispatch_payload(report_id_for(data), None, page, id, data, &())
+            .await
+    }
+
+    async fn dispatch_payload(
+        &self,
+        request_report_id: OiReportId,
+        device_address: Option<u8>,
+        page: u8,
+        id: u8,
+        data: &[u8],
+        user: &dyn Any,
+    ) -> DispatchReturn {
+        let dispatch = &self.dispatch;
+        if let Some(observer) = dispatch.observer {
+            observer.on_request(page, id, data);
+        }
+        let res = match dispatch.route(request_report_id, device_address, page, id, data, user) {
+            Ok(route) if self.handler.handles(page, route.id) => {
+                self.handler
+                    .call(page, route.id, route.data, route.ctx)
+                    .await
+            }
+            Ok(route) => route.call(),
+            Err(e) => Err(e),
+        };
+        dispatch.finish_request(page, id, res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    use super::*;
+    use crate::dispatch::{DispatchHandler, Error};
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut fut = Box::pin(fut);
+        loop {
+            if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    /// an adc read that finishes on the second poll
+    struct ReadLevel<'a> {
+        level: &'a Cell<u8>,
+        started: bool,
+    }
+
+    impl Future for ReadLevel<'_> {
+        type Output = DispatchReturn;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<DispatchReturn> {
+            if !self.started {
+                self.started = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(Ok(Vec::from_slice(&[self.level.get()]).unwrap().into()))
+        }
+    }
+
+    struct Battery {
+        level: Cell<u8>,
+    }
+
+    impl AsyncHandler for Battery {
+        type Future<'a> = ReadLevel<'a>;
+
+        fn handles(&self, page: u8, id: u8) -> bool {
+            (page, id) == (0x80, 0x01)
+        }
+
+        fn call<'a>(
+            &'a self,
+            _page: u8,
+            _id: u8,
+            _data: &'a [u8],
+            _ctx: DispatchContext<'a>,
+        ) -> ReadLevel<'a> {
+            ReadLevel {
+                level: &self.level,
+                started: false,
+            }
+        }
+    }
+
+    fn echo(data: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&data[..1]).unwrap().into())
+    }
+
+    fn busy(_: &[u8], _: DispatchContext) -> DispatchReturn {
+        Err(Error::Busy)
+    }
+
+    fn battery_dispatch() -> AsyncDispatch<Battery> {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0x80,
+                &[
+                    (0x00, DispatchHandler::Function(echo)),
+                    (0x01, DispatchHandler::Function(busy)),
+                ],
+            )
+            .unwrap();
+        AsyncDispatch::new(
+            dispatch,
+            Battery {
+                level: Cell::new(87),
+            },
+        )
+    }
+
+    #[test]
+    fn async_handler() {
+        let dispatch = battery_dispatch();
+        let request = OiReport::new_short(0x80, 0x01, &[0; 5]);
+        assert_eq!(
+            &block_on(dispatch.dispatch(&request))[..],
+            &[0x20, 0x80, 0x01, 87, 0, 0, 0, 0]
+        );
+        // the sync path falls back to the table's handler
+        assert_eq!(
+            &dispatch.dispatch.dispatch(&request)[..],
+            &[0x20, 0xFF, 0x05, 0x80, 0x01, 0, 0, 0]
+        );
+        assert_eq!(dispatch.dispatch.stats().requests, 2);
+    }
+
+    #[test]
+    fn shares_sync_path() {
+        let dispatch = battery_dispatch();
+        let requests = [
+            // protocol version, supported pages, supported functions of 0x80
+            OiReport::new_short(0x00, 0x00, &[0; 5]),
+            OiReport::new_short(0x00, 0x02, &[0; 5]),
+            OiReport::new_short(0x00, 0x03, &[0x80, 0, 0, 0, 0]),
+            // sync handler on the same page
+            OiReport::new_short(0x80, 0x00, &[0x42, 0, 0, 0, 0]),
+            // errors
+            OiReport::new_short(0x80, 0x0F, &[0; 5]),
+            OiReport::new_short(0x42, 0x00, &[0; 5]),
+        ];
+        for request in &requests {
+            assert_eq!(
+                block_on(dispatch.dispatch(request)),
+                dispatch.dispatch.dispatch(request),
+                "{:?}",
+                request
+            );
+        }
+        assert_eq!(
+            block_on(dispatch.dispatch_raw(0x80, 0x00, &[7; 5])).map(|res| res.payload()[0]),
+            Ok(7)
+        );
+    }
+}
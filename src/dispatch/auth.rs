@@ -0,0 +1,219 @@
+//! HMAC-SHA256 request authentication
+//!
+//! Once a key is installed every request outside the info page has to end with an 8 byte tag, the first
+//! 8 bytes of `HMAC-SHA256(key, page || fn_id || payload)`. The tag takes the last 8 bytes of the function
+//! data, which leaves 21 bytes of payload in a long report. A short report only has 5 bytes of data so it
+//! can never carry a tag, authenticated requests always go out as long reports (or fragmented transactions,
+//! where the tag ends the reassembled payload).
+//!
+//! The key is installed by firmware with [`DispatchMeta::set_auth_key`] (e.g. loaded from flash) or by the
+//! host with [`SET_KEY`]. Until a key is installed requests are accepted as is, so the first host to call
+//! [`SET_KEY`] provisions the device. Tags don't cover a counter, replayed requests are accepted.
+
+use super::args::call_typed;
+use super::crypto::{bytes_match, truncated_hmac};
+use super::{
+    DispatchContext, DispatchMeta, DispatchReturn, Error, FunctionPage, INFO_FUNCTION_PAGE,
+};
+use crate::{OiReport, LONG_LEN, OPENINPUT_LONG_REPORT_ID};
+
+/// not assigned by the openinput spec
+pub const AUTH_FUNCTION_PAGE: u8 = 0xFC;
+
+pub const SET_KEY: u8 = 0x00;
+
+pub const TAG_LEN: usize = 8;
+pub const KEY_LEN: usize = 16;
+
+pub type AuthKey = [u8; KEY_LEN];
+
+/// truncated HMAC over the page, function id and payload
+pub fn compute_tag(key: &AuthKey, page: u8, fn_id: u8, payload: &[u8]) -> [u8; TAG_LEN] {
+    truncated_hmac(key, &[&[page, fn_id], payload])
+}
+
+/// an OpenInput report with the tag split off the end of its data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedOiReport<'a> {
+    /// data is the payload without the tag
+    pub report: OiReport<'a>,
+    pub tag: [u8; TAG_LEN],
+}
+
+impl<'a> AuthenticatedOiReport<'a> {
+    /// errors if the report's data is too short to hold a tag (every short report)
+    pub fn from_report(report: OiReport<'a>) -> Result<Self, Error> {
+        let (payload, tag) = split_tag(report.data)?;
+        Ok(Self {
+            report: OiReport {
+                data: payload,
+                ..report
+            },
+            tag,
+        })
+    }
+
+    pub fn verify(&self, key: &AuthKey) -> bool {
+        let tag = compute_tag(
+            key,
+            self.report.function_page,
+            self.report.function_id,
+            self.report.data,
+        );
+        bytes_match(&tag, &self.tag)
+    }
+}
+
+fn split_tag(data: &[u8]) -> Result<(&[u8], [u8; TAG_LEN]), Error> {
+    let split = data
+        .len()
+        .checked_sub(TAG_LEN)
+        .ok_or(Error::AuthenticationFailed)?;
+    let (payload, tag) = data.split_at(split);
+    // can't fail, `tag` is TAG_LEN long
+    Ok((payload, tag.try_into().unwrap()))
+}
+
+/// host side: a signed long report, errors if `payload` is longer than 21 bytes
+pub fn sign(key: &AuthKey, page: u8, fn_id: u8, payload: &[u8]) -> Result<[u8; LONG_LEN], Error> {
+    let data_len = LONG_LEN - 3;
+    if payload.len() > data_len - TAG_LEN {
+        return Err(Error::InvalidValue(0));
+    }
+    let mut report = [0; LONG_LEN];
+    report[..3].copy_from_slice(&[OPENINPUT_LONG_REPORT_ID, page, fn_id]);
+    let (data, tag) = report[3..].split_at_mut(data_len - TAG_LEN);
+    data[..payload.len()].copy_from_slice(payload);
+    tag.copy_from_slice(&compute_tag(key, page, fn_id, data));
+    Ok(report)
+}
+
+/// check the tag on a request and strip it, passes everything through until a key is installed
+pub(crate) fn authenticate<'d>(
+    meta: &DispatchMeta,
+    page: u8,
+    fn_id: u8,
+    data: &'d [u8],
+) -> Result<&'d [u8], Error> {
+    let key = match meta.auth_key() {
+        Some(key) if page != INFO_FUNCTION_PAGE => key,
+        _ => return Ok(data),
+    };
+    let (payload, tag) = split_tag(data)?;
+    if bytes_match(&compute_tag(&key, page, fn_id, payload), &tag) {
+        Ok(payload)
+    } else {
+        Err(Error::AuthenticationFailed)
+    }
+}
+
+impl DispatchMeta {
+    pub fn auth_key(&self) -> Option<AuthKey> {
+        self.auth_key.get()
+    }
+
+    /// install (or with `None` remove) the key requests are checked against
+    pub fn set_auth_key(&self, key: Option<AuthKey>) {
+        self.auth_key.set(key)
+    }
+}
+
+fn set_key(key: AuthKey, ctx: DispatchContext) -> DispatchReturn {
+    ctx.meta().set_auth_key(Some(key));
+    Ok(heapless::Vec::new().into())
+}
+
+/// lets the host install or rotate the key, rotating needs a request signed with the current key
+pub struct AuthPage;
+
+impl FunctionPage for AuthPage {
+    fn page_id(&self) -> u8 {
+        AUTH_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[SET_KEY]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            SET_KEY => call_typed(data, ctx, set_key),
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    const KEY: AuthKey = *b"0123456789abcdef";
+
+    fn dispatch() -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(&AuthPage).ok().unwrap();
+        dispatch
+    }
+
+    fn data(report: &[u8; LONG_LEN]) -> &[u8] {
+        &report[3..]
+    }
+
+    #[test]
+    fn provision_then_require_tag() {
+        let dispatch = dispatch();
+        let mut request = [0; 29];
+        request[..KEY_LEN].copy_from_slice(&KEY);
+        dispatch
+            .dispatch_raw(AUTH_FUNCTION_PAGE, SET_KEY, &request)
+            .unwrap();
+        assert_eq!(dispatch.meta.auth_key(), Some(KEY));
+
+        // unsigned now fails, info page stays open
+        assert_eq!(
+            dispatch.dispatch_raw(AUTH_FUNCTION_PAGE, SET_KEY, &request),
+            Err(Error::AuthenticationFailed)
+        );
+        assert!(dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, 0x00, &[0; 5])
+            .is_ok());
+
+        // rotate with a signed request
+        let new_key = [0x55; KEY_LEN];
+        let report = sign(&KEY, AUTH_FUNCTION_PAGE, SET_KEY, &new_key).unwrap();
+        dispatch
+            .dispatch_raw(AUTH_FUNCTION_PAGE, SET_KEY, data(&report))
+            .unwrap();
+        assert_eq!(dispatch.meta.auth_key(), Some(new_key));
+
+        // old key no longer works
+        assert_eq!(
+            dispatch.dispatch_raw(AUTH_FUNCTION_PAGE, SET_KEY, data(&report)),
+            Err(Error::AuthenticationFailed)
+        );
+    }
+
+    #[test]
+    fn tampered_and_short() {
+        let report = sign(&KEY, 0x10, 0x01, &[1, 2, 3]).unwrap();
+        let auth = AuthenticatedOiReport::from_report(OiReport::read(&report).unwrap()).unwrap();
+        assert!(auth.verify(&KEY));
+        assert_eq!(&auth.report.data[..3], &[1, 2, 3]);
+
+        let mut tampered = report;
+        tampered[4] ^= 1;
+        let auth = AuthenticatedOiReport::from_report(OiReport::read(&tampered).unwrap()).unwrap();
+        assert!(!auth.verify(&KEY));
+
+        let short = OiReport::new_short(0x10, 0x01, &[0; 5]);
+        assert!(matches!(
+            AuthenticatedOiReport::from_report(short),
+            Err(Error::AuthenticationFailed)
+        ));
+        assert!(matches!(
+            sign(&KEY, 0x10, 0x01, &[0; 22]),
+            Err(Error::InvalidValue(0))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+//! Device capability bitmask so hosts don't have to probe every page
+
+use heapless::Vec;
+
+use super::actuation::ACTUATION_FUNCTION_PAGE;
+use super::{Dispatch, DispatchContext, DispatchReturn, Error, FunctionPage};
+
+/// not assigned by the openinput spec
+pub const CAPABILITIES_FUNCTION_PAGE: u8 = 0x01;
+
+pub const GET_CAPABILITIES: u8 = 0x00;
+
+bitflags::bitflags! {
+    /// sent as a little endian u32
+    #[derive(Default)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct DeviceCapabilityFlags: u32 {
+        const RGB_LEDS = 1 << 0;
+        const KEY_REMAPPING = 1 << 1;
+        const DPI_ADJUSTMENT = 1 << 2;
+        const PROFILES = 1 << 3;
+        const ANALOG_ACTUATION = 1 << 4;
+    }
+}
+
+/// pages this crate ships and the capability they imply
+const PAGE_CAPABILITIES: &[(u8, DeviceCapabilityFlags)] = &[(
+    ACTUATION_FUNCTION_PAGE,
+    DeviceCapabilityFlags::ANALOG_ACTUATION,
+)];
+
+impl DeviceCapabilityFlags {
+    /// infer flags from the pages registered in `dispatch`, only knows about pages provided by this crate
+    pub fn from_registered_pages<const MAX_PENDING: usize>(
+        dispatch: &Dispatch<MAX_PENDING>,
+    ) -> Self {
+        let pages = dispatch.table.page_ids();
+        PAGE_CAPABILITIES
+            .iter()
+            .filter(|(page, _)| pages.contains(page))
+            .fold(Self::empty(), |flags, &(_, flag)| flags | flag)
+    }
+}
+
+/// reports [`DispatchMeta::capabilities`](super::DispatchMeta::capabilities)
+pub struct CapabilitiesPage;
+
+impl FunctionPage for CapabilitiesPage {
+    fn page_id(&self) -> u8 {
+        CAPABILITIES_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[GET_CAPABILITIES]
+    }
+
+    fn call(&self, fn_id: u8, _: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_CAPABILITIES => {
+                let bits = ctx.meta().capabilities().bits().to_le_bytes();
+                Ok(Vec::from_slice(&bits).unwrap().into())
+            }
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::actuation::ActuationPointPage;
+    use crate::dispatch::DispatchMeta;
+
+    #[test]
+    fn get_capabilities() {
+        let meta = DispatchMeta::builder()
+            .with_capabilities(DeviceCapabilityFlags::RGB_LEDS | DeviceCapabilityFlags::PROFILES)
+            .build()
+            .unwrap();
+        let mut dispatch = Dispatch {
+            meta,
+            ..Dispatch::default()
+        };
+        dispatch.register_page(&CapabilitiesPage).ok().unwrap();
+
+        let res = dispatch
+            .dispatch_raw(CAPABILITIES_FUNCTION_PAGE, GET_CAPABILITIES, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[0b1001, 0, 0, 0]);
+    }
+
+    #[test]
+    fn from_registered_pages() {
+        let mut dispatch = Dispatch::default();
+        assert!(DeviceCapabilityFlags::from_registered_pages(&dispatch).is_empty());
+
+        static ACTUATION: ActuationPointPage = ActuationPointPage::new();
+        dispatch.register_page(&ACTUATION).ok().unwrap();
+        assert_eq!(
+            DeviceCapabilityFlags::from_registered_pages(&dispatch),
+            DeviceCapabilityFlags::ANALOG_ACTUATION
+        );
+    }
+}
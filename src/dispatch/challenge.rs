@@ -0,0 +1,380 @@
+//! Challenge-response authentication for sessions
+//!
+//! The host asks for a challenge and gets a random nonce, then answers with a response computed from the nonce
+//! and a key both sides share. A right response opens an authenticated session (see [`access`](super::access))
+//! that [`Dispatch::tick`](super::Dispatch::tick) closes after [`ChallengeConfig::session_timeout`] ticks:
+//!
+//! ```text
+//! host -> GET_CHALLENGE
+//! host <- nonce (16 bytes, long reply)
+//! host -> RESPOND response (16 bytes, long request)
+//! host <- empty reply, or AuthenticationFailed
+//! ```
+//!
+//! Every nonce is good for one response, right or wrong, so a recorded response can't be replayed. After
+//! [`ChallengeConfig::max_failures`] wrong responses in a row challenges answer [`Error::Busy`] for
+//! [`ChallengeConfig::lockout`] ticks.
+//!
+//! The response is computed by a [`KeyProvider`], so the key can stay in a secure element or crypto
+//! peripheral. With the `auth` feature [`HmacSha256`] computes it in software. Random numbers come from
+//! [`ChallengeConfig::rng`], which has to be a real RNG (e.g. the MCU's TRNG), predictable nonces make the
+//! handshake pointless.
+//!
+//! ```ignore
+//! static KEY: HmacSha256 = HmacSha256(*include_bytes!("key.bin"));
+//!
+//! fn rng(buf: &mut [u8]) {
+//!     TRNG.fill(buf);
+//! }
+//!
+//! static CHALLENGE: ChallengePage = ChallengePage::new(Some(ChallengeConfig::new(&KEY, rng)));
+//!
+//! dispatch.register_page(&CHALLENGE)?;
+//! ```
+
+use heapless::Vec;
+
+use super::args::FunctionArgs;
+use super::crypto::bytes_match;
+use super::{DispatchContext, DispatchReturn, Error, FunctionPage, SyncCell};
+
+/// not assigned by the openinput spec
+pub const CHALLENGE_FUNCTION_PAGE: u8 = 0xFA;
+
+pub const GET_CHALLENGE: u8 = 0x00;
+pub const RESPOND: u8 = 0x01;
+pub const CLOSE_SESSION: u8 = 0x02;
+
+pub const NONCE_LEN: usize = 16;
+pub const RESPONSE_LEN: usize = 16;
+
+/// computes the response a host holding the key sends for a nonce, `Sync` since the config lives in the
+/// dispatcher
+pub trait KeyProvider: Sync {
+    fn response(&self, nonce: &[u8; NONCE_LEN]) -> [u8; RESPONSE_LEN];
+}
+
+/// the first 16 bytes of `HMAC-SHA256(key, nonce)`
+#[cfg(feature = "auth")]
+pub struct HmacSha256(pub super::auth::AuthKey);
+
+#[cfg(feature = "auth")]
+impl KeyProvider for HmacSha256 {
+    fn response(&self, nonce: &[u8; NONCE_LEN]) -> [u8; RESPONSE_LEN] {
+        super::crypto::truncated_hmac(&self.0, &[nonce])
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ChallengeConfig {
+    pub key: &'static dyn KeyProvider,
+    /// fills the buffer with random bytes
+    pub rng: fn(&mut [u8]),
+    /// ticks a session stays open
+    pub session_timeout: u32,
+    /// wrong responses in a row before challenges are refused
+    pub max_failures: u8,
+    /// ticks challenges are refused for after too many wrong responses
+    pub lockout: u32,
+}
+
+impl ChallengeConfig {
+    /// sessions last 5 minutes and 3 wrong responses lock challenges for 10 seconds with 1ms ticks
+    pub const fn new(key: &'static dyn KeyProvider, rng: fn(&mut [u8])) -> Self {
+        Self {
+            key,
+            rng,
+            session_timeout: 5 * 60 * 1000,
+            max_failures: 3,
+            lockout: 10 * 1000,
+        }
+    }
+}
+
+/// one cell so every check of the handshake and the write after it happen in the same critical section
+#[derive(Clone, Copy)]
+struct Handshake {
+    /// the outstanding challenge, taken by the next response
+    nonce: Option<[u8; NONCE_LEN]>,
+    failures: u8,
+    /// tick the lockout started at
+    locked_at: Option<u32>,
+}
+
+impl Handshake {
+    const RESET: Self = Self {
+        nonce: None,
+        failures: 0,
+        locked_at: None,
+    };
+}
+
+/// answers [`Error::UnsupportedFunction`] while no [`ChallengeConfig`] is set
+pub struct ChallengePage {
+    // the host changes the handshake state through a shared reference
+    config: SyncCell<Option<ChallengeConfig>>,
+    handshake: SyncCell<Handshake>,
+}
+
+impl ChallengePage {
+    pub const fn new(config: Option<ChallengeConfig>) -> Self {
+        Self {
+            config: SyncCell::new(config),
+            handshake: SyncCell::new(Handshake::RESET),
+        }
+    }
+
+    pub fn config(&self) -> Option<ChallengeConfig> {
+        self.config.get()
+    }
+
+    /// enable (or with `None` disable) the page, drops an outstanding challenge and the failure count
+    pub fn set_config(&self, config: Option<ChallengeConfig>) {
+        self.config.set(config);
+        self.handshake.set(Handshake::RESET);
+    }
+
+    fn enabled_config(&self) -> Result<ChallengeConfig, Error> {
+        self.config().ok_or(Error::UnsupportedFunction)
+    }
+
+    fn get_challenge(&self, ctx: &DispatchContext) -> DispatchReturn {
+        let config = self.enabled_config()?;
+        let now = ctx.ticks();
+        let mut nonce = [0; NONCE_LEN];
+        (config.rng)(&mut nonce);
+        self.handshake.update(|handshake| {
+            if let Some(locked_at) = handshake.locked_at {
+                if now.wrapping_sub(locked_at) < config.lockout {
+                    return Err(Error::Busy);
+                }
+                *handshake = Handshake::RESET;
+            }
+            handshake.nonce = Some(nonce);
+            Ok(())
+        })?;
+        Ok(Vec::from_slice(&nonce).unwrap_or_default().into())
+    }
+
+    /// params: response to the outstanding challenge
+    fn respond(&self, response: [u8; RESPONSE_LEN], ctx: &DispatchContext) -> DispatchReturn {
+        let config = self.enabled_config()?;
+        let now = ctx.ticks();
+        // burned either way
+        let nonce = self
+            .handshake
+            .update(|handshake| handshake.nonce.take())
+            .ok_or(Error::AuthenticationFailed)?;
+        // outside the critical section, the key provider may be slow hardware
+        let matched = bytes_match(&config.key.response(&nonce), &response);
+        self.handshake.update(|handshake| {
+            if matched {
+                handshake.failures = 0;
+                return;
+            }
+            handshake.failures = handshake.failures.saturating_add(1);
+            if handshake.failures >= config.max_failures {
+                handshake.locked_at = Some(now);
+            }
+        });
+        if !matched {
+            return Err(Error::AuthenticationFailed);
+        }
+        ctx.open_session(config.session_timeout);
+        Ok(Vec::new().into())
+    }
+}
+
+impl FunctionPage for ChallengePage {
+    fn page_id(&self) -> u8 {
+        CHALLENGE_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[GET_CHALLENGE, RESPOND, CLOSE_SESSION]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_CHALLENGE => self.get_challenge(&ctx),
+            RESPOND => self.respond(FunctionArgs::parse(data)?, &ctx),
+            CLOSE_SESSION => {
+                ctx.close_session();
+                Ok(Vec::new().into())
+            }
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+    use crate::dispatch::access::{AccessLevel, SessionState};
+    use crate::dispatch::Dispatch;
+
+    /// stand-in for hardware crypto, not a MAC
+    struct XorKey;
+
+    impl KeyProvider for XorKey {
+        fn response(&self, nonce: &[u8; NONCE_LEN]) -> [u8; RESPONSE_LEN] {
+            nonce.map(|b| b ^ 0x5A)
+        }
+    }
+
+    static KEY: XorKey = XorKey;
+
+    /// counts up so every nonce differs
+    fn rng(buf: &mut [u8]) {
+        thread_local!(static NEXT: Cell<u8> = Cell::new(0));
+        for b in buf {
+            *b = NEXT.with(|next| {
+                next.set(next.get().wrapping_add(1));
+                next.get()
+            });
+        }
+    }
+
+    fn dispatch_with_page() -> (Dispatch, &'static ChallengePage) {
+        let page: &'static ChallengePage =
+            Box::leak(Box::new(ChallengePage::new(Some(ChallengeConfig {
+                session_timeout: 100,
+                lockout: 50,
+                ..ChallengeConfig::new(&KEY, rng)
+            }))));
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(page).unwrap();
+        // something to protect
+        assert!(dispatch.set_access(
+            CHALLENGE_FUNCTION_PAGE,
+            CLOSE_SESSION,
+            AccessLevel::Authenticated
+        ));
+        (dispatch, page)
+    }
+
+    fn dispatch() -> Dispatch {
+        dispatch_with_page().0
+    }
+
+    fn challenge(dispatch: &Dispatch) -> Result<[u8; NONCE_LEN], Error> {
+        let res = dispatch.dispatch_raw(CHALLENGE_FUNCTION_PAGE, GET_CHALLENGE, &[0; 5])?;
+        Ok(res.payload().try_into().unwrap())
+    }
+
+    fn respond(dispatch: &Dispatch, response: &[u8; RESPONSE_LEN]) -> DispatchReturn {
+        let mut data = [0; 29];
+        data[..RESPONSE_LEN].copy_from_slice(response);
+        dispatch.dispatch_raw(CHALLENGE_FUNCTION_PAGE, RESPOND, &data)
+    }
+
+    #[test]
+    fn handshake() {
+        let dispatch = dispatch();
+        assert_eq!(
+            dispatch.dispatch_raw(CHALLENGE_FUNCTION_PAGE, CLOSE_SESSION, &[0; 5]),
+            Err(Error::PermissionDenied)
+        );
+
+        let nonce = challenge(&dispatch).unwrap();
+        respond(&dispatch, &KEY.response(&nonce)).unwrap();
+        assert_eq!(dispatch.session(), SessionState::Authenticated);
+
+        // replaying the response fails, the nonce is gone
+        assert_eq!(
+            respond(&dispatch, &KEY.response(&nonce)),
+            Err(Error::AuthenticationFailed)
+        );
+        // so does the response to an old nonce after a new challenge
+        assert_ne!(challenge(&dispatch).unwrap(), nonce);
+        assert_eq!(
+            respond(&dispatch, &KEY.response(&nonce)),
+            Err(Error::AuthenticationFailed)
+        );
+
+        dispatch
+            .dispatch_raw(CHALLENGE_FUNCTION_PAGE, CLOSE_SESSION, &[0; 5])
+            .unwrap();
+        assert_eq!(dispatch.session(), SessionState::Closed);
+    }
+
+    #[test]
+    fn timeout() {
+        let dispatch = dispatch();
+        let nonce = challenge(&dispatch).unwrap();
+        respond(&dispatch, &KEY.response(&nonce)).unwrap();
+        dispatch.tick(99);
+        assert_eq!(dispatch.session(), SessionState::Authenticated);
+        dispatch.tick(1);
+        assert_eq!(dispatch.session(), SessionState::Closed);
+    }
+
+    #[test]
+    fn wrong_response_burns_nonce() {
+        let dispatch = dispatch();
+        let nonce = challenge(&dispatch).unwrap();
+        assert_eq!(
+            respond(&dispatch, &[0; RESPONSE_LEN]),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(
+            respond(&dispatch, &KEY.response(&nonce)),
+            Err(Error::AuthenticationFailed)
+        );
+        assert_eq!(dispatch.session(), SessionState::Closed);
+    }
+
+    #[test]
+    fn rate_limit() {
+        let dispatch = dispatch();
+        // short requests can't hold a response
+        challenge(&dispatch).unwrap();
+        assert_eq!(
+            dispatch.dispatch_raw(CHALLENGE_FUNCTION_PAGE, RESPOND, &[0; 5]),
+            Err(Error::InvalidValue(0))
+        );
+
+        for _ in 0..3 {
+            challenge(&dispatch).unwrap();
+            assert_eq!(
+                respond(&dispatch, &[0; RESPONSE_LEN]),
+                Err(Error::AuthenticationFailed)
+            );
+        }
+        assert_eq!(challenge(&dispatch), Err(Error::Busy));
+        dispatch.tick(49);
+        assert_eq!(challenge(&dispatch), Err(Error::Busy));
+        dispatch.tick(1);
+        let nonce = challenge(&dispatch).unwrap();
+        respond(&dispatch, &KEY.response(&nonce)).unwrap();
+        assert_eq!(dispatch.session(), SessionState::Authenticated);
+    }
+
+    #[test]
+    fn disabled() {
+        let (dispatch, page) = dispatch_with_page();
+        challenge(&dispatch).unwrap();
+        page.set_config(None);
+        assert_eq!(challenge(&dispatch), Err(Error::UnsupportedFunction));
+        assert_eq!(
+            respond(&dispatch, &[0; RESPONSE_LEN]),
+            Err(Error::UnsupportedFunction)
+        );
+    }
+
+    #[test]
+    fn beside_key_scan() {
+        use crate::dispatch::scan::{KeyScanPage, GET_MATRIX_DIMENSIONS, KEY_SCAN_FUNCTION_PAGE};
+
+        let mut dispatch = dispatch();
+        dispatch.register_page(&KeyScanPage).unwrap();
+        // the scan handler answers, without a matrix, where the challenge one would hand out a nonce
+        assert_eq!(
+            dispatch.dispatch_raw(KEY_SCAN_FUNCTION_PAGE, GET_MATRIX_DIMENSIONS, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+        challenge(&dispatch).unwrap();
+    }
+}
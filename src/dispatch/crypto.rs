@@ -0,0 +1,46 @@
+//! Primitives the [`auth`](super::auth) and [`challenge`](super::challenge) pages share
+
+#[cfg(feature = "auth")]
+use hmac::{Hmac, Mac};
+#[cfg(feature = "auth")]
+use sha2::Sha256;
+
+/// constant time, so the comparison doesn't leak how many leading bytes of a guess were right
+pub(crate) fn bytes_match<const N: usize>(a: &[u8; N], b: &[u8; N]) -> bool {
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// the first `N` bytes of `HMAC-SHA256(key, parts)`, `N` is at most 32
+#[cfg(feature = "auth")]
+pub(crate) fn truncated_hmac<const N: usize>(key: &[u8], parts: &[&[u8]]) -> [u8; N] {
+    // can't fail, HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+    for part in parts {
+        mac.update(part);
+    }
+    let mut out = [0; N];
+    out.copy_from_slice(&mac.finalize().into_bytes()[..N]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_every_byte() {
+        assert!(bytes_match(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!bytes_match(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!bytes_match(&[0, 2, 3], &[1, 2, 3]));
+    }
+
+    #[cfg(feature = "auth")]
+    #[test]
+    fn parts_are_concatenated() {
+        let key = [7; 16];
+        assert_eq!(
+            truncated_hmac::<8>(&key, &[&[1, 2], &[3]]),
+            truncated_hmac::<8>(&key, &[&[1, 2, 3]])
+        );
+    }
+}
@@ -0,0 +1,280 @@
+//! Remote logging for devices without a debug probe
+//!
+//! Firmware writes messages with [`DispatchMeta::write_log`], the host reads them back in [`LOG_CHUNK_LEN`] byte chunks.
+//! Messages are newline terminated, once the log is full the oldest bytes are dropped.
+//!
+//! The log sits behind a `critical_section::Mutex`, so firmware can write to it from interrupt handlers while
+//! dispatch reads it. Writes run with interrupts masked for as long as it takes to copy the message.
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use heapless::Vec;
+
+use super::args::call_typed;
+use super::{
+    DispatchContext, DispatchMeta, DispatchReturn, Error, FunctionPage, LONG_REPLY_CAPACITY,
+};
+
+/// not assigned by the openinput spec
+pub const DEBUG_FUNCTION_PAGE: u8 = 0xFD;
+
+pub const GET_LOG_LEVEL: u8 = 0x00;
+pub const SET_LOG_LEVEL: u8 = 0x01;
+pub const READ_LOG_CHUNK: u8 = 0x02;
+pub const CLEAR_LOG: u8 = 0x03;
+/// [`DispatchStats`](super::DispatchStats) serialized with [`DispatchStats::to_bytes`](super::DispatchStats::to_bytes)
+pub const GET_DISPATCH_STATS: u8 = 0x04;
+
+pub const LOG_LEN: usize = 512;
+/// bytes per [`READ_LOG_CHUNK`] reply
+pub const LOG_CHUNK_LEN: usize = LONG_REPLY_CAPACITY;
+
+/// lower is more severe, messages above the configured level are dropped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum LogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+impl TryFrom<u8> for LogLevel {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(LogLevel::Error),
+            1 => Ok(LogLevel::Warn),
+            2 => Ok(LogLevel::Info),
+            3 => Ok(LogLevel::Debug),
+            _ => Err(()),
+        }
+    }
+}
+
+/// log buffer kept in [`DispatchMeta`]
+pub(crate) struct DebugLog {
+    level: Mutex<Cell<LogLevel>>,
+    buf: Mutex<RefCell<Vec<u8, LOG_LEN>>>,
+}
+
+impl DebugLog {
+    pub(crate) const fn new() -> Self {
+        Self {
+            level: Mutex::new(Cell::new(LogLevel::Info)),
+            buf: Mutex::new(RefCell::new(Vec::new())),
+        }
+    }
+}
+
+impl DispatchMeta {
+    pub fn log_level(&self) -> LogLevel {
+        critical_section::with(|cs| self.log.level.borrow(cs).get())
+    }
+
+    pub fn set_log_level(&self, level: LogLevel) {
+        critical_section::with(|cs| self.log.level.borrow(cs).set(level))
+    }
+
+    /// append `msg` and a newline if `level` passes the filter, returns false if the message was dropped
+    pub fn write_log(&self, level: u8, msg: &[u8]) -> bool {
+        if level > self.log_level() as u8 {
+            return false;
+        }
+        // only the tail of a message longer than the whole log survives
+        let msg = &msg[msg.len().saturating_sub(LOG_LEN - 1)..];
+        critical_section::with(|cs| {
+            let mut buf = self.log.buf.borrow_ref_mut(cs);
+            let overflow = (buf.len() + msg.len() + 1).saturating_sub(LOG_LEN);
+            if overflow > 0 {
+                let len = buf.len();
+                buf.rotate_left(overflow);
+                buf.truncate(len - overflow);
+            }
+            // can't fail, made room above
+            let _ = buf.extend_from_slice(msg);
+            let _ = buf.push(b'\n');
+        });
+        true
+    }
+
+    pub fn clear_log(&self) {
+        critical_section::with(|cs| self.log.buf.borrow_ref_mut(cs).clear())
+    }
+
+    /// [`LOG_CHUNK_LEN`] bytes starting at `offset * LOG_CHUNK_LEN`, zero past the end of the log
+    pub fn read_log_chunk(&self, offset: u8) -> [u8; LOG_CHUNK_LEN] {
+        let mut chunk = [0; LOG_CHUNK_LEN];
+        critical_section::with(|cs| {
+            let buf = self.log.buf.borrow_ref(cs);
+            let start = (offset as usize * LOG_CHUNK_LEN).min(buf.len());
+            let end = (start + LOG_CHUNK_LEN).min(buf.len());
+            chunk[..end - start].copy_from_slice(&buf[start..end]);
+        });
+        chunk
+    }
+}
+
+fn get_log_level(_: (), ctx: DispatchContext) -> DispatchReturn {
+    Ok(Vec::from_slice(&[ctx.meta().log_level() as u8])
+        .unwrap()
+        .into())
+}
+
+fn set_log_level(level: u8, ctx: DispatchContext) -> DispatchReturn {
+    let level = LogLevel::try_from(level).map_err(|_| Error::InvalidValue(0))?;
+    ctx.meta().set_log_level(level);
+    Ok(Vec::new().into())
+}
+
+fn read_log_chunk(offset: u8, ctx: DispatchContext) -> DispatchReturn {
+    Ok(Vec::from_slice(&ctx.meta().read_log_chunk(offset))
+        .unwrap()
+        .into())
+}
+
+fn clear_log(_: (), ctx: DispatchContext) -> DispatchReturn {
+    ctx.meta().clear_log();
+    Ok(Vec::new().into())
+}
+
+/// counts every request before this one
+fn get_dispatch_stats(_: (), ctx: DispatchContext) -> DispatchReturn {
+    Ok(Vec::from_slice(&ctx.stats().to_bytes()).unwrap().into())
+}
+
+/// exposes the log in [`DispatchMeta`] to the host
+pub struct DebugPage;
+
+impl FunctionPage for DebugPage {
+    fn page_id(&self) -> u8 {
+        DEBUG_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[
+            GET_LOG_LEVEL,
+            SET_LOG_LEVEL,
+            READ_LOG_CHUNK,
+            CLEAR_LOG,
+            GET_DISPATCH_STATS,
+        ]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_LOG_LEVEL => call_typed(data, ctx, get_log_level),
+            SET_LOG_LEVEL => call_typed(data, ctx, set_log_level),
+            READ_LOG_CHUNK => call_typed(data, ctx, read_log_chunk),
+            CLEAR_LOG => call_typed(data, ctx, clear_log),
+            GET_DISPATCH_STATS => call_typed(data, ctx, get_dispatch_stats),
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    fn dispatch() -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(&DebugPage).ok().unwrap();
+        dispatch
+    }
+
+    #[test]
+    fn level_filter() {
+        let dispatch = dispatch();
+        let meta = &dispatch.meta;
+        assert!(meta.write_log(LogLevel::Warn as u8, b"warn"));
+        assert!(!meta.write_log(LogLevel::Debug as u8, b"debug"));
+
+        dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, SET_LOG_LEVEL, &[3, 0, 0, 0, 0])
+            .unwrap();
+        let res = dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, GET_LOG_LEVEL, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[3]);
+        assert!(meta.write_log(LogLevel::Debug as u8, b"debug"));
+
+        assert_eq!(
+            dispatch.dispatch_raw(DEBUG_FUNCTION_PAGE, SET_LOG_LEVEL, &[4, 0, 0, 0, 0]),
+            Err(Error::InvalidValue(0))
+        );
+    }
+
+    #[test]
+    fn read_chunks() {
+        let dispatch = dispatch();
+        let meta = &dispatch.meta;
+        meta.write_log(LogLevel::Error as u8, &[b'a'; 30]);
+
+        let res = dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, READ_LOG_CHUNK, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[b'a'; LOG_CHUNK_LEN]);
+        let res = dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, READ_LOG_CHUNK, &[1, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(&res.payload()[..3], b"a\n\0");
+
+        dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, CLEAR_LOG, &[0; 5])
+            .unwrap();
+        assert_eq!(meta.read_log_chunk(0), [0; LOG_CHUNK_LEN]);
+    }
+
+    #[test]
+    fn dispatch_stats() {
+        let dispatch = dispatch();
+        dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, GET_LOG_LEVEL, &[0; 5])
+            .unwrap();
+        assert!(dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, SET_LOG_LEVEL, &[9, 0, 0, 0, 0])
+            .is_err());
+
+        let res = dispatch
+            .dispatch_raw(DEBUG_FUNCTION_PAGE, GET_DISPATCH_STATS, &[0; 5])
+            .unwrap();
+        assert_eq!(
+            res.payload(),
+            &[
+                2,
+                0,
+                0,
+                0,
+                1,
+                0,
+                0,
+                0,
+                1,
+                DEBUG_FUNCTION_PAGE,
+                SET_LOG_LEVEL,
+                0x01
+            ]
+        );
+        assert_eq!(dispatch.stats().requests, 3);
+    }
+
+    #[test]
+    fn wraps_around() {
+        let meta = DispatchMeta::default();
+        for i in 0..LOG_LEN / 4 + 1 {
+            assert!(meta.write_log(0, &[b'0' + (i % 10) as u8; 3]));
+        }
+        critical_section::with(|cs| {
+            let buf = meta.log.buf.borrow_ref(cs);
+            assert_eq!(buf.len(), LOG_LEN);
+            // the first message was pushed out, the last one is at the end
+            assert_eq!(&buf[LOG_LEN - 4..], b"888\n");
+            assert_eq!(&buf[..4], b"111\n");
+        });
+    }
+}
@@ -0,0 +1,220 @@
+//! Reassembly of requests that don't fit in a single long report
+//!
+//! The host opens a transaction with the total payload length and target function, sends the payload
+//! in numbered fragments of [`FRAGMENT_LEN`] bytes (the last one may be shorter) in any order, then
+//! commits it to have the reassembled payload dispatched.
+
+use heapless::FnvIndexMap;
+
+use super::{Dispatch, DispatchReturn, Error, LONG_REPLY_CAPACITY};
+use crate::OiReportId;
+
+/// transactions that can be in flight at once
+pub const MAX_TRANSACTIONS: usize = 4;
+/// largest reassembled payload
+pub const MAX_PAYLOAD_LEN: usize = 256;
+/// fragment data per long report, the transaction id and sequence number take up the rest
+pub const FRAGMENT_LEN: usize = LONG_REPLY_CAPACITY - 2;
+
+const MAX_FRAGMENTS: usize = (MAX_PAYLOAD_LEN + FRAGMENT_LEN - 1) / FRAGMENT_LEN;
+
+/// one in-flight transaction
+pub struct FragmentBuffer {
+    page: u8,
+    fn_id: u8,
+    total_len: u16,
+    /// bit per sequence number
+    received: u16,
+    buf: [u8; MAX_PAYLOAD_LEN],
+}
+
+impl FragmentBuffer {
+    fn fragments(&self) -> usize {
+        (self.total_len as usize + FRAGMENT_LEN - 1) / FRAGMENT_LEN
+    }
+
+    fn is_complete(&self) -> bool {
+        self.received.count_ones() as usize == self.fragments()
+    }
+}
+
+#[derive(Default)]
+pub struct FragmentedTransaction {
+    transactions: FnvIndexMap<u8, FragmentBuffer, MAX_TRANSACTIONS>,
+    next_id: u8,
+}
+
+impl FragmentedTransaction {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// returns the transaction id, errors if `total_len` is over [`MAX_PAYLOAD_LEN`] or
+    /// [`MAX_TRANSACTIONS`] are already open
+    pub fn begin_transaction(&mut self, total_len: u16, page: u8, fn_id: u8) -> Result<u8, Error> {
+        if total_len == 0 || total_len as usize > MAX_PAYLOAD_LEN {
+            return Err(Error::InvalidValue(0));
+        }
+        if self.transactions.len() == self.transactions.capacity() {
+            return Err(Error::custom_truncating("too many transactions"));
+        }
+        while self.transactions.contains_key(&self.next_id) {
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+        let txn = FragmentBuffer {
+            page,
+            fn_id,
+            total_len,
+            received: 0,
+            buf: [0; MAX_PAYLOAD_LEN],
+        };
+        // can't fail, checked capacity above
+        let _ = self.transactions.insert(id, txn);
+        Ok(id)
+    }
+
+    /// `data` has to be exactly [`FRAGMENT_LEN`] bytes except for the last fragment, which holds the rest
+    pub fn write_fragment(&mut self, txn_id: u8, seq: u8, data: &[u8]) -> Result<(), Error> {
+        let txn = self
+            .transactions
+            .get_mut(&txn_id)
+            .ok_or(Error::InvalidValue(0))?;
+        let seq = seq as usize;
+        if seq >= txn.fragments() {
+            return Err(Error::InvalidValue(1));
+        }
+        if txn.received & (1 << seq) != 0 {
+            // duplicate sequence number
+            return Err(Error::InvalidValue(1));
+        }
+        let start = seq * FRAGMENT_LEN;
+        let end = (start + FRAGMENT_LEN).min(txn.total_len as usize);
+        if data.len() != end - start {
+            return Err(Error::InvalidValue(2));
+        }
+        txn.buf[start..end].copy_from_slice(data);
+        txn.received |= 1 << seq;
+        Ok(())
+    }
+
+    /// dispatch the reassembled payload, the transaction is closed either way
+    pub fn commit_transaction<const MAX_PENDING: usize>(
+        &mut self,
+        dispatch: &Dispatch<MAX_PENDING>,
+        txn_id: u8,
+    ) -> DispatchReturn {
+        let txn = self
+            .transactions
+            .remove(&txn_id)
+            .ok_or(Error::InvalidValue(0))?;
+        if !txn.is_complete() {
+            return Err(Error::custom_truncating("missing fragments"));
+        }
+        dispatch.dispatch_payload(
+            OiReportId::Long,
+            None,
+            txn.page,
+            txn.fn_id,
+            &txn.buf[..txn.total_len as usize],
+            &(),
+        )
+    }
+
+    /// drop an open transaction, returns false if it didn't exist
+    pub fn abort_transaction(&mut self, txn_id: u8) -> bool {
+        self.transactions.remove(&txn_id).is_some()
+    }
+}
+
+// `received` has a bit per fragment
+const _: () = assert!(MAX_FRAGMENTS <= u16::BITS as usize);
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+    use crate::dispatch::{DispatchContext, DispatchHandler};
+
+    const PAGE: u8 = 0x42;
+
+    /// replies with the payload length and a checksum
+    fn sum(data: &[u8], _: DispatchContext) -> DispatchReturn {
+        let total = data.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        Ok(Vec::from_slice(&[data.len() as u8, total]).unwrap().into())
+    }
+
+    fn dispatch() -> Dispatch {
+        let mut table = FnvIndexMap::new();
+        let mut page = FnvIndexMap::new();
+        page.insert(0x00, DispatchHandler::Function(sum))
+            .ok()
+            .unwrap();
+        table.insert(PAGE, page).ok().unwrap();
+        Dispatch::new_raw(table, Default::default())
+    }
+
+    #[test]
+    fn reassemble_out_of_order() {
+        let dispatch = dispatch();
+        let payload: [u8; 60] = core::array::from_fn(|i| i as u8);
+        let mut frag = FragmentedTransaction::new();
+        let txn = frag.begin_transaction(60, PAGE, 0x00).unwrap();
+
+        frag.write_fragment(txn, 2, &payload[54..]).unwrap();
+        frag.write_fragment(txn, 0, &payload[..27]).unwrap();
+        frag.write_fragment(txn, 1, &payload[27..54]).unwrap();
+
+        let res = frag.commit_transaction(&dispatch, txn).unwrap();
+        let total = payload.iter().fold(0u8, |acc, &b| acc.wrapping_add(b));
+        assert_eq!(res.payload(), &[60, total]);
+        // closed after commit
+        assert!(matches!(
+            frag.commit_transaction(&dispatch, txn),
+            Err(Error::InvalidValue(0))
+        ));
+    }
+
+    #[test]
+    fn bad_fragments() {
+        let dispatch = dispatch();
+        let mut frag = FragmentedTransaction::new();
+        let txn = frag.begin_transaction(30, PAGE, 0x00).unwrap();
+
+        frag.write_fragment(txn, 0, &[0; 27]).unwrap();
+        assert!(matches!(
+            frag.write_fragment(txn, 0, &[0; 27]),
+            Err(Error::InvalidValue(1))
+        ));
+        assert!(matches!(
+            frag.write_fragment(txn, 2, &[0; 3]),
+            Err(Error::InvalidValue(1))
+        ));
+        assert!(matches!(
+            frag.write_fragment(txn, 1, &[0; 4]),
+            Err(Error::InvalidValue(2))
+        ));
+        assert!(matches!(
+            frag.commit_transaction(&dispatch, txn),
+            Err(Error::Custom(_))
+        ));
+    }
+
+    #[test]
+    fn transaction_limits() {
+        let mut frag = FragmentedTransaction::new();
+        assert!(matches!(
+            frag.begin_transaction(MAX_PAYLOAD_LEN as u16 + 1, PAGE, 0x00),
+            Err(Error::InvalidValue(0))
+        ));
+        let ids: Vec<u8, MAX_TRANSACTIONS> = (0..MAX_TRANSACTIONS)
+            .map(|_| frag.begin_transaction(1, PAGE, 0x00).unwrap())
+            .collect();
+        assert!(frag.begin_transaction(1, PAGE, 0x00).is_err());
+        assert!(frag.abort_transaction(ids[0]));
+        let id = frag.begin_transaction(1, PAGE, 0x00).unwrap();
+        assert!(!ids[1..].contains(&id));
+    }
+}
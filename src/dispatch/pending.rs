@@ -0,0 +1,451 @@
+//! Deferred replies for handlers that wait on hardware
+//!
+//! A handler that can't answer right away (ADC conversion, flash erase) reserves a slot with
+//! [`DispatchContext::defer`] and replies with the token, nothing is sent to the host yet. Once the work is
+//! done firmware passes the result to [`Dispatch::resolve`] and pushes the report it returns.
+//!
+//! ```ignore
+//! fn battery_level(_: (), ctx: DispatchContext) -> DispatchReturn {
+//!     let token = ctx.defer()?;
+//!     ADC_REQUEST.set(Some(token));
+//!     start_conversion();
+//!     Ok(token.into())
+//! }
+//!
+//! // main loop
+//! if let (Some(token), Some(level)) = (ADC_REQUEST.get(), adc_result()) {
+//!     ADC_REQUEST.set(None);
+//!     let reply = dispatch.resolve(token, Ok(Vec::from_slice(&[level]).unwrap().into()));
+//!     class.inner.push_raw_input(&reply)?;
+//! }
+//! ```
+//!
+//! Firmware that would rather poll than push the reply from wherever the work finishes starts the request with
+//! [`Dispatch::dispatch_async_begin`], hands the result to [`Dispatch::complete`] and picks the reply up with
+//! [`Dispatch::dispatch_async_poll`]. A deferred request is answered with an [`Error::Busy`] report right away
+//! so the host knows it's being worked on:
+//!
+//! ```ignore
+//! let (token, reply) = dispatch.dispatch_async_begin(SELF_TEST_PAGE, RUN_SELF_TEST, &[0; 5]);
+//! class.inner.push_raw_input(&reply)?;
+//! SELF_TEST.set(token);
+//!
+//! // once the self test finished
+//! dispatch.complete(token, Ok(Vec::from_slice(&[passed]).unwrap().into()));
+//!
+//! // main loop
+//! if let Some(reply) = dispatch.dispatch_async_poll(token) {
+//!     class.inner.push_raw_input(&reply)?;
+//! }
+//! ```
+//!
+//! Only `MAX_PENDING` requests can wait at once ([`DEFAULT_MAX_PENDING`] unless the dispatcher was built
+//! with [`Dispatch::with_max_pending`]), deferring another one answers [`Error::Busy`] right away.
+
+use heapless::Vec;
+
+use super::{
+    report_id_for, Dispatch, DispatchContext, DispatchResponse, DispatchReturn, Error, SyncCell,
+};
+use crate::{OiReportId, LONG_LEN};
+
+/// deferred requests a [`Dispatch`] can hold unless built with [`Dispatch::with_max_pending`]
+pub const DEFAULT_MAX_PENDING: usize = 2;
+
+/// handle to a deferred request, pass it to [`Dispatch::resolve`] exactly once
+///
+/// handlers defer by returning it converted into a [`DispatchResponse`](super::DispatchResponse)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PendingToken {
+    slot: u8,
+    /// bumped every time the slot is reused so stale tokens don't resolve someone else's request
+    generation: u8,
+}
+
+/// what's needed to address the reply
+#[derive(Clone, Copy)]
+pub(crate) struct PendingRequest {
+    page: u8,
+    fn_id: u8,
+    report_id: OiReportId,
+    #[cfg(feature = "wireless")]
+    device_address: Option<u8>,
+}
+
+impl PendingRequest {
+    /// the reply (or ff_error report) to this request
+    fn reply(self, result: DispatchReturn) -> Vec<u8, LONG_LEN> {
+        let Self {
+            page,
+            fn_id,
+            report_id,
+            #[cfg(feature = "wireless")]
+            device_address,
+        } = self;
+        let reply = match result {
+            Ok(res) => res.reply(report_id.id(), page, fn_id),
+            Err(e) => e.serialize_error(report_id.id(), page, fn_id),
+        };
+        #[cfg(feature = "wireless")]
+        let reply = crate::wireless::address_reply(
+            device_address.unwrap_or(crate::wireless::LOCAL_ADDRESS),
+            reply,
+        );
+        reply
+    }
+}
+
+pub(crate) struct PendingSlot {
+    /// generation and the request waiting in the slot
+    state: SyncCell<(u8, Option<PendingRequest>)>,
+    /// set by [`Dispatch::complete`] until [`Dispatch::dispatch_async_poll`] takes it
+    result: SyncCell<Option<DispatchReturn>>,
+}
+
+impl PendingSlot {
+    /// the request if `token` is the slot's current one
+    fn request(&self, token: PendingToken) -> Option<PendingRequest> {
+        match self.state.get() {
+            (generation, Some(request)) if generation == token.generation => Some(request),
+            _ => None,
+        }
+    }
+
+    fn free(&self, token: PendingToken) {
+        self.state.set((token.generation, None));
+        self.result.set(None);
+    }
+
+    /// the request if `token` is the slot's current one, freeing the slot without another context resolving
+    /// it in between
+    fn take_request(&self, token: PendingToken) -> Option<PendingRequest> {
+        critical_section::with(|_| {
+            let request = self.request(token)?;
+            self.free(token);
+            Some(request)
+        })
+    }
+}
+
+pub(crate) const fn slots<const N: usize>() -> [PendingSlot; N] {
+    const EMPTY: PendingSlot = PendingSlot {
+        state: SyncCell::new((0, None)),
+        result: SyncCell::new(None),
+    };
+    [EMPTY; N]
+}
+
+impl<'a> DispatchContext<'a> {
+    /// reserve a slot to answer this request later, [`Error::Busy`] if every slot is taken
+    pub fn defer(&self) -> Result<PendingToken, Error> {
+        // claimed in one critical section so a handler running in an interrupt can't take the same slot
+        critical_section::with(|_| {
+            let (index, slot) = self
+                .pending
+                .iter()
+                .enumerate()
+                .find(|(_, slot)| slot.state.get().1.is_none())
+                .ok_or(Error::Busy)?;
+            let generation = slot.state.get().0.wrapping_add(1);
+            slot.state.set((
+                generation,
+                Some(PendingRequest {
+                    page: self.page,
+                    fn_id: self.fn_id,
+                    report_id: self.request_report_id,
+                    #[cfg(feature = "wireless")]
+                    device_address: self.device_address,
+                }),
+            ));
+            Ok(PendingToken {
+                slot: index as u8,
+                generation,
+            })
+        })
+    }
+}
+
+impl Dispatch {
+    /// allow `N` deferred requests at once instead of [`DEFAULT_MAX_PENDING`]
+    pub fn with_max_pending<const N: usize>(self) -> Dispatch<N> {
+        Dispatch {
+            table: self.table,
+            hidden: self.hidden,
+            hidden_functions: self.hidden_functions,
+            disabled: self.disabled,
+            meta: self.meta,
+            pending: slots(),
+            queue: self.queue,
+            stats: self.stats,
+            protected: self.protected,
+            session: self.session,
+            observer: self.observer,
+            defaults: self.defaults,
+        }
+    }
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// serialize the reply (or ff_error report) to a deferred request and free its slot
+    ///
+    /// empty if `token` was already resolved. a request can't be deferred twice, `result` being a deferred
+    /// reply is refused with an empty reply too and leaves the request waiting for a real one
+    pub fn resolve(&self, token: PendingToken, result: DispatchReturn) -> Vec<u8, LONG_LEN> {
+        let slot = match self.pending.get(token.slot as usize) {
+            Some(slot) => slot,
+            None => return Vec::new(),
+        };
+        if matches!(&result, Ok(res) if res.deferred().is_some()) {
+            return Vec::new();
+        }
+        match slot.take_request(token) {
+            Some(request) => request.reply(result),
+            None => Vec::new(),
+        }
+    }
+
+    /// requests waiting for [`Dispatch::resolve`] or [`Dispatch::dispatch_async_poll`]
+    pub fn pending_count(&self) -> usize {
+        self.pending
+            .iter()
+            .filter(|slot| slot.state.get().1.is_some())
+            .count()
+    }
+
+    /// call a function that may take a while, returns the report to push right away and a token to poll if
+    /// the handler deferred its reply
+    ///
+    /// a deferred request is answered with an [`Error::Busy`] report until [`Dispatch::dispatch_async_poll`]
+    /// has the real reply, otherwise the report is the handler's reply. The request's report size is taken
+    /// from the length of `data` like [`Dispatch::dispatch_raw`]
+    pub fn dispatch_async_begin(
+        &self,
+        page: u8,
+        id: u8,
+        data: &[u8],
+    ) -> (Option<PendingToken>, Vec<u8, LONG_LEN>) {
+        // replied to like a deferred request, from the receiver itself
+        let request = PendingRequest {
+            page,
+            fn_id: id,
+            report_id: report_id_for(data),
+            #[cfg(feature = "wireless")]
+            device_address: None,
+        };
+        let res = self.dispatch_raw(page, id, data);
+        match res.as_ref().ok().and_then(DispatchResponse::deferred) {
+            Some(token) => (Some(token), request.reply(Err(Error::Busy))),
+            None => (None, request.reply(res)),
+        }
+    }
+
+    /// store the result of a deferred request for [`Dispatch::dispatch_async_poll`], false if `token` was
+    /// resolved or polled already, or if `result` is another deferred reply
+    pub fn complete(&self, token: PendingToken, result: DispatchReturn) -> bool {
+        let slot = match self.pending.get(token.slot as usize) {
+            Some(slot) => slot,
+            None => return false,
+        };
+        if matches!(&result, Ok(res) if res.deferred().is_some()) {
+            return false;
+        }
+        critical_section::with(|_| {
+            if slot.request(token).is_none() {
+                return false;
+            }
+            slot.result.set(Some(result));
+            true
+        })
+    }
+
+    /// serialize the reply (or ff_error report) once [`Dispatch::complete`] was called for `token`, which
+    /// frees its slot
+    ///
+    /// `None` while the request is still running and for tokens that were resolved or polled already
+    pub fn dispatch_async_poll(&self, token: PendingToken) -> Option<Vec<u8, LONG_LEN>> {
+        let slot = self.pending.get(token.slot as usize)?;
+        let (request, result) = critical_section::with(|_| {
+            let request = slot.request(token)?;
+            let result = slot.result.take()?;
+            slot.free(token);
+            Some((request, result))
+        })?;
+        Some(request.reply(result))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::FnvIndexMap;
+
+    use super::*;
+    use crate::dispatch::DispatchHandler;
+    use crate::OiReport;
+
+    const PAGE: u8 = 0x42;
+
+    fn deferred(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        Ok(ctx.defer()?.into())
+    }
+
+    fn dispatch() -> Dispatch<1> {
+        let mut table = FnvIndexMap::new();
+        let mut page = FnvIndexMap::new();
+        page.insert(0x00, DispatchHandler::Function(deferred))
+            .ok()
+            .unwrap();
+        table.insert(PAGE, page).ok().unwrap();
+        Dispatch::new_raw(table, Default::default()).with_max_pending::<1>()
+    }
+
+    fn deferred_token(res: DispatchReturn) -> PendingToken {
+        res.ok()
+            .and_then(|res| res.deferred())
+            .expect("request wasn't deferred")
+    }
+
+    #[test]
+    fn defer_then_resolve() {
+        let dispatch = dispatch();
+        let request = OiReport::new_short(PAGE, 0x00, &[0; 5]);
+        // nothing to push yet
+        assert!(dispatch.dispatch(&request).is_empty());
+        assert_eq!(dispatch.pending_count(), 1);
+        // the handler would have handed the token to firmware, it's the first one of slot 0
+        let token = PendingToken {
+            slot: 0,
+            generation: 1,
+        };
+        let reply = dispatch.resolve(token, Err(Error::UnsupportedFunction));
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x02, PAGE, 0x00, 0, 0, 0]);
+        assert_eq!(dispatch.pending_count(), 0);
+
+        let token = deferred_token(dispatch.dispatch_report(&request));
+        let reply = dispatch.resolve(token, Ok(Vec::from_slice(&[0x64]).unwrap().into()));
+        assert_eq!(reply.as_slice(), &[0x20, PAGE, 0x00, 0x64, 0, 0, 0, 0]);
+        // already resolved
+        assert!(dispatch.resolve(token, Ok(Vec::new().into())).is_empty());
+
+        // errors and long requests
+        let request = OiReport::new_long(PAGE, 0x00, &[0; 29]);
+        let token = deferred_token(dispatch.dispatch_report(&request));
+        let reply = dispatch.resolve(token, Err(Error::InvalidValue(2)));
+        assert_eq!(&reply[..6], &[0x21, 0xFF, 0x01, PAGE, 0x00, 0x02]);
+        assert_eq!(reply.len(), LONG_LEN);
+    }
+
+    #[test]
+    fn async_poll() {
+        let mut table = FnvIndexMap::new();
+        let mut page = FnvIndexMap::new();
+        page.insert(0x00, DispatchHandler::Function(deferred))
+            .ok()
+            .unwrap();
+        page.insert(
+            0x01,
+            DispatchHandler::Function(|_, _| Ok(Vec::new().into())),
+        )
+        .ok()
+        .unwrap();
+        table.insert(PAGE, page).ok().unwrap();
+        let dispatch = Dispatch::new_raw(table, Default::default()).with_max_pending::<4>();
+
+        // immediate replies aren't deferred
+        let (token, reply) = dispatch.dispatch_async_begin(PAGE, 0x01, &[0; 5]);
+        assert_eq!(token, None);
+        assert_eq!(reply.as_slice(), &[0x20, PAGE, 0x01, 0, 0, 0, 0, 0]);
+
+        // deferred ones are answered busy until they complete
+        let tokens: std::vec::Vec<_> = (0..4)
+            .map(|_| {
+                let (token, reply) = dispatch.dispatch_async_begin(PAGE, 0x00, &[0; 5]);
+                assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x05, PAGE, 0x00, 0, 0, 0]);
+                token.unwrap()
+            })
+            .collect();
+        assert_eq!(dispatch.pending_count(), 4);
+        // every slot is taken
+        let (token, reply) = dispatch.dispatch_async_begin(PAGE, 0x00, &[0; 5]);
+        assert_eq!(token, None);
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x05, PAGE, 0x00, 0, 0, 0]);
+        assert_eq!(dispatch.pending_count(), 4);
+        assert!(tokens
+            .iter()
+            .all(|&token| dispatch.dispatch_async_poll(token).is_none()));
+
+        // completed out of order, each token gets its own result
+        for (i, &token) in tokens.iter().enumerate().rev() {
+            assert!(dispatch.complete(token, Ok(Vec::from_slice(&[i as u8]).unwrap().into())));
+        }
+        // completing again replaces the result
+        assert!(dispatch.complete(tokens[1], Err(Error::InvalidValue(1))));
+        for (i, &token) in tokens.iter().enumerate() {
+            let reply = dispatch.dispatch_async_poll(token).unwrap();
+            match i {
+                1 => assert_eq!(
+                    reply.as_slice(),
+                    &[0x20, 0xFF, 0x01, PAGE, 0x00, 0x01, 0, 0]
+                ),
+                i => assert_eq!(reply.as_slice(), &[0x20, PAGE, 0x00, i as u8, 0, 0, 0, 0]),
+            }
+            // polled once only
+            assert_eq!(dispatch.dispatch_async_poll(token), None);
+            assert!(!dispatch.complete(token, Ok(Vec::new().into())));
+        }
+        assert_eq!(dispatch.pending_count(), 0);
+
+        // a reused slot doesn't see the old result
+        let token = dispatch
+            .dispatch_async_begin(PAGE, 0x00, &[0; 5])
+            .0
+            .unwrap();
+        assert!(!tokens.contains(&token));
+        assert_eq!(dispatch.dispatch_async_poll(token), None);
+
+        // long requests get long replies
+        dispatch.complete(token, Ok(Vec::from_slice(&[0x07]).unwrap().into()));
+        dispatch.dispatch_async_poll(token).unwrap();
+        let (token, reply) = dispatch.dispatch_async_begin(PAGE, 0x00, &[0; 29]);
+        assert_eq!(&reply[..3], &[0x21, 0xFF, 0x05]);
+        assert_eq!(reply.len(), LONG_LEN);
+        dispatch.complete(token.unwrap(), Ok(Vec::new().into()));
+        let reply = dispatch.dispatch_async_poll(token.unwrap()).unwrap();
+        assert_eq!(&reply[..3], &[0x21, PAGE, 0x00]);
+        assert_eq!(reply.len(), LONG_LEN);
+    }
+
+    #[test]
+    fn redeferral_is_refused() {
+        let dispatch = dispatch();
+        let request = OiReport::new_short(PAGE, 0x00, &[0; 5]);
+        let token = deferred_token(dispatch.dispatch_report(&request));
+
+        assert!(dispatch.resolve(token, Ok(token.into())).is_empty());
+        assert!(!dispatch.complete(token, Ok(token.into())));
+        // still waiting for the real reply
+        assert_eq!(dispatch.pending_count(), 1);
+        let reply = dispatch.resolve(token, Ok(Vec::new().into()));
+        assert_eq!(reply.as_slice(), &[0x20, PAGE, 0x00, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn busy_while_pending() {
+        let dispatch = dispatch();
+        let request = OiReport::new_short(PAGE, 0x00, &[0; 5]);
+        let first = deferred_token(dispatch.dispatch_report(&request));
+
+        // the only slot is taken
+        let reply = dispatch.dispatch(&request);
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x05, PAGE, 0x00, 0, 0, 0]);
+
+        // the first request still resolves and frees the slot
+        let reply = dispatch.resolve(first, Ok(Vec::new().into()));
+        assert_eq!(reply.as_slice(), &[0x20, PAGE, 0x00, 0, 0, 0, 0, 0]);
+        let second = deferred_token(dispatch.dispatch_report(&request));
+        assert_ne!(first, second);
+        // a stale token doesn't resolve the new request
+        assert!(dispatch.resolve(first, Ok(Vec::new().into())).is_empty());
+        assert_eq!(dispatch.pending_count(), 1);
+    }
+}
@@ -0,0 +1,218 @@
+//! Typed reply construction
+//!
+//! Handlers describe a reply with a [`FunctionReply`] type instead of packing bytes by hand,
+//! the info page reply shapes live here so hosts have a documented type per reply.
+
+use heapless::Vec;
+
+use super::{DispatchResponse, Error, DISPATCH_LONG_RET_LEN, LONG_REPLY_CAPACITY};
+use crate::ProtocolVersion;
+
+/// bounded buffer for a reply payload, at most what a long reply carries (28 bytes with the `wireless` address)
+#[derive(Default)]
+pub struct ResponseWriter {
+    buf: Vec<u8, DISPATCH_LONG_RET_LEN>,
+}
+
+impl ResponseWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// bytes that can still be written
+    pub fn remaining(&self) -> usize {
+        LONG_REPLY_CAPACITY - self.buf.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn push_u8(&mut self, value: u8) -> Result<(), Error> {
+        self.push_bytes(&[value])
+    }
+
+    pub fn push_u16_le(&mut self, value: u16) -> Result<(), Error> {
+        self.push_bytes(&value.to_le_bytes())
+    }
+
+    /// all or nothing, the reply is left unchanged if `bytes` doesn't fit
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.len() > self.remaining() {
+            return Err(Error::custom_truncating("reply too long"));
+        }
+        // can't fail, LONG_REPLY_CAPACITY is at most DISPATCH_LONG_RET_LEN
+        self.buf.extend_from_slice(bytes).ok();
+        Ok(())
+    }
+
+    pub fn finish(self) -> DispatchResponse {
+        DispatchResponse::from(self.buf)
+    }
+}
+
+/// a reply payload that knows its own layout
+pub trait FunctionReply {
+    fn write(&self, out: &mut ResponseWriter) -> Result<(), Error>;
+}
+
+impl DispatchResponse {
+    /// serialize any reply, fails if it doesn't fit in a long report
+    pub fn from_reply<R: FunctionReply + ?Sized>(reply: &R) -> Result<Self, Error> {
+        let mut out = ResponseWriter::new();
+        reply.write(&mut out)?;
+        Ok(out.finish())
+    }
+}
+
+/// protocol version: `[major, minor, patch]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionReply(pub ProtocolVersion);
+
+impl FunctionReply for VersionReply {
+    fn write(&self, out: &mut ResponseWriter) -> Result<(), Error> {
+        out.push_bytes(&self.0.to_bytes())
+    }
+}
+
+/// supported pages/functions: `[total, count, elements...]`
+///
+/// `total` is the number of elements on the device and `count` how many follow in this reply, elements
+/// that don't fit are clipped so the host asks again starting at `start + count` until it has `total`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ElementList<'a> {
+    /// elements from the requested start index on
+    pub items: &'a [u8],
+    pub total: u8,
+}
+
+impl<'a> FunctionReply for ElementList<'a> {
+    fn write(&self, out: &mut ResponseWriter) -> Result<(), Error> {
+        let count = self.items.len().min(out.remaining().saturating_sub(2));
+        out.push_bytes(&[self.total, count as u8])?;
+        out.push_bytes(&self.items[..count])
+    }
+}
+
+/// firmware info string starting at `offset`, as much as fits in one reply
+///
+/// an offset past the end writes nothing, the host treats a short chunk as the end of the string
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareInfoChunk<'a> {
+    pub string: &'a [u8],
+    pub offset: usize,
+}
+
+impl<'a> FunctionReply for FirmwareInfoChunk<'a> {
+    fn write(&self, out: &mut ResponseWriter) -> Result<(), Error> {
+        let chunk = self.string.get(self.offset..).unwrap_or(&[]);
+        let len = chunk.len().min(out.remaining());
+        out.push_bytes(&chunk[..len])
+    }
+}
+
+// the info page replies always fit (by clipping), so they convert infallibly
+macro_rules! infallible_reply {
+    ($($reply:ty),*) => {
+        $(impl From<$reply> for DispatchResponse {
+            fn from(reply: $reply) -> Self {
+                // can't fail, the reply clips itself to the writer's capacity
+                DispatchResponse::from_reply(&reply).unwrap_or_else(|_| ResponseWriter::new().finish())
+            }
+        })*
+    };
+}
+
+infallible_reply!(VersionReply, ElementList<'_>, FirmwareInfoChunk<'_>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writer_capacity() {
+        let mut out = ResponseWriter::new();
+        out.push_u16_le(0x1234).unwrap();
+        out.push_u8(0x56).unwrap();
+        assert_eq!(out.remaining(), LONG_REPLY_CAPACITY - 3);
+        assert!(matches!(
+            out.push_bytes(&[0; DISPATCH_LONG_RET_LEN]),
+            Err(Error::Custom(_))
+        ));
+        // failed push leaves the reply alone
+        assert_eq!(out.finish().payload(), &[0x34, 0x12, 0x56]);
+    }
+
+    #[test]
+    fn info_reply_bytes() {
+        let res: DispatchResponse = VersionReply(ProtocolVersion::new(0, 0, 1)).into();
+        assert_eq!(res.payload(), &[0, 0, 1]);
+
+        let res: DispatchResponse = ElementList {
+            items: &[0x00, 0x10],
+            total: 2,
+        }
+        .into();
+        assert_eq!(res.payload(), &[2, 2, 0x00, 0x10]);
+
+        let res: DispatchResponse = FirmwareInfoChunk {
+            string: b"Dekanova",
+            offset: 4,
+        }
+        .into();
+        assert_eq!(res.payload(), b"nova");
+        let res: DispatchResponse = FirmwareInfoChunk {
+            string: b"Dekanova",
+            offset: 9,
+        }
+        .into();
+        assert!(res.payload().is_empty());
+    }
+
+    #[test]
+    fn element_list_clips() {
+        let items = [0xAA; 40];
+        let res: DispatchResponse = ElementList {
+            items: &items,
+            total: 40,
+        }
+        .into();
+        assert_eq!(res.payload().len(), LONG_REPLY_CAPACITY);
+        assert_eq!(&res.payload()[..2], &[40, LONG_REPLY_CAPACITY as u8 - 2]);
+    }
+
+    #[test]
+    fn element_list_pagination() {
+        let items: [u8; 40] = core::array::from_fn(|i| i as u8);
+        let mut received = std::vec::Vec::new();
+        let mut start = 0;
+        loop {
+            let res: DispatchResponse = ElementList {
+                items: &items[start..],
+                total: items.len() as u8,
+            }
+            .into();
+            let (total, count) = (res.payload()[0] as usize, res.payload()[1] as usize);
+            assert_eq!(total, items.len());
+            assert_eq!(res.payload().len(), 2 + count);
+            received.extend_from_slice(&res.payload()[2..]);
+            start += count;
+            if start == total {
+                break;
+            }
+        }
+        assert_eq!(received.as_slice(), &items);
+
+        // start at the end is an empty list
+        let res: DispatchResponse = ElementList {
+            items: &items[40..],
+            total: 40,
+        }
+        .into();
+        assert_eq!(res.payload(), &[40, 0]);
+    }
+}
@@ -0,0 +1,182 @@
+//! USB polling rate of gaming peripherals
+//!
+//! Firmware gives a [`ReportRatePage`] a [`ReportRateState`] with the rates it can run at, the host reads the
+//! current one and picks another from that list. Rates are in Hz (125, 250, 500, 1000...), sent as little
+//! endian u16s. Changing the rate calls [`ReportRateState::on_change`] so firmware can reconfigure the
+//! endpoint, usually by re-enumerating with a different `bInterval`.
+//!
+//! ```ignore
+//! static REPORT_RATE: ReportRatePage = ReportRatePage::new(Some(ReportRateState {
+//!     current: 1000,
+//!     supported: &[125, 250, 500, 1000],
+//!     on_change: reconfigure_endpoint,
+//! }));
+//!
+//! dispatch.register_page(&REPORT_RATE)?;
+//! ```
+
+use heapless::Vec;
+
+use super::args::FunctionArgs;
+use super::{DispatchContext, DispatchReturn, Error, FunctionPage, SyncCell};
+
+/// not assigned by the openinput spec
+pub const REPORT_RATE_FUNCTION_PAGE: u8 = 0x12;
+
+pub const GET_REPORT_RATE: u8 = 0x00;
+pub const SET_REPORT_RATE: u8 = 0x01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReportRateState {
+    /// rate in Hz
+    pub current: u16,
+    /// rates the host may set
+    pub supported: &'static [u16],
+    /// called with the new rate after the host changed it
+    pub on_change: fn(u16),
+}
+
+impl ReportRateState {
+    /// [`Error::InvalidValue`] if `rate` isn't supported, `on_change` isn't called when it is the current rate
+    pub fn set(&mut self, rate: u16) -> Result<(), Error> {
+        if !self.supported.contains(&rate) {
+            return Err(Error::InvalidValue(0));
+        }
+        if rate != self.current {
+            self.current = rate;
+            (self.on_change)(rate);
+        }
+        Ok(())
+    }
+}
+
+/// exposes its [`ReportRateState`] to the host, every function fails with [`Error::UnsupportedFunction`] while
+/// no state is set
+pub struct ReportRatePage {
+    // the host changes the rate through a shared reference
+    state: SyncCell<Option<ReportRateState>>,
+}
+
+impl ReportRatePage {
+    pub const fn new(state: Option<ReportRateState>) -> Self {
+        Self {
+            state: SyncCell::new(state),
+        }
+    }
+
+    pub fn state(&self) -> Option<ReportRateState> {
+        self.state.get()
+    }
+
+    pub fn set_state(&self, state: Option<ReportRateState>) {
+        self.state.set(state)
+    }
+
+    /// current rate in Hz
+    fn get_report_rate(&self) -> DispatchReturn {
+        let rate = self.state().ok_or(Error::UnsupportedFunction)?.current;
+        Ok(Vec::from_slice(&rate.to_le_bytes()).unwrap().into())
+    }
+
+    /// params: rate in Hz
+    fn set_report_rate(&self, rate: u16) -> DispatchReturn {
+        let mut state = self.state().ok_or(Error::UnsupportedFunction)?;
+        state.set(rate)?;
+        self.set_state(Some(state));
+        Ok(Vec::new().into())
+    }
+}
+
+impl FunctionPage for ReportRatePage {
+    fn page_id(&self) -> u8 {
+        REPORT_RATE_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[GET_REPORT_RATE, SET_REPORT_RATE]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], _: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_REPORT_RATE => self.get_report_rate(),
+            SET_REPORT_RATE => self.set_report_rate(u16::parse(data)?),
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU16, Ordering};
+
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    static CHANGED_TO: AtomicU16 = AtomicU16::new(0);
+
+    fn on_change(rate: u16) {
+        CHANGED_TO.store(rate, Ordering::SeqCst);
+    }
+
+    fn dispatch() -> Dispatch {
+        let page: &'static ReportRatePage =
+            Box::leak(Box::new(ReportRatePage::new(Some(ReportRateState {
+                current: 1000,
+                supported: &[125, 250, 500, 1000],
+                on_change,
+            }))));
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(page).ok().unwrap();
+        dispatch
+    }
+
+    fn get(dispatch: &Dispatch) -> std::vec::Vec<u8> {
+        let res = dispatch
+            .dispatch_raw(REPORT_RATE_FUNCTION_PAGE, GET_REPORT_RATE, &[0; 5])
+            .unwrap();
+        res.payload().to_vec()
+    }
+
+    #[test]
+    fn set_supported_rate() {
+        let dispatch = dispatch();
+        assert_eq!(get(&dispatch), 1000u16.to_le_bytes());
+
+        let [lo, hi] = 500u16.to_le_bytes();
+        dispatch
+            .dispatch_raw(
+                REPORT_RATE_FUNCTION_PAGE,
+                SET_REPORT_RATE,
+                &[lo, hi, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(get(&dispatch), [lo, hi]);
+        assert_eq!(CHANGED_TO.load(Ordering::SeqCst), 500);
+    }
+
+    #[test]
+    fn unsupported_rate() {
+        let dispatch = dispatch();
+        let [lo, hi] = 2000u16.to_le_bytes();
+        assert_eq!(
+            dispatch.dispatch_raw(
+                REPORT_RATE_FUNCTION_PAGE,
+                SET_REPORT_RATE,
+                &[lo, hi, 0, 0, 0]
+            ),
+            Err(Error::InvalidValue(0))
+        );
+        assert_eq!(get(&dispatch), 1000u16.to_le_bytes());
+    }
+
+    #[test]
+    fn no_state() {
+        static PAGE: ReportRatePage = ReportRatePage::new(None);
+        let mut dispatch = Dispatch::default();
+        dispatch.register_page(&PAGE).ok().unwrap();
+        assert_eq!(
+            dispatch.dispatch_raw(REPORT_RATE_FUNCTION_PAGE, GET_REPORT_RATE, &[0; 5]),
+            Err(Error::UnsupportedFunction)
+        );
+    }
+}
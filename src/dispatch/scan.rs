@@ -0,0 +1,214 @@
+//! Raw key matrix state for debugging keyboard matrices
+//!
+//! Firmware describes its matrix with a [`MatrixConfig`] in [`DispatchMeta`], the host can then read single
+//! rows or the whole matrix as a list of row bitmasks.
+
+use heapless::Vec;
+
+use super::args::call_typed;
+use super::{
+    DispatchContext, DispatchMeta, DispatchReturn, Error, FunctionPage, MetaError,
+    LONG_REPLY_CAPACITY,
+};
+
+/// not assigned by the openinput spec
+pub const KEY_SCAN_FUNCTION_PAGE: u8 = 0xFB;
+
+pub const GET_MATRIX_DIMENSIONS: u8 = 0x00;
+pub const SCAN_ROW: u8 = 0x01;
+pub const GET_FULL_SCAN_BITMAP: u8 = 0x02;
+
+/// rows per [`GET_FULL_SCAN_BITMAP`] reply
+pub const SCAN_BITMAP_LEN: usize = LONG_REPLY_CAPACITY;
+
+/// a row is scanned into a single byte
+pub const MAX_MATRIX_COLS: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatrixConfig {
+    pub rows: u8,
+    /// at most [`MAX_MATRIX_COLS`], [`DispatchMeta::set_matrix`] refuses wider matrices
+    pub cols: u8,
+    /// scan `row` and return a bitmask of the pressed columns, bit 0 is column 0
+    pub scan_fn: fn(u8) -> u8,
+}
+
+impl MatrixConfig {
+    /// errors if `row` is outside the matrix
+    pub fn scan_row(&self, row: u8) -> Result<u8, Error> {
+        if row >= self.rows {
+            return Err(Error::InvalidValue(0));
+        }
+        Ok((self.scan_fn)(row))
+    }
+
+    /// rows `offset..` scanned into [`SCAN_BITMAP_LEN`] bytes, zero past the last row
+    pub fn scan_bitmap(&self, offset: u8) -> [u8; SCAN_BITMAP_LEN] {
+        let mut bitmap = [0; SCAN_BITMAP_LEN];
+        for (row, mask) in (offset..self.rows).zip(bitmap.iter_mut()) {
+            *mask = (self.scan_fn)(row);
+        }
+        bitmap
+    }
+}
+
+fn matrix(ctx: &DispatchContext) -> Result<MatrixConfig, Error> {
+    ctx.meta().matrix().ok_or(Error::UnsupportedFunction)
+}
+
+fn get_matrix_dimensions(_: (), ctx: DispatchContext) -> DispatchReturn {
+    let matrix = matrix(&ctx)?;
+    Ok(Vec::from_slice(&[matrix.rows, matrix.cols]).unwrap().into())
+}
+
+fn scan_row(row: u8, ctx: DispatchContext) -> DispatchReturn {
+    let mask = matrix(&ctx)?.scan_row(row)?;
+    Ok(Vec::from_slice(&[mask]).unwrap().into())
+}
+
+fn get_full_scan_bitmap(offset: u8, ctx: DispatchContext) -> DispatchReturn {
+    let bitmap = matrix(&ctx)?.scan_bitmap(offset);
+    Ok(Vec::from_slice(&bitmap).unwrap().into())
+}
+
+/// exposes the matrix in [`DispatchMeta::matrix`] to the host, every function fails with
+/// [`Error::UnsupportedFunction`] if no matrix is configured
+pub struct KeyScanPage;
+
+impl FunctionPage for KeyScanPage {
+    fn page_id(&self) -> u8 {
+        KEY_SCAN_FUNCTION_PAGE
+    }
+
+    fn functions(&self) -> &'static [u8] {
+        &[GET_MATRIX_DIMENSIONS, SCAN_ROW, GET_FULL_SCAN_BITMAP]
+    }
+
+    fn call(&self, fn_id: u8, data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        match fn_id {
+            GET_MATRIX_DIMENSIONS => call_typed(data, ctx, get_matrix_dimensions),
+            SCAN_ROW => call_typed(data, ctx, scan_row),
+            GET_FULL_SCAN_BITMAP => call_typed(data, ctx, get_full_scan_bitmap),
+            _ => Err(Error::UnsupportedFunction),
+        }
+    }
+}
+
+impl DispatchMeta {
+    pub fn matrix(&self) -> Option<MatrixConfig> {
+        self.matrix
+    }
+
+    /// fails with [`MetaError::MatrixTooWide`] if the rows don't fit a byte, the old matrix is kept then
+    pub fn set_matrix(&mut self, matrix: Option<MatrixConfig>) -> Result<(), MetaError> {
+        if matrix.map_or(false, |matrix| matrix.cols > MAX_MATRIX_COLS) {
+            return Err(MetaError::MatrixTooWide);
+        }
+        self.matrix = matrix;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    /// every row has its own index pressed, row 2 also has column 7
+    fn mock_scan(row: u8) -> u8 {
+        let mask = 1 << (row % 8);
+        if row == 2 {
+            mask | 0x80
+        } else {
+            mask
+        }
+    }
+
+    fn dispatch(matrix: Option<MatrixConfig>) -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch.meta.set_matrix(matrix).unwrap();
+        dispatch.register_page(&KeyScanPage).ok().unwrap();
+        dispatch
+    }
+
+    const MATRIX: MatrixConfig = MatrixConfig {
+        rows: 6,
+        cols: 8,
+        scan_fn: mock_scan,
+    };
+
+    #[test]
+    fn dimensions_and_rows() {
+        let dispatch = dispatch(Some(MATRIX));
+        let res = dispatch
+            .dispatch_raw(KEY_SCAN_FUNCTION_PAGE, GET_MATRIX_DIMENSIONS, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload(), &[6, 8]);
+
+        let res = dispatch
+            .dispatch_raw(KEY_SCAN_FUNCTION_PAGE, SCAN_ROW, &[2, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(res.payload(), &[0x84]);
+        assert_eq!(
+            dispatch.dispatch_raw(KEY_SCAN_FUNCTION_PAGE, SCAN_ROW, &[6, 0, 0, 0, 0]),
+            Err(Error::InvalidValue(0))
+        );
+    }
+
+    #[test]
+    fn full_bitmap() {
+        let dispatch = dispatch(Some(MATRIX));
+        let res = dispatch
+            .dispatch_raw(KEY_SCAN_FUNCTION_PAGE, GET_FULL_SCAN_BITMAP, &[0; 5])
+            .unwrap();
+        assert_eq!(res.payload().len(), SCAN_BITMAP_LEN);
+        assert_eq!(
+            &res.payload()[..7],
+            &[0x01, 0x02, 0x84, 0x08, 0x10, 0x20, 0]
+        );
+
+        let res = dispatch
+            .dispatch_raw(
+                KEY_SCAN_FUNCTION_PAGE,
+                GET_FULL_SCAN_BITMAP,
+                &[4, 0, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(&res.payload()[..3], &[0x10, 0x20, 0]);
+
+        // past the end is all zero
+        let res = dispatch
+            .dispatch_raw(
+                KEY_SCAN_FUNCTION_PAGE,
+                GET_FULL_SCAN_BITMAP,
+                &[9, 0, 0, 0, 0],
+            )
+            .unwrap();
+        assert_eq!(res.payload(), &[0; SCAN_BITMAP_LEN]);
+    }
+
+    #[test]
+    fn too_wide() {
+        let wide = MatrixConfig { cols: 9, ..MATRIX };
+        let mut meta = DispatchMeta::default();
+        meta.set_matrix(Some(MATRIX)).unwrap();
+        assert_eq!(meta.set_matrix(Some(wide)), Err(MetaError::MatrixTooWide));
+        assert_eq!(meta.matrix(), Some(MATRIX));
+
+        assert_eq!(
+            DispatchMeta::builder().with_matrix(wide).build().err(),
+            Some(MetaError::MatrixTooWide)
+        );
+    }
+
+    #[test]
+    fn no_matrix() {
+        let dispatch = dispatch(None);
+        for fn_id in [GET_MATRIX_DIMENSIONS, SCAN_ROW, GET_FULL_SCAN_BITMAP] {
+            assert_eq!(
+                dispatch.dispatch_raw(KEY_SCAN_FUNCTION_PAGE, fn_id, &[0; 5]),
+                Err(Error::UnsupportedFunction)
+            );
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! Request counters for debugging devices in the field
+//!
+//! Every request that goes through [`Dispatch`] is counted, failed ones also remember their page and function
+//! id. Firmware reads them with [`Dispatch::stats`], hosts with the debug page's
+//! [`GET_DISPATCH_STATS`](super::debug::GET_DISPATCH_STATS) when the `debug-page` feature is enabled.
+
+use super::{Dispatch, DispatchReturn, SyncCell};
+
+/// counters stop at `u32::MAX` instead of wrapping
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DispatchStats {
+    pub requests: u32,
+    /// requests answered with an error, deferred replies aren't errors
+    pub errors: u32,
+    /// page, function id as sent and error id of the last failed request
+    pub last_error: Option<(u8, u8, u8)>,
+}
+
+/// serialized size of [`DispatchStats`]
+pub const DISPATCH_STATS_LEN: usize = 12;
+
+impl DispatchStats {
+    pub(crate) fn record(&mut self, page: u8, id: u8, res: &DispatchReturn) {
+        self.requests = self.requests.saturating_add(1);
+        match res {
+            Ok(_) => (),
+            Err(e) => {
+                self.errors = self.errors.saturating_add(1);
+                self.last_error = Some((page, id, e.id()));
+            }
+        }
+    }
+
+    /// requests and errors as little endian u32s, then 1 and the last error or 4 zero bytes
+    pub fn to_bytes(&self) -> [u8; DISPATCH_STATS_LEN] {
+        let mut bytes = [0; DISPATCH_STATS_LEN];
+        bytes[..4].copy_from_slice(&self.requests.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.errors.to_le_bytes());
+        if let Some((page, id, error)) = self.last_error {
+            bytes[8..].copy_from_slice(&[1, page, id, error]);
+        }
+        bytes
+    }
+}
+
+pub(crate) type StatsCell = SyncCell<DispatchStats>;
+
+pub(crate) const fn stats() -> StatsCell {
+    SyncCell::new(DispatchStats {
+        requests: 0,
+        errors: 0,
+        last_error: None,
+    })
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// counters since startup or the last [`Dispatch::reset_stats`]
+    pub fn stats(&self) -> DispatchStats {
+        self.stats.get()
+    }
+
+    pub fn reset_stats(&self) {
+        self.stats.take();
+    }
+
+    pub(crate) fn record_stats(&self, page: u8, id: u8, res: &DispatchReturn) {
+        self.stats.update(|stats| stats.record(page, id, res));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::info_table::INFO_VERSION;
+    use crate::dispatch::{Error, INFO_FUNCTION_PAGE};
+    use crate::OiReport;
+
+    #[test]
+    fn counts_requests_and_errors() {
+        let dispatch = Dispatch::default();
+        dispatch.dispatch(&OiReport::new_short(
+            INFO_FUNCTION_PAGE,
+            INFO_VERSION,
+            &[0; 5],
+        ));
+        dispatch.dispatch(&OiReport::new_short(0x43, 0x01, &[0; 5]));
+        dispatch.dispatch(&OiReport::new_short(INFO_FUNCTION_PAGE, 0x07, &[0; 5]));
+        assert!(dispatch
+            .dispatch_raw(INFO_FUNCTION_PAGE, INFO_VERSION, &[0; 5])
+            .is_ok());
+
+        let stats = dispatch.stats();
+        assert_eq!(
+            stats,
+            DispatchStats {
+                requests: 4,
+                errors: 2,
+                last_error: Some((INFO_FUNCTION_PAGE, 0x07, 0x02)),
+            }
+        );
+        assert_eq!(
+            stats.to_bytes(),
+            [4, 0, 0, 0, 2, 0, 0, 0, 1, INFO_FUNCTION_PAGE, 0x07, 0x02]
+        );
+
+        dispatch.reset_stats();
+        assert_eq!(dispatch.stats().to_bytes(), [0; DISPATCH_STATS_LEN]);
+    }
+
+    #[test]
+    fn saturates() {
+        let mut stats = DispatchStats {
+            requests: u32::MAX,
+            errors: u32::MAX,
+            last_error: None,
+        };
+        stats.record(0x43, 0x00, &Err(Error::UnsupportedPage));
+        assert_eq!((stats.requests, stats.errors), (u32::MAX, u32::MAX));
+        assert_eq!(stats.last_error, Some((0x43, 0x00, 0x04)));
+    }
+}
@@ -0,0 +1,270 @@
+//! Replies spread over several reports
+//!
+//! A handler with more to say than a long report holds (keymap dumps, logs) fills a [`DispatchResponses`] and
+//! returns [`DispatchContext::stream`]. Every frame is a regular reply to the request whose payload starts with
+//! the frame's sequence number and the frame count, so the host knows from the first one how many to read:
+//!
+//! ```text
+//! report id, page, fn id, sequence, count, data (27 bytes)
+//! ```
+//!
+//! With the `wireless` feature the address comes before the sequence number and every frame is addressed to the
+//! device the request was for, which leaves 26 bytes of data.
+//!
+//! The first frame is returned like any other reply, the rest wait in the dispatcher until firmware pushes them
+//! with [`Dispatch::flush_queued`] ([`OpenInputDevice::poll`](crate::device::OpenInputDevice::poll) does).
+//! Handlers that don't stream keep the single report layout without the header.
+//!
+//! ```ignore
+//! fn dump_keymap(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+//!     let mut res = DispatchResponses::<3>::new();
+//!     res.extend_from_slice(&KEYMAP)?;
+//!     ctx.stream(res)
+//! }
+//! ```
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::{Deque, Vec};
+
+use super::{
+    Dispatch, DispatchContext, DispatchResponse, DispatchReturn, Error, LONG_REPLY_CAPACITY,
+};
+use crate::LONG_LEN;
+
+/// sequence number, frame count
+const STREAM_HEADER_LEN: usize = 2;
+
+/// data bytes per frame
+pub const STREAM_FRAME_LEN: usize = LONG_REPLY_CAPACITY - STREAM_HEADER_LEN;
+
+/// frames that can wait for [`Dispatch::flush_queued`], a stream is at most one frame longer
+pub const MAX_QUEUED_FRAMES: usize = 4;
+
+pub(crate) type FrameQueue = Mutex<RefCell<Deque<Vec<u8, LONG_LEN>, MAX_QUEUED_FRAMES>>>;
+
+pub(crate) const fn queue() -> FrameQueue {
+    Mutex::new(RefCell::new(Deque::new()))
+}
+
+/// reply payload split into up to `N` frames of [`STREAM_FRAME_LEN`] bytes
+#[derive(Default)]
+pub struct DispatchResponses<const N: usize> {
+    frames: Vec<Vec<u8, STREAM_FRAME_LEN>, N>,
+}
+
+impl<const N: usize> DispatchResponses<N> {
+    pub const fn new() -> Self {
+        Self { frames: Vec::new() }
+    }
+
+    /// append to the last frame and start new ones as needed, nothing is appended if `data` doesn't fit
+    pub fn extend_from_slice(&mut self, mut data: &[u8]) -> Result<(), Error> {
+        if data.len() > N * STREAM_FRAME_LEN - self.len() {
+            return Err(Error::custom_truncating("reply too long"));
+        }
+        while !data.is_empty() {
+            if self
+                .frames
+                .last()
+                .map_or(true, |frame| frame.len() == STREAM_FRAME_LEN)
+            {
+                // can't fail, the length was checked above
+                self.frames.push(Vec::new()).ok();
+            }
+            let Some(frame) = self.frames.last_mut() else {
+                break;
+            };
+            let n = data.len().min(STREAM_FRAME_LEN - frame.len());
+            frame.extend_from_slice(&data[..n]).ok();
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    /// data bytes over all frames
+    pub fn len(&self) -> usize {
+        self.frames.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// reports the host receives, an empty stream is still sent as one
+    pub fn frame_count(&self) -> usize {
+        self.frames.len().max(1)
+    }
+}
+
+fn frame(seq: usize, count: usize, data: &[u8]) -> DispatchResponse {
+    let mut payload = Vec::new();
+    // can't fail, a frame holds STREAM_FRAME_LEN bytes
+    payload.extend_from_slice(&[seq as u8, count as u8]).ok();
+    payload.extend_from_slice(data).ok();
+    payload.into()
+}
+
+impl<'a> DispatchContext<'a> {
+    /// reply with every frame of `responses`, the first one is returned and the rest are queued for
+    /// [`Dispatch::flush_queued`]
+    ///
+    /// [`Error::Busy`] while frames of an earlier stream are still queued, they'd end up after this one's first
+    pub fn stream<const N: usize>(&self, responses: DispatchResponses<N>) -> DispatchReturn {
+        let count = responses.frame_count();
+        if count > MAX_QUEUED_FRAMES + 1 {
+            return Err(Error::custom_truncating("reply too long"));
+        }
+        let data = |seq: usize| responses.frames.get(seq).map_or(&[][..], |f| f.as_slice());
+        critical_section::with(|cs| {
+            let mut queue = self.queue.borrow_ref_mut(cs);
+            if !queue.is_empty() {
+                return Err(Error::Busy);
+            }
+            for seq in 1..count {
+                let reply = frame(seq, count, data(seq)).reply(
+                    self.request_report_id.id(),
+                    self.page,
+                    self.fn_id,
+                );
+                #[cfg(feature = "wireless")]
+                let reply = crate::wireless::address_reply(
+                    self.device_address
+                        .unwrap_or(crate::wireless::LOCAL_ADDRESS),
+                    reply,
+                );
+                // can't fail, the queue was empty and the count checked
+                queue.push_back(reply).ok();
+            }
+            Ok(())
+        })?;
+        Ok(frame(0, count, data(0)))
+    }
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// push the queued frames of a streamed reply in order until `push` fails, the frame it failed on stays
+    /// queued for the next call
+    ///
+    /// `push` runs outside the critical section, only taking frames off the queue is guarded
+    pub fn flush_queued<E>(&self, mut push: impl FnMut(&[u8]) -> Result<(), E>) -> Result<(), E> {
+        while let Some(frame) =
+            critical_section::with(|cs| self.queue.borrow_ref(cs).front().cloned())
+        {
+            push(&frame[..])?;
+            critical_section::with(|cs| self.queue.borrow_ref_mut(cs).pop_front());
+        }
+        Ok(())
+    }
+
+    /// frames waiting for [`Dispatch::flush_queued`]
+    pub fn queued_count(&self) -> usize {
+        critical_section::with(|cs| self.queue.borrow_ref(cs).len())
+    }
+
+    /// drop the queued frames, e.g. when the first frame of their stream couldn't be pushed
+    pub fn clear_queued(&self) {
+        critical_section::with(|cs| self.queue.borrow_ref_mut(cs).clear());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::FnvIndexMap;
+
+    use super::*;
+    use crate::dispatch::DispatchHandler;
+    use crate::OiReport;
+
+    const PAGE: u8 = 0x42;
+
+    /// 0..60 in 3 frames
+    fn counting(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let mut res = DispatchResponses::<3>::new();
+        for i in 0..60 {
+            res.extend_from_slice(&[i])?;
+        }
+        ctx.stream(res)
+    }
+
+    fn dispatch() -> Dispatch {
+        let mut table = FnvIndexMap::new();
+        let mut page = FnvIndexMap::new();
+        page.insert(0x00, DispatchHandler::Function(counting))
+            .ok()
+            .unwrap();
+        table.insert(PAGE, page).ok().unwrap();
+        Dispatch::new_raw(table, Default::default())
+    }
+
+    fn expected(seq: u8) -> std::vec::Vec<u8> {
+        let data = (seq * 27..60.min(seq * 27 + 27)).collect::<std::vec::Vec<_>>();
+        let mut report = vec![0x21, PAGE, 0x00, seq, 3];
+        report.extend_from_slice(&data);
+        report.resize(LONG_LEN, 0);
+        report
+    }
+
+    #[test]
+    fn three_frames() {
+        let dispatch = dispatch();
+        let request = OiReport::new_short(PAGE, 0x00, &[0; 5]);
+        let first = dispatch.dispatch(&request);
+        assert_eq!(first.as_slice(), expected(0));
+        assert_eq!(dispatch.queued_count(), 2);
+
+        let mut pushed = std::vec::Vec::new();
+        dispatch
+            .flush_queued(|frame| {
+                pushed.push(frame.to_vec());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(pushed, [expected(1), expected(2)]);
+        assert_eq!(dispatch.queued_count(), 0);
+    }
+
+    #[test]
+    fn flush_stops_when_busy() {
+        let dispatch = dispatch();
+        let request = OiReport::new_short(PAGE, 0x00, &[0; 5]);
+        dispatch.dispatch(&request);
+
+        // the endpoint takes one frame, then blocks
+        let mut pushed = std::vec::Vec::new();
+        let res = dispatch.flush_queued(|frame| {
+            if !pushed.is_empty() {
+                return Err(());
+            }
+            pushed.push(frame.to_vec());
+            Ok(())
+        });
+        assert_eq!(res, Err(()));
+        assert_eq!(pushed, [expected(1)]);
+        assert_eq!(dispatch.queued_count(), 1);
+
+        // another stream can't start before the rest is out
+        let reply = dispatch.dispatch(&request);
+        assert_eq!(reply.as_slice(), &[0x20, 0xFF, 0x05, PAGE, 0x00, 0, 0, 0]);
+
+        dispatch
+            .flush_queued(|frame| {
+                pushed.push(frame.to_vec());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(pushed, [expected(1), expected(2)]);
+        assert_eq!(dispatch.dispatch(&request).as_slice(), expected(0));
+    }
+
+    #[test]
+    fn too_long() {
+        let mut res = DispatchResponses::<2>::new();
+        res.extend_from_slice(&[0; 50]).unwrap();
+        assert!(res.extend_from_slice(&[0; 5]).is_err());
+        assert_eq!((res.len(), res.frame_count()), (50, 2));
+        res.extend_from_slice(&[0; 4]).unwrap();
+        assert_eq!(res.len(), 2 * STREAM_FRAME_LEN);
+    }
+}
@@ -0,0 +1,157 @@
+//! Device specific function pages
+//!
+//! Pages 0x80 to 0xFE are reserved for vendors, the openinput spec won't assign them so firmware can add its own
+//! functions there without clashing with a future standard page. Hosts only call them once they know the
+//! device, e.g. by vendor and device name from the info page.
+//!
+//! Some optional pages of this crate live in the same range (challenge 0xFA, key scan 0xFB, auth 0xFC, debug
+//! 0xFD). Firmware that enables one of them picks its vendor pages around it. Registering the crate pages first
+//! catches a clash, [`Dispatch::register_vendor_page`] answers [`VendorPageError::DuplicatePage`] for an id
+//! that's taken.
+//!
+//! [`Dispatch::register_vendor_page`] enforces the range and refuses to replace a registered page:
+//!
+//! ```
+//! use heapless::Vec;
+//! use openinput_rust::dispatch::{Dispatch, DispatchContext, DispatchHandler, DispatchReturn};
+//!
+//! const PRODUCT_PAGE: u8 = 0x80;
+//! const GET_PRODUCT_SKU: u8 = 0x00;
+//!
+//! /// ASCII model number
+//! fn get_product_sku(_: &[u8], _: DispatchContext) -> DispatchReturn {
+//!     Ok(Vec::from_slice(b"DK-65-ISO").unwrap().into())
+//! }
+//!
+//! let mut dispatch = Dispatch::default();
+//! dispatch
+//!     .register_vendor_page(
+//!         PRODUCT_PAGE,
+//!         &[(GET_PRODUCT_SKU, DispatchHandler::Function(get_product_sku))],
+//!     )
+//!     .unwrap();
+//!
+//! let reply = dispatch.dispatch_raw(PRODUCT_PAGE, GET_PRODUCT_SKU, &[0; 5]).unwrap();
+//! assert_eq!(reply.payload(), b"DK-65-ISO");
+//! ```
+
+use heapless::FnvIndexMap;
+
+use super::{Dispatch, DispatchHandler, MAX_FUNCTIONS};
+
+/// first page id reserved for vendors
+pub const VENDOR_PAGE_START: u8 = 0x80;
+/// last page id reserved for vendors, 0xFF is the error page
+pub const VENDOR_PAGE_END: u8 = 0xFE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum VendorPageError {
+    /// page id isn't in `VENDOR_PAGE_START..=VENDOR_PAGE_END`
+    OutOfVendorRange,
+    /// page is already registered
+    DuplicatePage,
+    /// `functions` lists the same function id twice
+    DuplicateFunction,
+    /// too many pages or functions, or the table is static
+    TableFull,
+}
+
+impl<const MAX_PENDING: usize> Dispatch<MAX_PENDING> {
+    /// add a vendor page with `functions`, see the [module docs](self)
+    ///
+    /// the table is left untouched on errors
+    pub fn register_vendor_page(
+        &mut self,
+        page: u8,
+        functions: &[(u8, DispatchHandler)],
+    ) -> Result<(), VendorPageError> {
+        if !(VENDOR_PAGE_START..=VENDOR_PAGE_END).contains(&page) {
+            return Err(VendorPageError::OutOfVendorRange);
+        }
+        let table = self.table.map_mut().ok_or(VendorPageError::TableFull)?;
+        if table.contains_key(&page) {
+            return Err(VendorPageError::DuplicatePage);
+        }
+        if functions.len() > MAX_FUNCTIONS {
+            return Err(VendorPageError::TableFull);
+        }
+        let mut fn_page = FnvIndexMap::new();
+        for &(id, handler) in functions {
+            // can't be full, checked the length above
+            if let Ok(Some(_)) = fn_page.insert(id, handler) {
+                return Err(VendorPageError::DuplicateFunction);
+            }
+        }
+        table
+            .insert(page, fn_page)
+            .map_err(|_| VendorPageError::TableFull)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::*;
+    use crate::dispatch::{DispatchContext, DispatchReturn, INFO_FUNCTION_PAGE};
+
+    fn get_product_sku(_: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(b"DK-65-ISO").unwrap().into())
+    }
+
+    const SKU: &[(u8, DispatchHandler)] = &[(0x00, DispatchHandler::Function(get_product_sku))];
+
+    #[test]
+    fn register() {
+        let mut dispatch = Dispatch::default();
+        dispatch.register_vendor_page(0x80, SKU).unwrap();
+        dispatch.register_vendor_page(0xFE, SKU).unwrap();
+        assert_eq!(
+            dispatch
+                .dispatch_raw(0x80, 0x00, &[0; 5])
+                .unwrap()
+                .payload(),
+            b"DK-65-ISO"
+        );
+        assert_eq!(
+            dispatch.pages().collect::<std::vec::Vec<_>>(),
+            [INFO_FUNCTION_PAGE, 0x80, 0xFE]
+        );
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut dispatch = Dispatch::default();
+        for page in [INFO_FUNCTION_PAGE, 0x01, 0x7F, 0xFF] {
+            assert_eq!(
+                dispatch.register_vendor_page(page, SKU),
+                Err(VendorPageError::OutOfVendorRange)
+            );
+        }
+        assert_eq!(dispatch.pages().count(), 1);
+    }
+
+    #[test]
+    fn duplicate() {
+        let mut dispatch = Dispatch::default();
+        dispatch.register_vendor_page(0x80, SKU).unwrap();
+        assert_eq!(
+            dispatch.register_vendor_page(0x80, &[]),
+            Err(VendorPageError::DuplicatePage)
+        );
+        assert!(dispatch.contains(0x80, 0x00));
+    }
+
+    #[test]
+    fn duplicate_function() {
+        let mut dispatch = Dispatch::default();
+        let functions = [SKU[0], SKU[0]];
+        assert_eq!(
+            dispatch.register_vendor_page(0x81, &functions),
+            Err(VendorPageError::DuplicateFunction)
+        );
+        assert_eq!(dispatch.pages().count(), 1);
+    }
+}
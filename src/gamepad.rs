@@ -0,0 +1,330 @@
+//! Gamepad / joystick report
+//!
+//! Populating the report from an ADC, sticks are centered around 0 and triggers start at 0:
+//!
+//! ```ignore
+//! let mid = (ADC_MAX / 2) as i32;
+//! let axis = |raw: u16| ((raw as i32 - mid) * i16::MAX as i32 / mid) as i16;
+//!
+//! let report = GamepadInputReport {
+//!     lx: axis(adc.read(LEFT_X)),
+//!     ly: axis(adc.read(LEFT_Y)),
+//!     rx: axis(adc.read(RIGHT_X)),
+//!     ry: axis(adc.read(RIGHT_Y)),
+//!     lt: (adc.read(LEFT_TRIGGER) >> 4) as u8,
+//!     rt: (adc.read(RIGHT_TRIGGER) >> 4) as u8,
+//!     buttons: buttons.read(),
+//!     hat: HAT_CENTERED,
+//! };
+//! class.report.push_report(&mut class.inner, OiGamepadInputReport::Gamepad(report))?;
+//! ```
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
+
+use crate::{
+    concat_desc, read_openinput, OIError, OiReport, LONG_LEN, OPENINPUT_DESCRIPTOR, SHORT_LEN,
+};
+
+use super::OpenInputHidReport;
+
+/// hat switch value when the d-pad isn't pressed, 0-7 are N, NE, E, SE, S, SW, W, NW
+pub const HAT_CENTERED: u8 = 0x08;
+
+const GAMEPAD: [u8; 70] = [
+    0x05, 0x01, /* USAGE_PAGE (Generic Desktop) */
+    0x09, 0x05, /* USAGE (Game Pad) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x03, /*  REPORT_ID (3) */
+    0x16, 0x00, 0x80, /*  LOGICAL_MINIMUM (-32768) */
+    0x26, 0xff, 0x7f, /*  LOGICAL_MAXIMUM (32767) */
+    0x75, 0x10, /*  REPORT_SIZE (16) */
+    0x95, 0x04, /*  REPORT_COUNT (4) */
+    0x09, 0x30, /*  USAGE (X) */
+    0x09, 0x31, /*  USAGE (Y) */
+    0x09, 0x33, /*  USAGE (Rx) */
+    0x09, 0x34, /*  USAGE (Ry) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x15, 0x00, /*  LOGICAL_MINIMUM (0) */
+    0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+    0x75, 0x08, /*  REPORT_SIZE (8) */
+    0x95, 0x02, /*  REPORT_COUNT (2) */
+    0x09, 0x32, /*  USAGE (Z) */
+    0x09, 0x35, /*  USAGE (Rz) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x05, 0x09, /*  USAGE_PAGE (Button) */
+    0x19, 0x01, /*  USAGE_MINIMUM (Button 1) */
+    0x29, 0x10, /*  USAGE_MAXIMUM (Button 16) */
+    0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+    0x75, 0x01, /*  REPORT_SIZE (1) */
+    0x95, 0x10, /*  REPORT_COUNT (16) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x05, 0x01, /*  USAGE_PAGE (Generic Desktop) */
+    0x09, 0x39, /*  USAGE (Hat switch) */
+    0x25, 0x07, /*  LOGICAL_MAXIMUM (7) */
+    0x75, 0x08, /*  REPORT_SIZE (8) */
+    0x95, 0x01, /*  REPORT_COUNT (1) */
+    0x81, 0x42, /*  INPUT (Data,Var,Abs,Null) */
+    0xc0, /* END_COLLECTION */
+];
+
+pub const GAMEPAD_DESC_LEN: usize = GAMEPAD.len() + OPENINPUT_DESCRIPTOR.len();
+
+/// gamepad input report 0x03 followed by the OpenInput collections. sticks are X/Y/Rx/Ry, triggers Z/Rz and
+/// the hat switch reports its null state for any value past 7, e.g. [`HAT_CENTERED`]
+static DESC: [u8; GAMEPAD_DESC_LEN] = concat_desc(&GAMEPAD, OPENINPUT_DESCRIPTOR);
+
+#[derive(Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OiGamepadReport {
+    // openinput
+    out_short_buf: [u8; SHORT_LEN],
+    out_long_buf: [u8; LONG_LEN],
+}
+
+impl SerializedDescriptor for OiGamepadReport {
+    fn desc() -> &'static [u8] {
+        &DESC
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum GamepadReportId {
+    OpenInputShort = 0x20,
+    OpenInputLong = 0x21,
+    Gamepad = 0x03,
+}
+
+impl TryFrom<u8> for GamepadReportId {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x03 => Ok(GamepadReportId::Gamepad),
+            0x20 => Ok(GamepadReportId::OpenInputShort),
+            0x21 => Ok(GamepadReportId::OpenInputLong),
+            _ => Err(()),
+        }
+    }
+}
+
+/// gamepads have no output report of their own (yet)
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiGamepadOutputReport<'a> {
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+/// `Deserialize` lets hosts and tests decode a report body (without the report id)
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GamepadInputReport {
+    pub lx: i16,
+    pub ly: i16,
+    pub rx: i16,
+    pub ry: i16,
+    pub lt: u8,
+    pub rt: u8,
+    /// one bit per button, button 1 is bit 0
+    pub buttons: u16,
+    pub hat: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiGamepadInputReport<'a> {
+    /// Gamepad report
+    Gamepad(GamepadInputReport),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+impl<'a> Serialize for OiGamepadInputReport<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            OiGamepadInputReport::Gamepad(gp) => {
+                // report id + report
+                let mut s = serializer.serialize_tuple(2)?;
+                s.serialize_element(&(GamepadReportId::Gamepad as u8))?;
+                s.serialize_element(gp)?;
+                s.end()
+            }
+            OiGamepadInputReport::OpenInput(oi) => oi.serialize(serializer),
+        }
+    }
+}
+
+impl OpenInputHidReport for OiGamepadReport {
+    type PullReport<'a> = OiGamepadOutputReport<'a>;
+    type PushReport<'a> = OiGamepadInputReport<'a>;
+    type ReportId = GamepadReportId;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
+            // no gamepad output items in the descriptor
+            GamepadReportId::Gamepad => Err(OIError::FuckyBuffer),
+            GamepadReportId::OpenInputShort => Ok(OiGamepadOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_short_buf)?,
+            )),
+            GamepadReportId::OpenInputLong => Ok(OiGamepadOutputReport::OpenInput(read_openinput(
+                buf,
+                &mut self.out_long_buf,
+            )?)),
+        }
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        let OiGamepadOutputReport::OpenInput(oi) = report;
+        Ok(oi)
+    }
+
+    fn push_report<'b, 'ep, B: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, B>,
+        report: Self::PushReport<'b>,
+    ) -> Result<(), OIError> {
+        let mut buf = [0; 64];
+        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        hid.push_raw_input(&buf[..m])?;
+        Ok(())
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+    use crate::descriptor::{has_openinput_collections, OPENINPUT_DESC, OPENINPUT_SNAPSHOT};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn conformance() {
+        let desc = OiGamepadReport::desc();
+        assert!(
+            contains(desc, &OPENINPUT_DESC),
+            "\n{:x?}\n{:x?}",
+            desc,
+            OPENINPUT_DESC
+        );
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // USAGE_PAGE (Generic Desktop), USAGE (Game Pad)
+        assert!(contains(desc, &[0x05, 0x01, 0x09, 0x05]), "{:x?}", desc);
+        // REPORT_ID (3)
+        assert!(contains(desc, &[0x85, 0x03]), "{:x?}", desc);
+        // USAGE (Hat switch), LOGICAL_MAXIMUM (7), 8 bits, INPUT (Data,Var,Abs,Null)
+        assert!(
+            contains(
+                desc,
+                &[0x09, 0x39, 0x25, 0x07, 0x75, 0x08, 0x95, 0x01, 0x81, 0x42]
+            ),
+            "{:x?}",
+            desc
+        );
+    }
+
+    #[test]
+    fn serialize() {
+        let report = OiGamepadInputReport::Gamepad(GamepadInputReport {
+            lx: -1,
+            ly: 0x0102,
+            rx: 0,
+            ry: 0,
+            lt: 0x10,
+            rt: 0x20,
+            buttons: 0x8001,
+            hat: HAT_CENTERED,
+        });
+        let mut buf = [0; 64];
+        let len = ssmarshal::serialize(&mut buf, &report).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &[0x03, 0xff, 0xff, 0x02, 0x01, 0, 0, 0, 0, 0x10, 0x20, 0x01, 0x80, 0x08]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let state = GamepadInputReport {
+            lx: i16::MIN,
+            ly: i16::MAX,
+            rx: -300,
+            ry: 300,
+            lt: 0xff,
+            rt: 0,
+            buttons: 0b0000_0100_0000_0011,
+            hat: 2,
+        };
+        let mut buf = [0; 64];
+        let len =
+            ssmarshal::serialize(&mut buf, &OiGamepadInputReport::Gamepad(state.clone())).unwrap();
+        assert_eq!(buf[0], GamepadReportId::Gamepad as u8);
+        let (decoded, read) = ssmarshal::deserialize::<GamepadInputReport>(&buf[1..len]).unwrap();
+        assert_eq!(read, len - 1);
+        assert_eq!(decoded, state);
+    }
+
+    const DESC_SNAPSHOT: [&[u8]; 3] = [
+        &[
+            0x05, 0x01, /* USAGE_PAGE (Generic Desktop) */
+            0x09, 0x05, /* USAGE (Game Pad) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x03, /*  REPORT_ID (3) */
+            0x16, 0x00, 0x80, /*  LOGICAL_MINIMUM (-32768) */
+            0x26, 0xff, 0x7f, /*  LOGICAL_MAXIMUM (32767) */
+            0x75, 0x10, /*  REPORT_SIZE (16) */
+            0x95, 0x04, /*  REPORT_COUNT (4) */
+            0x09, 0x30, /*  USAGE (X) */
+            0x09, 0x31, /*  USAGE (Y) */
+            0x09, 0x33, /*  USAGE (Rx) */
+            0x09, 0x34, /*  USAGE (Ry) */
+            0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+            0x15, 0x00, /*  LOGICAL_MINIMUM (0) */
+            0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+            0x75, 0x08, /*  REPORT_SIZE (8) */
+            0x95, 0x02, /*  REPORT_COUNT (2) */
+            0x09, 0x32, /*  USAGE (Z) */
+            0x09, 0x35, /*  USAGE (Rz) */
+            0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+            0x05, 0x09, /*  USAGE_PAGE (Button) */
+            0x19, 0x01, /*  USAGE_MINIMUM (Button 1) */
+            0x29, 0x10, /*  USAGE_MAXIMUM (Button 16) */
+            0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+            0x75, 0x01, /*  REPORT_SIZE (1) */
+            0x95, 0x10, /*  REPORT_COUNT (16) */
+            0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+            0x05, 0x01, /*  USAGE_PAGE (Generic Desktop) */
+            0x09, 0x39, /*  USAGE (Hat switch) */
+            0x25, 0x07, /*  LOGICAL_MAXIMUM (7) */
+            0x75, 0x08, /*  REPORT_SIZE (8) */
+            0x95, 0x01, /*  REPORT_COUNT (1) */
+            0x81, 0x42, /*  INPUT (Data,Var,Abs,Null) */
+            0xc0, /* END_COLLECTION */
+        ],
+        OPENINPUT_SNAPSHOT[0],
+        OPENINPUT_SNAPSHOT[1],
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        crate::descriptor::assert_snapshot("gamepad", OiGamepadReport::desc(), &DESC_SNAPSHOT);
+    }
+}
@@ -0,0 +1,373 @@
+//! Host side helpers for interpreting device replies
+
+use core::fmt;
+
+use heapless::Vec;
+
+use crate::dispatch::{DEVICE_ERROR_MARKER, ERROR_FUNCTION_PAGE, LONG_REPLY_CAPACITY};
+use crate::meta_feature::META_FEATURE_REPORT_ID;
+
+/// payload of a long reply, after the address with the `wireless` feature
+const CHUNK_LEN: usize = LONG_REPLY_CAPACITY;
+
+/// decoded ff_error report
+/// https://openinput.readthedocs.io/projects/protocol/en/latest/device-protocol/functions/ff_error.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorReply<'a> {
+    InvalidValue {
+        page: u8,
+        id: u8,
+        index: u8,
+    },
+    UnsupportedFunction {
+        page: u8,
+        id: u8,
+    },
+    AuthenticationFailed {
+        page: u8,
+        id: u8,
+    },
+    UnsupportedPage {
+        page: u8,
+        id: u8,
+    },
+    /// the device can't defer another request right now, retry later
+    Busy {
+        page: u8,
+        id: u8,
+    },
+    /// the function needs an authenticated session
+    PermissionDenied {
+        page: u8,
+        id: u8,
+    },
+    Custom {
+        page: u8,
+        id: u8,
+        message: &'a str,
+    },
+    /// see [`Error::Device`](crate::dispatch::Error::Device), `detail` is the rest of the report
+    Device {
+        page: u8,
+        id: u8,
+        code: u8,
+        detail: &'a [u8],
+    },
+    /// not an ff_error report, or an error code this crate doesn't know about
+    Unknown(&'a [u8]),
+}
+
+/// decode a full report (including the report id) from the error page
+///
+/// with the `wireless` feature the address of long reports is skipped, see [`crate::wireless`]
+pub fn decode_error(report: &[u8]) -> ErrorReply {
+    let (code, rest) = match report {
+        [_, ERROR_FUNCTION_PAGE, code, rest @ ..] => (*code, rest),
+        _ => return ErrorReply::Unknown(report),
+    };
+    #[cfg(feature = "wireless")]
+    let rest = match report[0] {
+        crate::OPENINPUT_LONG_REPORT_ID => rest.get(1..).unwrap_or_default(),
+        _ => rest,
+    };
+    let (page, id, data) = match rest {
+        [page, id, data @ ..] => (*page, *id, data),
+        _ => return ErrorReply::Unknown(report),
+    };
+    match code {
+        0x01 => match data.first() {
+            Some(&index) => ErrorReply::InvalidValue { page, id, index },
+            None => ErrorReply::Unknown(report),
+        },
+        0x02 => ErrorReply::UnsupportedFunction { page, id },
+        0x03 => ErrorReply::AuthenticationFailed { page, id },
+        0x04 => ErrorReply::UnsupportedPage { page, id },
+        0x05 => ErrorReply::Busy { page, id },
+        0x06 => ErrorReply::PermissionDenied { page, id },
+        0xFE => {
+            if let [DEVICE_ERROR_MARKER, code, detail @ ..] = data {
+                return ErrorReply::Device {
+                    page,
+                    id,
+                    code: *code,
+                    detail,
+                };
+            }
+            let len = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+            match core::str::from_utf8(&data[..len]) {
+                Ok(message) => ErrorReply::Custom { page, id, message },
+                Err(_) => ErrorReply::Unknown(report),
+            }
+        }
+        _ => ErrorReply::Unknown(report),
+    }
+}
+
+/// info strings from the meta feature report, see [`meta_feature`](crate::meta_feature)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetaFeatureReply<'a> {
+    pub vendor: &'a str,
+    pub version: &'a str,
+    pub name: &'a str,
+    pub serial: &'a [u8],
+}
+
+/// decode a meta feature report (including the report id), `None` if it's another report or a field was cut
+/// off, read those with the info functions instead
+pub fn decode_meta_feature(report: &[u8]) -> Option<MetaFeatureReply> {
+    let mut rest = match report {
+        [META_FEATURE_REPORT_ID, rest @ ..] => rest,
+        _ => return None,
+    };
+    let mut fields: [&[u8]; 4] = [&[]; 4];
+    for field in &mut fields {
+        let (&len, tail) = rest.split_first()?;
+        if tail.len() < len as usize {
+            return None;
+        }
+        let (value, tail) = tail.split_at(len as usize);
+        *field = value;
+        rest = tail;
+    }
+    let [vendor, version, name, serial] = fields;
+    Some(MetaFeatureReply {
+        vendor: core::str::from_utf8(vendor).ok()?,
+        version: core::str::from_utf8(version).ok()?,
+        name: core::str::from_utf8(name).ok()?,
+        serial,
+    })
+}
+
+impl<'a> fmt::Display for ErrorReply<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorReply::InvalidValue { page, id, index } => write!(
+                f,
+                "invalid value in parameter {} of function {:#04x}:{:#04x}",
+                index, page, id
+            ),
+            ErrorReply::UnsupportedFunction { page, id } => {
+                write!(f, "unsupported function {:#04x}:{:#04x}", page, id)
+            }
+            ErrorReply::AuthenticationFailed { page, id } => {
+                write!(
+                    f,
+                    "function {:#04x}:{:#04x} rejected an unauthenticated request",
+                    page, id
+                )
+            }
+            ErrorReply::UnsupportedPage { page, id } => {
+                write!(
+                    f,
+                    "unsupported function page {:#04x} (function {:#04x})",
+                    page, id
+                )
+            }
+            ErrorReply::Busy { page, id } => {
+                write!(f, "device busy, retry function {:#04x}:{:#04x}", page, id)
+            }
+            ErrorReply::PermissionDenied { page, id } => {
+                write!(
+                    f,
+                    "function {:#04x}:{:#04x} needs an authenticated session",
+                    page, id
+                )
+            }
+            ErrorReply::Custom { page, id, message } => {
+                write!(f, "function {:#04x}:{:#04x} failed: {}", page, id, message)
+            }
+            ErrorReply::Device { page, id, code, .. } => {
+                write!(
+                    f,
+                    "function {:#04x}:{:#04x} failed with device error {:#04x}",
+                    page, id, code
+                )
+            }
+            ErrorReply::Unknown(report) => write!(f, "unknown error report {:02x?}", report),
+        }
+    }
+}
+
+/// reassembles a firmware_info string that is longer than a single long report
+///
+/// request firmware_info with [`FirmwareInfoReader::next_offset`] as the second parameter and
+/// [`FirmwareInfoReader::push`] the reply payload until [`FirmwareInfoReader::is_complete`]
+pub struct FirmwareInfoReader<const N: usize> {
+    buf: Vec<u8, N>,
+    complete: bool,
+}
+
+impl<const N: usize> FirmwareInfoReader<N> {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            complete: false,
+        }
+    }
+
+    pub fn next_offset(&self) -> u8 {
+        self.buf.len() as u8
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// push the payload of a firmware_info reply (everything after page and function id, and after the address
+    /// with the `wireless` feature), errors if the string doesn't fit in `N`
+    pub fn push(&mut self, payload: &[u8]) -> Result<(), ()> {
+        let len = payload
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(payload.len());
+        self.buf.extend_from_slice(&payload[..len])?;
+        self.complete = len < CHUNK_LEN;
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl<const N: usize> Default for FirmwareInfoReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Error;
+
+    #[test]
+    fn invalid_value() {
+        let report = Error::InvalidValue(1).serialize_error(0x20, 0x00, 0x03);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::InvalidValue {
+                page: 0x00,
+                id: 0x03,
+                index: 1
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid value in parameter 1 of function 0x00:0x03"
+        );
+    }
+
+    #[test]
+    fn unsupported_function() {
+        let report = Error::UnsupportedFunction.serialize_error(0x20, 0x42, 0x07);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::UnsupportedFunction {
+                page: 0x42,
+                id: 0x07
+            }
+        );
+        assert_eq!(err.to_string(), "unsupported function 0x42:0x07");
+
+        let report = Error::UnsupportedPage.serialize_error(0x20, 0x42, 0x07);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::UnsupportedPage {
+                page: 0x42,
+                id: 0x07
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "unsupported function page 0x42 (function 0x07)"
+        );
+    }
+
+    #[test]
+    fn busy() {
+        let report = Error::Busy.serialize_error(0x20, 0x42, 0x07);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::Busy {
+                page: 0x42,
+                id: 0x07
+            }
+        );
+        assert_eq!(err.to_string(), "device busy, retry function 0x42:0x07");
+    }
+
+    #[test]
+    fn permission_denied() {
+        let report = Error::PermissionDenied.serialize_error(0x20, 0x10, 0x03);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::PermissionDenied {
+                page: 0x10,
+                id: 0x03
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "function 0x10:0x03 needs an authenticated session"
+        );
+    }
+
+    #[test]
+    fn custom() {
+        let mut report = [0; 32];
+        report[..5].copy_from_slice(&[0x21, 0xFF, 0xFE, 0x10, 0x01]);
+        report[5..15].copy_from_slice(b"flash busy");
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::Custom {
+                page: 0x10,
+                id: 0x01,
+                message: "flash busy"
+            }
+        );
+        assert_eq!(err.to_string(), "function 0x10:0x01 failed: flash busy");
+    }
+
+    #[test]
+    fn device() {
+        let report = Error::device(0x12, &[0xAB]).serialize_error(0x20, 0x10, 0x01);
+        let err = decode_error(&report);
+        assert_eq!(
+            err,
+            ErrorReply::Device {
+                page: 0x10,
+                id: 0x01,
+                code: 0x12,
+                detail: &[0xAB]
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "function 0x10:0x01 failed with device error 0x12"
+        );
+
+        // an empty custom message isn't mistaken for a device error
+        let report = Error::custom("").unwrap().serialize_error(0x20, 0x10, 0x01);
+        assert_eq!(
+            decode_error(&report),
+            ErrorReply::Custom {
+                page: 0x10,
+                id: 0x01,
+                message: ""
+            }
+        );
+    }
+
+    #[test]
+    fn not_an_error() {
+        let report = [0x20, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00];
+        assert_eq!(decode_error(&report), ErrorReply::Unknown(&report));
+    }
+}
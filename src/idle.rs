@@ -0,0 +1,337 @@
+//! SET_IDLE / GET_IDLE
+//!
+//! HID hosts set an idle rate to limit how often a device repeats an unchanged input report. Once the host sent
+//! SET_IDLE, [`OpenInputHIDClass::try_push_report`] drops a report identical to the last one sent until the idle
+//! rate elapsed, changed reports go out right away. An idle rate of 0 means unchanged reports are never repeated.
+//! Before the first SET_IDLE every report is sent.
+//!
+//! The class only sees the requests if it is polled itself rather than its inner HID class, and firmware has to
+//! drive the timer with [`OpenInputHIDClass::tick`]:
+//!
+//! ```ignore
+//! usb_dev.poll(&mut [&mut class]);
+//! class.tick(1);
+//! ```
+//!
+//! Only reports that implement [`OpenInputHidReport::idle_report`] are filtered (the keyboard report), OpenInput
+//! replies are always sent. Idle rates for a single report id are treated like the global one.
+//!
+//! Polling the class also answers GET_REPORT for input reports [`OpenInputHidReport::input_report`] returns,
+//! e.g. the OpenInput reports of the keyboard which hosts may read while enumerating, GET_REPORT for feature
+//! reports [`OpenInputHidReport::feature_report`] returns, e.g. the keyboard's
+//! [`meta_feature`](crate::meta_feature) report, and SET_PROTOCOL / GET_PROTOCOL through
+//! [`OpenInputHIDClass::set_protocol`]. A bus reset switches back to the report protocol.
+
+use heapless::Vec;
+use usb_device::class_prelude::*;
+use usb_device::control::{Recipient, RequestType};
+
+use crate::meta_feature::META_FEATURE_LEN;
+use crate::{BootProtocolMode, OIError, OpenInputHIDClass, OpenInputHidReport, REPORT_BUFFER_SIZE};
+
+/// HID class requests
+const GET_REPORT: u8 = 0x01;
+const GET_IDLE: u8 = 0x02;
+const GET_PROTOCOL: u8 = 0x03;
+const SET_IDLE: u8 = 0x0A;
+const SET_PROTOCOL: u8 = 0x0B;
+
+/// high byte of GET_REPORT's value
+const INPUT_REPORT_TYPE: u8 = 0x01;
+const FEATURE_REPORT_TYPE: u8 = 0x03;
+
+/// GET_REPORT replies, the meta feature report is longer than any input report
+const GET_REPORT_LEN: usize = if META_FEATURE_LEN > REPORT_BUFFER_SIZE {
+    META_FEATURE_LEN
+} else {
+    REPORT_BUFFER_SIZE
+};
+
+/// SET_IDLE durations are in 4ms steps
+const IDLE_STEP_MS: u32 = 4;
+
+#[derive(Default)]
+pub(crate) struct IdleFilter {
+    /// HID interface the requests are for
+    interface: u8,
+    /// duration from the last SET_IDLE, `None` until the host sends one
+    duration: Option<u8>,
+    since_last: u32,
+    last: Vec<u8, REPORT_BUFFER_SIZE>,
+}
+
+impl IdleFilter {
+    /// false if `report` repeats the last one within the idle window
+    fn should_send(&self, report: &[u8]) -> bool {
+        match self.duration {
+            Some(duration) if self.last == report => {
+                duration != 0 && self.since_last >= u32::from(duration) * IDLE_STEP_MS
+            }
+            _ => true,
+        }
+    }
+
+    fn sent(&mut self, report: &[u8]) {
+        self.since_last = 0;
+        self.last.clear();
+        // can't fail, reports are serialized into a buffer of the same size
+        self.last.extend_from_slice(report).ok();
+    }
+
+    fn is_for(&self, req: &usb_device::control::Request) -> bool {
+        req.request_type == RequestType::Class
+            && req.recipient == Recipient::Interface
+            && req.index == u16::from(self.interface)
+    }
+}
+
+impl<'ep, B: UsbBus, R: OpenInputHidReport> OpenInputHIDClass<'ep, B, R> {
+    /// number of the HID interface, 0 (the default) unless another class was allocated before it
+    pub fn set_interface(&mut self, interface: u8) {
+        self.idle.interface = interface;
+    }
+
+    /// idle rate set by the host in 4ms steps, `None` before the first SET_IDLE
+    pub fn idle_rate(&self) -> Option<u8> {
+        self.idle.duration
+    }
+
+    /// advance the idle timer by `elapsed_ms`
+    pub fn tick(&mut self, elapsed_ms: u32) {
+        self.idle.since_last = self.idle.since_last.saturating_add(elapsed_ms);
+    }
+
+    /// push unless SET_IDLE suppresses `report`, see the module docs
+    pub(crate) fn push_filtered<'r>(&mut self, report: R::PushReport<'r>) -> Result<(), OIError> {
+        let mut buf = [0; REPORT_BUFFER_SIZE];
+        let len = match self.report.idle_report(&report, &mut buf) {
+            Some(len) => len,
+            None => return self.report.push_report(&mut self.inner, report),
+        };
+        if !self.idle.should_send(&buf[..len]) {
+            return Ok(());
+        }
+        self.report.push_report(&mut self.inner, report)?;
+        self.idle.sent(&buf[..len]);
+        Ok(())
+    }
+}
+
+/// forwards to the inner HID class, answering SET_IDLE, GET_IDLE, SET_PROTOCOL and GET_PROTOCOL itself as well
+/// as GET_REPORT for the input and feature reports [`OpenInputHidReport::input_report`] and
+/// [`OpenInputHidReport::feature_report`] know
+impl<'ep, B: UsbBus, R: OpenInputHidReport> UsbClass<B> for OpenInputHIDClass<'ep, B, R> {
+    fn get_configuration_descriptors(
+        &self,
+        writer: &mut DescriptorWriter,
+    ) -> usb_device::Result<()> {
+        self.inner.get_configuration_descriptors(writer)
+    }
+
+    fn get_bos_descriptors(&self, writer: &mut BosWriter) -> usb_device::Result<()> {
+        self.inner.get_bos_descriptors(writer)
+    }
+
+    fn get_string(&self, index: StringIndex, lang_id: u16) -> Option<&str> {
+        self.inner.get_string(index, lang_id)
+    }
+
+    fn reset(&mut self) {
+        self.idle.duration = None;
+        self.set_protocol(BootProtocolMode::Report);
+        self.inner.reset()
+    }
+
+    fn poll(&mut self) {
+        self.inner.poll()
+    }
+
+    fn control_out(&mut self, xfer: ControlOut<B>) {
+        let req = *xfer.request();
+        if self.idle.is_for(&req) && req.request == SET_IDLE {
+            self.idle.duration = Some((req.value >> 8) as u8);
+            xfer.accept().ok();
+            return;
+        }
+        if self.idle.is_for(&req) && req.request == SET_PROTOCOL {
+            let mode = match req.value {
+                0 => BootProtocolMode::Boot,
+                _ => BootProtocolMode::Report,
+            };
+            self.set_protocol(mode);
+            xfer.accept().ok();
+            return;
+        }
+        self.inner.control_out(xfer)
+    }
+
+    fn control_in(&mut self, xfer: ControlIn<B>) {
+        let req = *xfer.request();
+        if self.idle.is_for(&req) && req.request == GET_IDLE {
+            xfer.accept_with(&[self.idle.duration.unwrap_or(0)]).ok();
+            return;
+        }
+        if self.idle.is_for(&req) && req.request == GET_PROTOCOL {
+            xfer.accept_with(&[self.get_protocol() as u8]).ok();
+            return;
+        }
+        let [report_type, report_id] = req.value.to_be_bytes();
+        if self.idle.is_for(&req) && req.request == GET_REPORT {
+            let mut buf = [0; GET_REPORT_LEN];
+            let len = match report_type {
+                INPUT_REPORT_TYPE => self.report.input_report(report_id, &mut buf),
+                FEATURE_REPORT_TYPE => {
+                    self.report
+                        .feature_report(report_id, self.device_info(), &mut buf)
+                }
+                _ => None,
+            };
+            if let Some(len) = len {
+                // longer than the host asked for is cut by the control pipe
+                xfer.accept_with(&buf[..len]).ok();
+                return;
+            }
+        }
+        self.inner.control_in(xfer)
+    }
+
+    fn endpoint_setup(&mut self, addr: EndpointAddress) {
+        self.inner.endpoint_setup(addr)
+    }
+
+    fn endpoint_out(&mut self, addr: EndpointAddress) {
+        self.inner.endpoint_out(addr)
+    }
+
+    fn endpoint_in_complete(&mut self, addr: EndpointAddress) {
+        self.inner.endpoint_in_complete(addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::prelude::*;
+    use usbd_hid::descriptor::SerializedDescriptor;
+    use usbd_hid::hid_class::HIDClass;
+
+    use crate::keyboard::OiKeyboardInputReport;
+    use crate::mock::{MockHost, MockUsbBus};
+    use crate::{BootProtocolMode, OiKeyboardReport, OiReport, OpenInputKeyboardHID};
+
+    #[test]
+    fn duplicates_are_coalesced() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // every report goes out before the host sets an idle rate
+        class.send_keys(0, &[0x04]).unwrap();
+        class.send_keys(0, &[0x04]).unwrap();
+        assert_eq!(std::iter::from_fn(|| host.recv()).count(), 2);
+
+        // 500ms
+        host.set_idle(125, 0);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert_eq!(class.idle_rate(), Some(125));
+
+        class.send_keys(0, &[0x04]).unwrap();
+        assert!(host.recv().is_some());
+        class.tick(100);
+        class.send_keys(0, &[0x04]).unwrap();
+        assert_eq!(host.recv(), None);
+
+        // a change is sent right away
+        class.send_keys(0, &[0x05]).unwrap();
+        assert_eq!(host.recv().unwrap(), [0x02, 0, 0, 0x05, 0, 0, 0, 0, 0]);
+
+        // and unchanged reports are repeated once the idle rate elapsed
+        class.tick(499);
+        class.send_keys(0, &[0x05]).unwrap();
+        assert_eq!(host.recv(), None);
+        class.tick(1);
+        class.send_keys(0, &[0x05]).unwrap();
+        assert!(host.recv().is_some());
+
+        host.get_idle(0);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert_eq!(host.take_control_in(), [125]);
+    }
+
+    fn get_input_report(
+        host: &MockHost,
+        dev: &mut UsbDevice<'_, MockUsbBus>,
+        class: &mut OpenInputKeyboardHID<'_, MockUsbBus>,
+        report_id: u8,
+        len: u16,
+    ) -> std::vec::Vec<u8> {
+        host.get_report(0x01, report_id, len);
+        while host.control_pending() {
+            dev.poll(&mut [&mut *class]);
+        }
+        host.take_control_in()
+    }
+
+    #[test]
+    fn get_openinput_input_report() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // zeros before anything was sent
+        assert_eq!(
+            get_input_report(&host, &mut dev, &mut class, 0x20, 8),
+            [0x20, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            get_input_report(&host, &mut dev, &mut class, 0x21, 32).len(),
+            32
+        );
+
+        let frame = [0x20, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00];
+        let report = OiKeyboardInputReport::OpenInput(OiReport::read(&frame).unwrap());
+        class.try_push_report(report).unwrap();
+        assert_eq!(host.recv().unwrap(), frame);
+        assert_eq!(
+            get_input_report(&host, &mut dev, &mut class, 0x20, 8),
+            frame
+        );
+    }
+
+    #[test]
+    fn set_protocol_request() {
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+        assert_eq!(class.get_protocol(), BootProtocolMode::Report);
+
+        host.set_protocol(0);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert_eq!(class.get_protocol(), BootProtocolMode::Boot);
+        host.get_protocol();
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert_eq!(host.take_control_in(), [0]);
+
+        // boot keyboard reports have no report id
+        class.send_keys(0, &[0x04]).unwrap();
+        assert_eq!(host.recv().unwrap(), [0, 0, 0x04, 0, 0, 0, 0, 0]);
+
+        host.set_protocol(1);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert_eq!(class.get_protocol(), BootProtocolMode::Report);
+        class.send_keys(0, &[0x04]).unwrap();
+        assert_eq!(host.recv().unwrap(), [0x02, 0, 0, 0x04, 0, 0, 0, 0, 0]);
+    }
+}
@@ -1,11 +1,12 @@
 use heapless::Vec;
 use usb_device::class_prelude::UsbBus;
 use usb_device::UsbError;
-use usbd_hid::hid_class::{ReportInfo, ReportType};
 use usbd_hid::Result as UsbResult;
 use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
 
-use crate::{OIError, OiReport};
+use crate::dispatch::DispatchMeta;
+use crate::meta_feature::{meta_report, META_FEATURE_LEN, META_FEATURE_REPORT_ID};
+use crate::{read_openinput, OIError, OiReport, OpenInputHIDClass, LONG_LEN, SHORT_LEN};
 
 use super::OpenInputHidReport;
 
@@ -43,9 +44,15 @@ use super::OpenInputHidReport;
                 #[item_settings data,array,absolute] out_long_buf=output;
             };
         }
+    },
+    (collection = APPLICATION, usage_page = VENDOR_DEFINED_START, usage = 0x01) = {
+        (report_id = 0x22,) = {
+            (usage = 0x00,) = {
+                #[item_settings data,array,absolute] meta_feature=feature;
+            };
+        }
     }
 )]
-#[derive(Default)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct OiKeyboardReport {
     pub modifier: u8,
@@ -53,11 +60,50 @@ pub struct OiKeyboardReport {
     pub leds: u8,
     pub keycodes: [u8; 6],
     // openinput
-    input_short_buf: [u8; 8],
-    out_short_buf: [u8; 8],
+    input_short_buf: [u8; SHORT_LEN],
+    out_short_buf: [u8; SHORT_LEN],
+
+    input_long_buf: [u8; LONG_LEN],
+    out_long_buf: [u8; LONG_LEN],
+
+    /// declares the [`meta_feature`](crate::meta_feature) report, `META_FEATURE_LEN` without the report id
+    meta_feature: [u8; 127],
 
-    input_long_buf: [u8; 32],
-    out_long_buf: [u8; 32],
+    /// host selected the boot protocol, keyboard reports are sent without a report id
+    boot_mode: bool,
+}
+
+// arrays over 32 elements don't implement Default
+impl Default for OiKeyboardReport {
+    fn default() -> Self {
+        Self {
+            modifier: 0,
+            reserved: 0,
+            leds: 0,
+            keycodes: [0; 6],
+            input_short_buf: [0; SHORT_LEN],
+            out_short_buf: [0; SHORT_LEN],
+            input_long_buf: [0; LONG_LEN],
+            out_long_buf: [0; LONG_LEN],
+            meta_feature: [0; 127],
+            boot_mode: false,
+        }
+    }
+}
+
+impl OiKeyboardReport {
+    /// serialize an input report into `buf` as it should appear on the wire for the current protocol
+    pub fn serialize_input(
+        &self,
+        report: &OiKeyboardInputReport,
+        buf: &mut [u8],
+    ) -> Result<usize, OIError> {
+        match report {
+            OiKeyboardInputReport::Keyboard(kb) if self.boot_mode => ssmarshal::serialize(buf, kb),
+            report => ssmarshal::serialize(buf, report),
+        }
+        .map_err(|_| OIError::SerializationError)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -79,11 +125,112 @@ impl TryFrom<u8> for KeyboardReportId {
     }
 }
 
+bitflags::bitflags! {
+    /// bits of the LED output report
+    #[derive(Default)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct LedFlags: u8 {
+        const NUM_LOCK = 1 << 0;
+        const CAPS_LOCK = 1 << 1;
+        const SCROLL_LOCK = 1 << 2;
+        const COMPOSE = 1 << 3;
+        const KANA = 1 << 4;
+    }
+}
+
+impl LedFlags {
+    /// bits the descriptor doesn't define are dropped
+    pub const fn from_raw(bits: u8) -> Self {
+        Self::from_bits_truncate(bits)
+    }
+
+    pub const fn into_raw(self) -> u8 {
+        self.bits()
+    }
+}
+
+/// names joined with `|` from the highest bit down, e.g. `CapsLock|NumLock`, nothing if no LED is on
+impl core::fmt::Display for LedFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        const NAMES: [(LedFlags, &str); 5] = [
+            (LedFlags::KANA, "Kana"),
+            (LedFlags::COMPOSE, "Compose"),
+            (LedFlags::SCROLL_LOCK, "ScrollLock"),
+            (LedFlags::CAPS_LOCK, "CapsLock"),
+            (LedFlags::NUM_LOCK, "NumLock"),
+        ];
+        let mut first = true;
+        for (flag, name) in NAMES {
+            if self.contains(flag) {
+                if !first {
+                    f.write_str("|")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+bitflags::bitflags! {
+    /// modifier byte of the keyboard input report
+    #[derive(Default)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct ModifierKeys: u8 {
+        const LEFT_CTRL = 0x01;
+        const LEFT_SHIFT = 0x02;
+        const LEFT_ALT = 0x04;
+        const LEFT_GUI = 0x08;
+        const RIGHT_CTRL = 0x10;
+        const RIGHT_SHIFT = 0x20;
+        const RIGHT_ALT = 0x40;
+        const RIGHT_GUI = 0x80;
+    }
+}
+
+impl ModifierKeys {
+    /// every bit is a modifier so nothing is lost
+    pub const fn from_u8(bits: u8) -> Self {
+        Self::from_bits_truncate(bits)
+    }
+
+    pub const fn into_u8(self) -> u8 {
+        self.bits()
+    }
+
+    pub const fn is_any_shift_held(&self) -> bool {
+        self.intersects(Self::LEFT_SHIFT.union(Self::RIGHT_SHIFT))
+    }
+}
+
+impl From<u8> for ModifierKeys {
+    fn from(bits: u8) -> Self {
+        Self::from_u8(bits)
+    }
+}
+
+impl From<ModifierKeys> for u8 {
+    fn from(modifiers: ModifierKeys) -> Self {
+        modifiers.into_u8()
+    }
+}
+
+/// on the wire it's the plain byte
+impl Serialize for ModifierKeys {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u8(self.bits())
+    }
+}
+
 // TODO use serialize/deserialize
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OiKeyboardOutputReport<'a> {
     /// Keyboard leds bitfeild
-    Keyboard(u8),
+    Keyboard(LedFlags),
     /// Openinput short/long report
     OpenInput(OiReport<'a>),
 }
@@ -91,11 +238,37 @@ pub enum OiKeyboardOutputReport<'a> {
 #[derive(Debug, Clone, Default, serde::Serialize, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct KeyboardInputReport {
-    pub modifier: u8,
+    pub modifier: ModifierKeys,
     pub reserved: u8,
     pub keycodes: [u8; 6],
 }
 
+/// usage ids below 4 are "no key" and the error codes, not keys
+const FIRST_KEY: u8 = 0x04;
+
+impl KeyboardInputReport {
+    /// keys held in `prev` but not in this report, in `prev`'s order
+    ///
+    /// a rollover report (every slot [`KEY_ERROR_ROLLOVER`]) holds no keys, so going into rollover releases
+    /// everything and coming out of it presses everything
+    pub fn released_keys(&self, prev: &KeyboardInputReport) -> impl Iterator<Item = u8> {
+        keys_not_in(&prev.keycodes, &self.keycodes)
+    }
+
+    /// keys held in this report but not in `prev`, in this report's order
+    pub fn pressed_keys(&self, prev: &KeyboardInputReport) -> impl Iterator<Item = u8> {
+        keys_not_in(&self.keycodes, &prev.keycodes)
+    }
+}
+
+fn keys_not_in(keys: &[u8; 6], other: &[u8; 6]) -> impl Iterator<Item = u8> {
+    keys.iter()
+        .copied()
+        .filter(|&key| key >= FIRST_KEY && !other.contains(&key))
+        .collect::<Vec<u8, 6>>()
+        .into_iter()
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum OiKeyboardInputReport<'a> {
@@ -113,8 +286,8 @@ impl<'a> Serialize for OiKeyboardInputReport<'a> {
         match self {
             OiKeyboardInputReport::Keyboard(kb) => {
                 // report id + 8 bytes
-                let mut s = serializer.serialize_tuple(8)?;
-                // s.serialize_element(&(KeyboardReportId::Keyboard as u8))?;
+                let mut s = serializer.serialize_tuple(9)?;
+                s.serialize_element(&(KeyboardReportId::Keyboard as u8))?;
                 s.serialize_element(&kb.modifier)?;
                 s.serialize_element(&kb.reserved)?;
                 s.serialize_element(&kb.keycodes)?;
@@ -130,62 +303,41 @@ impl OpenInputHidReport for OiKeyboardReport {
     type PushReport<'a> = OiKeyboardInputReport<'a>;
     type ReportId = KeyboardReportId;
 
-    fn pull_ep_out<'a, 'ep, B: UsbBus>(
+    fn read_report<'a>(
         &'a mut self,
-        hid: &mut HIDClass<'ep, B>,
+        report_id: u8,
+        buf: &[u8],
     ) -> Result<Self::PullReport<'a>, OIError> {
-        let mut temp_buf = [0; super::REPORT_BUFFER_SIZE];
-        // TODO should probably read from interrupt out ep as well (as per spec)
-        let report = hid.pull_raw_report(&mut temp_buf)?;
-        let ReportInfo {
-            len,
-            report_id,
-            report_type,
-        } = report;
-
-        // TODO what does pull_raw_report actually return, will return either output or feature or does it only return one?
-        match report_type {
-            ReportType::Output | ReportType::Feature => (),
-            // pulling report should _only_ give output or feature reports
-            ReportType::Input | ReportType::Reserved => {
-                return Err(usb_device::UsbError::InvalidState.into())
-            }
+        // boot protocol LED reports are the bare bitfield, SET_REPORT carries report id 0 and on the interrupt
+        // endpoint the "id" pull_ep_out sees is the bitfield itself
+        if buf.len() == 1 && (self.boot_mode || report_id == 0) {
+            return Ok(OiKeyboardOutputReport::Keyboard(LedFlags::from_raw(buf[0])));
         }
-
-        let buf = &temp_buf[..len];
-
         match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
-            KeyboardReportId::Keyboard => {
-                if buf.len() == 1 {
-                    Ok(OiKeyboardOutputReport::Keyboard(buf[0]))
-                } else {
-                    Err(OIError::FuckyBuffer)
-                }
-            }
-            KeyboardReportId::OpenInputShort => {
-                if buf.len() == 8 {
-                    // TODO: do i really need to re-zero here?
-                    self.out_short_buf = [0; 8];
-                    self.out_short_buf.copy_from_slice(buf);
-                    Ok(OiKeyboardOutputReport::OpenInput(
-                        OiReport::read(&self.input_short_buf).map_err(|_| UsbError::ParseError)?,
-                    ))
-                } else {
-                    Err(OIError::FuckyBuffer)
-                }
-            }
-            KeyboardReportId::OpenInputLong => {
-                if buf.len() == 32 {
-                    // TODO: do i really need to re-zero here?
-                    self.out_long_buf = [0; 32];
-                    self.out_long_buf.copy_from_slice(buf);
-                    Ok(OiKeyboardOutputReport::OpenInput(
-                        OiReport::read(&self.input_long_buf).map_err(|_| UsbError::ParseError)?,
-                    ))
-                } else {
-                    Err(OIError::FuckyBuffer)
+            // in report protocol hosts lead with the report id on both the control pipe and the interrupt
+            // endpoint, accept the bare bitfield too in case a stack strips it
+            KeyboardReportId::Keyboard => match *buf {
+                [id, leds] if id == KeyboardReportId::Keyboard as u8 => {
+                    Ok(OiKeyboardOutputReport::Keyboard(LedFlags::from_raw(leds)))
                 }
-            }
+                [leds] => Ok(OiKeyboardOutputReport::Keyboard(LedFlags::from_raw(leds))),
+                _ => Err(OIError::FuckyBuffer),
+            },
+            KeyboardReportId::OpenInputShort => Ok(OiKeyboardOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_short_buf)?,
+            )),
+            KeyboardReportId::OpenInputLong => Ok(OiKeyboardOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_long_buf)?,
+            )),
+        }
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        match report {
+            OiKeyboardOutputReport::OpenInput(oi) => Ok(oi),
+            other => Err(other),
         }
     }
 
@@ -195,10 +347,99 @@ impl OpenInputHidReport for OiKeyboardReport {
         report: Self::PushReport<'b>,
     ) -> Result<(), OIError> {
         let mut buf = [0; 64];
-        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        let m = self.serialize_input(&report, &mut buf)?;
         hid.push_raw_input(&buf[..m])?;
+        // OpenInput replies are kept for GET_REPORT
+        if let OiKeyboardInputReport::OpenInput(_) = report {
+            match m {
+                SHORT_LEN => self.input_short_buf.copy_from_slice(&buf[..m]),
+                LONG_LEN => self.input_long_buf.copy_from_slice(&buf[..m]),
+                _ => (),
+            }
+        }
         Ok(())
     }
+
+    /// the last OpenInput reply, zeros before the first one
+    fn input_report(&self, report_id: u8, buf: &mut [u8]) -> Option<usize> {
+        let staged: &[u8] = match KeyboardReportId::try_from(report_id).ok()? {
+            KeyboardReportId::OpenInputShort => &self.input_short_buf,
+            KeyboardReportId::OpenInputLong => &self.input_long_buf,
+            KeyboardReportId::Keyboard => return None,
+        };
+        let buf = buf.get_mut(..staged.len())?;
+        buf.copy_from_slice(staged);
+        buf[0] = report_id;
+        Some(staged.len())
+    }
+
+    /// the [`meta_feature`](crate::meta_feature) report, built from `meta` on every request
+    fn feature_report(
+        &self,
+        report_id: u8,
+        meta: Option<&DispatchMeta>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        if report_id != META_FEATURE_REPORT_ID {
+            return None;
+        }
+        buf.get_mut(..META_FEATURE_LEN)?
+            .copy_from_slice(&meta_report(meta?));
+        Some(META_FEATURE_LEN)
+    }
+
+    fn idle_report<'b>(&self, report: &Self::PushReport<'b>, buf: &mut [u8]) -> Option<usize> {
+        match report {
+            OiKeyboardInputReport::Keyboard(_) => self.serialize_input(report, buf).ok(),
+            OiKeyboardInputReport::OpenInput(_) => None,
+        }
+    }
+
+    fn set_boot_mode(&mut self, boot: bool) {
+        self.boot_mode = boot;
+    }
+
+    fn boot_mode(&self) -> bool {
+        self.boot_mode
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+/// keycode sent in every slot when more keys are held than the report has room for (ErrorRollOver)
+pub const KEY_ERROR_ROLLOVER: u8 = 0x01;
+
+impl<'ep, B: UsbBus> OpenInputHIDClass<'ep, B, OiKeyboardReport> {
+    /// push a keyboard report with `keys` held
+    ///
+    /// the report is 6 key rollover, with more than 6 keys every slot reads [`KEY_ERROR_ROLLOVER`] and hosts
+    /// keep the previous state until fewer keys are held. [`OIError::NotReady`] if the host hasn't read the last
+    /// report yet, see [`try_push_report`](Self::try_push_report)
+    pub fn send_keys(
+        &mut self,
+        modifier: impl Into<ModifierKeys>,
+        keys: &[u8],
+    ) -> Result<(), OIError> {
+        let mut keycodes = [0; 6];
+        if keys.len() > keycodes.len() {
+            keycodes = [KEY_ERROR_ROLLOVER; 6];
+        } else {
+            keycodes[..keys.len()].copy_from_slice(keys);
+        }
+        self.try_push_report(OiKeyboardInputReport::Keyboard(KeyboardInputReport {
+            modifier: modifier.into(),
+            reserved: 0,
+            keycodes,
+        }))
+    }
+
+    /// push a report with no keys or modifiers held, also ends a rollover from [`send_keys`](Self::send_keys)
+    pub fn release_all_keys(&mut self) -> Result<(), OIError> {
+        self.send_keys(ModifierKeys::empty(), &[])
+    }
 }
 
 // pub fn write(&mut self, data: &[u8]) -> Result<usize, Error> {
@@ -216,3 +457,347 @@ impl OpenInputHidReport for OiKeyboardReport {
 //         Err(_) => Err(Error),
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> OiKeyboardInputReport<'static> {
+        OiKeyboardInputReport::Keyboard(KeyboardInputReport {
+            modifier: ModifierKeys::LEFT_SHIFT,
+            reserved: 0,
+            keycodes: [0x04, 0x05, 0, 0, 0, 0],
+        })
+    }
+
+    fn held(keycodes: [u8; 6]) -> KeyboardInputReport {
+        KeyboardInputReport {
+            keycodes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn modifier_keys() {
+        for bits in [0x00, 0x02, 0x22, 0xFF] {
+            assert_eq!(ModifierKeys::from_u8(bits).into_u8(), bits);
+            assert_eq!(u8::from(ModifierKeys::from(bits)), bits);
+        }
+        assert_eq!(
+            ModifierKeys::from_u8(0x11),
+            ModifierKeys::LEFT_CTRL | ModifierKeys::RIGHT_CTRL
+        );
+
+        assert!(ModifierKeys::LEFT_SHIFT.is_any_shift_held());
+        assert!((ModifierKeys::RIGHT_SHIFT | ModifierKeys::LEFT_ALT).is_any_shift_held());
+        assert!(!(ModifierKeys::LEFT_CTRL | ModifierKeys::RIGHT_GUI).is_any_shift_held());
+        assert!(!ModifierKeys::default().is_any_shift_held());
+    }
+
+    #[test]
+    fn led_flags() {
+        assert!(LedFlags::from_raw(0x02).contains(LedFlags::CAPS_LOCK));
+        assert_eq!(LedFlags::from_raw(0x1F).into_raw(), 0x1F);
+        // bits 5-7 are padding in the output report
+        assert_eq!(LedFlags::from_raw(0xE4), LedFlags::SCROLL_LOCK);
+
+        assert_eq!(format!("{}", LedFlags::from_raw(0x03)), "CapsLock|NumLock");
+        assert_eq!(format!("{}", LedFlags::KANA), "Kana");
+        assert_eq!(format!("{}", LedFlags::empty()), "");
+    }
+
+    #[test]
+    fn key_transitions() {
+        let none = held([0; 6]);
+        let a = held([0x04, 0, 0, 0, 0, 0]);
+        let chord = held([0x04, 0x05, 0x06, 0, 0, 0]);
+        // a released, keys shift down
+        let chord_release = held([0x05, 0x06, 0, 0, 0, 0]);
+
+        assert!(a.pressed_keys(&none).eq([0x04]));
+        assert!(a.released_keys(&none).next().is_none());
+        assert!(none.released_keys(&a).eq([0x04]));
+
+        assert!(chord.pressed_keys(&a).eq([0x05, 0x06]));
+        assert!(chord.pressed_keys(&none).eq([0x04, 0x05, 0x06]));
+
+        assert!(chord_release.released_keys(&chord).eq([0x04]));
+        assert!(chord_release.pressed_keys(&chord).next().is_none());
+
+        let rollover = held([KEY_ERROR_ROLLOVER; 6]);
+        assert!(rollover.pressed_keys(&chord).next().is_none());
+        assert!(rollover.released_keys(&chord).eq([0x04, 0x05, 0x06]));
+    }
+
+    #[test]
+    fn boot_protocol_layout() {
+        let mut kb = OiKeyboardReport::default();
+        kb.set_boot_mode(true);
+        let mut buf = [0; 64];
+        let len = kb.serialize_input(&keys(), &mut buf).unwrap();
+        // modifier, reserved, 6 keycodes, no report id
+        assert_eq!(
+            &buf[..len],
+            &[0x02, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    fn leds(report: Result<OiKeyboardOutputReport, OIError>) -> LedFlags {
+        match report {
+            Ok(OiKeyboardOutputReport::Keyboard(leds)) => leds,
+            Ok(_) => panic!("pulled an OpenInput report"),
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn led_report_set_report() {
+        use usb_device::prelude::*;
+        use usbd_hid::descriptor::SerializedDescriptor;
+
+        use crate::{mock::MockUsbBus, OpenInputKeyboardHID};
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // SET_REPORT(Output, id 2) with the id leading the data
+        host.set_report(0x02, 0x02, &[0x02, 0b011]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        assert_eq!(
+            leds(class.pull_host_data()),
+            LedFlags::NUM_LOCK | LedFlags::CAPS_LOCK
+        );
+
+        // same report on the interrupt endpoint
+        host.send(&[0x02, 0b100]);
+        assert_eq!(leds(class.pull_host_data()), LedFlags::SCROLL_LOCK);
+    }
+
+    #[test]
+    fn led_report_boot_protocol() {
+        let mut kb = OiKeyboardReport::default();
+        // SET_REPORT in boot protocol has report id 0
+        assert_eq!(leds(kb.read_report(0x00, &[0b010])), LedFlags::CAPS_LOCK);
+
+        // on the interrupt endpoint pull_ep_out takes the bitfield as the id
+        kb.set_boot_mode(true);
+        assert_eq!(leds(kb.read_report(0b001, &[0b001])), LedFlags::NUM_LOCK);
+        assert!(matches!(
+            kb.read_report(0x02, &[0x02, 0x01, 0x00]),
+            Err(OIError::FuckyBuffer)
+        ));
+    }
+
+    #[test]
+    fn report_protocol_layout() {
+        let kb = OiKeyboardReport::default();
+        let mut buf = [0; 64];
+        let len = kb.serialize_input(&keys(), &mut buf).unwrap();
+        assert_eq!(
+            &buf[..len],
+            &[0x02, 0x02, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn send_keys() {
+        use usbd_hid::descriptor::SerializedDescriptor;
+
+        use crate::{mock::MockUsbBus, OpenInputKeyboardHID};
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+
+        class.send_keys(0x02, &[0x04, 0x05]).unwrap();
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x02, 0x02, 0x00, 0x04, 0x05, 0x00, 0x00, 0x00, 0x00])
+        );
+
+        // 7 keys don't fit
+        class
+            .send_keys(0x00, &[0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a])
+            .unwrap();
+        assert_eq!(
+            host.recv(),
+            Some(vec![0x02, 0x00, 0x00, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01])
+        );
+
+        class.release_all_keys().unwrap();
+        assert_eq!(host.recv(), Some(vec![0x02, 0, 0, 0, 0, 0, 0, 0, 0]));
+
+        host.set_in_busy(true);
+        assert!(matches!(
+            class.send_keys(0x00, &[0x04]),
+            Err(OIError::NotReady)
+        ));
+        assert_eq!(host.recv(), None);
+    }
+
+    #[test]
+    fn openinput_request_params() {
+        use usb_device::prelude::*;
+        use usbd_hid::descriptor::SerializedDescriptor;
+
+        use crate::dispatch::{Dispatch, DISPATCH_LONG_PARAM_LEN, DISPATCH_SHORT_PARAM_LEN};
+        use crate::{mock::MockUsbBus, OpenInputKeyboardHID};
+
+        fn dispatch_pulled(
+            dispatch: &Dispatch,
+            pulled: Result<OiKeyboardOutputReport, OIError>,
+            params: usize,
+        ) -> heapless::Vec<u8, LONG_LEN> {
+            match pulled {
+                Ok(OiKeyboardOutputReport::OpenInput(report)) => {
+                    assert_eq!(report.data.len(), params);
+                    dispatch.dispatch(&report)
+                }
+                Ok(_) => panic!("pulled an LED report"),
+                Err(e) => panic!("{:?}", e),
+            }
+        }
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+        let dispatch = Dispatch::default();
+
+        // frames laid out like libratbag's openinput driver sends them (struct oi_report_t): report id, page,
+        // function, the params, zero padded to the report size. Firmware info with param 0x02, the device name
+        host.send(&[0x20, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00]);
+        let reply = dispatch_pulled(&dispatch, class.pull_host_data(), DISPATCH_SHORT_PARAM_LEN);
+        assert_eq!(reply[..3], [0x21, 0x00, 0x01]);
+        assert!(reply[3..].starts_with(b"Unspecified Name"));
+
+        // supported functions of the info page starting at 0x01, through SET_REPORT
+        host.set_report(
+            0x02,
+            0x20,
+            &[0x20, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00],
+        );
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        let reply = dispatch_pulled(&dispatch, class.pull_host_data(), DISPATCH_SHORT_PARAM_LEN);
+        assert_eq!(
+            reply.as_slice(),
+            &[0x20, 0x00, 0x03, 0x04, 0x03, 0x01, 0x02, 0x03]
+        );
+
+        // long requests have 29 params
+        let mut long = [0; LONG_LEN];
+        long[..4].copy_from_slice(&[0x21, 0x00, 0x01, 0x01]);
+        host.send(&long);
+        let reply = dispatch_pulled(&dispatch, class.pull_host_data(), DISPATCH_LONG_PARAM_LEN);
+        assert_eq!(reply[..3], [0x21, 0x00, 0x01]);
+        assert!(reply[3..].starts_with(b"Unspecified Version"));
+    }
+
+    #[test]
+    fn meta_feature_declared() {
+        use usbd_hid::descriptor::SerializedDescriptor;
+
+        use crate::meta_feature::{META_FEATURE_LEN, META_FEATURE_REPORT_ID};
+
+        let desc = OiKeyboardReport::desc();
+        assert!(desc.windows(2).any(|w| w == [0x85, META_FEATURE_REPORT_ID]));
+        assert_eq!(
+            OiKeyboardReport::default().meta_feature.len() + 1,
+            META_FEATURE_LEN
+        );
+        // nothing to serve without a dispatcher
+        let mut buf = [0; META_FEATURE_LEN];
+        assert_eq!(
+            OiKeyboardReport::default().feature_report(META_FEATURE_REPORT_ID, None, &mut buf),
+            None
+        );
+    }
+
+    /// `OiKeyboardReport::desc()` as generated, one array per top level collection
+    const DESC_SNAPSHOT: [&[u8]; 4] = [
+        &[
+            0x05, 0x01, /* USAGE_PAGE (Generic Desktop) */
+            0x09, 0x06, /* USAGE (Keyboard) */
+            0x85, 0x02, /* REPORT_ID (0x02) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x05, 0x07, /*  USAGE_PAGE (Keyboard) */
+            0x19, 0xe0, /*  USAGE_MINIMUM (0xE0) */
+            0x29, 0xe7, /*  USAGE_MAXIMUM (0xE7) */
+            0x15, 0x00, /*  LOGICAL_MINIMUM (0) */
+            0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+            0x75, 0x01, /*  REPORT_SIZE (1) */
+            0x95, 0x08, /*  REPORT_COUNT (8) */
+            0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+            0x19, 0x00, /*  USAGE_MINIMUM (0x00) */
+            0x29, 0xff, /*  USAGE_MAXIMUM (0xFF) */
+            0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+            0x75, 0x08, /*  REPORT_SIZE (8) */
+            0x95, 0x01, /*  REPORT_COUNT (1) */
+            0x81, 0x03, /*  INPUT (Cnst,Var,Abs) */
+            0x05, 0x08, /*  USAGE_PAGE (LEDs) */
+            0x19, 0x01, /*  USAGE_MINIMUM (0x01) */
+            0x29, 0x05, /*  USAGE_MAXIMUM (0x05) */
+            0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+            0x75, 0x01, /*  REPORT_SIZE (1) */
+            0x95, 0x05, /*  REPORT_COUNT (5) */
+            0x91, 0x02, /*  OUTPUT (Data,Var,Abs) */
+            0x95, 0x03, /*  REPORT_COUNT (3) */
+            0x91, 0x03, /*  OUTPUT (Cnst,Var,Abs) */
+            0x05, 0x07, /*  USAGE_PAGE (Keyboard) */
+            0x19, 0x00, /*  USAGE_MINIMUM (0x00) */
+            0x29, 0xdd, /*  USAGE_MAXIMUM (0xDD) */
+            0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+            0x75, 0x08, /*  REPORT_SIZE (8) */
+            0x95, 0x06, /*  REPORT_COUNT (6) */
+            0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+            0xc0, /* END_COLLECTION */
+        ],
+        &[
+            0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+            0x09, 0x00, /* USAGE (Vendor Usage 0) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x20, /*  REPORT_ID (0x20) */
+            0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+            0x95, 0x08, /*  REPORT_COUNT (8) */
+            0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+            0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+            0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+            0xc0, /* END_COLLECTION */
+        ],
+        &[
+            0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+            0x09, 0x00, /* USAGE (Vendor Usage 0) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x21, /*  REPORT_ID (0x21) */
+            0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+            0x95, 0x20, /*  REPORT_COUNT (32) */
+            0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
+            0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+            0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
+            0xc0, /* END_COLLECTION */
+        ],
+        &[
+            0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
+            0x09, 0x01, /* USAGE (Vendor Usage 1) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x22, /*  REPORT_ID (0x22) */
+            0x09, 0x00, /*  USAGE (Vendor Usage 0) */
+            0x95, 0x7f, /*  REPORT_COUNT (127) */
+            0xb1, 0x00, /*  FEATURE (Data,Arr,Abs) */
+            0xc0, /* END_COLLECTION */
+        ],
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        use usbd_hid::descriptor::SerializedDescriptor;
+
+        crate::descriptor::assert_snapshot("keyboard", OiKeyboardReport::desc(), &DESC_SNAPSHOT);
+    }
+}
@@ -3,19 +3,59 @@
 pub use keyboard::OiKeyboardReport;
 use serde::ser::SerializeTuple;
 use serde::Serialize;
-use usb_device::class_prelude::UsbBus;
+use usb_device::class_prelude::{UsbBus, UsbBusAllocator};
+use usb_device::prelude::{UsbDeviceBuilder, UsbVidPid};
 use usb_device::UsbError;
-use usbd_hid::hid_class::HIDClass;
+use usbd_hid::descriptor::SerializedDescriptor;
+use usbd_hid::hid_class::{HIDClass, ReportInfo, ReportType};
 
-mod dispatch;
+use crate::dispatch::{Dispatch, DispatchMeta};
+
+#[cfg(feature = "absolute-pointer")]
+pub mod absolute_pointer;
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "crc")]
+pub mod checksum;
+pub mod composite;
+pub mod descriptor;
+pub mod device;
+pub mod dispatch;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod host;
+mod idle;
 #[cfg(feature = "dispatch")]
 pub mod keyboard;
+pub mod meta_feature;
+#[cfg(test)]
+mod mock;
+#[cfg(feature = "multitouch")]
+pub mod multitouch;
+#[cfg(feature = "sequence-tracking")]
+pub mod sequence;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "touchpad")]
+pub mod touchpad;
+#[cfg(feature = "trackball")]
+pub mod trackball;
+pub mod version;
+#[cfg(feature = "wireless")]
+pub mod wireless;
+
+pub use device::OpenInputDevice;
+#[cfg(feature = "macros")]
+pub use openinput_rust_macros::openinput_dispatch_table;
+pub use version::ProtocolVersion;
+
+// the generated code names `::openinput_rust`, which has to resolve inside the crate too
+#[cfg(feature = "macros")]
+extern crate self as openinput_rust;
 
 // TODO i'd like to add a new page for dispatch with params split into parts for larger requests/replies (refrence descriptor is 32 bytes but we can be 64 for USB FS)
 // TODO I would like to have OiHidClass have a type param for each descriptor so I can use it internally, but that may mess with ppl who want to realloc the class
 // TODO Are supported functions/pages required to be in a specific order? I've sorted the response for supported fn/pages since underlying structure iterates by order of insertion
-// TODO supported functions/pages should return the device relative set
-// TODO AUTH PLEASE FOR THE LOVE OF GOD
 
 const OPENINPUT_MAX_REPORT_SIZE: usize = 32;
 // TODO would like to not have this, reports shouldn't be larger than 64 bytes, though this is different for usb 2.0 HS (max 1024 bytes)
@@ -25,19 +65,33 @@ const REPORT_BUFFER_SIZE: usize = 64;
 const OPENINPUT_SHORT_REPORT_ID: u8 = 0x20;
 const OPENINPUT_LONG_REPORT_ID: u8 = 0x21;
 
-const SHORT_LEN: usize = 8;
-const LONG_LEN: usize = 32;
+const SHORT_LEN: usize = ReportSize::Short.total_len();
+const LONG_LEN: usize = ReportSize::Long.total_len();
 
-/// OpenInput Progocol version [major, minor, patch]
-pub const PROTOCOL_VERSION: [u8; 3] = [0, 0, 1];
+/// OpenInput Progocol version
+pub const PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::new(0, 0, 1);
 
 pub type OpenInputKeyboardHID<'ep, B> = OpenInputHIDClass<'ep, B, OiKeyboardReport>;
 
+/// HID protocol selected by the host with SET_PROTOCOL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum BootProtocolMode {
+    Boot = 0,
+    Report = 1,
+}
+
 /// Primary interface between openinput and the HID class
 pub struct OpenInputHIDClass<'ep, B: UsbBus, Report: OpenInputHidReport> {
     pub inner: HIDClass<'ep, B>,
     // inner report
     pub report: Report,
+    // the last push found the IN endpoint busy
+    in_busy: bool,
+    idle: idle::IdleFilter,
+    // dispatcher the firmware answers OpenInput requests with, see `with_dispatch`
+    dispatch: Option<&'ep Dispatch>,
 }
 
 impl<'ep, B: UsbBus, R: OpenInputHidReport> OpenInputHIDClass<'ep, B, R> {
@@ -45,13 +99,208 @@ impl<'ep, B: UsbBus, R: OpenInputHidReport> OpenInputHIDClass<'ep, B, R> {
         Self {
             inner: hid,
             report: R::default(),
+            in_busy: false,
+            idle: idle::IdleFilter::default(),
+            dispatch: None,
         }
     }
 
+    /// same as [`new`](Self::new), remembering the dispatcher the firmware answers OpenInput requests with so
+    /// its info strings are reachable through [`device_info`](Self::device_info)
+    ///
+    /// the class doesn't dispatch on its own, pass pulled frames to the dispatcher or use [`OpenInputDevice`]
+    pub fn with_dispatch(hid: HIDClass<'ep, B>, dispatch: &'ep Dispatch) -> Self {
+        Self {
+            dispatch: Some(dispatch),
+            ..Self::new(hid)
+        }
+    }
+
+    /// the dispatcher passed to [`with_dispatch`](Self::with_dispatch)
+    pub fn dispatch(&self) -> Option<&'ep Dispatch> {
+        self.dispatch
+    }
+
+    /// allocate the HID class with `R`'s descriptor, see [`OpenInputHIDClassBuilder`]
+    pub fn builder(alloc: &'ep UsbBusAllocator<B>) -> OpenInputHIDClassBuilder<'ep, B, R>
+    where
+        R: SerializedDescriptor,
+    {
+        OpenInputHIDClassBuilder::new(alloc)
+    }
+
     pub fn pull_host_data<'a>(&'a mut self) -> Result<R::PullReport<'a>, OIError> {
-        let Self { inner, report } = self;
+        let Self { inner, report, .. } = self;
         report.pull_ep_out(inner)
     }
+
+    /// pull a report the host set over the control pipe, feature reports and output reports sent with
+    /// SET_REPORT share its buffer so both come out here, the report variant tells them apart
+    ///
+    /// feature reports carry configuration the host expects to read back later, firmware should persist what
+    /// it gets here. [`OIError::Unsupported`] if `R` has no writable feature reports
+    pub fn pull_feature_report(&mut self) -> Result<R::PullReport<'_>, OIError> {
+        self.report.pull_feature(&mut self.inner)
+    }
+
+    /// `None` if the host hasn't sent anything, other errors are dropped (and logged with defmt), use
+    /// [`pull_host_data`](Self::pull_host_data) to handle them
+    pub fn pull_host_data_nonblocking(&mut self) -> Option<R::PullReport<'_>> {
+        match self.pull_host_data() {
+            Ok(report) => Some(report),
+            Err(OIError::UsbError(UsbError::WouldBlock)) => None,
+            Err(_e) => {
+                #[cfg(feature = "defmt")]
+                defmt::warn!("dropped host data: {}", _e);
+                None
+            }
+        }
+    }
+
+    /// push `report` if the IN endpoint is free, [`OIError::NotReady`] if the host hasn't read the last one yet
+    ///
+    /// repeated input reports may be dropped once the host set an idle rate, see [`tick`](Self::tick)
+    pub fn try_push_report<'r>(&mut self, report: R::PushReport<'r>) -> Result<(), OIError> {
+        let res = self.push_filtered(report);
+        self.in_busy = matches!(res, Err(OIError::UsbError(UsbError::WouldBlock)));
+        match res {
+            Err(OIError::UsbError(UsbError::WouldBlock)) => Err(OIError::NotReady),
+            res => res,
+        }
+    }
+
+    /// spin until the IN endpoint is free and push `report`
+    ///
+    /// the endpoint only frees up once the host polls it, so this must not be called from interrupt context
+    /// (or with the usb interrupt masked) where it would never return
+    pub fn push_report_blocking<'r>(&mut self, report: R::PushReport<'r>) -> Result<(), OIError>
+    where
+        R::PushReport<'r>: Clone,
+    {
+        loop {
+            match self.try_push_report(report.clone()) {
+                Err(OIError::NotReady) => core::hint::spin_loop(),
+                res => return res,
+            }
+        }
+    }
+
+    /// false if the last push found the IN endpoint busy
+    ///
+    /// usb-device can't query an endpoint without writing to it, so this is a hint based on the last push
+    /// rather than the current endpoint state
+    pub fn is_ready_to_push(&self) -> bool {
+        !self.in_busy
+    }
+
+    /// resynchronize after [`pull_host_data`](Self::pull_host_data) failed to parse a report
+    ///
+    /// clears the report's frame buffers, drops whatever the host queued behind the bad report on the control
+    /// pipe and the interrupt OUT endpoint and forgets the busy state of the IN endpoint. the host has to
+    /// resend anything that was dropped, so only call it after an error:
+    ///
+    /// ```ignore
+    /// loop {
+    ///     match class.pull_host_data() {
+    ///         Ok(report) => handle(report),
+    ///         Err(OIError::UsbError(UsbError::WouldBlock)) => break,
+    ///         Err(_) => {
+    ///             class.reset_buffers();
+    ///             break;
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn reset_buffers(&mut self) {
+        self.report.reset_buffers();
+        self.in_busy = false;
+
+        let mut buf = [0; REPORT_BUFFER_SIZE];
+        while self.inner.pull_raw_report(&mut buf).is_ok() {}
+        while self.inner.pull_raw_output(&mut buf).is_ok() {}
+    }
+
+    /// firmware vendor, version etc. the host reads from the info page
+    ///
+    /// from the dispatcher passed to [`with_dispatch`](Self::with_dispatch), otherwise from `R` if it carries
+    /// its own (see [`OpenInputHidReport::device_info`]), `None` if neither has one
+    pub fn device_info(&self) -> Option<&DispatchMeta> {
+        match self.dispatch {
+            Some(dispatch) => Some(&dispatch.meta),
+            None => self.report.device_info(),
+        }
+    }
+
+    /// switch the report layout, polling the class calls this when the host issues SET_PROTOCOL
+    pub fn set_protocol(&mut self, mode: BootProtocolMode) {
+        self.report.set_boot_mode(mode == BootProtocolMode::Boot);
+    }
+
+    /// always [`BootProtocolMode::Report`] for reports without a boot layout
+    pub fn get_protocol(&self) -> BootProtocolMode {
+        if self.report.boot_mode() {
+            BootProtocolMode::Boot
+        } else {
+            BootProtocolMode::Report
+        }
+    }
+}
+
+/// the two OpenInput report layouts, both start with report id, function page and function id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReportSize {
+    Short,
+    Long,
+}
+
+impl ReportSize {
+    /// function data after the 3 byte header, 5 or 29
+    pub const fn payload_len(&self) -> usize {
+        self.total_len() - 3
+    }
+
+    /// report id included, 8 or 32
+    pub const fn total_len(&self) -> usize {
+        match self {
+            Self::Short => 8,
+            Self::Long => 32,
+        }
+    }
+
+    pub const fn report_id(&self) -> u8 {
+        match self {
+            Self::Short => OPENINPUT_SHORT_REPORT_ID,
+            Self::Long => OPENINPUT_LONG_REPORT_ID,
+        }
+    }
+
+    /// `None` for anything but 0x20 and 0x21
+    pub const fn from_report_id(id: u8) -> Option<Self> {
+        match id {
+            OPENINPUT_SHORT_REPORT_ID => Some(Self::Short),
+            OPENINPUT_LONG_REPORT_ID => Some(Self::Long),
+            _ => None,
+        }
+    }
+
+    /// `None` unless `len` is 8 or 32
+    pub const fn from_total_len(len: usize) -> Option<Self> {
+        match len {
+            SHORT_LEN => Some(Self::Short),
+            LONG_LEN => Some(Self::Long),
+            _ => None,
+        }
+    }
+}
+
+impl From<ReportSize> for OiReportId {
+    fn from(size: ReportSize) -> Self {
+        match size {
+            ReportSize::Short => Self::Short,
+            ReportSize::Long => Self::Long,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -71,11 +320,7 @@ impl OiReportId {
 impl TryFrom<u8> for OiReportId {
     type Error = ();
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        match value {
-            0x20 => Ok(OiReportId::Short),
-            0x21 => Ok(OiReportId::Long),
-            _ => Err(()),
-        }
+        ReportSize::from_report_id(value).map(Self::from).ok_or(())
     }
 }
 
@@ -86,7 +331,16 @@ pub enum OIError {
     SerializationError,
     InternalError,
     FuckyBuffer,
+    /// the buffer passed in is too short for the result, see [`composite::CompositeReport::write_desc`]
+    BufferTooSmall,
     UsbError(UsbError),
+    /// the IN endpoint still holds the last report, see [`OpenInputHIDClass::try_push_report`]
+    NotReady,
+    /// the report type has no such report, e.g. [`OpenInputHIDClass::pull_feature_report`] on a keyboard
+    Unsupported,
+    /// a long report arrived with a wrong checksum, see [`checksum`]
+    #[cfg(feature = "crc")]
+    CrcMismatch,
 }
 
 impl From<UsbError> for OIError {
@@ -95,6 +349,135 @@ impl From<UsbError> for OIError {
     }
 }
 
+/// builds an [`OpenInputHIDClass`] whose HID class uses `R`'s descriptor, so the two can't get out of sync
+///
+/// [`build_with_device`](Self::build_with_device) also starts the [`UsbDeviceBuilder`] with the manufacturer,
+/// product and serial number string descriptors. These are what the OS shows in its device list, while OpenInput
+/// tools read the firmware info page (0x00, 0x01) instead, so both should say the same. Pass the dispatcher's
+/// [`DispatchMeta`] with [`meta`](Self::meta) and its vendor, device name and serial become the defaults for
+/// the strings that aren't set explicitly:
+///
+/// ```ignore
+/// let dispatch = Dispatch::default_const(DispatchMeta::from_static("Dekanova", "1.2.3", "Test Keyboard"));
+///
+/// let (mut class, device) = OpenInputKeyboardHID::builder(&usb_alloc)
+///     .meta(&dispatch.meta)
+///     .serial_number("DK-0001")
+///     .build_with_device(UsbVidPid(0x1209, 0x0001));
+/// let mut usb_dev = device.max_packet_size_0(64).build();
+/// ```
+pub struct OpenInputHIDClassBuilder<'ep, B: UsbBus, R> {
+    alloc: &'ep UsbBusAllocator<B>,
+    poll_ms: u8,
+    manufacturer: Option<&'ep str>,
+    product: Option<&'ep str>,
+    serial_number: Option<&'ep str>,
+    meta: Option<&'ep DispatchMeta>,
+    desc: &'static [u8],
+    report: core::marker::PhantomData<R>,
+}
+
+impl<'ep, B: UsbBus, R: OpenInputHidReport + SerializedDescriptor>
+    OpenInputHIDClassBuilder<'ep, B, R>
+{
+    /// polls every 1ms by default
+    pub fn new(alloc: &'ep UsbBusAllocator<B>) -> Self {
+        Self {
+            alloc,
+            poll_ms: 1,
+            manufacturer: None,
+            product: None,
+            serial_number: None,
+            meta: None,
+            desc: R::desc(),
+            report: core::marker::PhantomData,
+        }
+    }
+
+    /// interrupt endpoint polling interval
+    pub fn poll_ms(mut self, poll_ms: u8) -> Self {
+        self.poll_ms = poll_ms;
+        self
+    }
+
+    /// manufacturer string descriptor, defaults to the firmware vendor of [`meta`](Self::meta)
+    pub fn manufacturer(mut self, s: &'ep str) -> Self {
+        self.manufacturer = Some(s);
+        self
+    }
+
+    /// product string descriptor, defaults to the device name of [`meta`](Self::meta)
+    pub fn product(mut self, s: &'ep str) -> Self {
+        self.product = Some(s);
+        self
+    }
+
+    /// serial number string descriptor, defaults to the serial of [`meta`](Self::meta) if it is set and valid
+    /// utf-8. a [`SerialFn`](dispatch::SerialFn) can't be used here, read it and pass the result
+    pub fn serial_number(mut self, s: &'ep str) -> Self {
+        self.serial_number = Some(s);
+        self
+    }
+
+    /// info strings to use for string descriptors that aren't set explicitly
+    pub fn meta(mut self, meta: &'ep DispatchMeta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    /// put the OpenInput collections of `R`'s descriptor on another vendor usage page, see
+    /// [`descriptor::write_with_usage`]. the descriptor is copied into `buf`
+    ///
+    /// panics if `buf` is shorter than the descriptor or `R` has no OpenInput collections
+    pub fn openinput_usage(mut self, usage_page: u16, usage: u8, buf: &'static mut [u8]) -> Self {
+        let len = descriptor::write_with_usage(R::desc(), usage_page, usage, buf)
+            .expect("can't move the OpenInput collections");
+        let buf: &'static [u8] = buf;
+        self.desc = &buf[..len];
+        self
+    }
+
+    /// manufacturer, product and serial number after falling back to [`meta`](Self::meta)
+    fn strings(&self) -> [Option<&'ep str>; 3] {
+        let meta = self.meta;
+        [
+            self.manufacturer.or_else(|| meta?.firmware_vendor_str()),
+            self.product.or_else(|| meta?.device_name_str()),
+            self.serial_number.or_else(|| {
+                core::str::from_utf8(meta?.serial())
+                    .ok()
+                    .filter(|s| !s.is_empty())
+            }),
+        ]
+    }
+
+    pub fn build(self) -> OpenInputHIDClass<'ep, B, R> {
+        OpenInputHIDClass::new(HIDClass::new(self.alloc, self.desc, self.poll_ms))
+    }
+
+    /// the class and a device builder with the string descriptors set, see the [type docs](Self)
+    ///
+    /// the class is allocated first since building the device freezes the allocator, any other classes
+    /// have to be allocated before the returned builder's `build`
+    pub fn build_with_device(
+        self,
+        vid_pid: UsbVidPid,
+    ) -> (OpenInputHIDClass<'ep, B, R>, UsbDeviceBuilder<'ep, B>) {
+        let [manufacturer, product, serial_number] = self.strings();
+        let mut device = UsbDeviceBuilder::new(self.alloc, vid_pid);
+        if let Some(s) = manufacturer {
+            device = device.manufacturer(s);
+        }
+        if let Some(s) = product {
+            device = device.product(s);
+        }
+        if let Some(s) = serial_number {
+            device = device.serial_number(s);
+        }
+        (self.build(), device)
+    }
+}
+
 pub trait OpenInputHidReport: Default {
     // TODO maybe just from?
     type ReportId: TryFrom<u8>;
@@ -106,16 +489,144 @@ pub trait OpenInputHidReport: Default {
     fn pull_ep_out<'a, 'ep, B: UsbBus>(
         &'a mut self,
         hid: &mut HIDClass<'ep, B>,
-    ) -> Result<Self::PullReport<'a>, OIError>;
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        let mut temp_buf = [0; REPORT_BUFFER_SIZE];
+        let (report_id, len) = pull_raw(hid, &mut temp_buf)?;
+        self.read_report(report_id, &temp_buf[..len])
+    }
+
+    /// split an OpenInput frame out of a pulled report, anything else is handed back
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>>
+    where
+        Self: 'a,
+    {
+        Err(report)
+    }
+
+    /// pull a feature report the host sent with SET_REPORT, [`OIError::Unsupported`] for reports without
+    /// writable feature reports (the default), implementations that have some call [`pull_feature_report`]
+    fn pull_feature<'a, 'ep, B: UsbBus>(
+        &'a mut self,
+        _hid: &mut HIDClass<'ep, B>,
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        Err(OIError::Unsupported)
+    }
+
+    /// parse an output or feature report the host sent, [`OIError::Unsupported`] (the default) for reports
+    /// the host can't write to
+    fn read_report<'a>(
+        &'a mut self,
+        _report_id: u8,
+        _buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        Err(OIError::Unsupported)
+    }
 
     fn push_report<'r, 'ep, B: UsbBus>(
         &mut self,
         hid: &mut HIDClass<'ep, B>,
         report: Self::PushReport<'r>,
     ) -> Result<(), OIError>;
+
+    /// serialize an input report that SET_IDLE applies to into `buf`, `None` for reports that are always sent
+    /// (the default), OpenInput replies must never be
+    fn idle_report<'r>(&self, _report: &Self::PushReport<'r>, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+
+    /// write the input report `report_id` as last sent into `buf` for a GET_REPORT(Input), `None` (the
+    /// default) leaves the request to the HID class which rejects it
+    fn input_report(&self, _report_id: u8, _buf: &mut [u8]) -> Option<usize> {
+        None
+    }
+
+    /// write the feature report `report_id` into `buf` for a GET_REPORT(Feature), `None` (the default) leaves
+    /// the request to the HID class which rejects it
+    ///
+    /// `meta` is the class's [`device_info`](OpenInputHIDClass::device_info) for reports that serve the info
+    /// strings
+    fn feature_report(
+        &self,
+        _report_id: u8,
+        _meta: Option<&DispatchMeta>,
+        _buf: &mut [u8],
+    ) -> Option<usize> {
+        None
+    }
+
+    /// info strings of the dispatcher the report answers OpenInput requests with, `None` (the default) for
+    /// reports that leave dispatching to the caller (e.g. through [`OpenInputDevice`] or
+    /// [`OpenInputHIDClass::with_dispatch`])
+    fn device_info(&self) -> Option<&DispatchMeta> {
+        None
+    }
+
+    /// switch between boot and report protocol layouts, reports without a boot layout can ignore this
+    fn set_boot_mode(&mut self, _boot: bool) {}
+
+    /// true while the boot layout is selected, reports without one always return false (the default)
+    fn boot_mode(&self) -> bool {
+        false
+    }
+
+    /// clear the buffers OpenInput frames are read into, reports without buffers can ignore this
+    fn reset_buffers(&mut self) {}
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// read the next output or feature report into `buf`, returns its report id and length
+pub(crate) fn pull_raw<B: UsbBus>(
+    hid: &mut HIDClass<'_, B>,
+    buf: &mut [u8; REPORT_BUFFER_SIZE],
+) -> Result<(u8, usize), OIError> {
+    // hosts send output reports either with SET_REPORT on the control pipe (`pull_raw_report`, which also
+    // carries feature reports) or on the interrupt OUT endpoint when the interface has one (`pull_raw_output`,
+    // windows and linux prefer it for output reports). check the control pipe first then fall back to the
+    // endpoint, either way the data starts with the report id
+    let (report_id, len) = match hid.pull_raw_report(buf) {
+        Ok(ReportInfo {
+            len,
+            report_id,
+            report_type,
+        }) => {
+            match report_type {
+                ReportType::Output | ReportType::Feature => (),
+                // pulling report should _only_ give output or feature reports
+                ReportType::Input | ReportType::Reserved => {
+                    return Err(usb_device::UsbError::InvalidState.into())
+                }
+            }
+            (report_id, len)
+        }
+        Err(UsbError::WouldBlock) => {
+            let len = hid.pull_raw_output(buf)?;
+            // interrupt transfers don't carry the id out of band
+            let report_id = *buf[..len].first().ok_or(UsbError::ParseError)?;
+            (report_id, len)
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    #[cfg(feature = "crc")]
+    checksum::check_frame(&buf[..len])?;
+    Ok((report_id, len))
+}
+
+/// [`OpenInputHidReport::pull_feature`] for reports that parse their feature reports in `read_report`
+///
+/// only looks at the control pipe. output reports sent with SET_REPORT share its buffer, they're parsed like
+/// feature reports rather than dropped
+pub(crate) fn pull_feature_report<'a, 'ep, B: UsbBus, R: OpenInputHidReport>(
+    report: &'a mut R,
+    hid: &mut HIDClass<'ep, B>,
+) -> Result<R::PullReport<'a>, OIError> {
+    let mut temp_buf = [0; REPORT_BUFFER_SIZE];
+    let ReportInfo { len, report_id, .. } = hid.pull_raw_report(&mut temp_buf)?;
+    report.read_report(report_id, &temp_buf[..len])
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct OiReport<'a> {
     id: u8,
@@ -124,17 +635,30 @@ pub struct OiReport<'a> {
     data: &'a [u8],
 }
 
+/// all fields in hex, trailing zeros (the padding) are left out of the data
+impl<'a> core::fmt::Debug for OiReport<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let len = self.data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+        f.debug_struct("OiReport")
+            .field("id", &format_args!("{:#04x}", self.id))
+            .field(
+                "function_page",
+                &format_args!("{:#04x}", self.function_page),
+            )
+            .field("function_id", &format_args!("{:#04x}", self.function_id))
+            .field("data", &format_args!("{:02x?}", &self.data[..len]))
+            .finish()
+    }
+}
+
 impl<'a> serde::Serialize for OiReport<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        let size = if self.id == OPENINPUT_LONG_REPORT_ID {
-            LONG_LEN
-        } else if self.id == OPENINPUT_SHORT_REPORT_ID {
-            SHORT_LEN
-        } else {
-            panic!("unexpected report id")
+        let size = match self.size() {
+            Some(size) => size.total_len(),
+            None => return Err(serde::ser::Error::custom("unexpected report id")),
         };
         let mut s = serializer.serialize_tuple(size)?;
         s.serialize_element(&self.id)?;
@@ -144,13 +668,17 @@ impl<'a> serde::Serialize for OiReport<'a> {
         for i in self.data {
             s.serialize_element(i)?;
         }
+        // reports from `new_long_slice` are padded on the wire only
+        for _ in self.data.len() + 3..size {
+            s.serialize_element(&0u8)?;
+        }
         s.end()
     }
 }
 
 impl<'a> OiReport<'a> {
     pub const fn read(bytes: &'a [u8]) -> Result<Self, ()> {
-        if bytes.len() != 8 || bytes.len() != 32 {
+        if ReportSize::from_total_len(bytes.len()).is_none() {
             return Err(());
         }
         let (id, function_page, function_id, data) = if let [id, page, fn_id, data @ ..] = bytes {
@@ -166,28 +694,164 @@ impl<'a> OiReport<'a> {
         })
     }
 
+    /// walk OpenInput frames concatenated in one buffer, the report id decides each frame's length
+    ///
+    /// bytes that don't start a frame are skipped as a single `ParseError`, a truncated trailing frame yields
+    /// `FuckyBuffer` and ends the iteration
+    pub fn iter_reports(buf: &'a [u8]) -> OiReportIter<'a> {
+        OiReportIter { buf }
+    }
+
     pub fn is_short(&self) -> bool {
         self.id == OPENINPUT_SHORT_REPORT_ID
     }
 
-    // TODO use consts for len
-    pub const fn new_short(page: u8, fn_id: u8, data: &'a [u8; 5]) -> Self {
+    /// `None` if the report was read with a report id other than 0x20 and 0x21
+    pub const fn size(&self) -> Option<ReportSize> {
+        ReportSize::from_report_id(self.id)
+    }
+
+    /// 0x20 for short reports, 0x21 for long ones
+    pub const fn id(&self) -> u8 {
+        self.id
+    }
+
+    pub const fn function_page(&self) -> u8 {
+        self.function_page
+    }
+
+    /// as sent, including the sequence number if the host uses one
+    pub const fn function_id(&self) -> u8 {
+        self.function_id
+    }
+
+    /// function data after the id, padding included unless the report came from
+    /// [`new_long_slice`](Self::new_long_slice)
+    pub const fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub const fn new_short(
+        page: u8,
+        fn_id: u8,
+        data: &'a [u8; ReportSize::Short.payload_len()],
+    ) -> Self {
         OiReport {
-            id: OPENINPUT_SHORT_REPORT_ID,
+            id: ReportSize::Short.report_id(),
             function_page: page,
             function_id: fn_id,
             data,
         }
     }
 
-    pub const fn new_long(page: u8, fn_id: u8, data: &'a [u8; 29]) -> Self {
+    pub const fn new_long(
+        page: u8,
+        fn_id: u8,
+        data: &'a [u8; ReportSize::Long.payload_len()],
+    ) -> Self {
         OiReport {
-            id: OPENINPUT_LONG_REPORT_ID,
+            id: ReportSize::Long.report_id(),
             function_page: page,
             function_id: fn_id,
             data,
         }
     }
+
+    /// long report with up to 29 bytes of data, zero padded when serialized instead of by the caller
+    ///
+    /// [`OIError::SerializationError`] if `data` is longer
+    pub const fn new_long_slice(page: u8, fn_id: u8, data: &'a [u8]) -> Result<Self, OIError> {
+        if data.len() > ReportSize::Long.payload_len() {
+            return Err(OIError::SerializationError);
+        }
+        Ok(OiReport {
+            id: ReportSize::Long.report_id(),
+            function_page: page,
+            function_id: fn_id,
+            data,
+        })
+    }
+}
+
+/// see [`OiReport::iter_reports`]
+pub struct OiReportIter<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Iterator for OiReportIter<'a> {
+    type Item = Result<OiReport<'a>, OIError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = match ReportSize::from_report_id(*self.buf.first()?) {
+            Some(size) => size.total_len(),
+            None => {
+                // resync on the next byte that looks like a report id
+                let skip = self
+                    .buf
+                    .iter()
+                    .position(|&b| b == OPENINPUT_SHORT_REPORT_ID || b == OPENINPUT_LONG_REPORT_ID)
+                    .unwrap_or(self.buf.len());
+                self.buf = &self.buf[skip..];
+                return Some(Err(UsbError::ParseError.into()));
+            }
+        };
+        if self.buf.len() < len {
+            self.buf = &[];
+            return Some(Err(OIError::FuckyBuffer));
+        }
+        let (frame, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(OiReport::read(frame).map_err(|_| UsbError::ParseError.into()))
+    }
+}
+
+/// logical range and report size of the OpenInput collections, hand written descriptors set them up front
+/// since globals carry over from whatever collection came before
+pub(crate) const OPENINPUT_GLOBALS: [u8; 7] = [
+    0x15, 0x00, /* LOGICAL_MINIMUM (0) */
+    0x26, 0xff, 0x00, /* LOGICAL_MAXIMUM (255) */
+    0x75, 0x08, /* REPORT_SIZE (8) */
+];
+
+/// OpenInput short and long collections for hand written descriptors
+#[cfg(any(
+    feature = "absolute-pointer",
+    feature = "gamepad",
+    feature = "multitouch",
+    feature = "touchpad",
+    feature = "trackball"
+))]
+pub(crate) const OPENINPUT_DESCRIPTOR: &[u8] =
+    &concat_desc::<47>(&OPENINPUT_GLOBALS, &descriptor::OPENINPUT_DESC);
+
+/// concatenate two descriptors in a const context, `N` must be the sum of both lengths
+pub(crate) const fn concat_desc<const N: usize>(a: &[u8], b: &[u8]) -> [u8; N] {
+    assert!(a.len() + b.len() == N, "descriptor length mismatch");
+    let mut out = [0; N];
+    let mut i = 0;
+    while i < a.len() {
+        out[i] = a[i];
+        i += 1;
+    }
+    let mut j = 0;
+    while j < b.len() {
+        out[i + j] = b[j];
+        j += 1;
+    }
+    out
+}
+
+/// copy an OpenInput output frame into a report's buffer and parse it
+pub(crate) fn read_openinput<'a, const N: usize>(
+    buf: &[u8],
+    dst: &'a mut [u8; N],
+) -> Result<OiReport<'a>, OIError> {
+    if buf.len() != N {
+        return Err(OIError::FuckyBuffer);
+    }
+    dst.copy_from_slice(buf);
+    let dst: &'a [u8] = dst;
+    OiReport::read(dst).map_err(|_| UsbError::ParseError.into())
 }
 
 impl<'a> From<OiReport<'a>> for heapless::Vec<u8, 32> {
@@ -196,14 +860,14 @@ impl<'a> From<OiReport<'a>> for heapless::Vec<u8, 32> {
         v.extend_from_slice(&[src.id, src.function_page, src.function_id])
             .unwrap();
         v.extend_from_slice(src.data).unwrap();
+        let size = src.size().unwrap_or(ReportSize::Long);
+        v.resize(size.total_len(), 0).unwrap();
         v
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use usbd_hid::descriptor::SerializedDescriptor;
-
     use super::*;
 
     #[test]
@@ -211,50 +875,388 @@ mod tests {
     fn conformance() {
         let desc = OiKeyboardReport::desc();
         let desc_hex = hex::encode(desc);
-        let oi = hex::encode(OI_DESC);
+        let oi = hex::encode(descriptor::OPENINPUT_DESC);
 
         println!(
             "got\nexpect\n{}\n{}",
             desc_hex.replace("c0", "c0\n"),
             oi.replace("c0", "c0\n")
         );
-        assert!(desc_hex.contains(&oi), "\n{:x?}\n{:x?}", desc, OI_DESC);
-    }
-
-    // TODO discuss ordering and derived value diff with openinput ppl
-    // modified from https://github.com/openinput-fw/openinput/blob/a8723282bd50aa01a2062d9289c16087c4712c7e/src/protocol/reports.h
-    const OI_DESC: &[u8] = &[
-        /* clang-format off */
-        /* short report */
-        0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
-        0x09, 0x00, /* USAGE (Vendor Usage 0) */
-        0xa1, 0x01, /* COLLECTION (Application) */
-        0x85, 0x20, /*  REPORT_ID (0x20) */
-        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
-        /* derived from keyboard */
-        // 0x15, 0x00,			/*  LOGICAL MINIMUM (0) */
-        // 0x26, 0xff, 0x00,		/*  LOGICAL MAXIMUM (255) */
-        // 0x75, 0x08,			/*  REPORT_SIZE (8) */
-        0x95, 0x08, /*  REPORT_COUNT (8) */
-        0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
-        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
-        0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
-        0xc0, /* END_COLLECTION */
-        /* long report */
-        0x06, 0x00, 0xff, /* USAGE_PAGE (Vendor Page) */
-        0x09, 0x00, /* USAGE (Vendor Usage 0) */
-        0xa1, 0x01, /* COLLECTION (Application) */
-        0x85, 0x21, /*  REPORT_ID (0x21) */
-        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
-        /* derived from above */
-        // 0x15, 0x00,			/*  LOGICAL MINIMUM (0) */
-        // 0x26, 0xff, 0x00,		/*  LOGICAL MAXIMUM (255) */
-        // 0x75, 0x08,			/*  REPORT_SIZE (8) */
-        0x95, 0x20, /*  REPORT_COUNT (32) */
-        0x81, 0x00, /*  INPUT (Data,Arr,Abs) */
-        0x09, 0x00, /*  USAGE (Vendor Usage 0) */
-        0x91, 0x00, /*  OUTPUT (Data,Arr,Abs) */
-        0xc0, /* END_COLLECTION */
-              /* clang-format on */
-    ];
+        assert!(
+            desc_hex.contains(&oi),
+            "\n{:x?}\n{:x?}",
+            desc,
+            descriptor::OPENINPUT_DESC
+        );
+        assert!(descriptor::has_openinput_collections(desc));
+    }
+
+    #[test]
+    fn iter_reports() {
+        let mut long = [0; LONG_LEN];
+        long[..4].copy_from_slice(&[0x21, 0x00, 0x01, 0x02]);
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&FRAME);
+        buf.extend_from_slice(&long);
+
+        let reports: Vec<_> = OiReport::iter_reports(&buf).collect();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(
+            reports[0].as_ref().unwrap(),
+            &OiReport::read(&FRAME).unwrap()
+        );
+        assert_eq!(
+            reports[1].as_ref().unwrap(),
+            &OiReport::read(&long).unwrap()
+        );
+
+        // garbage between frames is skipped, a truncated frame ends the iteration
+        let mut buf = Vec::new();
+        buf.extend_from_slice(&[0xAA, 0xBB]);
+        buf.extend_from_slice(&FRAME);
+        buf.extend_from_slice(&long[..10]);
+        let mut iter = OiReport::iter_reports(&buf);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(OIError::UsbError(UsbError::ParseError)))
+        ));
+        assert_eq!(
+            iter.next().unwrap().unwrap(),
+            OiReport::read(&FRAME).unwrap()
+        );
+        assert!(matches!(iter.next(), Some(Err(OIError::FuckyBuffer))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn accessors() {
+        const SHORT: OiReport = OiReport::new_short(0x10, 0x02, &[1, 2, 3, 4, 5]);
+        const PAGE: u8 = SHORT.function_page();
+        assert_eq!(
+            (SHORT.id(), PAGE, SHORT.function_id()),
+            (OPENINPUT_SHORT_REPORT_ID, 0x10, 0x02)
+        );
+        assert_eq!(SHORT.data(), &[1, 2, 3, 4, 5]);
+
+        let mut data = [0; 29];
+        data[28] = 0xAA;
+        let long = OiReport::new_long(0x11, 0x03, &data);
+        assert_eq!(
+            (long.id(), long.function_page(), long.function_id()),
+            (OPENINPUT_LONG_REPORT_ID, 0x11, 0x03)
+        );
+        assert_eq!(long.data(), &data);
+
+        // same fields after serializing and reading back
+        let bytes: heapless::Vec<u8, 32> = long.clone().into();
+        assert_eq!(OiReport::read(&bytes).unwrap(), long);
+    }
+
+    #[test]
+    fn debug_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let report = OiReport::read(&FRAME).unwrap();
+        assert_eq!(
+            format!("{:?}", report),
+            "OiReport { id: 0x20, function_page: 0x00, function_id: 0x01, data: [02] }"
+        );
+        let empty = OiReport::new_short(0x10, 0x0A, &[0; 5]);
+        assert_eq!(
+            format!("{:?}", empty),
+            "OiReport { id: 0x20, function_page: 0x10, function_id: 0x0a, data: [] }"
+        );
+
+        let hash = |report: &OiReport| {
+            let mut hasher = DefaultHasher::new();
+            report.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(report, OiReport::new_short(0x00, 0x01, &[2, 0, 0, 0, 0]));
+        assert_eq!(
+            hash(&report),
+            hash(&OiReport::new_short(0x00, 0x01, &[2, 0, 0, 0, 0]))
+        );
+        // padding still counts for equality
+        assert_ne!(report, OiReport::new_long_slice(0x00, 0x01, &[2]).unwrap());
+    }
+
+    #[test]
+    fn report_size() {
+        assert_eq!(ReportSize::Short.payload_len(), 5);
+        assert_eq!(ReportSize::Short.total_len(), 8);
+        assert_eq!(ReportSize::Long.payload_len(), 29);
+        assert_eq!(ReportSize::Long.total_len(), 32);
+
+        for size in [ReportSize::Short, ReportSize::Long] {
+            assert_eq!(ReportSize::from_report_id(size.report_id()), Some(size));
+            assert_eq!(ReportSize::from_total_len(size.total_len()), Some(size));
+            assert_eq!(OiReportId::from(size).id(), size.report_id());
+        }
+        assert_eq!(ReportSize::from_report_id(0x02), None);
+        assert_eq!(ReportSize::from_total_len(29), None);
+
+        let report = OiReport::new_short(0x10, 0x02, &[0; 5]);
+        assert_eq!(report.size(), Some(ReportSize::Short));
+    }
+
+    #[test]
+    fn long_slice() {
+        let report = OiReport::new_long_slice(0x10, 0x02, &[0xAA; 10]).unwrap();
+        assert_eq!(report.data().len(), 10);
+
+        let mut buf = [0; LONG_LEN];
+        assert_eq!(ssmarshal::serialize(&mut buf, &report).unwrap(), LONG_LEN);
+        assert_eq!(&buf[..3], &[0x21, 0x10, 0x02]);
+        assert_eq!(&buf[3..13], &[0xAA; 10]);
+        assert!(buf[13..].iter().all(|&b| b == 0));
+        let bytes: heapless::Vec<u8, 32> = report.into();
+        assert_eq!(bytes.as_slice(), buf);
+
+        assert!(OiReport::new_long_slice(0x10, 0x02, &[0; 29]).is_ok());
+        assert!(matches!(
+            OiReport::new_long_slice(0x10, 0x02, &[0; 30]),
+            Err(OIError::SerializationError)
+        ));
+    }
+
+    #[test]
+    fn serialize_unknown_id() {
+        // read only checks the length, the id is rejected when the report is written back
+        let report = OiReport::read(&[0x55; SHORT_LEN]).unwrap();
+        let mut buf = [0; LONG_LEN];
+        assert!(ssmarshal::serialize(&mut buf, &report).is_err());
+        for bytes in [&[][..], &[0x20], &[0x21; LONG_LEN + 1]] {
+            assert!(OiReport::read(bytes).is_err());
+        }
+    }
+
+    const FRAME: [u8; SHORT_LEN] = [0x20, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00];
+
+    fn assert_pulled_frame<B: UsbBus>(class: &mut OpenInputKeyboardHID<'_, B>) {
+        match class.pull_host_data() {
+            Ok(keyboard::OiKeyboardOutputReport::OpenInput(report)) => {
+                assert_eq!(report, OiReport::read(&FRAME).unwrap())
+            }
+            Ok(_) => panic!("pulled a keyboard report"),
+            Err(e) => panic!("{:?}", e),
+        }
+        assert!(matches!(
+            class.pull_host_data(),
+            Err(OIError::UsbError(UsbError::WouldBlock))
+        ));
+    }
+
+    #[test]
+    fn pull_ep_out_set_report() {
+        use usb_device::prelude::*;
+
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // output report over the control pipe
+        host.set_report(0x02, 0x20, &FRAME);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        assert_pulled_frame(&mut class);
+    }
+
+    #[test]
+    fn pull_feature_report_unsupported() {
+        let (alloc, _host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        assert!(matches!(
+            class.pull_feature_report(),
+            Err(OIError::Unsupported)
+        ));
+    }
+
+    #[test]
+    fn builder_descriptor() {
+        use usb_device::prelude::*;
+
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class = OpenInputKeyboardHID::builder(&alloc).poll_ms(10).build();
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        host.get_report_descriptor(512);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        assert_eq!(host.take_control_in(), OiKeyboardReport::desc());
+    }
+
+    #[test]
+    fn builder_openinput_usage() {
+        use usb_device::prelude::*;
+
+        let buf = std::boxed::Box::leak(std::vec![0; 512].into_boxed_slice());
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class = OpenInputKeyboardHID::builder(&alloc)
+            .openinput_usage(0xFF42, 0x01, buf)
+            .build();
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        host.get_report_descriptor(512);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        let desc = host.take_control_in();
+        assert_eq!(desc.len(), OiKeyboardReport::desc().len());
+        assert!(descriptor::has_openinput_collections_with(
+            &desc, 0xFF42, 0x01
+        ));
+    }
+
+    /// utf-16 string descriptor as sent by the device
+    fn string_descriptor(s: &str) -> std::vec::Vec<u8> {
+        let mut desc = vec![0, 0x03];
+        desc.extend(s.encode_utf16().flat_map(u16::to_le_bytes));
+        desc[0] = desc.len() as u8;
+        desc
+    }
+
+    #[test]
+    fn builder_string_descriptors() {
+        use usb_device::prelude::*;
+
+        let meta = DispatchMeta::from_static("Dekanova", "1.2.3", "Test Keyboard");
+        let (alloc, host) = mock::MockUsbBus::new();
+        let (mut class, device) = OpenInputKeyboardHID::builder(&alloc)
+            .meta(&meta)
+            .serial_number("DK-0001")
+            .build_with_device(UsbVidPid(0x1209, 0x0001));
+        let mut dev = device.build();
+
+        let mut read = |index| {
+            host.get_string_descriptor(index);
+            while host.control_pending() {
+                dev.poll(&mut [&mut class.inner]);
+            }
+            host.take_control_in()
+        };
+        // manufacturer, product, serial number
+        assert_eq!(read(1), string_descriptor("Dekanova"));
+        assert_eq!(read(2), string_descriptor("Test Keyboard"));
+        assert_eq!(read(3), string_descriptor("DK-0001"));
+
+        // explicit strings win over the meta, unset ones without a meta stay unset
+        let builder = OpenInputKeyboardHID::builder(&alloc)
+            .meta(&meta)
+            .manufacturer("Dekanova LLC");
+        assert_eq!(
+            builder.strings(),
+            [Some("Dekanova LLC"), Some("Test Keyboard"), None]
+        );
+        let builder = OpenInputKeyboardHID::builder(&alloc).product("Keyboard");
+        assert_eq!(builder.strings(), [None, Some("Keyboard"), None]);
+    }
+
+    #[test]
+    fn pull_nonblocking() {
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        assert!(class.pull_host_data_nonblocking().is_none());
+
+        host.send(&FRAME);
+        match class.pull_host_data_nonblocking() {
+            Some(keyboard::OiKeyboardOutputReport::OpenInput(report)) => {
+                assert_eq!(report, OiReport::read(&FRAME).unwrap())
+            }
+            _ => panic!("frame wasn't pulled"),
+        }
+        assert!(class.pull_host_data_nonblocking().is_none());
+
+        // malformed reports are dropped as well
+        host.send(&FRAME[..3]);
+        assert!(class.pull_host_data_nonblocking().is_none());
+        assert!(matches!(
+            class.pull_host_data(),
+            Err(OIError::UsbError(UsbError::WouldBlock))
+        ));
+    }
+
+    #[test]
+    fn device_info() {
+        static DISPATCH: Dispatch = Dispatch::default_const(DispatchMeta::from_static(
+            "Dekanova",
+            "1.2.3",
+            "Test Keyboard",
+        ));
+
+        let (alloc, _host) = mock::MockUsbBus::new();
+        let class = OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        assert!(class.dispatch().is_none());
+        assert!(class.device_info().is_none());
+
+        let class = OpenInputKeyboardHID::with_dispatch(
+            HIDClass::new(&alloc, OiKeyboardReport::desc(), 1),
+            &DISPATCH,
+        );
+        assert!(core::ptr::eq(class.dispatch().unwrap(), &DISPATCH));
+        let meta = class.device_info().unwrap();
+        assert_eq!(meta.firmware_vendor_str(), Some("Dekanova"));
+        assert_eq!(meta.device_name_str(), Some("Test Keyboard"));
+        assert_eq!(meta.protocol_version(), PROTOCOL_VERSION);
+
+        let device = OpenInputDevice::new(class, Dispatch::default());
+        assert_eq!(
+            device.device_info().device_name_str(),
+            Some("Unspecified Name")
+        );
+    }
+
+    #[test]
+    fn try_push_report() {
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+        let report = keyboard::OiKeyboardInputReport::OpenInput(OiReport::read(&FRAME).unwrap());
+
+        host.set_in_busy(true);
+        assert!(matches!(
+            class.try_push_report(report.clone()),
+            Err(OIError::NotReady)
+        ));
+        assert!(!class.is_ready_to_push());
+        assert_eq!(host.recv(), None);
+
+        host.set_in_busy(false);
+        class.push_report_blocking(report).unwrap();
+        assert!(class.is_ready_to_push());
+        assert_eq!(host.recv(), Some(FRAME.to_vec()));
+    }
+
+    #[test]
+    fn reset_after_malformed_report() {
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+
+        // truncated frame followed by the rest of it
+        host.send(&FRAME[..3]);
+        host.send(&FRAME[3..]);
+        assert!(matches!(class.pull_host_data(), Err(OIError::FuckyBuffer)));
+
+        class.reset_buffers();
+        host.send(&FRAME);
+        assert_pulled_frame(&mut class);
+    }
+
+    #[test]
+    fn pull_ep_out_interrupt() {
+        let (alloc, host) = mock::MockUsbBus::new();
+        let mut class =
+            OpenInputKeyboardHID::new(HIDClass::new(&alloc, OiKeyboardReport::desc(), 1));
+
+        // nothing came in with SET_REPORT, falls back to the interrupt OUT endpoint
+        host.send(&FRAME);
+        assert_pulled_frame(&mut class);
+    }
 }
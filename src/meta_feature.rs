@@ -0,0 +1,118 @@
+//! All of [`DispatchMeta`]'s info strings in one feature report
+//!
+//! Reading vendor, version, name and serial with the info functions takes four requests (more for strings
+//! past 29 bytes). Hosts that can issue GET_REPORT read feature report [`META_FEATURE_REPORT_ID`] instead,
+//! declared by [`OiKeyboardReport`](crate::OiKeyboardReport):
+//!
+//! ```text
+//! 0x22 | vendor len | vendor | version len | version | name len | name | serial len | serial | zero padding
+//! ```
+//!
+//! The report is [`META_FEATURE_LEN`] bytes. A field that runs past the end keeps its full length byte and
+//! the fields after it are left out, hosts read those with the info functions instead (see
+//! [`host::decode_meta_feature`](crate::host::decode_meta_feature)).
+//!
+//! [`OiKeyboardReport`](crate::OiKeyboardReport) answers GET_REPORT for it when the class is polled itself
+//! rather than its inner HID class, built from the class's [`device_info`](crate::OpenInputHIDClass::device_info) on
+//! every request so a changed serial number shows up right away. Without a dispatcher (see
+//! [`OpenInputHIDClass::with_dispatch`](crate::OpenInputHIDClass::with_dispatch)) the HID class rejects the
+//! request.
+
+use crate::dispatch::{DispatchMeta, META_STRING_LEN};
+
+pub const META_FEATURE_REPORT_ID: u8 = 0x22;
+
+/// report id and data, as much as usb-device's control buffer holds
+pub const META_FEATURE_LEN: usize = 128;
+
+/// serialize the info strings of `meta`, see the module docs for the layout
+pub fn meta_report(meta: &DispatchMeta) -> [u8; META_FEATURE_LEN] {
+    let mut report = [0; META_FEATURE_LEN];
+    report[0] = META_FEATURE_REPORT_ID;
+    let mut serial = [0; META_STRING_LEN];
+    let fields: [&[u8]; 4] = [
+        meta.firmware_vendor().as_bytes(),
+        meta.firmware_version().as_bytes(),
+        meta.device_name().as_bytes(),
+        meta.read_serial(&mut serial),
+    ];
+    let mut pos = 1;
+    for field in fields {
+        if pos == META_FEATURE_LEN {
+            break;
+        }
+        // at most META_STRING_LEN long
+        report[pos] = field.len() as u8;
+        pos += 1;
+        let len = field.len().min(META_FEATURE_LEN - pos);
+        report[pos..pos + len].copy_from_slice(&field[..len]);
+        pos += len;
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use usb_device::prelude::*;
+    use usbd_hid::descriptor::SerializedDescriptor;
+    use usbd_hid::hid_class::HIDClass;
+
+    use super::*;
+    use crate::dispatch::Dispatch;
+    use crate::host::{decode_meta_feature, MetaFeatureReply};
+    use crate::mock::MockUsbBus;
+    use crate::{OiKeyboardReport, OpenInputKeyboardHID};
+
+    #[test]
+    fn get_feature_report() {
+        let meta = DispatchMeta::builder()
+            .vendor("Dekanova")
+            .version("1.2.3")
+            .name("Keyboard")
+            .serial(&[0xde, 0xad, 0xbe, 0xef])
+            .build()
+            .unwrap();
+        let dispatch = Dispatch::default_const(meta);
+        let (alloc, host) = MockUsbBus::new();
+        let mut class = OpenInputKeyboardHID::with_dispatch(
+            HIDClass::new(&alloc, OiKeyboardReport::desc(), 1),
+            &dispatch,
+        );
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        host.get_report(0x03, META_FEATURE_REPORT_ID, META_FEATURE_LEN as u16);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        let report = host.take_control_in();
+        assert_eq!(report.len(), META_FEATURE_LEN);
+        assert_eq!(
+            decode_meta_feature(&report),
+            Some(MetaFeatureReply {
+                vendor: "Dekanova",
+                version: "1.2.3",
+                name: "Keyboard",
+                serial: &[0xde, 0xad, 0xbe, 0xef],
+            })
+        );
+    }
+
+    #[test]
+    fn long_strings_are_cut() {
+        let long = core::str::from_utf8(&[b'a'; 64]).unwrap();
+        let meta = DispatchMeta::builder()
+            .vendor(long)
+            .version("1.0.0")
+            .name(long)
+            .serial(b"serial")
+            .build()
+            .unwrap();
+        let report = meta_report(&meta);
+        // vendor fits, the name runs past the end and the serial is left out
+        assert_eq!(report[1], 64);
+        assert_eq!(&report[66..72], &[5, b'1', b'.', b'0', b'.', b'0']);
+        assert_eq!(report[72], 64);
+        assert!(report[73..].iter().all(|&b| b == b'a'));
+        assert_eq!(decode_meta_feature(&report), None);
+    }
+}
@@ -0,0 +1,246 @@
+//! Test-only in-memory usb bus, interrupt endpoints plus enough of EP0 for class control requests
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use usb_device::bus::{PollResult, UsbBus, UsbBusAllocator};
+use usb_device::endpoint::{EndpointAddress, EndpointType};
+use usb_device::{Result, UsbDirection, UsbError};
+
+#[derive(Default)]
+struct State {
+    next_ep: u8,
+    /// host -> device packets per endpoint index
+    out: HashMap<u8, VecDeque<Vec<u8>>>,
+    /// EP0 OUT packets, flagged if they are a SETUP packet
+    control: VecDeque<(bool, Vec<u8>)>,
+    /// device wrote to EP0 IN and the completion wasn't reported by `poll` yet
+    control_in_complete: bool,
+    /// everything written to EP0 IN
+    control_in: Vec<u8>,
+    /// device -> host packets
+    input: VecDeque<Vec<u8>>,
+    /// simulate the host not having read the last IN packet yet
+    in_busy: bool,
+}
+
+/// handle to the bus state that stays with the test after the bus is moved into an allocator
+#[derive(Clone, Default)]
+pub struct MockHost(Arc<Mutex<State>>);
+
+impl MockHost {
+    /// queue an interrupt OUT packet on the first OUT endpoint
+    pub fn send(&self, data: &[u8]) {
+        let mut state = self.0.lock().unwrap();
+        let ep = *state.out.keys().min().expect("no OUT endpoint allocated");
+        state.out.get_mut(&ep).unwrap().push_back(data.to_vec());
+    }
+
+    /// read the oldest IN packet the device pushed
+    pub fn recv(&self) -> Option<Vec<u8>> {
+        self.0.lock().unwrap().input.pop_front()
+    }
+
+    pub fn set_in_busy(&self, busy: bool) {
+        self.0.lock().unwrap().in_busy = busy;
+    }
+
+    /// queue a SET_REPORT to interface 0, `UsbDevice::poll` has to run until [`MockHost::control_pending`] is false
+    pub fn set_report(&self, report_type: u8, report_id: u8, data: &[u8]) {
+        let len = (data.len() as u16).to_le_bytes();
+        let setup = [0x21, 0x09, report_id, report_type, 0, 0, len[0], len[1]];
+        let mut state = self.0.lock().unwrap();
+        state.control.push_back((true, setup.to_vec()));
+        for packet in data.chunks(CONTROL_PACKET_SIZE) {
+            state.control.push_back((false, packet.to_vec()));
+        }
+    }
+
+    /// queue a GET_REPORT to interface 0, read the reply with [`MockHost::take_control_in`]
+    pub fn get_report(&self, report_type: u8, report_id: u8, len: u16) {
+        let len = len.to_le_bytes();
+        let setup = [0xa1, 0x01, report_id, report_type, 0, 0, len[0], len[1]];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a SET_IDLE to interface 0, `duration` in 4ms steps
+    pub fn set_idle(&self, duration: u8, report_id: u8) {
+        let setup = [0x21, 0x0a, report_id, duration, 0, 0, 0, 0];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a GET_IDLE to interface 0, read the reply with [`MockHost::take_control_in`]
+    pub fn get_idle(&self, report_id: u8) {
+        let setup = [0xa1, 0x02, report_id, 0, 0, 0, 1, 0];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a SET_PROTOCOL to interface 0, 0 is the boot protocol
+    pub fn set_protocol(&self, protocol: u8) {
+        let setup = [0x21, 0x0b, protocol, 0, 0, 0, 0, 0];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a GET_PROTOCOL to interface 0, read the reply with [`MockHost::take_control_in`]
+    pub fn get_protocol(&self) {
+        let setup = [0xa1, 0x03, 0, 0, 0, 0, 1, 0];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a GET_DESCRIPTOR(Report) for interface 0, read the reply with [`MockHost::take_control_in`]
+    pub fn get_report_descriptor(&self, len: u16) {
+        let len = len.to_le_bytes();
+        let setup = [0x81, 0x06, 0x00, 0x22, 0, 0, len[0], len[1]];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// queue a GET_DESCRIPTOR(String) in US English, read the reply with [`MockHost::take_control_in`]
+    pub fn get_string_descriptor(&self, index: u8) {
+        let setup = [0x80, 0x06, index, 0x03, 0x09, 0x04, 0xff, 0];
+        self.0
+            .lock()
+            .unwrap()
+            .control
+            .push_back((true, setup.to_vec()));
+    }
+
+    /// data the device sent on EP0 IN since the last call
+    pub fn take_control_in(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.lock().unwrap().control_in)
+    }
+
+    pub fn control_pending(&self) -> bool {
+        let state = self.0.lock().unwrap();
+        !state.control.is_empty() || state.control_in_complete
+    }
+}
+
+/// default EP0 max packet size
+const CONTROL_PACKET_SIZE: usize = 8;
+
+pub struct MockUsbBus(MockHost);
+
+impl MockUsbBus {
+    pub fn new() -> (UsbBusAllocator<Self>, MockHost) {
+        let host = MockHost::default();
+        (UsbBusAllocator::new(Self(host.clone())), host)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        (self.0).0.lock().unwrap()
+    }
+}
+
+impl UsbBus for MockUsbBus {
+    fn alloc_ep(
+        &mut self,
+        ep_dir: UsbDirection,
+        ep_addr: Option<EndpointAddress>,
+        _ep_type: EndpointType,
+        _max_packet_size: u16,
+        _interval: u8,
+    ) -> Result<EndpointAddress> {
+        // the control pipe always asks for EP0, it lives in `control` instead of `out`
+        if let Some(addr) = ep_addr.filter(|addr| addr.index() == 0) {
+            return Ok(addr);
+        }
+        let mut state = self.state();
+        state.next_ep += 1;
+        let index = state.next_ep;
+        if ep_dir == UsbDirection::Out {
+            state.out.insert(index, VecDeque::new());
+        }
+        Ok(EndpointAddress::from_parts(index as usize, ep_dir))
+    }
+
+    fn enable(&mut self) {}
+
+    fn reset(&self) {}
+
+    fn set_device_address(&self, _addr: u8) {}
+
+    fn write(&self, ep_addr: EndpointAddress, buf: &[u8]) -> Result<usize> {
+        let mut state = self.state();
+        if ep_addr.index() == 0 {
+            state.control_in_complete = true;
+            state.control_in.extend_from_slice(buf);
+            return Ok(buf.len());
+        }
+        if state.in_busy {
+            return Err(UsbError::WouldBlock);
+        }
+        state.input.push_back(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn read(&self, ep_addr: EndpointAddress, buf: &mut [u8]) -> Result<usize> {
+        let mut state = self.state();
+        if ep_addr.index() == 0 {
+            let (_, packet) = state.control.pop_front().ok_or(UsbError::WouldBlock)?;
+            buf[..packet.len()].copy_from_slice(&packet);
+            return Ok(packet.len());
+        }
+        let queue = state
+            .out
+            .get_mut(&(ep_addr.index() as u8))
+            .ok_or(UsbError::InvalidEndpoint)?;
+        let packet = queue.pop_front().ok_or(UsbError::WouldBlock)?;
+        if packet.len() > buf.len() {
+            return Err(UsbError::BufferOverflow);
+        }
+        buf[..packet.len()].copy_from_slice(&packet);
+        Ok(packet.len())
+    }
+
+    fn set_stalled(&self, _ep_addr: EndpointAddress, _stalled: bool) {}
+
+    fn is_stalled(&self, _ep_addr: EndpointAddress) -> bool {
+        false
+    }
+
+    fn suspend(&self) {}
+
+    fn resume(&self) {}
+
+    fn poll(&self) -> PollResult {
+        let mut state = self.state();
+        let ep_in_complete = std::mem::take(&mut state.control_in_complete) as u16;
+        let (ep_out, ep_setup) = match state.control.front() {
+            Some((true, _)) => (0, 1),
+            Some((false, _)) => (1, 0),
+            None => (0, 0),
+        };
+        if ep_in_complete | ep_out | ep_setup == 0 {
+            return PollResult::None;
+        }
+        PollResult::Data {
+            ep_out,
+            ep_in_complete,
+            ep_setup,
+        }
+    }
+}
@@ -0,0 +1,640 @@
+//! Multi-touch touch screen report
+//!
+//! Up to ten contacts in a single input report ("parallel mode"), with the usages Windows expects from a
+//! touch screen digitizer. The descriptor is hand written for the same reasons as the
+//! [`touchpad`](crate::touchpad) one. Coordinates are in logical units of `0..=4095` mapped onto a
+//! 222.0 x 125.0 mm screen.
+//!
+//! What the hosts need from the descriptor:
+//!
+//! - Windows 7 only switches to multi-touch once it has set Input Mode (feature report 0x0d) to
+//!   [`INPUT_MODE_MULTI_INPUT`], until then the contacts are ignored
+//! - Windows 8 and later also require Scan Time in the input report and Contact Count Maximum (feature report
+//!   0x0c). The certification blob (usage 0xC5) is only checked for the Windows logo and left out
+//! - Linux (hid-multitouch) only needs the finger collections with Contact Identifier and Contact Count
+//!
+//! The class answers GET_REPORT for the Contact Count Maximum and Input Mode feature reports when it's polled
+//! itself rather than its inner HID class, like for the touchpad.
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
+
+use crate::dispatch::DispatchMeta;
+use crate::{
+    concat_desc, pull_feature_report, read_openinput, OIError, OiReport, LONG_LEN,
+    OPENINPUT_DESCRIPTOR, SHORT_LEN,
+};
+
+use super::OpenInputHidReport;
+
+/// max simultaneous contacts reported in one input report
+pub const MAX_CONTACTS: usize = 10;
+
+/// largest x/y logical value
+pub const LOGICAL_MAX: u16 = 4095;
+
+const HEADER: &[u8] = &[
+    0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+    0x09, 0x04, /* USAGE (Touch Screen) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x0b, /*  REPORT_ID (0x0b) */
+];
+
+const FINGER: &[u8] = &[
+    0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+    0x09, 0x22, /*  USAGE (Finger) */
+    0xa1, 0x02, /*  COLLECTION (Logical) */
+    0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+    0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+    0x09, 0x42, /*   USAGE (Tip Switch) */
+    0x75, 0x01, /*   REPORT_SIZE (1) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x95, 0x07, /*   REPORT_COUNT (7) */
+    0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+    0x25, 0x7f, /*   LOGICAL_MAXIMUM (127) */
+    0x75, 0x08, /*   REPORT_SIZE (8) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0x09, 0x51, /*   USAGE (Contact Identifier) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+    0x26, 0xff, 0x0f, /*   LOGICAL_MAXIMUM (4095) */
+    0x75, 0x10, /*   REPORT_SIZE (16) */
+    0x55, 0x0e, /*   UNIT_EXPONENT (-2) */
+    0x65, 0x11, /*   UNIT (SI Linear: cm) */
+    0x35, 0x00, /*   PHYSICAL_MINIMUM (0) */
+    0x46, 0xac, 0x08, /*   PHYSICAL_MAXIMUM (2220) */
+    0x09, 0x30, /*   USAGE (X) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x46, 0xe2, 0x04, /*   PHYSICAL_MAXIMUM (1250) */
+    0x09, 0x31, /*   USAGE (Y) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    // units are global, the next finger's tip switch would inherit them
+    0x45, 0x00, /*   PHYSICAL_MAXIMUM (0) */
+    0x65, 0x00, /*   UNIT (None) */
+    0x55, 0x00, /*   UNIT_EXPONENT (0) */
+    0xc0, /*  END_COLLECTION */
+];
+
+const TRAILER: &[u8] = &[
+    0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+    0x55, 0x0c, /*  UNIT_EXPONENT (-4) */
+    0x66, 0x01, 0x10, /*  UNIT (SI Linear: seconds) */
+    0x47, 0xff, 0xff, 0x00, 0x00, /*  PHYSICAL_MAXIMUM (65535) */
+    0x27, 0xff, 0xff, 0x00, 0x00, /*  LOGICAL_MAXIMUM (65535) */
+    0x75, 0x10, /*  REPORT_SIZE (16) */
+    0x95, 0x01, /*  REPORT_COUNT (1) */
+    0x09, 0x56, /*  USAGE (Scan Time) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x65, 0x00, /*  UNIT (None) */
+    0x55, 0x00, /*  UNIT_EXPONENT (0) */
+    0x45, 0x00, /*  PHYSICAL_MAXIMUM (0) */
+    0x09, 0x54, /*  USAGE (Contact Count) */
+    0x25, 0x7f, /*  LOGICAL_MAXIMUM (127) */
+    0x75, 0x08, /*  REPORT_SIZE (8) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    /* device capabilities */
+    0x85, 0x0c, /*  REPORT_ID (0x0c) */
+    0x09, 0x55, /*  USAGE (Contact Count Maximum) */
+    0x25, 0x0a, /*  LOGICAL_MAXIMUM (10) */
+    0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+    0xc0, /* END_COLLECTION */
+    /* configuration */
+    0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+    0x09, 0x0e, /* USAGE (Device Configuration) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x0d, /*  REPORT_ID (0x0d) */
+    0x09, 0x22, /*  USAGE (Finger) */
+    0xa1, 0x02, /*  COLLECTION (Logical) */
+    0x09, 0x52, /*   USAGE (Input Mode) */
+    0x25, 0x0a, /*   LOGICAL_MAXIMUM (10) */
+    0x75, 0x08, /*   REPORT_SIZE (8) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+    0xc0, /*  END_COLLECTION */
+    0xc0, /* END_COLLECTION */
+];
+
+const TOUCH_SCREEN_LEN: usize = HEADER.len() + FINGER.len() * MAX_CONTACTS + TRAILER.len();
+
+const fn touch_screen() -> [u8; TOUCH_SCREEN_LEN] {
+    let mut out = [0; TOUCH_SCREEN_LEN];
+    let mut i = 0;
+    let mut j = 0;
+    while j < HEADER.len() {
+        out[i] = HEADER[j];
+        i += 1;
+        j += 1;
+    }
+    let mut finger = 0;
+    while finger < MAX_CONTACTS {
+        j = 0;
+        while j < FINGER.len() {
+            out[i] = FINGER[j];
+            i += 1;
+            j += 1;
+        }
+        finger += 1;
+    }
+    j = 0;
+    while j < TRAILER.len() {
+        out[i] = TRAILER[j];
+        i += 1;
+        j += 1;
+    }
+    out
+}
+
+/// touch screen descriptor: input report 0x0b, Contact Count Maximum feature report 0x0c and the
+/// configuration collection with the Input Mode feature report 0x0d
+pub const TOUCH_SCREEN_DESCRIPTOR: &[u8] = &touch_screen();
+
+const DESC_LEN: usize = TOUCH_SCREEN_LEN + OPENINPUT_DESCRIPTOR.len();
+static DESC: [u8; DESC_LEN] = concat_desc(TOUCH_SCREEN_DESCRIPTOR, OPENINPUT_DESCRIPTOR);
+
+/// input mode feature values
+pub const INPUT_MODE_MOUSE: u8 = 0x00;
+pub const INPUT_MODE_SINGLE_INPUT: u8 = 0x01;
+pub const INPUT_MODE_MULTI_INPUT: u8 = 0x02;
+
+#[derive(Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OiMultitouchReport {
+    /// input mode last set by the host, windows switches to [`INPUT_MODE_MULTI_INPUT`] once configured
+    pub input_mode: u8,
+    // openinput
+    out_short_buf: [u8; SHORT_LEN],
+    out_long_buf: [u8; LONG_LEN],
+}
+
+impl SerializedDescriptor for OiMultitouchReport {
+    fn desc() -> &'static [u8] {
+        &DESC
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MultitouchReportId {
+    OpenInputShort = 0x20,
+    OpenInputLong = 0x21,
+    Multitouch = 0x0b,
+    ContactCountMaximum = 0x0c,
+    InputMode = 0x0d,
+}
+
+impl TryFrom<u8> for MultitouchReportId {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x0b => Ok(MultitouchReportId::Multitouch),
+            0x0c => Ok(MultitouchReportId::ContactCountMaximum),
+            0x0d => Ok(MultitouchReportId::InputMode),
+            0x20 => Ok(MultitouchReportId::OpenInputShort),
+            0x21 => Ok(MultitouchReportId::OpenInputLong),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiMultitouchOutputReport<'a> {
+    /// Input mode feature report
+    InputMode(u8),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TouchContact {
+    /// contact identifier, has to stay the same while the finger is down
+    pub id: u8,
+    pub x: u16,
+    pub y: u16,
+    /// tip switch, the last report of a contact has it cleared
+    pub active: bool,
+}
+
+impl Serialize for TouchContact {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // tip switch and 7 padding bits
+        let mut s = serializer.serialize_tuple(4)?;
+        s.serialize_element(&(self.active as u8))?;
+        s.serialize_element(&self.id)?;
+        s.serialize_element(&self.x)?;
+        s.serialize_element(&self.y)?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MultitouchInputReport {
+    /// the first `contact_count` are reported, unused slots should be zeroed
+    pub contacts: [TouchContact; MAX_CONTACTS],
+    /// relative scan time in 100us units
+    pub scan_time: u16,
+    pub contact_count: u8,
+}
+
+impl Serialize for MultitouchInputReport {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_tuple(3)?;
+        s.serialize_element(&self.contacts)?;
+        s.serialize_element(&self.scan_time)?;
+        s.serialize_element(&self.contact_count)?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiMultitouchInputReport<'a> {
+    /// Touch screen report
+    Multitouch(MultitouchInputReport),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+impl<'a> Serialize for OiMultitouchInputReport<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            OiMultitouchInputReport::Multitouch(mt) => {
+                // report id + report
+                let mut s = serializer.serialize_tuple(2)?;
+                s.serialize_element(&(MultitouchReportId::Multitouch as u8))?;
+                s.serialize_element(mt)?;
+                s.end()
+            }
+            OiMultitouchInputReport::OpenInput(oi) => oi.serialize(serializer),
+        }
+    }
+}
+
+impl OpenInputHidReport for OiMultitouchReport {
+    type PullReport<'a> = OiMultitouchOutputReport<'a>;
+    type PushReport<'a> = OiMultitouchInputReport<'a>;
+    type ReportId = MultitouchReportId;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
+            // SET_REPORT data starts with the report id
+            MultitouchReportId::InputMode => match buf {
+                [_, mode] => {
+                    self.input_mode = *mode;
+                    Ok(OiMultitouchOutputReport::InputMode(*mode))
+                }
+                _ => Err(OIError::FuckyBuffer),
+            },
+            // input and read only feature reports
+            MultitouchReportId::Multitouch | MultitouchReportId::ContactCountMaximum => {
+                Err(OIError::FuckyBuffer)
+            }
+            MultitouchReportId::OpenInputShort => Ok(OiMultitouchOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_short_buf)?,
+            )),
+            MultitouchReportId::OpenInputLong => Ok(OiMultitouchOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_long_buf)?,
+            )),
+        }
+    }
+
+    fn pull_feature<'a, 'ep, B: UsbBus>(
+        &'a mut self,
+        hid: &mut HIDClass<'ep, B>,
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        pull_feature_report(self, hid)
+    }
+
+    /// contact count maximum and input mode
+    fn feature_report(
+        &self,
+        report_id: u8,
+        _meta: Option<&DispatchMeta>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        let value = match MultitouchReportId::try_from(report_id).ok()? {
+            MultitouchReportId::ContactCountMaximum => MAX_CONTACTS as u8,
+            MultitouchReportId::InputMode => self.input_mode,
+            _ => return None,
+        };
+        buf.get_mut(..2)?.copy_from_slice(&[report_id, value]);
+        Some(2)
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        match report {
+            OiMultitouchOutputReport::OpenInput(oi) => Ok(oi),
+            other => Err(other),
+        }
+    }
+
+    fn push_report<'b, 'ep, B: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, B>,
+        report: Self::PushReport<'b>,
+    ) -> Result<(), OIError> {
+        let mut buf = [0; 64];
+        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        hid.push_raw_input(&buf[..m])?;
+        Ok(())
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+    use crate::descriptor::{has_openinput_collections, OPENINPUT_SNAPSHOT};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    /// bits of the input and feature reports with `report_id`, walking the short items
+    fn report_bits(desc: &[u8], report_id: u8) -> (usize, usize) {
+        let (mut id, mut size, mut count) = (0, 0, 0);
+        let (mut input, mut feature) = (0, 0);
+        let mut i = 0;
+        while i < desc.len() {
+            let len = [0, 1, 2, 4][(desc[i] & 0x03) as usize];
+            let mut data = [0; 4];
+            data[..len].copy_from_slice(&desc[i + 1..i + 1 + len]);
+            let value = u32::from_le_bytes(data) as usize;
+            match desc[i] & 0xfc {
+                0x84 => id = value,
+                0x74 => size = value,
+                0x94 => count = value,
+                0x80 if id == report_id as usize => input += size * count,
+                0xb0 if id == report_id as usize => feature += size * count,
+                _ => (),
+            }
+            i += 1 + len;
+        }
+        (input, feature)
+    }
+
+    #[test]
+    fn conformance() {
+        let desc = OiMultitouchReport::desc();
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // USAGE_PAGE (Digitizer), USAGE (Touch Screen), COLLECTION (Application), REPORT_ID (0x0b)
+        assert!(desc.starts_with(&[0x05, 0x0d, 0x09, 0x04, 0xa1, 0x01, 0x85, 0x0b]));
+
+        // one finger collection per contact + the input mode collection, each finger with tip switch,
+        // contact identifier, X and Y
+        let fingers = desc
+            .windows(4)
+            .filter(|w| w == &[0x09, 0x22, 0xa1, 0x02])
+            .count();
+        assert_eq!(fingers, MAX_CONTACTS + 1);
+        for usage in [[0x09, 0x42], [0x09, 0x51], [0x09, 0x30], [0x09, 0x31]] {
+            let found = desc.windows(2).filter(|w| w == &usage).count();
+            assert_eq!(found, MAX_CONTACTS, "{:x?}", usage);
+        }
+
+        // windows 8: scan time, contact count and contact count maximum; windows 7: input mode
+        for usage in [[0x09, 0x56], [0x09, 0x54], [0x09, 0x55], [0x09, 0x52]] {
+            assert!(contains(desc, &usage), "{:x?}", usage);
+        }
+        // Device Configuration application collection
+        assert!(contains(desc, &[0x05, 0x0d, 0x09, 0x0e, 0xa1, 0x01]));
+
+        // the input report fills a full speed packet with its id, features are a byte each
+        assert_eq!(report_bits(desc, 0x0b), (63 * 8, 0));
+        assert_eq!(report_bits(desc, 0x0c), (0, 8));
+        assert_eq!(report_bits(desc, 0x0d), (0, 8));
+
+        // walk the short items so data bytes aren't mistaken for collection tags
+        let (mut depth, mut i) = (0, 0);
+        while i < desc.len() {
+            match desc[i] & 0xfc {
+                0xa0 => depth += 1,
+                0xc0 => depth -= 1,
+                _ => (),
+            }
+            i += 1 + [0, 1, 2, 4][(desc[i] & 0x03) as usize];
+        }
+        assert_eq!((depth, i), (0, desc.len()));
+    }
+
+    #[test]
+    fn serialize() {
+        let mut report = MultitouchInputReport {
+            scan_time: 0x1234,
+            contact_count: 2,
+            ..Default::default()
+        };
+        report.contacts[0] = TouchContact {
+            id: 3,
+            x: 4095,
+            y: 1,
+            active: true,
+        };
+        report.contacts[1] = TouchContact {
+            id: 4,
+            x: 0x0102,
+            y: 0x0304,
+            active: false,
+        };
+        let mut buf = [0; 64];
+        let len =
+            ssmarshal::serialize(&mut buf, &OiMultitouchInputReport::Multitouch(report)).unwrap();
+        // report id + 10 * 6 byte contacts + scan time + count
+        assert_eq!(len, 64);
+        assert_eq!(
+            &buf[..13],
+            &[0x0b, 0x01, 0x03, 0xff, 0x0f, 0x01, 0x00, 0x00, 0x04, 0x02, 0x01, 0x04, 0x03]
+        );
+        assert_eq!(&buf[61..64], &[0x34, 0x12, 0x02]);
+    }
+
+    #[test]
+    fn input_mode() {
+        let mut report = OiMultitouchReport::default();
+        assert!(matches!(
+            report.read_report(0x0d, &[0x0d, INPUT_MODE_MULTI_INPUT]),
+            Ok(OiMultitouchOutputReport::InputMode(INPUT_MODE_MULTI_INPUT))
+        ));
+        assert_eq!(report.input_mode, INPUT_MODE_MULTI_INPUT);
+        assert!(report.read_report(0x0c, &[0x0c, 10]).is_err());
+        assert!(report.read_report(0x0d, &[0x0d]).is_err());
+    }
+
+    #[test]
+    fn get_feature_report() {
+        use usb_device::prelude::*;
+
+        use crate::mock::{MockHost, MockUsbBus};
+        use crate::OpenInputHIDClass;
+
+        fn get(
+            host: &MockHost,
+            dev: &mut UsbDevice<'_, MockUsbBus>,
+            class: &mut OpenInputHIDClass<'_, MockUsbBus, OiMultitouchReport>,
+            report_id: u8,
+        ) -> std::vec::Vec<u8> {
+            host.get_report(0x03, report_id, 2);
+            while host.control_pending() {
+                dev.poll(&mut [&mut *class]);
+            }
+            host.take_control_in()
+        }
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class: OpenInputHIDClass<_, OiMultitouchReport> =
+            OpenInputHIDClass::new(HIDClass::new(&alloc, OiMultitouchReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // windows 8+ reads the contact count maximum before enabling multi-touch
+        assert_eq!(get(&host, &mut dev, &mut class, 0x0c), [0x0c, 10]);
+        assert_eq!(
+            get(&host, &mut dev, &mut class, 0x0d),
+            [0x0d, INPUT_MODE_MOUSE]
+        );
+
+        // the mode the host set is read back
+        host.set_report(0x03, 0x0d, &[0x0d, INPUT_MODE_MULTI_INPUT]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert!(matches!(
+            class.pull_feature_report(),
+            Ok(OiMultitouchOutputReport::InputMode(INPUT_MODE_MULTI_INPUT))
+        ));
+        assert_eq!(
+            get(&host, &mut dev, &mut class, 0x0d),
+            [0x0d, INPUT_MODE_MULTI_INPUT]
+        );
+        // the input report isn't a feature report
+        assert!(get(&host, &mut dev, &mut class, 0x0b).is_empty());
+    }
+
+    // the touch screen collection is the header, one finger collection per contact and the rest of the collection
+    const SNAPSHOT_HEADER: &[u8] = &[
+        0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+        0x09, 0x04, /* USAGE (Touch Screen) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x0b, /*  REPORT_ID (0x0b) */
+    ];
+
+    const SNAPSHOT_FINGER: &[u8] = &[
+        0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+        0x09, 0x22, /*  USAGE (Finger) */
+        0xa1, 0x02, /*  COLLECTION (Logical) */
+        0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+        0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+        0x09, 0x42, /*   USAGE (Tip Switch) */
+        0x75, 0x01, /*   REPORT_SIZE (1) */
+        0x95, 0x01, /*   REPORT_COUNT (1) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x95, 0x07, /*   REPORT_COUNT (7) */
+        0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+        0x25, 0x7f, /*   LOGICAL_MAXIMUM (127) */
+        0x75, 0x08, /*   REPORT_SIZE (8) */
+        0x95, 0x01, /*   REPORT_COUNT (1) */
+        0x09, 0x51, /*   USAGE (Contact Identifier) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+        0x26, 0xff, 0x0f, /*   LOGICAL_MAXIMUM (4095) */
+        0x75, 0x10, /*   REPORT_SIZE (16) */
+        0x55, 0x0e, /*   UNIT_EXPONENT (-2) */
+        0x65, 0x11, /*   UNIT (SI Linear: cm) */
+        0x35, 0x00, /*   PHYSICAL_MINIMUM (0) */
+        0x46, 0xac, 0x08, /*   PHYSICAL_MAXIMUM (2220) */
+        0x09, 0x30, /*   USAGE (X) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x46, 0xe2, 0x04, /*   PHYSICAL_MAXIMUM (1250) */
+        0x09, 0x31, /*   USAGE (Y) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        // units are global, the next finger's tip switch would inherit them
+        0x45, 0x00, /*   PHYSICAL_MAXIMUM (0) */
+        0x65, 0x00, /*   UNIT (None) */
+        0x55, 0x00, /*   UNIT_EXPONENT (0) */
+        0xc0, /*  END_COLLECTION */
+    ];
+
+    const SNAPSHOT_TRAILER: &[u8] = &[
+        0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+        0x55, 0x0c, /*  UNIT_EXPONENT (-4) */
+        0x66, 0x01, 0x10, /*  UNIT (SI Linear: seconds) */
+        0x47, 0xff, 0xff, 0x00, 0x00, /*  PHYSICAL_MAXIMUM (65535) */
+        0x27, 0xff, 0xff, 0x00, 0x00, /*  LOGICAL_MAXIMUM (65535) */
+        0x75, 0x10, /*  REPORT_SIZE (16) */
+        0x95, 0x01, /*  REPORT_COUNT (1) */
+        0x09, 0x56, /*  USAGE (Scan Time) */
+        0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+        0x65, 0x00, /*  UNIT (None) */
+        0x55, 0x00, /*  UNIT_EXPONENT (0) */
+        0x45, 0x00, /*  PHYSICAL_MAXIMUM (0) */
+        0x09, 0x54, /*  USAGE (Contact Count) */
+        0x25, 0x7f, /*  LOGICAL_MAXIMUM (127) */
+        0x75, 0x08, /*  REPORT_SIZE (8) */
+        0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+        /* device capabilities */
+        0x85, 0x0c, /*  REPORT_ID (0x0c) */
+        0x09, 0x55, /*  USAGE (Contact Count Maximum) */
+        0x25, 0x0a, /*  LOGICAL_MAXIMUM (10) */
+        0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+        0xc0, /* END_COLLECTION */
+    ];
+
+    const SNAPSHOT_CONFIGURATION: &[u8] = &[
+        0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+        0x09, 0x0e, /* USAGE (Device Configuration) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x0d, /*  REPORT_ID (0x0d) */
+        0x09, 0x22, /*  USAGE (Finger) */
+        0xa1, 0x02, /*  COLLECTION (Logical) */
+        0x09, 0x52, /*   USAGE (Input Mode) */
+        0x25, 0x0a, /*   LOGICAL_MAXIMUM (10) */
+        0x75, 0x08, /*   REPORT_SIZE (8) */
+        0x95, 0x01, /*   REPORT_COUNT (1) */
+        0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+        0xc0, /*  END_COLLECTION */
+        0xc0, /* END_COLLECTION */
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        let touch_screen = [
+            SNAPSHOT_HEADER,
+            SNAPSHOT_FINGER.repeat(10).as_slice(),
+            SNAPSHOT_TRAILER,
+        ]
+        .concat();
+        crate::descriptor::assert_snapshot(
+            "multitouch",
+            OiMultitouchReport::desc(),
+            &[
+                touch_screen.as_slice(),
+                SNAPSHOT_CONFIGURATION,
+                OPENINPUT_SNAPSHOT[0],
+                OPENINPUT_SNAPSHOT[1],
+            ],
+        );
+    }
+}
@@ -0,0 +1,185 @@
+//! Sequence numbers for tracking multi report transactions
+//!
+//! With the `sequence-tracking` feature the high nibble of the function id carries a sequence number
+//! ([`OiReport::with_sequence`]), which limits function ids to 0x00-0x0F. Dispatch only looks at the low
+//! nibble and replies echo the full byte, so the host can match replies to requests. Registered functions
+//! with higher ids can't be reached and are left out of the supported functions.
+//!
+//! [`SequenceTracker`] works on any period up to 256, [`REPORT_SEQUENCE_PERIOD`] for the nibble in a report
+//! or the full `u8` range for transports with a dedicated sequence byte.
+
+use crate::OiReport;
+
+/// sequence numbers that fit the high nibble of the function id
+pub const REPORT_SEQUENCE_PERIOD: u16 = 16;
+
+/// function ids are limited to the low nibble when sequence numbers are in use
+pub const FUNCTION_ID_MASK: u8 = 0x0F;
+
+impl<'a> OiReport<'a> {
+    /// store `seq` (modulo [`REPORT_SEQUENCE_PERIOD`]) in the high nibble of the function id
+    pub fn with_sequence(mut self, seq: u8) -> Self {
+        self.function_id = (self.function_id & FUNCTION_ID_MASK) | (seq << 4);
+        self
+    }
+
+    pub fn sequence(&self) -> u8 {
+        self.function_id >> 4
+    }
+}
+
+/// how a sequence number relates to the last one seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SequenceStatus {
+    /// first number seen or the one right after the last
+    InOrder,
+    /// newer than expected, this many numbers were skipped
+    Skipped(u8),
+    /// same as the last one, likely a retransmission
+    Duplicate,
+    /// older than the last one, ignored
+    OutOfOrder,
+}
+
+/// detects duplicate, skipped and out of order sequence numbers
+///
+/// numbers up to half the period ahead of the last one count as newer, anything else as older
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SequenceTracker {
+    last: Option<u8>,
+    period: u16,
+}
+
+impl SequenceTracker {
+    /// full `u8` range, 255 wraps to 0
+    pub const fn new() -> Self {
+        Self::with_period(256)
+    }
+
+    /// sequence numbers run from 0 to `period - 1`, panics if `period` isn't in 2..=256
+    pub const fn with_period(period: u16) -> Self {
+        assert!(period >= 2 && period <= 256, "sequence period out of range");
+        Self { last: None, period }
+    }
+
+    /// for the sequence nibble in [`OiReport`]
+    pub const fn for_reports() -> Self {
+        Self::with_period(REPORT_SEQUENCE_PERIOD)
+    }
+
+    /// check `seq` and remember it if it's newer than the last one
+    pub fn check(&mut self, seq: u8) -> SequenceStatus {
+        let seq = (seq as u16 % self.period) as u8;
+        let last = match self.last {
+            Some(last) => last,
+            None => {
+                self.last = Some(seq);
+                return SequenceStatus::InOrder;
+            }
+        };
+        let ahead = (seq as u16 + self.period - last as u16) % self.period;
+        match ahead {
+            0 => SequenceStatus::Duplicate,
+            ahead if ahead <= self.period / 2 => {
+                self.last = Some(seq);
+                match ahead {
+                    1 => SequenceStatus::InOrder,
+                    ahead => SequenceStatus::Skipped((ahead - 1) as u8),
+                }
+            }
+            _ => SequenceStatus::OutOfOrder,
+        }
+    }
+
+    /// forget the last number, the next one is always in order
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+impl Default for SequenceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::Dispatch;
+
+    #[test]
+    fn wraps_around() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(tracker.check(254), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(255), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(0), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(0), SequenceStatus::Duplicate);
+        assert_eq!(tracker.check(255), SequenceStatus::OutOfOrder);
+        assert_eq!(tracker.check(3), SequenceStatus::Skipped(2));
+
+        // skipping across the wrap
+        let mut tracker = SequenceTracker::new();
+        tracker.check(250);
+        assert_eq!(tracker.check(1), SequenceStatus::Skipped(6));
+    }
+
+    #[test]
+    fn report_nibble() {
+        let mut tracker = SequenceTracker::for_reports();
+        assert_eq!(tracker.check(15), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(0), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(15), SequenceStatus::OutOfOrder);
+        tracker.reset();
+        assert_eq!(tracker.check(15), SequenceStatus::InOrder);
+
+        let report = OiReport::new_short(0x00, 0x03, &[0; 5]).with_sequence(0xA);
+        assert_eq!(report.sequence(), 0xA);
+        assert_eq!(report.function_id, 0xA3);
+        // out of range numbers wrap
+        assert_eq!(report.with_sequence(17).sequence(), 1);
+    }
+
+    #[test]
+    fn dispatch_echoes_sequence() {
+        let dispatch = Dispatch::default();
+        // protocol version
+        let request = OiReport::new_short(0x00, 0x00, &[0; 5]).with_sequence(5);
+        let reply = dispatch.dispatch(&request);
+        assert_eq!(&reply[..3], &[0x20, 0x00, 0x50]);
+        assert_eq!(&reply[3..6], &crate::PROTOCOL_VERSION.to_bytes());
+
+        // errors echo it as well
+        let request = OiReport::new_short(0x00, 0x0F, &[0; 5]).with_sequence(7);
+        let reply = dispatch.dispatch(&request);
+        assert_eq!(&reply[..5], &[0x20, 0xFF, 0x02, 0x00, 0x7F]);
+    }
+
+    #[test]
+    fn unreachable_functions_hidden() {
+        use crate::dispatch::{DispatchContext, DispatchHandler, DispatchReturn};
+
+        fn stub(_: &[u8], _: DispatchContext) -> DispatchReturn {
+            Ok(heapless::Vec::new().into())
+        }
+
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0x80,
+                &[
+                    (0x01, DispatchHandler::Function(stub)),
+                    (0x12, DispatchHandler::Function(stub)),
+                ],
+            )
+            .unwrap();
+        // 0x12 is function 0x02 with sequence number 1 on the wire, it's neither listed nor callable
+        assert!(dispatch.functions(0x80).unwrap().eq([0x01]));
+        assert!(!dispatch.contains(0x80, 0x12));
+        let request = OiReport::new_short(0x00, 0x03, &[0x80, 0, 0, 0, 0]);
+        let reply = dispatch.dispatch(&request);
+        assert_eq!(&reply[3..6], &[1, 1, 0x01]);
+    }
+}
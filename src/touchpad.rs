@@ -0,0 +1,738 @@
+//! Precision touchpad report
+//!
+//! The descriptor follows the Windows Precision Touchpad collection layout, it is hand written since
+//! `gen_hid_descriptor` can't express the repeated finger collections or the unit items.
+//! Coordinates are in logical units of `0..=4095` mapped onto a 120.5 x 90.6 mm surface.
+//!
+//! Windows sets the input mode (0x08) and selective reporting (0x09) feature reports with SET_REPORT, pull them
+//! with [`OpenInputHIDClass::pull_feature_report`](crate::OpenInputHIDClass::pull_feature_report). The class
+//! answers GET_REPORT for the Contact Count Max / Pad Type (0x06), input mode and selective reporting feature
+//! reports when it's polled itself rather than its inner HID class.
+//!
+//! The certification blob (0x07) is 256 bytes, more than the usb-device control buffer holds, so GET_REPORT for
+//! it is left to `usbd-hid` which rejects it. Windows then treats the touchpad as not certified.
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
+
+use crate::dispatch::DispatchMeta;
+use crate::{
+    concat_desc, pull_feature_report, read_openinput, OIError, OiReport, LONG_LEN,
+    OPENINPUT_DESCRIPTOR, SHORT_LEN,
+};
+
+use super::OpenInputHidReport;
+
+/// max simultaneous contacts reported in one input report
+pub const MAX_CONTACTS: usize = 5;
+
+/// largest x/y logical value
+pub const LOGICAL_MAX: u16 = 4095;
+
+const HEADER: &[u8] = &[
+    0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+    0x09, 0x05, /* USAGE (Touch Pad) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x05, /*  REPORT_ID (0x05) */
+];
+
+const FINGER: &[u8] = &[
+    0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+    0x09, 0x22, /*  USAGE (Finger) */
+    0xa1, 0x02, /*  COLLECTION (Logical) */
+    0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+    0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+    0x09, 0x47, /*   USAGE (Confidence) */
+    0x09, 0x42, /*   USAGE (Tip Switch) */
+    0x95, 0x02, /*   REPORT_COUNT (2) */
+    0x75, 0x01, /*   REPORT_SIZE (1) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x95, 0x06, /*   REPORT_COUNT (6) */
+    0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+    0x25, 0x04, /*   LOGICAL_MAXIMUM (4) */
+    0x75, 0x08, /*   REPORT_SIZE (8) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0x09, 0x51, /*   USAGE (Contact Identifier) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+    0x26, 0xff, 0x0f, /*   LOGICAL_MAXIMUM (4095) */
+    0x75, 0x10, /*   REPORT_SIZE (16) */
+    0x55, 0x0e, /*   UNIT_EXPONENT (-2) */
+    0x65, 0x11, /*   UNIT (SI Linear: cm) */
+    0x35, 0x00, /*   PHYSICAL_MINIMUM (0) */
+    0x46, 0xb5, 0x04, /*   PHYSICAL_MAXIMUM (1205) */
+    0x09, 0x30, /*   USAGE (X) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x46, 0x8a, 0x03, /*   PHYSICAL_MAXIMUM (906) */
+    0x09, 0x31, /*   USAGE (Y) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x05, 0x0d, /*   USAGE_PAGE (Digitizer) */
+    0x09, 0x48, /*   USAGE (Width) */
+    0x09, 0x49, /*   USAGE (Height) */
+    0x95, 0x02, /*   REPORT_COUNT (2) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0xc0, /*  END_COLLECTION */
+];
+
+const TRAILER: &[u8] = &[
+    0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+    0x55, 0x0c, /*  UNIT_EXPONENT (-4) */
+    0x66, 0x01, 0x10, /*  UNIT (SI Linear: seconds) */
+    0x47, 0xff, 0xff, 0x00, 0x00, /*  PHYSICAL_MAXIMUM (65535) */
+    0x27, 0xff, 0xff, 0x00, 0x00, /*  LOGICAL_MAXIMUM (65535) */
+    0x75, 0x10, /*  REPORT_SIZE (16) */
+    0x95, 0x01, /*  REPORT_COUNT (1) */
+    0x09, 0x56, /*  USAGE (Scan Time) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x65, 0x00, /*  UNIT (None) */
+    0x55, 0x00, /*  UNIT_EXPONENT (0) */
+    0x45, 0x00, /*  PHYSICAL_MAXIMUM (0) */
+    0x09, 0x54, /*  USAGE (Contact Count) */
+    0x25, 0x7f, /*  LOGICAL_MAXIMUM (127) */
+    0x75, 0x08, /*  REPORT_SIZE (8) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x05, 0x09, /*  USAGE_PAGE (Button) */
+    0x09, 0x01, /*  USAGE (Button 1) */
+    0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+    0x75, 0x01, /*  REPORT_SIZE (1) */
+    0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+    0x95, 0x07, /*  REPORT_COUNT (7) */
+    0x81, 0x03, /*  INPUT (Cnst,Var,Abs) */
+    /* device capabilities */
+    0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+    0x85, 0x06, /*  REPORT_ID (0x06) */
+    0x09, 0x55, /*  USAGE (Contact Count Maximum) */
+    0x09, 0x59, /*  USAGE (Pad Type) */
+    0x25, 0x0f, /*  LOGICAL_MAXIMUM (15) */
+    0x75, 0x04, /*  REPORT_SIZE (4) */
+    0x95, 0x02, /*  REPORT_COUNT (2) */
+    0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+    /* certification status */
+    0x06, 0x00, 0xff, /*  USAGE_PAGE (Vendor Page) */
+    0x85, 0x07, /*  REPORT_ID (0x07) */
+    0x09, 0xc5, /*  USAGE (Vendor Usage 0xC5) */
+    0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+    0x75, 0x08, /*  REPORT_SIZE (8) */
+    0x96, 0x00, 0x01, /*  REPORT_COUNT (256) */
+    0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+    0xc0, /* END_COLLECTION */
+    /* configuration */
+    0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+    0x09, 0x0e, /* USAGE (Device Configuration) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x08, /*  REPORT_ID (0x08) */
+    0x09, 0x22, /*  USAGE (Finger) */
+    0xa1, 0x02, /*  COLLECTION (Logical) */
+    0x09, 0x52, /*   USAGE (Input Mode) */
+    0x25, 0x0a, /*   LOGICAL_MAXIMUM (10) */
+    0x75, 0x08, /*   REPORT_SIZE (8) */
+    0x95, 0x01, /*   REPORT_COUNT (1) */
+    0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+    0xc0, /*  END_COLLECTION */
+    0x09, 0x22, /*  USAGE (Finger) */
+    0xa1, 0x00, /*  COLLECTION (Physical) */
+    0x85, 0x09, /*   REPORT_ID (0x09) */
+    0x09, 0x57, /*   USAGE (Surface Switch) */
+    0x09, 0x58, /*   USAGE (Button Switch) */
+    0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+    0x75, 0x01, /*   REPORT_SIZE (1) */
+    0x95, 0x02, /*   REPORT_COUNT (2) */
+    0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+    0x95, 0x06, /*   REPORT_COUNT (6) */
+    0xb1, 0x03, /*   FEATURE (Cnst,Var,Abs) */
+    0xc0, /*  END_COLLECTION */
+    0xc0, /* END_COLLECTION */
+];
+
+const PTP_LEN: usize = HEADER.len() + FINGER.len() * MAX_CONTACTS + TRAILER.len();
+
+const fn precision_touchpad() -> [u8; PTP_LEN] {
+    let mut out = [0; PTP_LEN];
+    let mut i = 0;
+    let mut j = 0;
+    while j < HEADER.len() {
+        out[i] = HEADER[j];
+        i += 1;
+        j += 1;
+    }
+    let mut finger = 0;
+    while finger < MAX_CONTACTS {
+        j = 0;
+        while j < FINGER.len() {
+            out[i] = FINGER[j];
+            i += 1;
+            j += 1;
+        }
+        finger += 1;
+    }
+    j = 0;
+    while j < TRAILER.len() {
+        out[i] = TRAILER[j];
+        i += 1;
+        j += 1;
+    }
+    out
+}
+
+/// Windows Precision Touchpad descriptor: touch pad input report 0x05, capability feature
+/// reports 0x06/0x07 and the configuration collection with feature reports 0x08/0x09
+pub const PRECISION_TOUCHPAD_DESCRIPTOR: &[u8] = &precision_touchpad();
+
+const DESC_LEN: usize = PTP_LEN + OPENINPUT_DESCRIPTOR.len();
+static DESC: [u8; DESC_LEN] = concat_desc(PRECISION_TOUCHPAD_DESCRIPTOR, OPENINPUT_DESCRIPTOR);
+
+/// input mode feature values
+pub const INPUT_MODE_MOUSE: u8 = 0x00;
+pub const INPUT_MODE_TOUCHPAD: u8 = 0x03;
+
+/// pad type feature values
+pub const PAD_TYPE_CLICKPAD: u8 = 0x00;
+pub const PAD_TYPE_PRESSUREPAD: u8 = 0x01;
+pub const PAD_TYPE_NON_CLICKABLE: u8 = 0x02;
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OiTouchpadReport {
+    /// input mode last set by the host, windows switches to [`INPUT_MODE_TOUCHPAD`] once configured
+    pub input_mode: u8,
+    /// selective reporting last set by the host, both on until it changes them
+    pub surface_reporting: bool,
+    pub button_reporting: bool,
+    /// reported in the capabilities feature report, [`PAD_TYPE_CLICKPAD`] by default
+    pub pad_type: u8,
+    // openinput
+    out_short_buf: [u8; SHORT_LEN],
+    out_long_buf: [u8; LONG_LEN],
+}
+
+impl Default for OiTouchpadReport {
+    fn default() -> Self {
+        Self {
+            input_mode: INPUT_MODE_MOUSE,
+            surface_reporting: true,
+            button_reporting: true,
+            pad_type: PAD_TYPE_CLICKPAD,
+            out_short_buf: [0; SHORT_LEN],
+            out_long_buf: [0; LONG_LEN],
+        }
+    }
+}
+
+impl SerializedDescriptor for OiTouchpadReport {
+    fn desc() -> &'static [u8] {
+        &DESC
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TouchpadReportId {
+    OpenInputShort = 0x20,
+    OpenInputLong = 0x21,
+    Touchpad = 0x05,
+    Capabilities = 0x06,
+    Certification = 0x07,
+    InputMode = 0x08,
+    SelectiveReporting = 0x09,
+}
+
+impl TryFrom<u8> for TouchpadReportId {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x05 => Ok(TouchpadReportId::Touchpad),
+            0x06 => Ok(TouchpadReportId::Capabilities),
+            0x07 => Ok(TouchpadReportId::Certification),
+            0x08 => Ok(TouchpadReportId::InputMode),
+            0x09 => Ok(TouchpadReportId::SelectiveReporting),
+            0x20 => Ok(TouchpadReportId::OpenInputShort),
+            0x21 => Ok(TouchpadReportId::OpenInputLong),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiTouchpadOutputReport<'a> {
+    /// Input mode feature report
+    InputMode(u8),
+    /// Selective reporting feature report
+    SelectiveReporting { surface: bool, button: bool },
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TouchContact {
+    /// contact is a finger and not e.g. a palm
+    pub confidence: bool,
+    pub tip_switch: bool,
+    pub contact_id: u8,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Serialize for TouchContact {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // confidence and tip switch share a byte with 6 padding bits
+        let flags = self.confidence as u8 | (self.tip_switch as u8) << 1;
+        let mut s = serializer.serialize_tuple(6)?;
+        s.serialize_element(&flags)?;
+        s.serialize_element(&self.contact_id)?;
+        s.serialize_element(&self.x)?;
+        s.serialize_element(&self.y)?;
+        s.serialize_element(&self.width)?;
+        s.serialize_element(&self.height)?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TouchpadInputReport {
+    /// unused slots should be zeroed
+    pub contacts: [TouchContact; MAX_CONTACTS],
+    /// relative scan time in 100us units
+    pub scan_time: u16,
+    /// number of valid contacts
+    pub contact_count: u8,
+    pub button: bool,
+}
+
+impl Serialize for TouchpadInputReport {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_tuple(4)?;
+        s.serialize_element(&self.contacts)?;
+        s.serialize_element(&self.scan_time)?;
+        s.serialize_element(&self.contact_count)?;
+        s.serialize_element(&(self.button as u8))?;
+        s.end()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiTouchpadInputReport<'a> {
+    /// Touchpad report
+    Touchpad(TouchpadInputReport),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+impl<'a> Serialize for OiTouchpadInputReport<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            OiTouchpadInputReport::Touchpad(tp) => {
+                // report id + report
+                let mut s = serializer.serialize_tuple(2)?;
+                s.serialize_element(&(TouchpadReportId::Touchpad as u8))?;
+                s.serialize_element(tp)?;
+                s.end()
+            }
+            OiTouchpadInputReport::OpenInput(oi) => oi.serialize(serializer),
+        }
+    }
+}
+
+impl OpenInputHidReport for OiTouchpadReport {
+    type PullReport<'a> = OiTouchpadOutputReport<'a>;
+    type PushReport<'a> = OiTouchpadInputReport<'a>;
+    type ReportId = TouchpadReportId;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
+            // SET_REPORT data starts with the report id
+            TouchpadReportId::InputMode => match buf {
+                [_, mode] => {
+                    self.input_mode = *mode;
+                    Ok(OiTouchpadOutputReport::InputMode(*mode))
+                }
+                _ => Err(OIError::FuckyBuffer),
+            },
+            TouchpadReportId::SelectiveReporting => match buf {
+                [_, bits] => {
+                    self.surface_reporting = bits & 0x01 != 0;
+                    self.button_reporting = bits & 0x02 != 0;
+                    Ok(OiTouchpadOutputReport::SelectiveReporting {
+                        surface: self.surface_reporting,
+                        button: self.button_reporting,
+                    })
+                }
+                _ => Err(OIError::FuckyBuffer),
+            },
+            // input and read only feature reports
+            TouchpadReportId::Touchpad
+            | TouchpadReportId::Capabilities
+            | TouchpadReportId::Certification => Err(OIError::FuckyBuffer),
+            TouchpadReportId::OpenInputShort => Ok(OiTouchpadOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_short_buf)?,
+            )),
+            TouchpadReportId::OpenInputLong => Ok(OiTouchpadOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_long_buf)?,
+            )),
+        }
+    }
+
+    fn pull_feature<'a, 'ep, B: UsbBus>(
+        &'a mut self,
+        hid: &mut HIDClass<'ep, B>,
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        pull_feature_report(self, hid)
+    }
+
+    /// capabilities, input mode and selective reporting, see the module docs for the certification blob
+    fn feature_report(
+        &self,
+        report_id: u8,
+        _meta: Option<&DispatchMeta>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        let value = match TouchpadReportId::try_from(report_id).ok()? {
+            // contact count max in the low nibble, pad type in the high one
+            TouchpadReportId::Capabilities => MAX_CONTACTS as u8 | self.pad_type << 4,
+            TouchpadReportId::InputMode => self.input_mode,
+            TouchpadReportId::SelectiveReporting => {
+                self.surface_reporting as u8 | (self.button_reporting as u8) << 1
+            }
+            _ => return None,
+        };
+        buf.get_mut(..2)?.copy_from_slice(&[report_id, value]);
+        Some(2)
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        match report {
+            OiTouchpadOutputReport::OpenInput(oi) => Ok(oi),
+            other => Err(other),
+        }
+    }
+
+    fn push_report<'b, 'ep, B: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, B>,
+        report: Self::PushReport<'b>,
+    ) -> Result<(), OIError> {
+        let mut buf = [0; 64];
+        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        hid.push_raw_input(&buf[..m])?;
+        Ok(())
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+    use crate::descriptor::{has_openinput_collections, OPENINPUT_DESC, OPENINPUT_SNAPSHOT};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn conformance() {
+        let desc = OiTouchpadReport::desc();
+        assert!(
+            contains(desc, &OPENINPUT_DESC),
+            "\n{:x?}\n{:x?}",
+            desc,
+            OPENINPUT_DESC
+        );
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // USAGE_PAGE (Digitizer), USAGE (Touch Pad), COLLECTION (Application), REPORT_ID (5)
+        assert!(desc.starts_with(&[0x05, 0x0d, 0x09, 0x05, 0xa1, 0x01, 0x85, 0x05]));
+        // one finger collection per contact
+        let fingers = desc
+            .windows(4)
+            .filter(|w| w == &[0x09, 0x22, 0xa1, 0x02])
+            .count();
+        // + the input mode collection
+        assert_eq!(fingers, MAX_CONTACTS + 1);
+        // walk the short items so data bytes aren't mistaken for collection tags
+        let (mut depth, mut i) = (0, 0);
+        while i < desc.len() {
+            match desc[i] & 0xfc {
+                0xa0 => depth += 1,
+                0xc0 => depth -= 1,
+                _ => (),
+            }
+            i += 1 + [0, 1, 2, 4][(desc[i] & 0x03) as usize];
+        }
+        assert_eq!((depth, i), (0, desc.len()));
+    }
+
+    #[test]
+    fn pull_feature_report() {
+        use usb_device::prelude::*;
+
+        use crate::mock::MockUsbBus;
+        use crate::OpenInputHIDClass;
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class: OpenInputHIDClass<_, OiTouchpadReport> =
+            OpenInputHIDClass::new(HIDClass::new(&alloc, OiTouchpadReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // SET_REPORT(Feature, input mode) switching to touchpad reports
+        host.set_report(0x03, 0x08, &[0x08, 0x03]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        assert!(matches!(
+            class.pull_feature_report(),
+            Ok(OiTouchpadOutputReport::InputMode(3))
+        ));
+        assert!(matches!(
+            class.pull_feature_report(),
+            Err(OIError::UsbError(UsbError::WouldBlock))
+        ));
+
+        // output reports share the control pipe and come out here too instead of being dropped
+        host.set_report(0x02, 0x20, &[0x20, 0x00, 0x01, 0x02, 0, 0, 0, 0]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        match class.pull_feature_report() {
+            Ok(OiTouchpadOutputReport::OpenInput(report)) => {
+                assert_eq!((report.function_page, report.function_id), (0x00, 0x01))
+            }
+            res => panic!("{:?}", res.err()),
+        }
+    }
+
+    #[test]
+    fn serialize() {
+        let mut report = TouchpadInputReport {
+            scan_time: 0x1234,
+            contact_count: 1,
+            button: true,
+            ..Default::default()
+        };
+        report.contacts[0] = TouchContact {
+            confidence: true,
+            tip_switch: true,
+            contact_id: 2,
+            x: 4095,
+            y: 1,
+            width: 0x10,
+            height: 0x20,
+        };
+        let mut buf = [0; 64];
+        let len = ssmarshal::serialize(&mut buf, &OiTouchpadInputReport::Touchpad(report)).unwrap();
+        // report id + 5 * 10 byte contacts + scan time + count + button
+        assert_eq!(len, 55);
+        assert_eq!(
+            &buf[..11],
+            &[0x05, 0x03, 0x02, 0xff, 0x0f, 0x01, 0x00, 0x10, 0x00, 0x20, 0x00]
+        );
+        assert_eq!(&buf[51..55], &[0x34, 0x12, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn get_feature_report() {
+        use usb_device::prelude::*;
+
+        use crate::mock::{MockHost, MockUsbBus};
+        use crate::OpenInputHIDClass;
+
+        fn get(
+            host: &MockHost,
+            dev: &mut UsbDevice<'_, MockUsbBus>,
+            class: &mut OpenInputHIDClass<'_, MockUsbBus, OiTouchpadReport>,
+            report_id: u8,
+        ) -> std::vec::Vec<u8> {
+            host.get_report(0x03, report_id, 2);
+            while host.control_pending() {
+                dev.poll(&mut [&mut *class]);
+            }
+            host.take_control_in()
+        }
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class: OpenInputHIDClass<_, OiTouchpadReport> =
+            OpenInputHIDClass::new(HIDClass::new(&alloc, OiTouchpadReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // 5 contacts, click pad
+        assert_eq!(get(&host, &mut dev, &mut class, 0x06), [0x06, 0x05]);
+        assert_eq!(
+            get(&host, &mut dev, &mut class, 0x08),
+            [0x08, INPUT_MODE_MOUSE]
+        );
+        assert_eq!(get(&host, &mut dev, &mut class, 0x09), [0x09, 0x03]);
+        // no room for the certification blob
+        assert!(get(&host, &mut dev, &mut class, 0x07).is_empty());
+
+        class.report.input_mode = INPUT_MODE_TOUCHPAD;
+        class.report.button_reporting = false;
+        class.report.pad_type = PAD_TYPE_PRESSUREPAD;
+        assert_eq!(get(&host, &mut dev, &mut class, 0x06), [0x06, 0x15]);
+        assert_eq!(
+            get(&host, &mut dev, &mut class, 0x08),
+            [0x08, INPUT_MODE_TOUCHPAD]
+        );
+        assert_eq!(get(&host, &mut dev, &mut class, 0x09), [0x09, 0x01]);
+    }
+
+    // the touch pad collection is the header, one finger collection per contact and the rest of the collection
+    const SNAPSHOT_HEADER: &[u8] = &[
+        0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+        0x09, 0x05, /* USAGE (Touch Pad) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x05, /*  REPORT_ID (0x05) */
+    ];
+
+    const SNAPSHOT_FINGER: &[u8] = &[
+        0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+        0x09, 0x22, /*  USAGE (Finger) */
+        0xa1, 0x02, /*  COLLECTION (Logical) */
+        0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+        0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+        0x09, 0x47, /*   USAGE (Confidence) */
+        0x09, 0x42, /*   USAGE (Tip Switch) */
+        0x95, 0x02, /*   REPORT_COUNT (2) */
+        0x75, 0x01, /*   REPORT_SIZE (1) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x95, 0x06, /*   REPORT_COUNT (6) */
+        0x81, 0x03, /*   INPUT (Cnst,Var,Abs) */
+        0x25, 0x04, /*   LOGICAL_MAXIMUM (4) */
+        0x75, 0x08, /*   REPORT_SIZE (8) */
+        0x95, 0x01, /*   REPORT_COUNT (1) */
+        0x09, 0x51, /*   USAGE (Contact Identifier) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+        0x26, 0xff, 0x0f, /*   LOGICAL_MAXIMUM (4095) */
+        0x75, 0x10, /*   REPORT_SIZE (16) */
+        0x55, 0x0e, /*   UNIT_EXPONENT (-2) */
+        0x65, 0x11, /*   UNIT (SI Linear: cm) */
+        0x35, 0x00, /*   PHYSICAL_MINIMUM (0) */
+        0x46, 0xb5, 0x04, /*   PHYSICAL_MAXIMUM (1205) */
+        0x09, 0x30, /*   USAGE (X) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x46, 0x8a, 0x03, /*   PHYSICAL_MAXIMUM (906) */
+        0x09, 0x31, /*   USAGE (Y) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0x05, 0x0d, /*   USAGE_PAGE (Digitizer) */
+        0x09, 0x48, /*   USAGE (Width) */
+        0x09, 0x49, /*   USAGE (Height) */
+        0x95, 0x02, /*   REPORT_COUNT (2) */
+        0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+        0xc0, /*  END_COLLECTION */
+    ];
+
+    const SNAPSHOT_TRAILER: &[u8] = &[
+        0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+        0x55, 0x0c, /*  UNIT_EXPONENT (-4) */
+        0x66, 0x01, 0x10, /*  UNIT (SI Linear: seconds) */
+        0x47, 0xff, 0xff, 0x00, 0x00, /*  PHYSICAL_MAXIMUM (65535) */
+        0x27, 0xff, 0xff, 0x00, 0x00, /*  LOGICAL_MAXIMUM (65535) */
+        0x75, 0x10, /*  REPORT_SIZE (16) */
+        0x95, 0x01, /*  REPORT_COUNT (1) */
+        0x09, 0x56, /*  USAGE (Scan Time) */
+        0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+        0x65, 0x00, /*  UNIT (None) */
+        0x55, 0x00, /*  UNIT_EXPONENT (0) */
+        0x45, 0x00, /*  PHYSICAL_MAXIMUM (0) */
+        0x09, 0x54, /*  USAGE (Contact Count) */
+        0x25, 0x7f, /*  LOGICAL_MAXIMUM (127) */
+        0x75, 0x08, /*  REPORT_SIZE (8) */
+        0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+        0x05, 0x09, /*  USAGE_PAGE (Button) */
+        0x09, 0x01, /*  USAGE (Button 1) */
+        0x25, 0x01, /*  LOGICAL_MAXIMUM (1) */
+        0x75, 0x01, /*  REPORT_SIZE (1) */
+        0x81, 0x02, /*  INPUT (Data,Var,Abs) */
+        0x95, 0x07, /*  REPORT_COUNT (7) */
+        0x81, 0x03, /*  INPUT (Cnst,Var,Abs) */
+        /* device capabilities */
+        0x05, 0x0d, /*  USAGE_PAGE (Digitizer) */
+        0x85, 0x06, /*  REPORT_ID (0x06) */
+        0x09, 0x55, /*  USAGE (Contact Count Maximum) */
+        0x09, 0x59, /*  USAGE (Pad Type) */
+        0x25, 0x0f, /*  LOGICAL_MAXIMUM (15) */
+        0x75, 0x04, /*  REPORT_SIZE (4) */
+        0x95, 0x02, /*  REPORT_COUNT (2) */
+        0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+        /* certification status */
+        0x06, 0x00, 0xff, /*  USAGE_PAGE (Vendor Page) */
+        0x85, 0x07, /*  REPORT_ID (0x07) */
+        0x09, 0xc5, /*  USAGE (Vendor Usage 0xC5) */
+        0x26, 0xff, 0x00, /*  LOGICAL_MAXIMUM (255) */
+        0x75, 0x08, /*  REPORT_SIZE (8) */
+        0x96, 0x00, 0x01, /*  REPORT_COUNT (256) */
+        0xb1, 0x02, /*  FEATURE (Data,Var,Abs) */
+        0xc0, /* END_COLLECTION */
+    ];
+
+    const SNAPSHOT_CONFIGURATION: &[u8] = &[
+        0x05, 0x0d, /* USAGE_PAGE (Digitizer) */
+        0x09, 0x0e, /* USAGE (Device Configuration) */
+        0xa1, 0x01, /* COLLECTION (Application) */
+        0x85, 0x08, /*  REPORT_ID (0x08) */
+        0x09, 0x22, /*  USAGE (Finger) */
+        0xa1, 0x02, /*  COLLECTION (Logical) */
+        0x09, 0x52, /*   USAGE (Input Mode) */
+        0x25, 0x0a, /*   LOGICAL_MAXIMUM (10) */
+        0x75, 0x08, /*   REPORT_SIZE (8) */
+        0x95, 0x01, /*   REPORT_COUNT (1) */
+        0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+        0xc0, /*  END_COLLECTION */
+        0x09, 0x22, /*  USAGE (Finger) */
+        0xa1, 0x00, /*  COLLECTION (Physical) */
+        0x85, 0x09, /*   REPORT_ID (0x09) */
+        0x09, 0x57, /*   USAGE (Surface Switch) */
+        0x09, 0x58, /*   USAGE (Button Switch) */
+        0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+        0x75, 0x01, /*   REPORT_SIZE (1) */
+        0x95, 0x02, /*   REPORT_COUNT (2) */
+        0xb1, 0x02, /*   FEATURE (Data,Var,Abs) */
+        0x95, 0x06, /*   REPORT_COUNT (6) */
+        0xb1, 0x03, /*   FEATURE (Cnst,Var,Abs) */
+        0xc0, /*  END_COLLECTION */
+        0xc0, /* END_COLLECTION */
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        let touch_pad = [
+            SNAPSHOT_HEADER,
+            SNAPSHOT_FINGER.repeat(5).as_slice(),
+            SNAPSHOT_TRAILER,
+        ]
+        .concat();
+        crate::descriptor::assert_snapshot(
+            "touchpad",
+            OiTouchpadReport::desc(),
+            &[
+                touch_pad.as_slice(),
+                SNAPSHOT_CONFIGURATION,
+                OPENINPUT_SNAPSHOT[0],
+                OPENINPUT_SNAPSHOT[1],
+            ],
+        );
+    }
+}
@@ -0,0 +1,455 @@
+//! Trackball report
+//!
+//! Axes are 16 bit since high CPI trackballs regularly overflow an `i8` between polls.
+//! Motion is in sensor counts, to convert to distance: `mm = raw_count / cpi * 25.4`
+//! (e.g. 400 counts at 1600 CPI is 6.35mm).
+//!
+//! The wheel sits in a logical collection with a Resolution Multiplier feature (report 0x05). Hosts that
+//! support hi-res scrolling set it once enumerated, from then on they expect
+//! [`WHEEL_RESOLUTION_MULTIPLIER`] counts per detent. GET_REPORT reads the current setting back. The
+//! descriptor is hand written since `gen_hid_descriptor` can't express the physical range of the multiplier.
+
+use usb_device::class_prelude::UsbBus;
+use usb_device::UsbError;
+use usbd_hid::{descriptor::generator_prelude::*, hid_class::HIDClass};
+
+use crate::dispatch::DispatchMeta;
+use crate::{
+    concat_desc, pull_feature_report, read_openinput, OIError, OiReport, LONG_LEN,
+    OPENINPUT_DESCRIPTOR, SHORT_LEN,
+};
+
+use super::OpenInputHidReport;
+
+const MOUSE: &[u8] = &[
+    0x05, 0x01, /* USAGE_PAGE (Generic Desktop) */
+    0x09, 0x02, /* USAGE (Mouse) */
+    0xa1, 0x01, /* COLLECTION (Application) */
+    0x85, 0x04, /*  REPORT_ID (0x04) */
+    0x09, 0x01, /*  USAGE (Pointer) */
+    0xa1, 0x00, /*  COLLECTION (Physical) */
+    0x05, 0x09, /*   USAGE_PAGE (Button) */
+    0x19, 0x01, /*   USAGE_MINIMUM (Button 1) */
+    0x29, 0x08, /*   USAGE_MAXIMUM (Button 8) */
+    0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+    0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+    0x75, 0x01, /*   REPORT_SIZE (1) */
+    0x95, 0x08, /*   REPORT_COUNT (8) */
+    0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+    0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+    0x16, 0x00, 0x80, /*   LOGICAL_MINIMUM (-32768) */
+    0x26, 0xff, 0x7f, /*   LOGICAL_MAXIMUM (32767) */
+    0x75, 0x10, /*   REPORT_SIZE (16) */
+    0x95, 0x02, /*   REPORT_COUNT (2) */
+    0x09, 0x30, /*   USAGE (X) */
+    0x09, 0x31, /*   USAGE (Y) */
+    0x81, 0x06, /*   INPUT (Data,Var,Rel) */
+    0xa1, 0x02, /*   COLLECTION (Logical) */
+    0x85, 0x05, /*    REPORT_ID (0x05) */
+    0x09, 0x48, /*    USAGE (Resolution Multiplier) */
+    0x15, 0x00, /*    LOGICAL_MINIMUM (0) */
+    0x25, 0x01, /*    LOGICAL_MAXIMUM (1) */
+    0x35, 0x01, /*    PHYSICAL_MINIMUM (1) */
+    0x45, 0x08, /*    PHYSICAL_MAXIMUM (8) */
+    0x75, 0x02, /*    REPORT_SIZE (2) */
+    0x95, 0x01, /*    REPORT_COUNT (1) */
+    0xb1, 0x02, /*    FEATURE (Data,Var,Abs) */
+    0x75, 0x06, /*    REPORT_SIZE (6) */
+    0xb1, 0x03, /*    FEATURE (Cnst,Var,Abs) */
+    0x35, 0x00, /*    PHYSICAL_MINIMUM (0) */
+    0x45, 0x00, /*    PHYSICAL_MAXIMUM (0) */
+    0x85, 0x04, /*    REPORT_ID (0x04) */
+    0x09, 0x38, /*    USAGE (Wheel) */
+    0x15, 0x81, /*    LOGICAL_MINIMUM (-127) */
+    0x25, 0x7f, /*    LOGICAL_MAXIMUM (127) */
+    0x75, 0x08, /*    REPORT_SIZE (8) */
+    0x81, 0x06, /*    INPUT (Data,Var,Rel) */
+    0xc0, /*   END_COLLECTION */
+    0xc0, /*  END_COLLECTION */
+    0xc0, /* END_COLLECTION */
+];
+
+/// wheel counts per detent once the host enabled hi-res scrolling, the multiplier's PHYSICAL_MAXIMUM
+pub const WHEEL_RESOLUTION_MULTIPLIER: u8 = 8;
+
+const DESC_LEN: usize = MOUSE.len() + OPENINPUT_DESCRIPTOR.len();
+static DESC: [u8; DESC_LEN] = concat_desc(MOUSE, OPENINPUT_DESCRIPTOR);
+
+#[derive(Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct OiTrackballReport {
+    /// the host enabled the resolution multiplier, off (standard resolution) until it does
+    pub hi_res_wheel: bool,
+    // openinput
+    out_short_buf: [u8; SHORT_LEN],
+    out_long_buf: [u8; LONG_LEN],
+}
+
+impl OiTrackballReport {
+    /// wheel counts per detent, [`TrackballInputReport::scroll`] has to be scaled by this
+    pub fn wheel_multiplier(&self) -> u8 {
+        if self.hi_res_wheel {
+            WHEEL_RESOLUTION_MULTIPLIER
+        } else {
+            1
+        }
+    }
+}
+
+impl SerializedDescriptor for OiTrackballReport {
+    fn desc() -> &'static [u8] {
+        &DESC
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TrackballReportId {
+    OpenInputShort = 0x20,
+    OpenInputLong = 0x21,
+    Trackball = 0x04,
+    ResolutionMultiplier = 0x05,
+}
+
+impl TryFrom<u8> for TrackballReportId {
+    type Error = ();
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x04 => Ok(TrackballReportId::Trackball),
+            0x05 => Ok(TrackballReportId::ResolutionMultiplier),
+            0x20 => Ok(TrackballReportId::OpenInputShort),
+            0x21 => Ok(TrackballReportId::OpenInputLong),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiTrackballOutputReport<'a> {
+    /// Resolution multiplier feature report, true for hi-res scrolling
+    ResolutionMultiplier(bool),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TrackballInputReport {
+    pub buttons: u8,
+    pub x: i16,
+    pub y: i16,
+    /// wheel counts, [`OiTrackballReport::wheel_multiplier`] per detent
+    pub scroll: i8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OiTrackballInputReport<'a> {
+    /// Trackball report
+    Trackball(TrackballInputReport),
+    /// Openinput short/long report
+    OpenInput(OiReport<'a>),
+}
+
+impl<'a> Serialize for OiTrackballInputReport<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            OiTrackballInputReport::Trackball(tb) => {
+                // report id + report
+                let mut s = serializer.serialize_tuple(2)?;
+                s.serialize_element(&(TrackballReportId::Trackball as u8))?;
+                s.serialize_element(tb)?;
+                s.end()
+            }
+            OiTrackballInputReport::OpenInput(oi) => oi.serialize(serializer),
+        }
+    }
+}
+
+impl OpenInputHidReport for OiTrackballReport {
+    type PullReport<'a> = OiTrackballOutputReport<'a>;
+    type PushReport<'a> = OiTrackballInputReport<'a>;
+    type ReportId = TrackballReportId;
+
+    fn read_report<'a>(
+        &'a mut self,
+        report_id: u8,
+        buf: &[u8],
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        match Self::ReportId::try_from(report_id).map_err(|_| UsbError::ParseError)? {
+            // SET_REPORT data starts with the report id
+            TrackballReportId::ResolutionMultiplier => match buf {
+                [_, bits] => {
+                    self.hi_res_wheel = bits & 0x03 != 0;
+                    Ok(OiTrackballOutputReport::ResolutionMultiplier(
+                        self.hi_res_wheel,
+                    ))
+                }
+                _ => Err(OIError::FuckyBuffer),
+            },
+            // no trackball output items in the descriptor
+            TrackballReportId::Trackball => Err(OIError::FuckyBuffer),
+            TrackballReportId::OpenInputShort => Ok(OiTrackballOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_short_buf)?,
+            )),
+            TrackballReportId::OpenInputLong => Ok(OiTrackballOutputReport::OpenInput(
+                read_openinput(buf, &mut self.out_long_buf)?,
+            )),
+        }
+    }
+
+    fn pull_feature<'a, 'ep, B: UsbBus>(
+        &'a mut self,
+        hid: &mut HIDClass<'ep, B>,
+    ) -> Result<Self::PullReport<'a>, OIError> {
+        pull_feature_report(self, hid)
+    }
+
+    fn feature_report(
+        &self,
+        report_id: u8,
+        _meta: Option<&DispatchMeta>,
+        buf: &mut [u8],
+    ) -> Option<usize> {
+        match TrackballReportId::try_from(report_id).ok()? {
+            // the multiplier's logical value, the padding bits stay 0
+            TrackballReportId::ResolutionMultiplier => {
+                buf.get_mut(..2)?
+                    .copy_from_slice(&[report_id, self.hi_res_wheel as u8]);
+                Some(2)
+            }
+            _ => None,
+        }
+    }
+
+    fn openinput_frame<'a>(
+        report: Self::PullReport<'a>,
+    ) -> Result<OiReport<'a>, Self::PullReport<'a>> {
+        match report {
+            OiTrackballOutputReport::OpenInput(oi) => Ok(oi),
+            other => Err(other),
+        }
+    }
+
+    fn push_report<'b, 'ep, B: UsbBus>(
+        &mut self,
+        hid: &mut HIDClass<'ep, B>,
+        report: Self::PushReport<'b>,
+    ) -> Result<(), OIError> {
+        let mut buf = [0; 64];
+        let m = ssmarshal::serialize(&mut buf, &report).map_err(|_| OIError::SerializationError)?;
+        hid.push_raw_input(&buf[..m])?;
+        Ok(())
+    }
+
+    fn reset_buffers(&mut self) {
+        self.out_short_buf = [0; SHORT_LEN];
+        self.out_long_buf = [0; LONG_LEN];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use usbd_hid::descriptor::SerializedDescriptor;
+
+    use super::*;
+    use crate::descriptor::{has_openinput_collections, OPENINPUT_DESC, OPENINPUT_SNAPSHOT};
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn conformance() {
+        let desc = OiTrackballReport::desc();
+        assert!(
+            contains(desc, &OPENINPUT_DESC),
+            "\n{:x?}\n{:x?}",
+            desc,
+            OPENINPUT_DESC
+        );
+        assert!(has_openinput_collections(desc), "{:x?}", desc);
+        // USAGE_PAGE (Generic Desktop), USAGE (Mouse)
+        assert!(contains(desc, &[0x05, 0x01, 0x09, 0x02]), "{:x?}", desc);
+        // REPORT_ID (4)
+        assert!(contains(desc, &[0x85, 0x04]), "{:x?}", desc);
+        // 16 bit relative axes: REPORT_SIZE (16)
+        assert!(contains(desc, &[0x75, 0x10]), "{:x?}", desc);
+    }
+
+    #[test]
+    fn resolution_multiplier_descriptor() {
+        let desc = OiTrackballReport::desc();
+        // COLLECTION (Logical), REPORT_ID (5), USAGE (Resolution Multiplier)
+        assert!(
+            contains(desc, &[0xa1, 0x02, 0x85, 0x05, 0x09, 0x48]),
+            "{:x?}",
+            desc
+        );
+        // PHYSICAL_MAXIMUM (8) ... FEATURE (Data,Var,Abs)
+        assert!(contains(
+            desc,
+            &[
+                0x45,
+                WHEEL_RESOLUTION_MULTIPLIER,
+                0x75,
+                0x02,
+                0x95,
+                0x01,
+                0xb1,
+                0x02
+            ]
+        ));
+        // USAGE (Wheel) in the same collection
+        assert!(contains(desc, &[0x85, 0x04, 0x09, 0x38]));
+    }
+
+    #[test]
+    fn resolution_multiplier_feature() {
+        use usb_device::prelude::*;
+
+        use crate::mock::MockUsbBus;
+        use crate::OpenInputHIDClass;
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class: OpenInputHIDClass<_, OiTrackballReport> =
+            OpenInputHIDClass::new(HIDClass::new(&alloc, OiTrackballReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+        assert_eq!(class.report.wheel_multiplier(), 1);
+
+        // SET_REPORT(Feature, resolution multiplier)
+        host.set_report(0x03, 0x05, &[0x05, 0x01]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class.inner]);
+        }
+        assert!(matches!(
+            class.pull_feature_report(),
+            Ok(OiTrackballOutputReport::ResolutionMultiplier(true))
+        ));
+        assert_eq!(class.report.wheel_multiplier(), WHEEL_RESOLUTION_MULTIPLIER);
+
+        let mut report = OiTrackballReport::default();
+        assert!(matches!(
+            report.read_report(0x05, &[0x05, 0x00]),
+            Ok(OiTrackballOutputReport::ResolutionMultiplier(false))
+        ));
+        assert!(matches!(
+            report.read_report(0x05, &[0x05]),
+            Err(OIError::FuckyBuffer)
+        ));
+    }
+
+    #[test]
+    fn get_resolution_multiplier() {
+        use usb_device::prelude::*;
+
+        use crate::mock::{MockHost, MockUsbBus};
+        use crate::OpenInputHIDClass;
+
+        fn get(
+            host: &MockHost,
+            dev: &mut UsbDevice<'_, MockUsbBus>,
+            class: &mut OpenInputHIDClass<'_, MockUsbBus, OiTrackballReport>,
+            report_id: u8,
+        ) -> std::vec::Vec<u8> {
+            host.get_report(0x03, report_id, 2);
+            while host.control_pending() {
+                dev.poll(&mut [&mut *class]);
+            }
+            host.take_control_in()
+        }
+
+        let (alloc, host) = MockUsbBus::new();
+        let mut class: OpenInputHIDClass<_, OiTrackballReport> =
+            OpenInputHIDClass::new(HIDClass::new(&alloc, OiTrackballReport::desc(), 1));
+        let mut dev = UsbDeviceBuilder::new(&alloc, UsbVidPid(0x1209, 0x0001)).build();
+
+        // standard resolution until the host sets it
+        assert_eq!(get(&host, &mut dev, &mut class, 0x05), [0x05, 0x00]);
+
+        host.set_report(0x03, 0x05, &[0x05, 0x01]);
+        while host.control_pending() {
+            dev.poll(&mut [&mut class]);
+        }
+        assert!(matches!(
+            class.pull_feature_report(),
+            Ok(OiTrackballOutputReport::ResolutionMultiplier(true))
+        ));
+        assert_eq!(get(&host, &mut dev, &mut class, 0x05), [0x05, 0x01]);
+        // the trackball report is input only
+        assert!(get(&host, &mut dev, &mut class, 0x04).is_empty());
+    }
+
+    #[test]
+    fn serialize() {
+        let report = OiTrackballInputReport::Trackball(TrackballInputReport {
+            buttons: 0x01,
+            x: 1000,
+            y: -2,
+            scroll: -1,
+        });
+        let mut buf = [0; 64];
+        let len = ssmarshal::serialize(&mut buf, &report).unwrap();
+        assert_eq!(&buf[..len], &[0x04, 0x01, 0xe8, 0x03, 0xfe, 0xff, 0xff]);
+    }
+
+    const DESC_SNAPSHOT: [&[u8]; 3] = [
+        &[
+            0x05, 0x01, /* USAGE_PAGE (Generic Desktop) */
+            0x09, 0x02, /* USAGE (Mouse) */
+            0xa1, 0x01, /* COLLECTION (Application) */
+            0x85, 0x04, /*  REPORT_ID (0x04) */
+            0x09, 0x01, /*  USAGE (Pointer) */
+            0xa1, 0x00, /*  COLLECTION (Physical) */
+            0x05, 0x09, /*   USAGE_PAGE (Button) */
+            0x19, 0x01, /*   USAGE_MINIMUM (Button 1) */
+            0x29, 0x08, /*   USAGE_MAXIMUM (Button 8) */
+            0x15, 0x00, /*   LOGICAL_MINIMUM (0) */
+            0x25, 0x01, /*   LOGICAL_MAXIMUM (1) */
+            0x75, 0x01, /*   REPORT_SIZE (1) */
+            0x95, 0x08, /*   REPORT_COUNT (8) */
+            0x81, 0x02, /*   INPUT (Data,Var,Abs) */
+            0x05, 0x01, /*   USAGE_PAGE (Generic Desktop) */
+            0x16, 0x00, 0x80, /*   LOGICAL_MINIMUM (-32768) */
+            0x26, 0xff, 0x7f, /*   LOGICAL_MAXIMUM (32767) */
+            0x75, 0x10, /*   REPORT_SIZE (16) */
+            0x95, 0x02, /*   REPORT_COUNT (2) */
+            0x09, 0x30, /*   USAGE (X) */
+            0x09, 0x31, /*   USAGE (Y) */
+            0x81, 0x06, /*   INPUT (Data,Var,Rel) */
+            0xa1, 0x02, /*   COLLECTION (Logical) */
+            0x85, 0x05, /*    REPORT_ID (0x05) */
+            0x09, 0x48, /*    USAGE (Resolution Multiplier) */
+            0x15, 0x00, /*    LOGICAL_MINIMUM (0) */
+            0x25, 0x01, /*    LOGICAL_MAXIMUM (1) */
+            0x35, 0x01, /*    PHYSICAL_MINIMUM (1) */
+            0x45, 0x08, /*    PHYSICAL_MAXIMUM (8) */
+            0x75, 0x02, /*    REPORT_SIZE (2) */
+            0x95, 0x01, /*    REPORT_COUNT (1) */
+            0xb1, 0x02, /*    FEATURE (Data,Var,Abs) */
+            0x75, 0x06, /*    REPORT_SIZE (6) */
+            0xb1, 0x03, /*    FEATURE (Cnst,Var,Abs) */
+            0x35, 0x00, /*    PHYSICAL_MINIMUM (0) */
+            0x45, 0x00, /*    PHYSICAL_MAXIMUM (0) */
+            0x85, 0x04, /*    REPORT_ID (0x04) */
+            0x09, 0x38, /*    USAGE (Wheel) */
+            0x15, 0x81, /*    LOGICAL_MINIMUM (-127) */
+            0x25, 0x7f, /*    LOGICAL_MAXIMUM (127) */
+            0x75, 0x08, /*    REPORT_SIZE (8) */
+            0x81, 0x06, /*    INPUT (Data,Var,Rel) */
+            0xc0, /*   END_COLLECTION */
+            0xc0, /*  END_COLLECTION */
+            0xc0, /* END_COLLECTION */
+        ],
+        OPENINPUT_SNAPSHOT[0],
+        OPENINPUT_SNAPSHOT[1],
+    ];
+
+    #[test]
+    fn descriptor_snapshot() {
+        crate::descriptor::assert_snapshot("trackball", OiTrackballReport::desc(), &DESC_SNAPSHOT);
+    }
+}
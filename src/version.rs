@@ -0,0 +1,93 @@
+//! OpenInput protocol version
+
+use core::fmt;
+
+/// [major, minor, patch], orders the same way the wire bytes compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ProtocolVersion([u8; 3]);
+
+impl ProtocolVersion {
+    pub const fn new(major: u8, minor: u8, patch: u8) -> Self {
+        Self([major, minor, patch])
+    }
+
+    pub const fn major(&self) -> u8 {
+        self.0[0]
+    }
+
+    pub const fn minor(&self) -> u8 {
+        self.0[1]
+    }
+
+    pub const fn patch(&self) -> u8 {
+        self.0[2]
+    }
+
+    /// bytes as sent in the info page version reply
+    pub const fn to_bytes(&self) -> [u8; 3] {
+        self.0
+    }
+
+    /// `self` can talk to a peer expecting `other`: same major and at least the same minor
+    pub const fn is_compatible_with(&self, other: ProtocolVersion) -> bool {
+        self.major() == other.major() && self.minor() >= other.minor()
+    }
+}
+
+// every [u8; 3] is a valid version, `TryFrom<[u8; 3]>` comes from the blanket impl
+impl From<[u8; 3]> for ProtocolVersion {
+    fn from(bytes: [u8; 3]) -> Self {
+        Self(bytes)
+    }
+}
+
+/// for version replies read off the wire, errors if the slice isn't exactly 3 bytes
+impl TryFrom<&[u8]> for ProtocolVersion {
+    type Error = ();
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        <[u8; 3]>::try_from(bytes).map(Self).map_err(|_| ())
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major(), self.minor(), self.patch())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordering() {
+        let v = ProtocolVersion::new;
+        assert!(v(0, 0, 1) < v(0, 0, 2));
+        assert!(v(0, 0, 9) < v(0, 1, 0));
+        assert!(v(0, 9, 9) < v(1, 0, 0));
+        assert_eq!(v(1, 2, 3), ProtocolVersion::try_from([1, 2, 3]).unwrap());
+        assert_eq!(v(1, 2, 3).to_string(), "1.2.3");
+        assert_eq!(crate::PROTOCOL_VERSION.to_string(), "0.0.1");
+    }
+
+    #[test]
+    fn compatibility() {
+        let v = ProtocolVersion::new;
+        assert!(v(1, 2, 0).is_compatible_with(v(1, 2, 5)));
+        assert!(v(1, 3, 0).is_compatible_with(v(1, 2, 0)));
+        assert!(!v(1, 1, 0).is_compatible_with(v(1, 2, 0)));
+        assert!(!v(2, 0, 0).is_compatible_with(v(1, 0, 0)));
+        assert!(!v(1, 0, 0).is_compatible_with(v(2, 0, 0)));
+    }
+
+    #[test]
+    fn from_slice() {
+        assert_eq!(
+            ProtocolVersion::try_from(&[0, 1, 2][..]),
+            Ok(ProtocolVersion::new(0, 1, 2))
+        );
+        assert_eq!(ProtocolVersion::try_from(&[0, 1][..]), Err(()));
+    }
+}
@@ -0,0 +1,285 @@
+//! Device addresses for receivers (dongles) that forward OpenInput reports to paired devices
+//!
+//! With the `wireless` feature every long report, request or reply, carries a device address right after the 3
+//! byte prefix (report id, function page, function id), which leaves [`ADDRESSED_DATA_LEN`] bytes of data:
+//!
+//! ```text
+//! 0x21 | page | function id | address | 28 bytes of data
+//! ```
+//!
+//! Address [`LOCAL_ADDRESS`] is the receiver itself, 1 to 255 are paired devices and up to the firmware to
+//! assign. Short reports have no room for an address and stay exactly like wired ones, they always go to the
+//! receiver. [`Dispatch`](crate::dispatch::Dispatch) strips the address before calling handlers, which read it
+//! with [`DispatchContext::device_address`](crate::dispatch::DispatchContext::device_address), and puts it back
+//! into the reply, errors included. Deferred replies (see [`pending`](crate::dispatch::pending)) and the queued
+//! frames of a [`stream`](crate::dispatch::stream) go to the same device. Long replies to short requests are
+//! addressed to the receiver.
+//!
+//! With the `crc` feature the checksum covers the address like any other data byte.
+
+use heapless::Vec;
+
+use crate::{OiReport, LONG_LEN, OPENINPUT_LONG_REPORT_ID};
+
+/// the receiver, what unaddressed and wired reports talk to
+pub const LOCAL_ADDRESS: u8 = 0x00;
+
+/// data bytes of a long report after the address
+pub const ADDRESSED_DATA_LEN: usize = LONG_LEN - 3 - 1;
+
+impl<'a> OiReport<'a> {
+    /// serialized long report for the device at `address`
+    pub fn new_long_addressed(
+        page: u8,
+        fn_id: u8,
+        address: u8,
+        data: &[u8; ADDRESSED_DATA_LEN],
+    ) -> [u8; LONG_LEN] {
+        let mut report = [0; LONG_LEN];
+        report[..4].copy_from_slice(&[OPENINPUT_LONG_REPORT_ID, page, fn_id, address]);
+        report[4..].copy_from_slice(data);
+        report
+    }
+
+    /// `None` for short reports and reports to the receiver itself
+    pub fn device_address(&self) -> Option<u8> {
+        match self.address_and_data() {
+            (LOCAL_ADDRESS, _) => None,
+            (address, _) => Some(address),
+        }
+    }
+
+    /// the address and the data after it, short reports go to the receiver
+    pub(crate) fn address_and_data(&self) -> (u8, &'a [u8]) {
+        match self.data {
+            [address, data @ ..] if !self.is_short() => (*address, data),
+            data => (LOCAL_ADDRESS, data),
+        }
+    }
+}
+
+/// put `address` into a serialized long reply, `reply` is empty for deferred replies
+///
+/// [`DispatchResponse`](crate::dispatch::DispatchResponse) leaves the byte free when it serializes a long reply
+/// and answers payloads that need it with an error
+pub(crate) fn address_reply(address: u8, mut reply: Vec<u8, LONG_LEN>) -> Vec<u8, LONG_LEN> {
+    if reply.len() == LONG_LEN {
+        reply[3] = address;
+    }
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dispatch::stream::DispatchResponses;
+    use crate::dispatch::{
+        Dispatch, DispatchContext, DispatchHandler, DispatchMeta, DispatchReturn, Error,
+    };
+    use crate::host::{decode_error, ErrorReply, FirmwareInfoReader};
+
+    /// battery level of the device the request is for
+    fn battery(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let level = match ctx.device_address() {
+            None => return Err(Error::UnsupportedFunction),
+            Some(address) => address * 10,
+        };
+        Ok(Vec::from_slice(&[level]).unwrap().into())
+    }
+
+    fn echo(data: &[u8], _: DispatchContext) -> DispatchReturn {
+        Ok(Vec::from_slice(&data[..data.len().min(28)]).unwrap().into())
+    }
+
+    fn deferred(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        Ok(ctx.defer()?.into())
+    }
+
+    /// 40 bytes in two frames
+    fn streamed(_: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        let mut res = DispatchResponses::<2>::new();
+        res.extend_from_slice(&[0xAA; 40])?;
+        ctx.stream(res)
+    }
+
+    fn dispatch() -> Dispatch {
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0x80,
+                &[
+                    (0x00, DispatchHandler::Function(battery)),
+                    (0x01, DispatchHandler::Function(echo)),
+                    (0x02, DispatchHandler::Function(deferred)),
+                    (0x03, DispatchHandler::Function(streamed)),
+                ],
+            )
+            .unwrap();
+        dispatch
+    }
+
+    #[test]
+    fn addressed() {
+        let dispatch = dispatch();
+        for address in [1, 2, 7] {
+            let request = OiReport::new_long_addressed(0x80, 0x00, address, &[0; 28]);
+            let reply = dispatch.parse_and_dispatch(&request).unwrap();
+            assert_eq!(&reply[..5], &[0x21, 0x80, 0x00, address, address * 10]);
+            assert_eq!(reply.len(), LONG_LEN);
+        }
+
+        // the address isn't part of the data handlers see
+        let mut data = [0; ADDRESSED_DATA_LEN];
+        data[..3].copy_from_slice(&[1, 2, 3]);
+        let request = OiReport::new_long_addressed(0x80, 0x01, 4, &data);
+        let report = OiReport::read(&request).unwrap();
+        assert_eq!(report.device_address(), Some(4));
+        assert_eq!(
+            &dispatch.dispatch(&report)[..7],
+            &[0x21, 0x80, 0x01, 4, 1, 2, 3]
+        );
+
+        // errors go back to the same device
+        let request = OiReport::new_long_addressed(0x42, 0x00, 3, &[0; 28]);
+        let reply = dispatch.parse_and_dispatch(&request).unwrap();
+        assert_eq!(&reply[..6], &[0x21, 0xFF, 0x04, 3, 0x42, 0x00]);
+    }
+
+    #[test]
+    fn unaddressed() {
+        let dispatch = dispatch();
+        // short reports are the same as wired ones
+        let request = [0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
+        let reply = dispatch.parse_and_dispatch(&request).unwrap();
+        assert_eq!(
+            reply.as_slice(),
+            &[0x20, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(OiReport::read(&request).unwrap().device_address(), None);
+
+        let request = OiReport::new_short(0x80, 0x00, &[0; 5]);
+        assert_eq!(
+            dispatch.dispatch(&request).as_slice(),
+            &[0x20, 0xFF, 0x02, 0x80, 0x00, 0, 0, 0]
+        );
+
+        // long ones to the receiver itself
+        let request = OiReport::new_long_addressed(0x00, 0x00, LOCAL_ADDRESS, &[0; 28]);
+        let reply = dispatch.parse_and_dispatch(&request).unwrap();
+        assert_eq!(
+            &reply[..7],
+            &[0x21, 0x00, 0x00, LOCAL_ADDRESS, 0x00, 0x00, 0x01]
+        );
+    }
+
+    #[test]
+    fn reply_too_long() {
+        fn full(_: &[u8], _: DispatchContext) -> DispatchReturn {
+            Ok(Vec::from_slice(&[0xAA; 29]).unwrap().into())
+        }
+
+        /// as long, but the last byte happens to be zero
+        fn zero_padded(_: &[u8], _: DispatchContext) -> DispatchReturn {
+            let mut payload = [0xAA; 29];
+            payload[28] = 0;
+            Ok(Vec::from_slice(&payload).unwrap().into())
+        }
+
+        let mut dispatch = Dispatch::default();
+        dispatch
+            .register_vendor_page(
+                0x80,
+                &[
+                    (0x00, DispatchHandler::Function(full)),
+                    (0x01, DispatchHandler::Function(zero_padded)),
+                ],
+            )
+            .unwrap();
+        for fn_id in [0x00, 0x01] {
+            let request = OiReport::new_long_addressed(0x80, fn_id, 1, &[0; 28]);
+            let reply = dispatch.parse_and_dispatch(&request).unwrap();
+            assert_eq!(&reply[..6], &[0x21, 0xFF, 0xFE, 1, 0x80, fn_id]);
+        }
+    }
+
+    #[test]
+    fn long_device_name() {
+        let name = "Openinput Wireless Gaming Mouse rev B";
+        let mut dispatch = dispatch();
+        dispatch.meta = DispatchMeta::builder().name(name).build().unwrap();
+
+        let mut reader = FirmwareInfoReader::<64>::new();
+        while !reader.is_complete() {
+            // firmware_info (0x00:0x01) of the device name (2)
+            let mut data = [0; ADDRESSED_DATA_LEN];
+            data[..2].copy_from_slice(&[2, reader.next_offset()]);
+            let request = OiReport::new_long_addressed(0x00, 0x01, 3, &data);
+            let reply = dispatch.parse_and_dispatch(&request).unwrap();
+            assert_eq!(&reply[..4], &[0x21, 0x00, 0x01, 3]);
+            reader.push(&reply[4..]).unwrap();
+        }
+        assert_eq!(reader.as_bytes(), name.as_bytes());
+    }
+
+    #[test]
+    fn deferred_reply() {
+        let dispatch = dispatch();
+        let request = OiReport::new_long_addressed(0x80, 0x02, 5, &[0; 28]);
+        let token = dispatch
+            .dispatch_report(&OiReport::read(&request).unwrap())
+            .unwrap()
+            .deferred()
+            .unwrap();
+        let reply = dispatch.resolve(token, Ok(Vec::from_slice(&[1, 2]).unwrap().into()));
+        assert_eq!(&reply[..6], &[0x21, 0x80, 0x02, 5, 1, 2]);
+        assert_eq!(reply.len(), LONG_LEN);
+
+        // polled replies too
+        let token = dispatch
+            .dispatch_report(&OiReport::read(&request).unwrap())
+            .unwrap()
+            .deferred()
+            .unwrap();
+        assert!(dispatch.complete(token, Err(Error::Busy)));
+        let reply = dispatch.dispatch_async_poll(token).unwrap();
+        assert_eq!(&reply[..6], &[0x21, 0xFF, 0x05, 5, 0x80, 0x02]);
+    }
+
+    #[test]
+    fn streamed_frames() {
+        let dispatch = dispatch();
+        let request = OiReport::new_long_addressed(0x80, 0x03, 6, &[0; 28]);
+        let first = dispatch.parse_and_dispatch(&request).unwrap();
+        assert_eq!(&first[..6], &[0x21, 0x80, 0x03, 6, 0, 2]);
+        assert!(first[6..].iter().all(|&b| b == 0xAA));
+
+        let mut frames = std::vec::Vec::new();
+        dispatch
+            .flush_queued(|frame| {
+                frames.push(frame.to_vec());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(&frames[0][..6], &[0x21, 0x80, 0x03, 6, 1, 2]);
+        assert_eq!(&frames[0][6..20], &[0xAA; 14]);
+        assert!(frames[0][20..].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn decode_addressed_error() {
+        let dispatch = dispatch();
+        let unsupported = ErrorReply::UnsupportedPage {
+            page: 0x42,
+            id: 0x07,
+        };
+        let request = OiReport::new_long_addressed(0x42, 0x07, 3, &[0; 28]);
+        let reply = dispatch.parse_and_dispatch(&request).unwrap();
+        assert_eq!(reply[3], 3);
+        assert_eq!(decode_error(&reply), unsupported);
+
+        // short ones have no address
+        let reply = dispatch.dispatch(&OiReport::new_short(0x42, 0x07, &[0; 5]));
+        assert_eq!(decode_error(&reply), unsupported);
+    }
+}
@@ -0,0 +1,6 @@
+/* STM32F411CE, change for other boards along with the chip in .cargo/config.toml */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 512K
+  RAM : ORIGIN = 0x20000000, LENGTH = 128K
+}
@@ -0,0 +1,150 @@
+//! cycles per `dispatch_raw` call on a Cortex-M4 for the runtime map, `dispatch_table!` and
+//! `#[openinput_dispatch_table]`, see the [`MatchTable`] docs
+//!
+//! ```text
+//! rustup target add thumbv7em-none-eabihf
+//! cargo install probe-rs-tools
+//! cd tests/cortex-m4-bench
+//! cargo run --release
+//! ```
+//!
+//! the runner in `.cargo/config.toml` flashes an STM32F411 (e.g. a "black pill"), change the chip there and
+//! `memory.x` for other boards. QEMU can't run it, it doesn't emulate the DWT cycle counter
+//!
+//! every table holds the info page and the same 3 pages of 4 functions, each round calls all of them once.
+//! the whole call is timed (access checks, stats and the reply included), the lookup is the only part that
+//! differs between the tables
+
+#![no_std]
+#![no_main]
+
+use cortex_m::peripheral::DWT;
+use cortex_m_rt::entry;
+use defmt_rtt as _;
+use panic_probe as _;
+
+use heapless::Vec;
+use openinput_rust::dispatch::{
+    Dispatch, DispatchContext, DispatchHandler, DispatchMeta, DispatchReturn, MatchTable,
+};
+
+const PAGES: [u8; 3] = [0xA0, 0xA1, 0xA2];
+const FUNCTIONS: u8 = 4;
+const ROUNDS: u32 = 1000;
+
+fn nop(_: &[u8], _: DispatchContext) -> DispatchReturn {
+    Ok(Vec::new().into())
+}
+
+const NOP_PAGE: [(u8, DispatchHandler); FUNCTIONS as usize] = [
+    (0x00, DispatchHandler::Function(nop)),
+    (0x01, DispatchHandler::Function(nop)),
+    (0x02, DispatchHandler::Function(nop)),
+    (0x03, DispatchHandler::Function(nop)),
+];
+
+struct Table;
+
+#[openinput_rust::openinput_dispatch_table]
+impl Table {
+    #[function(page = 0xA0, id = 0x00)]
+    fn a0_0(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA0, id = 0x01)]
+    fn a0_1(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA0, id = 0x02)]
+    fn a0_2(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA0, id = 0x03)]
+    fn a0_3(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA1, id = 0x00)]
+    fn a1_0(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA1, id = 0x01)]
+    fn a1_1(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA1, id = 0x02)]
+    fn a1_2(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA1, id = 0x03)]
+    fn a1_3(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA2, id = 0x00)]
+    fn a2_0(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA2, id = 0x01)]
+    fn a2_1(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA2, id = 0x02)]
+    fn a2_2(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+    #[function(page = 0xA2, id = 0x03)]
+    fn a2_3(data: &[u8], ctx: DispatchContext) -> DispatchReturn {
+        nop(data, ctx)
+    }
+}
+
+/// average cycles per call, the reply lengths are summed so the calls can't be optimized out
+fn cycles_per_call(dispatch: &Dispatch) -> u32 {
+    let mut sink = 0;
+    let start = DWT::cycle_count();
+    for _ in 0..ROUNDS {
+        for page in PAGES {
+            for id in 0..FUNCTIONS {
+                sink += dispatch
+                    .dispatch_raw(page, id, &[0; 5])
+                    .map_or(1, |res| res.payload().len());
+            }
+        }
+    }
+    let cycles = DWT::cycle_count().wrapping_sub(start);
+    defmt::assert_eq!(sink, 0, "a call failed");
+    cycles / (ROUNDS * PAGES.len() as u32 * u32::from(FUNCTIONS))
+}
+
+#[entry]
+fn main() -> ! {
+    let mut cp = cortex_m::Peripherals::take().unwrap();
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+
+    let mut map = Dispatch::default();
+    for page in PAGES {
+        map.register_vendor_page(page, &NOP_PAGE).unwrap();
+    }
+    let table = openinput_rust::dispatch_table! {
+        0xA0 => { 0x00 => nop, 0x01 => nop, 0x02 => nop, 0x03 => nop },
+        0xA1 => { 0x00 => nop, 0x01 => nop, 0x02 => nop, 0x03 => nop },
+        0xA2 => { 0x00 => nop, 0x01 => nop, 0x02 => nop, 0x03 => nop },
+    };
+    let scan = Dispatch::new_static(table, DispatchMeta::default());
+    let matched = Dispatch::new_match::<Table>(DispatchMeta::default());
+    defmt::assert_eq!(<Table as MatchTable>::PAGES.len(), PAGES.len() + 1);
+
+    defmt::info!("FnvIndexMap: {=u32} cycles per call", cycles_per_call(&map));
+    defmt::info!(
+        "dispatch_table!: {=u32} cycles per call",
+        cycles_per_call(&scan)
+    );
+    defmt::info!(
+        "#[openinput_dispatch_table]: {=u32} cycles per call",
+        cycles_per_call(&matched)
+    );
+
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
@@ -0,0 +1,6 @@
+/* LM3S6965, the board QEMU emulates with -machine lm3s6965evb */
+MEMORY
+{
+  FLASH : ORIGIN = 0x00000000, LENGTH = 256K
+  RAM : ORIGIN = 0x20000000, LENGTH = 64K
+}
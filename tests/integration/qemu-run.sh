@@ -0,0 +1,5 @@
+#!/bin/sh
+# cargo runner, defmt frames come out over semihosting and are decoded with the elf's symbols
+set -e
+qemu-system-arm -cpu cortex-m3 -machine lm3s6965evb -nographic \
+    -semihosting-config enable=on,target=native -kernel "$1" | defmt-print -e "$1"
@@ -0,0 +1,13 @@
+//! no_std smoke tests for openinput-rust on a QEMU Cortex-M3, the tests are in `tests/`
+//!
+//! ```text
+//! rustup target add thumbv7m-none-eabi
+//! cargo install defmt-print
+//! cd tests/integration
+//! cargo test --target thumbv7m-none-eabi
+//! ```
+//!
+//! needs `qemu-system-arm` on the path. the target is the default in `.cargo/config.toml` so a plain
+//! `cargo test` works too, failures exit QEMU with a non-zero code through semihosting
+
+#![no_std]
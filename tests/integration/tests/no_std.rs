@@ -0,0 +1,115 @@
+#![no_std]
+#![no_main]
+
+use cortex_m_rt::{exception, ExceptionFrame};
+use cortex_m_semihosting::debug;
+use defmt_semihosting as _;
+use panic_semihosting as _;
+
+use heapless::Vec;
+use openinput_rust::dispatch::Dispatch;
+use openinput_rust::OiReport;
+
+/// `bkpt #0`, what defmt-test runs once every test passed
+const BKPT_0: u16 = 0xBE00;
+
+/// defmt-test ends on a breakpoint, without a debugger attached that's a hard fault. any other fault (bad
+/// pointer, undefined instruction, another breakpoint) fails the run
+#[exception]
+unsafe fn HardFault(frame: &ExceptionFrame) -> ! {
+    extern "C" {
+        // .text bounds from cortex-m-rt's linker script
+        static __stext: u8;
+        static __etext: u8;
+    }
+
+    // the stacked pc points at the faulting instruction, only read it if it's in .text so a jump to a bad
+    // address doesn't fault again in here
+    let pc = frame.pc();
+    let text = &__stext as *const u8 as u32..&__etext as *const u8 as u32;
+    if text.contains(&pc) && core::ptr::read_volatile(pc as *const u16) == BKPT_0 {
+        debug::exit(debug::EXIT_SUCCESS);
+    } else {
+        debug::exit(debug::EXIT_FAILURE);
+    }
+    loop {}
+}
+
+const INFO_PAGE: u8 = 0x00;
+const SHORT_LEN: usize = 8;
+const LONG_LEN: usize = 32;
+
+fn serialize(report: &OiReport) -> Vec<u8, LONG_LEN> {
+    let mut buf = [0; LONG_LEN];
+    let len = ssmarshal::serialize(&mut buf, report).unwrap();
+    Vec::from_slice(&buf[..len]).unwrap()
+}
+
+/// reply to a short request, asserting it isn't an error
+fn info(dispatch: &Dispatch, id: u8, params: &[u8]) -> Vec<u8, LONG_LEN> {
+    let mut data = [0; 5];
+    data[..params.len()].copy_from_slice(params);
+    let reply = dispatch.dispatch(&OiReport::new_short(INFO_PAGE, id, &data));
+    defmt::assert_eq!(&reply[1..3], &[INFO_PAGE, id]);
+    reply
+}
+
+#[defmt_test::tests]
+mod tests {
+    use openinput_rust::PROTOCOL_VERSION;
+
+    use super::*;
+
+    #[test]
+    fn default_dispatch() {
+        let dispatch = Dispatch::default();
+        defmt::assert!(dispatch.contains(INFO_PAGE, 0x00));
+        defmt::assert_eq!(dispatch.pages().count(), 1);
+    }
+
+    #[test]
+    fn short_round_trip() {
+        let report = OiReport::new_short(0x10, 0x02, &[1, 2, 3, 4, 5]);
+        let bytes = serialize(&report);
+        defmt::assert_eq!(bytes.len(), SHORT_LEN);
+        defmt::assert!(OiReport::read(&bytes).unwrap() == report);
+    }
+
+    #[test]
+    fn long_round_trip() {
+        let mut data = [0; 29];
+        data[28] = 0xAA;
+        let report = OiReport::new_long(0x10, 0x02, &data);
+        let bytes = serialize(&report);
+        defmt::assert_eq!(bytes.len(), LONG_LEN);
+        defmt::assert!(OiReport::read(&bytes).unwrap() == report);
+    }
+
+    #[test]
+    fn info_version() {
+        let reply = info(&Dispatch::default(), 0x00, &[]);
+        defmt::assert_eq!(&reply[3..6], &PROTOCOL_VERSION.to_bytes());
+    }
+
+    #[test]
+    fn info_firmware_info() {
+        // vendor from offset 0, promoted to a long report
+        let reply = info(&Dispatch::default(), 0x01, &[0, 0]);
+        defmt::assert_eq!(reply.len(), LONG_LEN);
+        defmt::assert!(reply[3..].starts_with(b"Unspecified Vendor"));
+    }
+
+    #[test]
+    fn info_supported_pages() {
+        let reply = info(&Dispatch::default(), 0x02, &[0]);
+        defmt::assert_eq!(&reply[3..6], &[1, 1, INFO_PAGE]);
+    }
+
+    #[test]
+    fn info_supported_functions() {
+        let reply = info(&Dispatch::default(), 0x03, &[INFO_PAGE, 0]);
+        defmt::assert_eq!(&reply[3..8], &[4, 3, 0x00, 0x01, 0x02]);
+        let reply = info(&Dispatch::default(), 0x03, &[INFO_PAGE, 3]);
+        defmt::assert_eq!(&reply[3..6], &[4, 1, 0x03]);
+    }
+}